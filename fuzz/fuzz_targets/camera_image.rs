@@ -0,0 +1,23 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Fuzz the parsing of a whole image, including the data after the JPEG image.
+#![no_main]
+
+use gcamera_tools::camera_image::CameraImage;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(image) = CameraImage::try_from(data) {
+        // Walk the metadata, since it is only parsed on request.
+        drop(image.get_extended_xmp());
+        drop(image.get_exif());
+        drop(image.get_depth_map());
+        drop(image.get_gain_map_mismatches());
+        drop(image.get_gain_map_scale());
+        drop(image.get_thumbnails());
+        drop(image.get_icc_profile());
+    }
+});
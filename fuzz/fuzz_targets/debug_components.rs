@@ -0,0 +1,14 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Fuzz the parsing of the camera debug data that follows the JPEG image.
+#![no_main]
+
+use gcamera_tools::debug_components::DebugComponents;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    drop(DebugComponents::try_from(data));
+});
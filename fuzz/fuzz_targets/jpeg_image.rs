@@ -0,0 +1,20 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Fuzz the parsing of the JPEG segments.
+#![no_main]
+
+use gcamera_tools::jpeg::jpeg_image::JpegImage;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(image) = JpegImage::try_from(data) {
+        // Walk the tables and scans, since these are only parsed on request.
+        drop(image.get_frame_header());
+        drop(image.get_quantization_tables());
+        drop(image.get_huffman_tables());
+        drop(image.scans());
+    }
+});
@@ -0,0 +1,36 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Check that parsing, serializing, and parsing again gives the same result.
+//!
+//! Any input that fails this check should be added to `fuzz/regressions`,
+//! where it is checked by the unit tests of the main crate.
+#![no_main]
+
+use gcamera_tools::camera_image::CameraImage;
+use gcamera_tools::jpeg::jpeg_image::JpegImage;
+use libfuzzer_sys::fuzz_target;
+use std::io::Cursor;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(image) = JpegImage::try_from(data) {
+        let reparsed = JpegImage::try_from(image.as_bytes().as_slice());
+        assert_eq!(reparsed, Ok(image), "JPEG image changed after a round trip");
+    }
+
+    // The total size of the loaded file is not kept, and the resources that
+    // are left in the file are at new positions, so compare the bytes.
+    if let Ok(bytes) =
+        CameraImage::try_from(data).and_then(|image| return image.as_bytes(&mut Cursor::new(data)))
+    {
+        let reparsed = CameraImage::try_from(bytes.as_slice())
+            .and_then(|image| return image.as_bytes(&mut Cursor::new(bytes.as_slice())));
+        assert_eq!(
+            reparsed,
+            Ok(bytes),
+            "Camera image changed after a round trip"
+        );
+    }
+});
@@ -0,0 +1,16 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Fuzz the parsing of the XMP data.
+#![no_main]
+
+use gcamera_tools::jpeg::xmp::XMPData;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        drop(XMPData::try_from(text.to_owned()));
+    }
+});
@@ -0,0 +1,131 @@
+#!/usr/bin/env python3
+# -*- coding: UTF-8 -*-
+"""Generate a seed corpus of synthetic motion photos for the fuzz targets."""
+from argparse import ArgumentParser
+from pathlib import Path
+import struct
+
+XMP_MARKER = b"http://ns.adobe.com/xap/1.0/\x00"
+
+CONTAINER_XMP = """<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF \
+xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' \
+xmlns:GCamera='http://ns.google.com/photos/1.0/camera/' \
+xmlns:Container='http://ns.google.com/photos/1.0/container/' \
+xmlns:Item='http://ns.google.com/photos/1.0/container/item/' \
+GCamera:MotionPhoto='1' GCamera:MotionPhotoVersion='1' \
+GCamera:MotionPhotoPresentationTimestampUs='0'><Container:Directory><rdf:Seq>\
+<rdf:li rdf:parseType='Resource'><Container:Item Item:Semantic='Primary' \
+Item:Mime='image/jpeg'/></rdf:li><rdf:li rdf:parseType='Resource'><Container:Item \
+Item:Semantic='MotionPhoto' Item:Mime='video/mp4' Item:Length='{length}' \
+Item:Padding='{padding}'/></rdf:li></rdf:Seq></Container:Directory></rdf:Description>\
+</rdf:RDF></x:xmpmeta>"""
+
+MICRO_VIDEO_XMP = """<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF \
+xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' \
+xmlns:GCamera='http://ns.google.com/photos/1.0/camera/' GCamera:MicroVideo='1' \
+GCamera:MicroVideoVersion='1' GCamera:MicroVideoOffset='{length}' \
+GCamera:MicroVideoPresentationTimestampUs='-1'/></rdf:RDF></x:xmpmeta>"""
+
+
+def segment(marker: int, data: bytes) -> bytes:
+    """Create a JPEG segment with a length.
+
+    Args:
+        marker: The second byte of the marker.
+        data: The data of the segment.
+
+    Returns:
+        The bytes of the segment.
+    """
+    return bytes([0xFF, marker]) + struct.pack(">H", len(data) + 2) + data
+
+
+def jpeg(xmp: str, restart_interval: int = 0) -> bytes:
+    """Create a small baseline JPEG image with a single grey block.
+
+    Args:
+        xmp: The XMP data to store in the image.
+        restart_interval: The restart interval, or 0 to leave out the DRI segment.
+
+    Returns:
+        The bytes of the image.
+    """
+    # A single code of length 1 for symbol 0 decodes every coefficient as zero.
+    huffman = bytes([1] + [0] * 15 + [0])
+    scan = bytes([0x00])
+    if restart_interval:
+        scan += bytes([0xFF, 0xD0, 0x00, 0xFF, 0xD1, 0x00])
+    return b"".join(
+        [
+            bytes([0xFF, 0xD8]),
+            segment(0xE1, XMP_MARKER + xmp.encode()),
+            segment(0xDB, bytes([0x00] + [1] * 64)),
+            segment(0xC0, bytes([8, 0, 8, 0, 8, 1, 1, 0x11, 0])),
+            segment(0xC4, bytes([0x00]) + huffman + bytes([0x10]) + huffman),
+            segment(0xDD, struct.pack(">H", restart_interval)) if restart_interval else b"",
+            segment(0xDA, bytes([1, 1, 0x00, 0, 63, 0])),
+            scan,
+            bytes([0xFF, 0xD9]),
+        ]
+    )
+
+
+def debug_data() -> bytes:
+    """Create the camera debug data that follows the JPEG image.
+
+    Returns:
+        The bytes of the debug data.
+    """
+    return b"aecDebug\x01\x02\x03afDebug\x04\x05awbDebug\x06\x07\x08\x09"
+
+
+def video() -> bytes:
+    """Create the start of an MP4 file.
+
+    Returns:
+        The bytes of the video.
+    """
+    return struct.pack(">I", 24) + b"ftypmp42" + bytes(12)
+
+
+def main() -> None:
+    """Generate the corpus."""
+    parser = ArgumentParser(description=__doc__)
+    parser.add_argument(
+        "--output",
+        type=Path,
+        default=Path(__file__).parent / "corpus",
+        help="Directory to create the corpus of each target in",
+    )
+    arguments = parser.parse_args()
+    output: Path = arguments.output
+
+    motion = video()
+    xmps = {
+        "container": CONTAINER_XMP.format(length=len(motion), padding=0),
+        "padded": CONTAINER_XMP.format(length=len(motion), padding=4),
+        "micro_video": MICRO_VIDEO_XMP.format(length=len(motion)),
+    }
+    images = {
+        "container": jpeg(xmps["container"]) + debug_data() + motion,
+        "padded": jpeg(xmps["padded"]) + debug_data() + bytes(4) + motion,
+        "micro_video": jpeg(xmps["micro_video"]) + motion,
+        "restart": jpeg(xmps["container"], restart_interval=1) + debug_data() + motion,
+    }
+
+    corpora = {
+        "jpeg_image": images,
+        "camera_image": images,
+        "round_trip": images,
+        "xmp_data": {name: xmp.encode() for name, xmp in xmps.items()},
+        "debug_components": {"debug": debug_data()},
+    }
+    for target, seeds in corpora.items():
+        directory = output / target
+        directory.mkdir(parents=True, exist_ok=True)
+        for name, data in seeds.items():
+            (directory / name).write_bytes(data)
+
+
+if __name__ == "__main__":
+    main()
@@ -6,26 +6,81 @@
 //! Top-Level logic for processing an image.
 use crate::debug_components::DebugComponents;
 use crate::errors::GCameraError;
+use crate::image_index::{copy_range, read_range, ImageIndex, ResourceRange};
+use crate::jpeg::exif::ExifData;
+use crate::jpeg::frame::{gain_map_scale, FrameHeader};
+use crate::jpeg::icc::IccProfile;
 use crate::jpeg::jpeg_image::JpegImage;
-use crate::jpeg::xmp::{Item, SemanticType, XMPData};
+use crate::jpeg::mpf::MpIndex;
+use crate::jpeg::thumbnail::Thumbnail;
+use crate::jpeg::xmp::depth::{DepthMap, OriginalImage, GIMAGE_NS};
+use crate::jpeg::xmp::gain_map::GainMapMetadata;
+use crate::jpeg::xmp::model::{XMPTree, XMPValue};
+use crate::jpeg::xmp::serializer::{serialize, SerializeOptions};
+use crate::jpeg::xmp::{set_item_lengths, Item, MimeType, SemanticType, XMPData};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
 use std::convert::TryFrom;
 use std::fmt::Write as _; // import without risk of name clashing
-use std::fs;
 use std::fs::File;
-use std::io::Write;
+use std::io::{self, Cursor, Read, Seek, Write};
+use std::ops::Range;
 use std::path::PathBuf;
+/// The bytes of a single non-primary resource.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ResourceData {
+    /// The bytes are held in memory.
+    Loaded(Vec<u8>),
+
+    /// The bytes are left in the file the image was loaded from, at this
+    /// range, so they are only copied when the image is saved.
+    Stored(Range<usize>),
+}
+
 /// Struct for a single non-primary resource in the image.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Resource {
     /// The bytes of the resource.
-    pub data: Vec<u8>,
+    pub data: ResourceData,
 
     /// Information about the resource.
     pub info: Item,
 }
 
+impl Resource {
+    /// Copy the bytes of the resource to a writer.
+    ///
+    /// # Arguments
+    /// * `reader`: The reader the image was loaded from.
+    /// * `writer`: The writer to copy the bytes to.
+    ///
+    /// # Returns
+    /// Result of copying the bytes.
+    ///
+    /// # Errors
+    /// Will error if reading the bytes from the reader, or writing them,
+    /// fails.
+    pub fn copy<R: Read + Seek, W: Write>(&self, reader: &mut R, writer: &mut W) -> io::Result<()> {
+        return match &self.data {
+            ResourceData::Loaded(bytes) => writer.write_all(bytes),
+            ResourceData::Stored(range) => copy_range(reader, range, writer),
+        };
+    }
+
+    /// Get the size of the resource.
+    ///
+    /// # Returns
+    /// The size of the resource, in bytes.
+    pub fn size(&self) -> usize {
+        return match &self.data {
+            ResourceData::Loaded(bytes) => bytes.len(),
+            ResourceData::Stored(range) => range.len(),
+        };
+    }
+}
+
 /// Struct holding all the data for a single image.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct CameraImage {
     /// Vector of the segments in the JPEG portion of the image.
     image: JpegImage,
@@ -43,6 +98,9 @@ pub struct CameraImage {
 impl CameraImage {
     /// Load a camera image from a file on the disk.
     ///
+    /// The resources that are not JPEG images are left in the file, so the
+    /// file is needed again to save them.
+    ///
     /// # Arguments
     /// * `filepath`: The path to the file to load.
     ///
@@ -52,9 +110,84 @@ impl CameraImage {
     /// # Errors
     /// Will return an error if reading the image from disk fails.
     pub fn from_file(filepath: &PathBuf) -> Result<Self, GCameraError> {
-        return match fs::read(filepath) {
-            Ok(contents) => Self::try_from(contents),
-            Err(error) => Err(GCameraError::ImageReadError { kind: error.kind() }),
+        let mut file = File::open(filepath)
+            .map_err(|error| return GCameraError::ImageReadError { kind: error.kind() })?;
+        return Self::from_reader(&mut file);
+    }
+
+    /// Load a camera image that has already been indexed.
+    ///
+    /// Each part of the image is read straight into the structure that
+    /// holds it, so the file is never held in memory as a whole. Only the
+    /// JPEG resources are read, while the other resources, such as the
+    /// video of a motion photo, are left in the file and copied from the
+    /// same reader when the image is saved.
+    ///
+    /// # Arguments
+    /// * `index`: The index of the image.
+    /// * `reader`: The reader the image was indexed from.
+    ///
+    /// # Returns
+    /// Instance of the structure, or an error code.
+    ///
+    /// # Errors
+    /// Will return an error if reading the image fails, or the JPEG image
+    /// or debug data cannot be parsed.
+    pub fn from_index<R: Read + Seek>(
+        index: &ImageIndex,
+        reader: &mut R,
+    ) -> Result<Self, GCameraError> {
+        let image = index.read_image(reader)?;
+        let debug_components = index.read_debug_data(reader)?;
+        let resources = index
+            .resources
+            .iter()
+            .map(|resource| {
+                let data = if resource.info.mimetype == MimeType::Jpeg {
+                    ResourceData::Loaded(read_range(reader, &resource.range, "resource")?)
+                } else {
+                    ResourceData::Stored(resource.range.clone())
+                };
+                return Ok(Resource {
+                    data,
+                    info: resource.info.clone(),
+                });
+            })
+            .collect::<Result<Vec<Resource>, GCameraError>>()?;
+        return Ok(Self {
+            image,
+            debug_components,
+            resources,
+            total_size: index.total_size,
+        });
+    }
+
+    /// Load a camera image from a reader.
+    ///
+    /// # Arguments
+    /// * `reader`: The reader to load the image from.
+    ///
+    /// # Returns
+    /// Instance of the structure, or an error code.
+    ///
+    /// # Errors
+    /// Will return an error if reading the image fails, or it cannot be
+    /// indexed.
+    pub fn from_reader<R: Read + Seek>(reader: &mut R) -> Result<Self, GCameraError> {
+        let index = ImageIndex::from_reader(reader)?;
+        return Self::from_index(&index, reader);
+    }
+
+    /// Get the bytes of the `UltraHDR` gain map JPEG.
+    ///
+    /// # Returns
+    /// The bytes of the first gain map resource, or None if there is none or
+    /// it was left in the file.
+    fn gain_map_data(&self) -> Option<&[u8]> {
+        let resource = self.get_resource_by_type(SemanticType::GainMap).ok()?;
+        return match &resource.data {
+            ResourceData::Loaded(bytes) => Some(bytes.as_slice()),
+            ResourceData::Stored(_) => None,
         };
     }
 
@@ -68,7 +201,10 @@ impl CameraImage {
     ///
     /// # Errors
     /// Will error if there are no resources of the given semantic type
-    fn get_resource_by_type(&self, semantic_type: SemanticType) -> Result<&Resource, GCameraError> {
+    pub fn get_resource_by_type(
+        &self,
+        semantic_type: SemanticType,
+    ) -> Result<&Resource, GCameraError> {
         return self
             .resources
             .iter()
@@ -76,6 +212,133 @@ impl CameraImage {
             .ok_or(GCameraError::NoResourcesOfType { semantic_type });
     }
 
+    /// Get the XMP data of the image.
+    ///
+    /// # Returns
+    /// The XMP data, including every property it contains.
+    ///
+    /// # Errors
+    /// Will error if the image has no XMP data, or it cannot be parsed.
+    pub fn get_xmp(&self) -> Result<XMPData, GCameraError> {
+        return self.image.get_xmp();
+    }
+
+    /// Get the Extended XMP data of the image.
+    ///
+    /// # Returns
+    /// The merged Extended XMP data.
+    ///
+    /// # Errors
+    /// Will error if the image has no Extended XMP data, or it is not valid.
+    pub fn get_extended_xmp(&self) -> Result<XMPData, GCameraError> {
+        return self.image.get_extended_xmp();
+    }
+
+    /// Get the EXIF data of the image.
+    ///
+    /// # Returns
+    /// The decoded EXIF data.
+    ///
+    /// # Errors
+    /// Will error if the image does not have valid EXIF data.
+    pub fn get_exif(&self) -> Result<ExifData, GCameraError> {
+        return self.image.get_exif();
+    }
+
+    /// Get the depth map of a portrait photo.
+    ///
+    /// The properties of the depth map are read from the main XMP data,
+    /// and then from the Extended XMP data if the image has any.
+    ///
+    /// # Returns
+    /// The depth map, with its data decoded.
+    ///
+    /// # Errors
+    /// Will error if the image has no depth map, or it is not valid.
+    pub fn get_depth_map(&self) -> Result<DepthMap, GCameraError> {
+        return self.image.get_depth_map();
+    }
+
+    /// Get the original image of a lens blur photo.
+    ///
+    /// The original image is stored as base64 data in the XMP data, so it is
+    /// only decoded when it is requested.
+    ///
+    /// # Returns
+    /// The original image, with its data decoded.
+    ///
+    /// # Errors
+    /// Will error if the image has no original image, or it is not valid.
+    pub fn get_original_image(&self) -> Result<OriginalImage, GCameraError> {
+        return self.image.get_original_image();
+    }
+
+    /// Get the metadata of the `UltraHDR` gain map.
+    ///
+    /// The metadata is read from the XMP data of the gain map JPEG, and
+    /// then from the XMP data of the primary image.
+    ///
+    /// # Returns
+    /// The gain map metadata.
+    ///
+    /// # Errors
+    /// Will error if the gain map JPEG cannot be parsed, or the image does
+    /// not have valid gain map metadata.
+    pub fn get_gain_map_metadata(&self) -> Result<GainMapMetadata, GCameraError> {
+        return gain_map_metadata(&self.image, self.gain_map_data());
+    }
+
+    /// Get the ISO 21496-1 gain map metadata of the `UltraHDR` gain map.
+    ///
+    /// The binary metadata is read from the APP2 segments of the gain map
+    /// JPEG, and then from those of the primary image.
+    ///
+    /// # Returns
+    /// The gain map metadata.
+    ///
+    /// # Errors
+    /// Will error if the gain map JPEG cannot be parsed, or the image does
+    /// not have valid ISO 21496-1 gain map metadata.
+    pub fn get_iso_gain_map_metadata(&self) -> Result<GainMapMetadata, GCameraError> {
+        return iso_gain_map_metadata(&self.image, self.gain_map_data());
+    }
+
+    /// Compare the XMP and ISO 21496-1 copies of the gain map metadata.
+    ///
+    /// # Returns
+    /// The names of the parameters that differ between the two copies.
+    ///
+    /// # Errors
+    /// Will error if either copy of the metadata cannot be read.
+    pub fn get_gain_map_mismatches(&self) -> Result<Vec<&'static str>, GCameraError> {
+        return Ok(self
+            .get_gain_map_metadata()?
+            .differences(&self.get_iso_gain_map_metadata()?));
+    }
+
+    /// Get the frame header of the primary image.
+    ///
+    /// # Returns
+    /// The frame header, with the size and components of the image.
+    ///
+    /// # Errors
+    /// Will error if the image has no SOF segment, or it is not valid.
+    pub fn get_frame_header(&self) -> Result<FrameHeader, GCameraError> {
+        return self.image.get_frame_header();
+    }
+
+    /// Check that the `UltraHDR` gain map fits the primary image.
+    ///
+    /// # Returns
+    /// The factor the gain map is scaled up by to cover the primary image.
+    ///
+    /// # Errors
+    /// Will error if the image has no gain map, either frame header cannot
+    /// be read, or the sizes of the two images are not compatible.
+    pub fn get_gain_map_scale(&self) -> Result<u16, GCameraError> {
+        return image_gain_map_scale(&self.image, self.gain_map_data());
+    }
+
     /// Save the JPEG component of the image.
     ///
     /// # Arguments
@@ -89,10 +352,329 @@ impl CameraImage {
     pub fn save_image(&self, filepath: PathBuf) -> Result<(), GCameraError> {
         return File::create(filepath)
             .map_err(|error| return GCameraError::ImageWriteError { kind: error.kind() })?
-            .write_all(&self.image.as_resourceless_bytes())
+            .write_all(&self.image.as_resourceless_bytes()?)
             .map_err(|error| return GCameraError::ImageWriteError { kind: error.kind() });
     }
 
+    /// Replace the XMP data of the image.
+    ///
+    /// If the XMP data is too large to fit in a single JPEG segment, the
+    /// largest properties are moved into Extended XMP.
+    ///
+    /// # Arguments
+    /// * `xmp`: The new XMP data, as an XML string.
+    ///
+    /// # Errors
+    /// Will error if the XMP data cannot be parsed, or is too large to be
+    /// stored in the image.
+    pub fn set_xmp(&mut self, xmp: &str) -> Result<(), GCameraError> {
+        return self.image.set_xmp(xmp);
+    }
+
+    /// Replace the EXIF data of the image.
+    ///
+    /// The resources after the primary image are kept, and the MPF index is
+    /// updated for the new size of the primary image when it is written.
+    ///
+    /// # Arguments
+    /// * `exif`: The new EXIF data.
+    ///
+    /// # Errors
+    /// Will error if the EXIF data cannot be written, or is too large to be
+    /// stored in the image.
+    pub fn set_exif(&mut self, exif: &ExifData) -> Result<(), GCameraError> {
+        return self.image.set_exif(exif);
+    }
+
+    /// Get the thumbnails embedded in the primary image.
+    ///
+    /// # Returns
+    /// The EXIF thumbnail, followed by any JFXX thumbnails.
+    ///
+    /// # Errors
+    /// Will error if the EXIF data of the image is not valid.
+    pub fn get_thumbnails(&self) -> Result<Vec<Thumbnail>, GCameraError> {
+        return self.image.get_thumbnails();
+    }
+
+    /// Remove the thumbnails embedded in the primary image.
+    ///
+    /// # Returns
+    /// The number of thumbnails that were removed.
+    ///
+    /// # Errors
+    /// Will error if the EXIF data of the image is not valid.
+    pub fn remove_thumbnails(&mut self) -> Result<usize, GCameraError> {
+        return self.image.remove_thumbnails();
+    }
+
+    /// Replace the thumbnails embedded in the primary image.
+    ///
+    /// This should be used when the primary image is replaced, so that the
+    /// thumbnails show the new image.
+    ///
+    /// # Arguments
+    /// * `data`: The JPEG data of the new thumbnail.
+    ///
+    /// # Errors
+    /// Will error if the thumbnail is not a JPEG image, the EXIF data of the
+    /// image is not valid, or the thumbnail is too large to be stored.
+    pub fn set_thumbnail(&mut self, data: &[u8]) -> Result<(), GCameraError> {
+        return self.image.set_thumbnail(data);
+    }
+
+    /// Get the ICC profile of the primary image.
+    ///
+    /// # Returns
+    /// The profile, reassembled from its chunks.
+    ///
+    /// # Errors
+    /// Will error if the image has no ICC profile, or it is not valid.
+    pub fn get_icc_profile(&self) -> Result<IccProfile, GCameraError> {
+        return self.image.get_icc_profile();
+    }
+
+    /// Replace the ICC profile of the primary image.
+    ///
+    /// # Arguments
+    /// * `profile`: The new ICC profile.
+    ///
+    /// # Errors
+    /// Will error if the profile is not valid, or is too large to be stored.
+    pub fn set_icc_profile(&mut self, profile: &[u8]) -> Result<(), GCameraError> {
+        return self.image.set_icc_profile(profile);
+    }
+
+    /// Convert the image to bytes.
+    ///
+    /// # Arguments
+    /// * `reader`: The reader the image was loaded from.
+    ///
+    /// # Returns
+    /// The entire image file as a vector of bytes.
+    ///
+    /// # Errors
+    /// Will error if the image cannot be written, as for `write`.
+    pub fn as_bytes<R: Read + Seek>(&self, reader: &mut R) -> Result<Vec<u8>, GCameraError> {
+        let mut bytes = Vec::with_capacity(self.size());
+        self.write(reader, &mut bytes)?;
+        return Ok(bytes);
+    }
+
+    /// Save the entire image, including the debug data and resources.
+    ///
+    /// # Arguments
+    /// * `reader`: The reader the image was loaded from.
+    /// * `filepath`: Path to save the image to.
+    ///
+    /// # Returns
+    /// Result of saving the file.
+    ///
+    /// # Errors
+    /// Will error if the MPF index cannot be updated, or copying a resource
+    /// or writing the data to disk fails.
+    pub fn save<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        filepath: PathBuf,
+    ) -> Result<(), GCameraError> {
+        let mut file = File::create(filepath)
+            .map_err(|error| return GCameraError::ImageWriteError { kind: error.kind() })?;
+        return self.write(reader, &mut file);
+    }
+
+    /// Get the size of the entire image when it is written.
+    ///
+    /// # Returns
+    /// The size of the image, in bytes.
+    fn size(&self) -> usize {
+        return self.image.as_bytes().len()
+            + self.debug_components.size()
+            + self
+                .resources
+                .iter()
+                .map(|resource| return resource.info.padding + resource.size())
+                .sum::<usize>();
+    }
+
+    /// Write the entire image, including the debug data and resources.
+    ///
+    /// The JPEG image is followed by the debug data, and then by each of the
+    /// additional resources, with their padding. The resources that were
+    /// left in the file are copied from the reader. If the image has an MPF
+    /// index, it is updated to point at where the images were written.
+    ///
+    /// # Arguments
+    /// * `reader`: The reader the image was loaded from.
+    /// * `writer`: The writer to write the image to.
+    ///
+    /// # Returns
+    /// Result of writing the image.
+    ///
+    /// # Errors
+    /// Will error if the MPF index cannot be updated for where the images
+    /// were written, or copying a resource or writing the data fails.
+    pub fn write<R: Read + Seek, W: Write>(
+        &self,
+        reader: &mut R,
+        writer: &mut W,
+    ) -> Result<(), GCameraError> {
+        let mut bytes = self.image.as_bytes();
+        let primary_size = bytes.len();
+        bytes.extend(self.debug_components.as_bytes());
+        let resources_start = bytes.len();
+        if let Ok((position, index)) = self.image.get_mpf_index() {
+            update_mpf_index(
+                &mut bytes,
+                position,
+                &index,
+                primary_size,
+                &image_ranges(&self.resources, resources_start),
+            )?;
+        }
+
+        let write_error =
+            |error: io::Error| return GCameraError::ImageWriteError { kind: error.kind() };
+        writer.write_all(&bytes).map_err(write_error)?;
+        for resource in &self.resources {
+            writer
+                .write_all(&vec![0x00; resource.info.padding])
+                .map_err(write_error)?;
+            resource.copy(reader, writer).map_err(write_error)?;
+        }
+        return Ok(());
+    }
+
+    /// Convert a legacy `MicroVideo` motion photo to the container format.
+    ///
+    /// The XMP data is rewritten to describe the video with a
+    /// `Container:Directory`, and any Extended XMP is merged back in before
+    /// it is split again. The video is placed after the debug data, at the
+    /// end of the file.
+    ///
+    /// # Errors
+    /// Will error if the image is not a legacy motion photo, or the new XMP
+    /// data cannot be stored in the image.
+    pub fn convert_micro_video(&mut self) -> Result<(), GCameraError> {
+        let extended = self.get_extended_xmp().ok();
+        let tree = self
+            .get_xmp()?
+            .convert_micro_video(extended.as_ref().map(|data| return &data.tree))?;
+
+        self.set_xmp(&serialize(&tree, SerializeOptions::default()))?;
+        self.total_size = self.size();
+        return Ok(());
+    }
+
+    /// Remove the location and identifying data from the image.
+    ///
+    /// The EXIF GPS data and identifying tags, the XMP location and
+    /// identifying properties, and the camera debug data are removed. The
+    /// motion photo video is removed as well, unless it is kept. The original
+    /// image of a lens blur photo and the JPEG resources, such as the gain
+    /// map, are complete images with their own EXIF and XMP data, so they
+    /// are scrubbed in the same way. The pixels of every image are left
+    /// unchanged.
+    ///
+    /// # Arguments
+    /// * `keep_motion`: Whether to keep the motion photo video.
+    ///
+    /// # Returns
+    /// A description of each item that was removed.
+    ///
+    /// # Errors
+    /// Will error if the EXIF or XMP data cannot be parsed, or cannot be
+    /// stored in the image again.
+    pub fn scrub(&mut self, keep_motion: bool) -> Result<Vec<String>, GCameraError> {
+        let mut removed = self.image.scrub_exif()?;
+
+        // The video is removed first, so that the remaining resources match
+        // the items that are left in the directory.
+        let mut motion = Vec::new();
+        if !keep_motion {
+            let other: Vec<Resource>;
+            (motion, other) = self
+                .resources
+                .drain(..)
+                .partition(|resource| return resource.info.semantic == SemanticType::MotionPhoto);
+            self.resources = other;
+        }
+
+        let mut resources_removed = Vec::new();
+        for resource in &mut self.resources {
+            // Only the JPEG resources are loaded, and they are the only ones
+            // that can hold metadata.
+            let ResourceData::Loaded(data) = &mut resource.data else {
+                continue;
+            };
+            let image_removed = scrub_embedded_image(data)?;
+            if !image_removed.is_empty() {
+                resource.info.length = Some(data.len());
+                let semantic = resource.info.semantic.as_str();
+                resources_removed.extend(
+                    image_removed
+                        .into_iter()
+                        .map(|item| return format!("{semantic} image {item}")),
+                );
+            }
+        }
+
+        match self.get_xmp() {
+            Ok(xmp) => {
+                let extended = self.get_extended_xmp().ok();
+                let (mut tree, xmp_removed) =
+                    xmp.scrub(extended.as_ref().map(|data| return &data.tree), keep_motion);
+                let mut changed = !xmp_removed.is_empty();
+                removed.extend(xmp_removed);
+
+                if let Ok(mut original) = OriginalImage::from_trees(&[&tree]) {
+                    let image_removed = scrub_embedded_image(&mut original.data)?;
+                    if !image_removed.is_empty() {
+                        tree.set(
+                            GIMAGE_NS,
+                            "Data",
+                            XMPValue::Simple(STANDARD.encode(&original.data)),
+                        );
+                        changed = true;
+                        removed.extend(
+                            image_removed
+                                .into_iter()
+                                .map(|item| return format!("Original image {item}")),
+                        );
+                    }
+                }
+
+                let lengths: Vec<(SemanticType, usize)> = self
+                    .resources
+                    .iter()
+                    .map(|resource| return (resource.info.semantic, resource.size()))
+                    .collect();
+                changed |= set_item_lengths(&mut tree, &lengths);
+                if changed {
+                    self.set_xmp(&serialize(&tree, SerializeOptions::default()))?;
+                }
+            }
+            Err(GCameraError::NoXMPData) => {}
+            Err(error) => return Err(error),
+        }
+        removed.extend(resources_removed);
+
+        for chunk in self.debug_components.clear() {
+            removed.push(format!(
+                "Debug data {} ({} bytes)",
+                chunk.magic,
+                chunk.size()
+            ));
+        }
+
+        for resource in motion {
+            removed.push(format!("Motion photo video ({} bytes)", resource.size()));
+        }
+
+        self.total_size = self.size();
+        return Ok(removed);
+    }
+
     /// Save the debug data from the image.
     ///
     /// # Arguments
@@ -110,17 +692,25 @@ impl CameraImage {
     /// Save the motion photo from the image.
     ///
     /// # Arguments
+    /// * `reader`: The reader the image was loaded from.
     /// * `filepath`: Path to save the video to
     ///
     /// # Returns
     /// Result from saving the file
     ///
     /// # Errors
-    /// Will error if writing the video to the disk fails
-    pub fn save_motion_video(&self, filepath: PathBuf) -> Result<(), GCameraError> {
-        return File::create(filepath)
-            .map_err(|error| return GCameraError::MotionVideoWriteError { kind: error.kind() })?
-            .write_all(&self.get_resource_by_type(SemanticType::MotionPhoto)?.data)
+    /// Will error if the image has no motion photo, or copying the video to
+    /// the disk fails
+    pub fn save_motion_video<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        filepath: PathBuf,
+    ) -> Result<(), GCameraError> {
+        let video = self.get_resource_by_type(SemanticType::MotionPhoto)?;
+        let mut file = File::create(filepath)
+            .map_err(|error| return GCameraError::MotionVideoWriteError { kind: error.kind() })?;
+        return video
+            .copy(reader, &mut file)
             .map_err(|error| return GCameraError::MotionVideoWriteError { kind: error.kind() });
     }
 
@@ -129,16 +719,11 @@ impl CameraImage {
     /// # Returns
     /// A string with the debug info to print
     fn get_debug_info(&self) -> String {
-        return format!(
-            "\
-Number of JPEG segments: {}
-JPEG image size:         {}
-Debug section size:      {}
-Number of resources:     {}",
-            self.image.segments.len(),
-            self.image.image_size(),
+        return debug_info(
+            &self.image,
             self.debug_components.size(),
             self.resources.len(),
+            self.gain_map_data(),
         );
     }
 
@@ -153,18 +738,11 @@ Number of resources:     {}",
     /// # Returns
     /// A string containing a list of the additional resources in the file.
     fn get_resource_str(&self) -> String {
-        let mut resource_str = String::new();
-        resource_str.push_str("Additional Resources:\n");
-        for (index, resource) in self.resources.iter().enumerate() {
-            writeln!(
-                resource_str,
-                "\tResource {index} has a size of {} and is of type '{:?}'",
-                resource.data.len(),
-                resource.info.semantic
-            )
-            .unwrap();
-        }
-        return resource_str;
+        return resource_list(
+            self.resources
+                .iter()
+                .map(|resource| return (resource.size(), resource.info.semantic)),
+        );
     }
 
     /// Print out a list of the additional resources
@@ -173,37 +751,395 @@ Number of resources:     {}",
     }
 }
 
-/// Create resource vector based on XMP Data, and bytes
+/// Get a string of the debug info of an image.
+///
+/// The image size and quality, camera settings, ICC profile and gain map
+/// metadata are included if the image has any.
+///
+/// # Arguments
+/// * `image`: The primary JPEG image.
+/// * `debug_size`: The size of the debug data.
+/// * `resource_count`: The number of resources after the primary image.
+/// * `gain_map`: The bytes of the gain map JPEG, if there is one.
+///
+/// # Returns
+/// A string with the debug info to print.
+pub fn debug_info(
+    image: &JpegImage,
+    debug_size: usize,
+    resource_count: usize,
+    gain_map: Option<&[u8]>,
+) -> String {
+    let mut info = format!(
+        "\
+Number of JPEG segments: {}
+JPEG image size:         {}
+Debug section size:      {debug_size}
+Number of resources:     {resource_count}",
+        image.segments.len(),
+        image.image_size(),
+    );
+    if let Ok(frame) = image.get_frame_header() {
+        info.push('\n');
+        info.push_str(&frame.info_str());
+    }
+    match image.scans() {
+        Ok(scans) if !scans.is_empty() => {
+            write!(info, "\nNumber of scans:         {}", scans.len()).unwrap();
+        }
+        Ok(_) | Err(_) => {}
+    }
+    if let Err(error) = image.check_restart_markers() {
+        write!(info, "\nWarning: {error}").unwrap();
+    }
+    if let Ok(quality) = image.estimate_quality() {
+        write!(info, "\nEstimated quality:       {quality}").unwrap();
+    }
+    if let Ok(exif) = image.get_exif() {
+        let settings = exif.camera_settings().info_str();
+        if !settings.is_empty() {
+            info.push('\n');
+            info.push_str(&settings);
+        }
+    }
+    if let Ok(profile) = image.get_icc_profile() {
+        info.push('\n');
+        info.push_str(&profile.info_str());
+    }
+    match (
+        gain_map_metadata(image, gain_map),
+        iso_gain_map_metadata(image, gain_map),
+    ) {
+        (Ok(metadata), iso_metadata) => {
+            info.push('\n');
+            info.push_str(&metadata.info_str());
+            let mismatches = iso_metadata
+                .map(|iso| return metadata.differences(&iso))
+                .unwrap_or_default();
+            if !mismatches.is_empty() {
+                info.push_str("\nGain map ISO 21496-1 metadata differs from XMP in: ");
+                info.push_str(&mismatches.join(", "));
+            }
+        }
+        (Err(_), Ok(metadata)) => {
+            info.push('\n');
+            info.push_str(&metadata.info_str());
+        }
+        (Err(_), Err(_)) => {}
+    }
+    match image_gain_map_scale(image, gain_map) {
+        Ok(scale) => {
+            write!(info, "\nGain map scale:          1/{scale}").unwrap();
+        }
+        Err(error @ GCameraError::IncompatibleGainMapSize { .. }) => {
+            info.push('\n');
+            info.push_str(&error.to_string());
+        }
+        Err(_) => {}
+    }
+    if let Ok((_, index)) = image.get_mpf_index() {
+        info.push('\n');
+        info.push_str(&index.info_str());
+    }
+    return info;
+}
+
+/// Get the metadata of the `UltraHDR` gain map of an image.
+///
+/// The metadata is read from the XMP data of the gain map JPEG, and then
+/// from the XMP data of the primary image.
+///
+/// # Arguments
+/// * `image`: The primary JPEG image.
+/// * `gain_map`: The bytes of the gain map JPEG, if there is one.
+///
+/// # Returns
+/// The gain map metadata.
+///
+/// # Errors
+/// Will error if the gain map JPEG cannot be parsed, or the image does not
+/// have valid gain map metadata.
+fn gain_map_metadata(
+    image: &JpegImage,
+    gain_map: Option<&[u8]>,
+) -> Result<GainMapMetadata, GCameraError> {
+    let gain_map_xmp = match gain_map {
+        Some(data) => JpegImage::try_from(data)?.get_xmp().ok(),
+        None => None,
+    };
+    let primary_xmp = image.get_xmp().ok();
+
+    let trees: Vec<&XMPTree> = [gain_map_xmp.as_ref(), primary_xmp.as_ref()]
+        .into_iter()
+        .flatten()
+        .map(|xmp| return &xmp.tree)
+        .collect();
+    return GainMapMetadata::from_trees(&trees);
+}
+
+/// Check that the `UltraHDR` gain map of an image fits the primary image.
+///
+/// # Arguments
+/// * `image`: The primary JPEG image.
+/// * `gain_map`: The bytes of the gain map JPEG, if there is one.
+///
+/// # Returns
+/// The factor the gain map is scaled up by to cover the primary image.
+///
+/// # Errors
+/// Will error if there is no gain map, either frame header cannot be read,
+/// or the sizes of the two images are not compatible.
+fn image_gain_map_scale(image: &JpegImage, gain_map: Option<&[u8]>) -> Result<u16, GCameraError> {
+    let data = gain_map.ok_or(GCameraError::NoResourcesOfType {
+        semantic_type: SemanticType::GainMap,
+    })?;
+    let gain_map_frame = JpegImage::try_from(data)?.get_frame_header()?;
+    return gain_map_scale(&image.get_frame_header()?, &gain_map_frame);
+}
+
+/// Get the ISO 21496-1 gain map metadata of the `UltraHDR` gain map of an
+/// image.
+///
+/// The binary metadata is read from the APP2 segments of the gain map JPEG,
+/// and then from those of the primary image.
+///
+/// # Arguments
+/// * `image`: The primary JPEG image.
+/// * `gain_map`: The bytes of the gain map JPEG, if there is one.
+///
+/// # Returns
+/// The gain map metadata.
+///
+/// # Errors
+/// Will error if the gain map JPEG cannot be parsed, or the image does not
+/// have valid ISO 21496-1 gain map metadata.
+fn iso_gain_map_metadata(
+    image: &JpegImage,
+    gain_map: Option<&[u8]>,
+) -> Result<GainMapMetadata, GCameraError> {
+    if let Some(data) = gain_map {
+        match JpegImage::try_from(data)?.get_iso_gain_map_metadata() {
+            Err(GCameraError::NoGainMapMetadata) => {}
+            result => return result,
+        }
+    }
+    return image.get_iso_gain_map_metadata();
+}
+
+/// Get a string list of the additional resources of an image.
+///
+/// # Arguments
+/// * `resources`: The size and semantic type of each resource.
+///
+/// # Returns
+/// A string containing a list of the additional resources in the file.
+pub fn resource_list<I: IntoIterator<Item = (usize, SemanticType)>>(resources: I) -> String {
+    let mut resource_str = String::new();
+    resource_str.push_str("Additional Resources:\n");
+    for (index, (size, semantic)) in resources.into_iter().enumerate() {
+        writeln!(
+            resource_str,
+            "\tResource {index} has a size of {size} and is of type '{semantic:?}'"
+        )
+        .unwrap();
+    }
+    return resource_str;
+}
+
+/// Remove the location and identifying data from an embedded JPEG image.
+///
+/// # Arguments
+/// * `data`: The bytes of the embedded image, which are replaced by the
+///   scrubbed image. Data that is not a JPEG image is left unchanged.
+///
+/// # Returns
+/// A description of each item that was removed.
+///
+/// # Errors
+/// Will error if the EXIF or XMP data of the image cannot be parsed, or
+/// cannot be stored in the image again.
+fn scrub_embedded_image(data: &mut Vec<u8>) -> Result<Vec<String>, GCameraError> {
+    let Ok(mut image) = JpegImage::try_from(data.as_slice()) else {
+        return Ok(Vec::new());
+    };
+    let removed = image.scrub()?;
+    if !removed.is_empty() {
+        *data = image.as_bytes();
+    }
+    return Ok(removed);
+}
+
+/// Find where each resource listed in the XMP data is stored.
+///
+/// Legacy motion photos, which give the offset of the video with
+/// `GCamera:MicroVideoOffset` instead of using a `Container:Directory`, are
+/// also supported.
 ///
 /// # Arguments
 /// * `xmp`: The `XMPData` to parse to find the resources.
-/// * `bytes`: The bytes to extract resources from
+/// * `total_size`: The size of the whole file.
 ///
 /// # Returns
-/// Tuple where the first element is a vector of all non-primary resources.
-/// and the second element is the offset where the resources start.
-fn get_resources_from_xmp(xmp: &XMPData, bytes: &[u8]) -> (Vec<Resource>, usize) {
-    let mut resources: Vec<Resource> = Vec::with_capacity(xmp.resources.len());
+/// Tuple where the first element is the byte range of each non-primary
+/// resource, and the second element is the offset where the resources start.
+///
+/// # Errors
+/// Will error if the micro video offset is larger than the image, if a
+/// resource has no length, or if the lengths of the resources add up to
+/// more than the size of the image. These errors give the offset where the
+/// resource ends.
+pub fn get_resource_ranges_from_xmp(
+    xmp: &XMPData,
+    total_size: usize,
+) -> Result<(Vec<ResourceRange>, usize), GCameraError> {
+    if xmp.resources.is_empty() {
+        if let Some(offset) = xmp.description.micro_video_offset() {
+            let video_start =
+                total_size
+                    .checked_sub(offset)
+                    .ok_or(GCameraError::InvalidMicroVideoOffset {
+                        offset,
+                        size: total_size,
+                    })?;
+            let resource = ResourceRange {
+                info: Item {
+                    mimetype: MimeType::Mp4,
+                    length: Some(offset),
+                    padding: 0,
+                    semantic: SemanticType::MotionPhoto,
+                    label: None,
+                    uri: None,
+                },
+                range: video_start..total_size,
+            };
+            return Ok((vec![resource], video_start));
+        }
+    }
+
+    let mut resources: Vec<ResourceRange> = Vec::with_capacity(xmp.resources.len());
     // Accumulator that starts at file end. We will iterate over
     // resources from XMP backwards and use each resource's length and
     // padding members to compute the start of the resource.
-    let mut length_accumulator = bytes.len();
+    let mut length_accumulator = total_size;
     for resource in xmp.resources.iter().rev() {
         // data chunk ends at the previous accumulator values.
         if resource.semantic != SemanticType::Primary {
             let data_end = length_accumulator;
-            length_accumulator -= resource.length.unwrap();
-            resources.push(Resource {
-                data: Vec::from(&bytes[length_accumulator..data_end]),
+            let length = resource.length.ok_or(GCameraError::InvalidLength {
+                structure: "resource",
+                offset: data_end,
+            })?;
+            length_accumulator =
+                data_end
+                    .checked_sub(length)
+                    .ok_or(GCameraError::TruncatedData {
+                        structure: "resource",
+                        offset: data_end,
+                    })?;
+            resources.push(ResourceRange {
                 info: resource.clone(),
+                range: length_accumulator..data_end,
             });
 
             // Account for any data padding.
-            length_accumulator -= resource.padding;
+            length_accumulator = length_accumulator.checked_sub(resource.padding).ok_or(
+                GCameraError::TruncatedData {
+                    structure: "resource padding",
+                    offset: length_accumulator,
+                },
+            )?;
         }
     }
     // Get resources back into correct order when re
-    return (resources.into_iter().rev().collect(), length_accumulator);
+    return Ok((resources.into_iter().rev().collect(), length_accumulator));
+}
+
+/// Get the position and length of each JPEG resource stored after the image.
+///
+/// # Arguments
+/// * `resources`: The resources of the image.
+/// * `resources_start`: The offset where the resources start.
+///
+/// # Returns
+/// The offset in the file and the length of each JPEG resource.
+fn image_ranges(resources: &[Resource], resources_start: usize) -> Vec<(usize, usize)> {
+    let mut position = resources_start;
+    let mut ranges = Vec::new();
+    for resource in resources {
+        position += resource.info.padding;
+        if resource.info.mimetype == MimeType::Jpeg {
+            ranges.push((position, resource.size()));
+        }
+        position += resource.size();
+    }
+    return ranges;
+}
+
+/// Update the MPF index so it points at where the images were written.
+///
+/// The primary image is given the size of the JPEG image, and the secondary
+/// images are matched with the JPEG resources in the order they are stored.
+/// The index must list one secondary image for each JPEG resource.
+///
+/// # Arguments
+/// * `bytes`: The bytes of the file.
+/// * `position`: The offset of the TIFF header of the index in the file.
+/// * `index`: The MPF index, as it was parsed.
+/// * `primary_size`: The size of the primary JPEG image.
+/// * `images`: The offset and length of each JPEG resource in the file.
+///
+/// # Errors
+/// Will error if the index is not within the bytes, it does not list one
+/// secondary image for each JPEG resource, an image is written before the
+/// index, or an image cannot be described by the 32-bit values of the index.
+fn update_mpf_index(
+    bytes: &mut [u8],
+    position: usize,
+    index: &MpIndex,
+    primary_size: usize,
+    images: &[(usize, usize)],
+) -> Result<(), GCameraError> {
+    let data = bytes
+        .get_mut(position..)
+        .ok_or(GCameraError::InvalidMpfIndex)?;
+    let mut secondary: Vec<(usize, u32)> = index
+        .entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| return entry.offset != 0)
+        .map(|(entry_index, entry)| return (entry_index, entry.offset))
+        .collect();
+    secondary.sort_unstable_by_key(|&(_, offset)| return offset);
+    if secondary.len() != images.len() {
+        return Err(GCameraError::InvalidMpfIndex);
+    }
+
+    let primary = index
+        .entries
+        .iter()
+        .position(|entry| return entry.offset == 0)
+        .map(|entry_index| return (entry_index, 0, primary_size));
+    let secondary_updates = secondary
+        .into_iter()
+        .zip(images)
+        .map(|((entry_index, _), &(start, size))| {
+            // Offsets are relative to the index, so an image cannot be before it.
+            return start
+                .checked_sub(position)
+                .map(|offset| return (entry_index, offset, size))
+                .ok_or(GCameraError::InvalidMpfIndex);
+        })
+        .collect::<Result<Vec<(usize, usize, usize)>, GCameraError>>()?;
+    for (entry_index, offset, size) in primary.into_iter().chain(secondary_updates) {
+        let invalid = |_| return GCameraError::InvalidMpfIndex;
+        index.write_entry(
+            data,
+            entry_index,
+            u32::try_from(size).map_err(invalid)?,
+            u32::try_from(offset).map_err(invalid)?,
+        )?;
+    }
+    return Ok(());
 }
 
 // Implementation of TryFrom for CameraImage
@@ -218,24 +1154,7 @@ impl TryFrom<&[u8]> for CameraImage {
     /// # Returns
     /// Result holding the created instance, or an error message
     fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
-        if bytes[0..2] != vec![0xFF, 0xD8] {
-            return Err(GCameraError::InvalidJpegMagic);
-        }
-        let image = JpegImage::try_from(bytes)?;
-        let (resources, resources_start) = match image.get_xmp() {
-            // TODO: Test case for OK
-            Ok(xmp_data) => get_resources_from_xmp(&xmp_data, bytes),
-            Err(_) => (Vec::new(), bytes.len()),
-        };
-
-        let debug_components = DebugComponents::from(&bytes[image.image_size()..resources_start]);
-
-        return Ok(Self {
-            image,
-            debug_components,
-            resources,
-            total_size: bytes.len(),
-        });
+        return Self::from_reader(&mut Cursor::new(bytes));
     }
 }
 
@@ -251,8 +1170,13 @@ impl TryFrom<Vec<u8>> for CameraImage {
 mod test {
     use crate::{
         debug_components::DebugChunk,
-        jpeg::{jpeg_components::JpegSegment, xmp::MimeType},
+        jpeg::{
+            exif::EXIF_MARKER, jpeg_components::JpegSegment, mpf::MPF_MARKER,
+            xmp::create_xmp_segment,
+        },
     };
+    use std::env;
+    use std::fs;
 
     use super::*;
 
@@ -286,7 +1210,7 @@ mod test {
             },
             resources: vec![
                 Resource {
-                    data: vec![0x01, 0x02],
+                    data: ResourceData::Loaded(vec![0x01, 0x02]),
                     info: Item {
                         mimetype: MimeType::Mp4,
                         length: Some(2),
@@ -298,7 +1222,7 @@ mod test {
                     },
                 },
                 Resource {
-                    data: vec![0x03, 0x04],
+                    data: ResourceData::Loaded(vec![0x03, 0x04]),
                     info: Item {
                         mimetype: MimeType::Jpeg,
                         length: Some(2),
@@ -362,6 +1286,20 @@ mod test {
         assert_eq!(function_result, Err(GCameraError::InvalidJpegMagic));
     }
 
+    /// Test that debug data that is not valid gives its offset in the file.
+    #[test]
+    fn test_invalid_debug_data_offset() {
+        let bytes = [
+            [0xFF, 0xD8, 0xFF, 0xD9].as_slice(),
+            "awbDebug ghi afDebug def".as_bytes(),
+        ]
+        .concat();
+        assert_eq!(
+            CameraImage::try_from(bytes.as_slice()),
+            Err(GCameraError::DebugChunkOutOfOrder { offset: 17 })
+        );
+    }
+
     /// Test the `get_debug_info` function
     #[test]
     fn test_get_debug_info() {
@@ -403,7 +1341,7 @@ Additional Resources:
         assert_eq!(
             resource,
             Ok(&Resource {
-                data: vec![0x03, 0x04],
+                data: ResourceData::Loaded(vec![0x03, 0x04]),
                 info: Item {
                     mimetype: MimeType::Jpeg,
                     length: Some(2),
@@ -428,7 +1366,7 @@ Additional Resources:
         );
     }
 
-    /// Test the `get_resources_from_xmp` method
+    /// Test the `get_resource_ranges_from_xmp` function.
     #[test]
     fn test_get_resources_from_xmp() {
         let xmp = XMPData::try_from(String::from(
@@ -478,13 +1416,13 @@ Additional Resources:
             0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x00, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E,
         ];
 
-        let (resources, resource_start_point) = get_resources_from_xmp(&xmp, &test_bytes);
+        let (resources, resource_start_point) =
+            get_resource_ranges_from_xmp(&xmp, test_bytes.len()).unwrap();
 
         assert_eq!(
             resources,
             vec![
-                Resource {
-                    data: vec![0x05, 0x06, 0x07, 0x08],
+                ResourceRange {
                     info: Item {
                         mimetype: MimeType::Mp4,
                         length: Some(4),
@@ -492,10 +1430,10 @@ Additional Resources:
                         semantic: SemanticType::MotionPhoto,
                         label: None,
                         uri: None
-                    }
+                    },
+                    range: 4..8,
                 },
-                Resource {
-                    data: vec![0x0A, 0x0B, 0x0C, 0x0D, 0x0E],
+                ResourceRange {
                     info: Item {
                         mimetype: MimeType::Jpeg,
                         length: Some(5),
@@ -503,10 +1441,701 @@ Additional Resources:
                         semantic: SemanticType::GainMap,
                         label: None,
                         uri: None
-                    }
+                    },
+                    range: 9..14,
                 }
             ]
         );
+        assert_eq!(test_bytes[9..14], [0x0A, 0x0B, 0x0C, 0x0D, 0x0E]);
         assert_eq!(resource_start_point, 4);
     }
+
+    /// Test the `get_resource_ranges_from_xmp` function with a legacy micro
+    /// video image.
+    #[test]
+    fn test_get_resources_from_xmp_micro_video() {
+        let xmp = XMPData::try_from(String::from(
+            "<x:xmpmeta xmlns:x='adobe:ns:meta/'>
+                <rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'>
+                    <rdf:Description rdf:about=''
+                    xmlns:GCamera='http://ns.google.com/photos/1.0/camera/'
+                    GCamera:MicroVideo='1'
+                    GCamera:MicroVideoVersion='1'
+                    GCamera:MicroVideoOffset='3'
+                    GCamera:MicroVideoPresentationTimestampUs='-1'/>
+                </rdf:RDF>
+                </x:xmpmeta>",
+        ))
+        .unwrap();
+
+        let test_bytes = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
+
+        assert_eq!(
+            get_resource_ranges_from_xmp(&xmp, test_bytes.len()),
+            Ok((
+                vec![ResourceRange {
+                    info: Item {
+                        mimetype: MimeType::Mp4,
+                        length: Some(3),
+                        padding: 0,
+                        semantic: SemanticType::MotionPhoto,
+                        label: None,
+                        uri: None
+                    },
+                    range: 3..6,
+                }],
+                3
+            ))
+        );
+        assert_eq!(
+            get_resource_ranges_from_xmp(&xmp, 2),
+            Err(GCameraError::InvalidMicroVideoOffset { offset: 3, size: 2 })
+        );
+    }
+
+    /// Test converting a legacy micro video image to the container format.
+    #[test]
+    fn test_convert_micro_video() {
+        let xmp = "<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:GCamera='http://ns.google.com/photos/1.0/camera/' GCamera:MicroVideo='1' GCamera:MicroVideoVersion='1' GCamera:MicroVideoOffset='4' GCamera:MicroVideoPresentationTimestampUs='-1'/></rdf:RDF></x:xmpmeta>";
+        let bytes = [
+            vec![0xFF, 0xD8],
+            create_xmp_segment(xmp).unwrap().as_bytes(),
+            vec![0xFF, 0xD9],
+            String::from("afDebugbye").into_bytes(),
+            vec![0x01, 0x02, 0x03, 0x04],
+        ]
+        .concat();
+
+        let mut image = CameraImage::try_from(bytes.as_slice()).unwrap();
+        image.convert_micro_video().unwrap();
+        let converted_bytes = image.as_bytes(&mut Cursor::new(bytes.as_slice())).unwrap();
+        let converted = CameraImage::try_from(converted_bytes.as_slice()).unwrap();
+
+        assert_eq!(converted.image, image.image);
+        assert_eq!(image.total_size, converted_bytes.len());
+        assert_eq!(converted.total_size, converted_bytes.len());
+        let mut video = Vec::new();
+        converted
+            .get_resource_by_type(SemanticType::MotionPhoto)
+            .unwrap()
+            .copy(&mut Cursor::new(converted_bytes.as_slice()), &mut video)
+            .unwrap();
+        assert_eq!(video, vec![0x01, 0x02, 0x03, 0x04]);
+        assert_eq!(converted.get_xmp().unwrap().resources.len(), 2);
+        assert_eq!(converted.debug_components.size(), 10);
+
+        let mut converted_again = converted;
+        assert_eq!(
+            converted_again.convert_micro_video(),
+            Err(GCameraError::NotMicroVideo)
+        );
+    }
+
+    /// Test that the motion photo video is left in the file, and copied from
+    /// it when the image or the video is saved.
+    #[test]
+    fn test_motion_video_stored() {
+        let xmp = "<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:GCamera='http://ns.google.com/photos/1.0/camera/' GCamera:MicroVideo='1' GCamera:MicroVideoVersion='1' GCamera:MicroVideoOffset='4'/></rdf:RDF></x:xmpmeta>";
+        let bytes = [
+            vec![0xFF, 0xD8],
+            create_xmp_segment(xmp).unwrap().as_bytes(),
+            vec![0xFF, 0xD9],
+            String::from("afDebugbye").into_bytes(),
+            vec![0x01, 0x02, 0x03, 0x04],
+        ]
+        .concat();
+
+        let image = CameraImage::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(
+            image.resources[0].data,
+            ResourceData::Stored((bytes.len() - 4)..bytes.len())
+        );
+        assert_eq!(
+            image.as_bytes(&mut Cursor::new(bytes.as_slice())),
+            Ok(bytes.clone())
+        );
+        assert_eq!(
+            image.as_bytes(&mut Cursor::new(&bytes[..(bytes.len() - 1)])),
+            Err(GCameraError::ImageWriteError {
+                kind: io::ErrorKind::UnexpectedEof
+            })
+        );
+
+        let path = env::temp_dir().join("gcamera_tools_test_motion_video_stored.mp4");
+        image
+            .save_motion_video(&mut Cursor::new(bytes.as_slice()), path.clone())
+            .unwrap();
+        let saved = fs::read(&path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(saved, vec![0x01, 0x02, 0x03, 0x04]);
+    }
+
+    /// Test removing the private data from a motion photo.
+    #[test]
+    fn test_scrub() {
+        let xmp = "<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:exif='http://ns.adobe.com/exif/1.0/' xmlns:GCamera='http://ns.google.com/photos/1.0/camera/' exif:GPSLatitude='52,22.0N' GCamera:MicroVideo='1' GCamera:MicroVideoVersion='1' GCamera:MicroVideoOffset='4'/></rdf:RDF></x:xmpmeta>";
+        let bytes = [
+            vec![0xFF, 0xD8],
+            create_xmp_segment(xmp).unwrap().as_bytes(),
+            vec![0xFF, 0xD9],
+            String::from("afDebugbye").into_bytes(),
+            vec![0x01, 0x02, 0x03, 0x04],
+        ]
+        .concat();
+
+        let mut kept = CameraImage::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(
+            kept.scrub(true),
+            Ok(vec![
+                String::from("XMP property exif:GPSLatitude"),
+                String::from("Debug data afDebug (10 bytes)"),
+            ])
+        );
+        let kept_bytes = kept.as_bytes(&mut Cursor::new(bytes.as_slice())).unwrap();
+        let reparsed = CameraImage::try_from(kept_bytes.as_slice()).unwrap();
+        assert_eq!(reparsed.image, kept.image);
+        assert_eq!(reparsed.total_size, kept.total_size);
+        assert!(kept_bytes.ends_with(&[0xFF, 0xD9, 0x01, 0x02, 0x03, 0x04]));
+
+        let mut image = CameraImage::try_from(bytes.as_slice()).unwrap();
+        let removed = image.scrub(false).unwrap();
+        assert_eq!(removed.len(), 6);
+        assert_eq!(removed.last().unwrap(), "Motion photo video (4 bytes)");
+
+        let scrubbed_bytes = image.as_bytes(&mut Cursor::new(bytes.as_slice())).unwrap();
+        let scrubbed = CameraImage::try_from(scrubbed_bytes.as_slice()).unwrap();
+        assert_eq!(scrubbed, image);
+        assert!(scrubbed.resources.is_empty());
+        assert!(scrubbed_bytes.ends_with(&[0xFF, 0xD9]));
+        assert_eq!(scrubbed.total_size, scrubbed_bytes.len());
+    }
+
+    /// Test removing the location from the gain map and the original image.
+    #[test]
+    fn test_scrub_embedded_images() {
+        let location_xmp = "<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:exif='http://ns.adobe.com/exif/1.0/' exif:GPSLatitude='52,22.0N'/></rdf:RDF></x:xmpmeta>";
+        let embedded = [
+            vec![0xFF, 0xD8],
+            create_xmp_segment(location_xmp).unwrap().as_bytes(),
+            vec![0xFF, 0xD9],
+        ]
+        .concat();
+        let primary_xmp = format!("<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:GImage='http://ns.google.com/photos/1.0/image/' xmlns:Container='http://ns.google.com/photos/1.0/container/' xmlns:Item='http://ns.google.com/photos/1.0/container/item/' GImage:Mime='image/jpeg' GImage:Data='{}'><Container:Directory><rdf:Seq><rdf:li rdf:parseType='Resource'><Container:Item Item:Semantic='Primary' Item:Mime='image/jpeg'/></rdf:li><rdf:li rdf:parseType='Resource'><Container:Item Item:Semantic='GainMap' Item:Mime='image/jpeg' Item:Length='{}'/></rdf:li></rdf:Seq></Container:Directory></rdf:Description></rdf:RDF></x:xmpmeta>", STANDARD.encode(&embedded), embedded.len());
+        let bytes = [
+            vec![0xFF, 0xD8],
+            create_xmp_segment(&primary_xmp).unwrap().as_bytes(),
+            vec![0xFF, 0xD9],
+            embedded,
+        ]
+        .concat();
+
+        let mut image = CameraImage::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(
+            image.scrub(false),
+            Ok(vec![
+                String::from("Original image XMP property exif:GPSLatitude"),
+                String::from("GainMap image XMP property exif:GPSLatitude"),
+            ])
+        );
+
+        let scrubbed_bytes = image.as_bytes(&mut Cursor::new(bytes.as_slice())).unwrap();
+        let scrubbed = CameraImage::try_from(scrubbed_bytes.as_slice()).unwrap();
+        assert_eq!(scrubbed, image);
+        assert_eq!(scrubbed.resources.len(), 1);
+        let gain_map = scrubbed.gain_map_data().unwrap();
+        assert!(!gain_map
+            .windows(11)
+            .any(|window| return window == b"GPSLatitude"));
+        let original = scrubbed.get_original_image().unwrap();
+        assert!(!original
+            .data
+            .windows(11)
+            .any(|window| return window == b"GPSLatitude"));
+        assert_eq!(scrubbed.resources[0].info.length, Some(gain_map.len()));
+    }
+
+    /// Test reading the original image of a lens blur photo.
+    #[test]
+    fn test_original_image() {
+        let xmp = "<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:GImage='http://ns.google.com/photos/1.0/image/' GImage:Mime='image/jpeg' GImage:Data='/9j/4A=='/></rdf:RDF></x:xmpmeta>";
+        let bytes = [
+            vec![0xFF, 0xD8],
+            create_xmp_segment(xmp).unwrap().as_bytes(),
+            vec![0xFF, 0xD9],
+        ]
+        .concat();
+
+        let image = CameraImage::try_from(bytes.as_slice()).unwrap();
+
+        assert_eq!(
+            image.get_original_image(),
+            Ok(OriginalImage {
+                mimetype: MimeType::Jpeg,
+                data: vec![0xFF, 0xD8, 0xFF, 0xE0],
+            })
+        );
+        assert!(image.resources.is_empty());
+        assert_eq!(
+            image.as_bytes(&mut Cursor::new(bytes.as_slice())).unwrap(),
+            bytes
+        );
+    }
+
+    /// Test reading the gain map metadata from the primary image and gain map.
+    #[test]
+    #[allow(clippy::float_cmp)] // The values are parsed from exact decimal strings
+    fn test_gain_map_metadata() {
+        let gain_map_xmp = "<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:hdrgm='http://ns.adobe.com/hdr-gain-map/1.0/' hdrgm:Version='1.0' hdrgm:GainMapMax='2.5' hdrgm:HDRCapacityMax='2.3'/></rdf:RDF></x:xmpmeta>";
+        let gain_map = [
+            vec![0xFF, 0xD8],
+            create_xmp_segment(gain_map_xmp).unwrap().as_bytes(),
+            vec![0xFF, 0xD9],
+        ]
+        .concat();
+        let primary_xmp = format!("<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:hdrgm='http://ns.adobe.com/hdr-gain-map/1.0/' xmlns:Container='http://ns.google.com/photos/1.0/container/' xmlns:Item='http://ns.google.com/photos/1.0/container/item/' hdrgm:Version='1.0'><Container:Directory><rdf:Seq><rdf:li rdf:parseType='Resource'><Container:Item Item:Semantic='Primary' Item:Mime='image/jpeg'/></rdf:li><rdf:li rdf:parseType='Resource'><Container:Item Item:Semantic='GainMap' Item:Mime='image/jpeg' Item:Length='{}'/></rdf:li></rdf:Seq></Container:Directory></rdf:Description></rdf:RDF></x:xmpmeta>", gain_map.len());
+        let bytes = [
+            vec![0xFF, 0xD8],
+            create_xmp_segment(&primary_xmp).unwrap().as_bytes(),
+            vec![0xFF, 0xD9],
+            gain_map,
+        ]
+        .concat();
+
+        let image = CameraImage::try_from(bytes.as_slice()).unwrap();
+        let metadata = image.get_gain_map_metadata().unwrap();
+
+        assert_eq!(metadata.gain_map_max, [2.5; 3]);
+        assert_eq!(metadata.hdr_capacity_max, 2.3);
+        assert!(image.get_debug_info().ends_with(&format!(
+            "Number of resources:     1\n{}",
+            metadata.info_str()
+        )));
+        assert_eq!(
+            CameraImage::try_from(vec![0xFF, 0xD8, 0xFF, 0xD9])
+                .unwrap()
+                .get_gain_map_metadata(),
+            Err(GCameraError::NoGainMapMetadata)
+        );
+    }
+
+    /// Test comparing the ISO 21496-1 gain map metadata with the XMP copy.
+    #[test]
+    fn test_gain_map_mismatches() {
+        let gain_map_xmp = "<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:hdrgm='http://ns.adobe.com/hdr-gain-map/1.0/' hdrgm:Version='1.0' hdrgm:GainMapMax='2.5' hdrgm:HDRCapacityMax='2.5'/></rdf:RDF></x:xmpmeta>";
+        let mut iso = "urn:iso:std:iso:ts:21496:-1\0".as_bytes().to_vec();
+        iso.extend([0x00, 0x00, 0x00, 0x00, 0x08]);
+        // Common denominator, headroom, then min, max, gamma and offsets.
+        let values: [u32; 8] = [4, 0, 10, 0, 10, 4, 0, 0];
+        for value in values {
+            iso.extend(value.to_be_bytes());
+        }
+        let gain_map = [
+            vec![0xFF, 0xD8],
+            create_xmp_segment(gain_map_xmp).unwrap().as_bytes(),
+            vec![0xFF, 0xE2],
+            u16::try_from(iso.len() + 2).unwrap().to_be_bytes().to_vec(),
+            iso,
+            vec![0xFF, 0xD9],
+        ]
+        .concat();
+        let primary_xmp = format!("<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:Container='http://ns.google.com/photos/1.0/container/' xmlns:Item='http://ns.google.com/photos/1.0/container/item/'><Container:Directory><rdf:Seq><rdf:li rdf:parseType='Resource'><Container:Item Item:Semantic='Primary' Item:Mime='image/jpeg'/></rdf:li><rdf:li rdf:parseType='Resource'><Container:Item Item:Semantic='GainMap' Item:Mime='image/jpeg' Item:Length='{}'/></rdf:li></rdf:Seq></Container:Directory></rdf:Description></rdf:RDF></x:xmpmeta>", gain_map.len());
+        let bytes = [
+            vec![0xFF, 0xD8],
+            create_xmp_segment(&primary_xmp).unwrap().as_bytes(),
+            vec![0xFF, 0xD9],
+            gain_map,
+        ]
+        .concat();
+
+        let image = CameraImage::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(image.get_iso_gain_map_metadata().unwrap().version, "0.0");
+        assert_eq!(
+            image.get_gain_map_mismatches(),
+            Ok(vec!["OffsetSDR", "OffsetHDR"])
+        );
+        assert!(image.get_debug_info().ends_with(
+            "\nGain map ISO 21496-1 metadata differs from XMP in: OffsetSDR, OffsetHDR"
+        ));
+    }
+
+    /// Test images whose resource lengths do not fit in the file.
+    #[test]
+    fn test_invalid_resource_lengths() {
+        let image = |item: &str| {
+            let xmp = format!("<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:Container='http://ns.google.com/photos/1.0/container/' xmlns:Item='http://ns.google.com/photos/1.0/container/item/'><Container:Directory><rdf:Seq><rdf:li rdf:parseType='Resource'><Container:Item Item:Semantic='Primary' Item:Mime='image/jpeg'/></rdf:li><rdf:li rdf:parseType='Resource'><Container:Item Item:Semantic='GainMap' Item:Mime='image/jpeg' {item}/></rdf:li></rdf:Seq></Container:Directory></rdf:Description></rdf:RDF></x:xmpmeta>");
+            return [
+                vec![0xFF, 0xD8],
+                create_xmp_segment(&xmp).unwrap().as_bytes(),
+                vec![0xFF, 0xD9, 0x01, 0x02, 0x03, 0x04],
+            ]
+            .concat();
+        };
+
+        let too_long = image("Item:Length='5000'");
+        assert_eq!(
+            CameraImage::try_from(too_long.as_slice()),
+            Err(GCameraError::TruncatedData {
+                structure: "resource",
+                offset: too_long.len(),
+            })
+        );
+
+        let overlapping = image("Item:Length='6'");
+        assert_eq!(
+            CameraImage::try_from(overlapping.as_slice()),
+            Err(GCameraError::InvalidLength {
+                structure: "resource",
+                offset: overlapping.len() - 6,
+            })
+        );
+
+        let bytes = image("");
+        assert_eq!(
+            CameraImage::try_from(bytes.as_slice()),
+            Err(GCameraError::InvalidLength {
+                structure: "resource",
+                offset: bytes.len(),
+            })
+        );
+        assert_eq!(
+            CameraImage::try_from(&bytes[..1]),
+            Err(GCameraError::InvalidJpegMagic)
+        );
+    }
+
+    /// Create a big endian MPF index, as written by `UltraHDR` encoders.
+    ///
+    /// # Arguments
+    /// * `entries`: The attribute, size and offset of each image.
+    ///
+    /// # Returns
+    /// The TIFF structure holding the index.
+    ///
+    /// # Panics
+    /// Will panic if there are too many entries for the index.
+    fn create_index(entries: &[(u32, u32, u32)]) -> Vec<u8> {
+        let count = u32::try_from(entries.len()).unwrap();
+        let mut data = vec![0x4D, 0x4D, 0x00, 0x2A, 0x00, 0x00, 0x00, 0x08, 0x00, 0x03];
+        // Version, number of images and the list of images.
+        data.extend([0xB0, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x04]);
+        data.extend("0100".as_bytes());
+        data.extend([0xB0, 0x01, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01]);
+        data.extend(count.to_be_bytes());
+        data.extend([0xB0, 0x02, 0x00, 0x07]);
+        let list_offset: u32 = 0x32;
+        data.extend((count * 16).to_be_bytes());
+        data.extend(list_offset.to_be_bytes());
+        data.extend([0x00; 4]);
+        for (attribute, size, offset) in entries {
+            data.extend(attribute.to_be_bytes());
+            data.extend(size.to_be_bytes());
+            data.extend(offset.to_be_bytes());
+            data.extend([0x00; 4]);
+        }
+        return data;
+    }
+
+    /// Create an image with an MPF index that lists a gain map.
+    ///
+    /// # Arguments
+    /// * `xmp`: XMP data to add to the primary image, if any.
+    /// * `padding`: The number of bytes between the primary image and the gain map.
+    ///
+    /// # Returns
+    /// The bytes of the image, and the bytes of the gain map.
+    fn create_mpf_image(xmp: Option<&str>, padding: usize) -> (Vec<u8>, Vec<u8>) {
+        let gain_map_xmp = "<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:hdrgm='http://ns.adobe.com/hdr-gain-map/1.0/' hdrgm:Version='1.0' hdrgm:GainMapMax='2.5' hdrgm:HDRCapacityMax='2.5'/></rdf:RDF></x:xmpmeta>";
+        let gain_map = [
+            vec![0xFF, 0xD8],
+            create_xmp_segment(gain_map_xmp).unwrap().as_bytes(),
+            vec![0xFF, 0xD9],
+        ]
+        .concat();
+        let xmp_bytes = xmp.map_or(Vec::new(), |data| {
+            return create_xmp_segment(data).unwrap().as_bytes();
+        });
+
+        let index_size = create_index(&[(0, 0, 0), (0, 0, 0)]).len();
+        let position = 2 + xmp_bytes.len() + 4 + MPF_MARKER.len();
+        let primary_size = position + index_size + 2;
+        let index = create_index(&[
+            (0x2003_0000, u32::try_from(primary_size).unwrap(), 0),
+            (
+                0x0000_0000,
+                u32::try_from(gain_map.len()).unwrap(),
+                u32::try_from(primary_size + padding - position).unwrap(),
+            ),
+        ]);
+        let bytes = [
+            vec![0xFF, 0xD8],
+            xmp_bytes,
+            vec![0xFF, 0xE2],
+            u16::try_from(index_size + MPF_MARKER.len() + 2)
+                .unwrap()
+                .to_be_bytes()
+                .to_vec(),
+            MPF_MARKER.to_vec(),
+            index,
+            vec![0xFF, 0xD9],
+            vec![0x00; padding],
+            gain_map.clone(),
+        ]
+        .concat();
+        return (bytes, gain_map);
+    }
+
+    /// Test reading the gain map from the MPF index when there is no XMP data.
+    #[test]
+    fn test_mpf_resources() {
+        let (bytes, gain_map) = create_mpf_image(None, 4);
+        let image = CameraImage::try_from(bytes.as_slice()).unwrap();
+
+        assert_eq!(
+            image.resources,
+            vec![Resource {
+                info: Item {
+                    mimetype: MimeType::Jpeg,
+                    length: Some(gain_map.len()),
+                    padding: 4,
+                    semantic: SemanticType::GainMap,
+                    label: None,
+                    uri: None,
+                },
+                data: ResourceData::Loaded(gain_map),
+            }]
+        );
+        assert_eq!(
+            image.as_bytes(&mut Cursor::new(bytes.as_slice())).unwrap(),
+            bytes
+        );
+        assert!(image
+            .get_debug_info()
+            .contains("MPF image 1:             type Undefined"));
+
+        let mut reader = Cursor::new(bytes.as_slice());
+        let index = ImageIndex::from_reader(&mut reader).unwrap();
+        assert_eq!(
+            index
+                .get_resource_by_type(SemanticType::GainMap)
+                .unwrap()
+                .range,
+            (bytes.len() - image.resources[0].size())..bytes.len()
+        );
+        assert_eq!(
+            index.get_debug_info(&mut reader),
+            Ok(image.get_debug_info())
+        );
+    }
+
+    /// Test that the MPF index is used when the XMP directory disagrees with it.
+    #[test]
+    fn test_mpf_overrides_xmp() {
+        let (_, gain_map) = create_mpf_image(None, 0);
+        let xmp = format!("<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:Container='http://ns.google.com/photos/1.0/container/' xmlns:Item='http://ns.google.com/photos/1.0/container/item/'><Container:Directory><rdf:Seq><rdf:li rdf:parseType='Resource'><Container:Item Item:Semantic='Primary' Item:Mime='image/jpeg'/></rdf:li><rdf:li rdf:parseType='Resource'><Container:Item Item:Semantic='GainMap' Item:Mime='image/jpeg' Item:Length='{}'/></rdf:li></rdf:Seq></Container:Directory></rdf:Description></rdf:RDF></x:xmpmeta>", gain_map.len() - 2);
+        let (bytes, _) = create_mpf_image(Some(&xmp), 0);
+        let image = CameraImage::try_from(bytes.as_slice()).unwrap();
+
+        assert_eq!(
+            image
+                .get_resource_by_type(SemanticType::GainMap)
+                .unwrap()
+                .data,
+            ResourceData::Loaded(gain_map)
+        );
+        assert_eq!(
+            image.as_bytes(&mut Cursor::new(bytes.as_slice())).unwrap(),
+            bytes
+        );
+    }
+
+    /// Test that the MPF offsets are updated when the primary image changes size.
+    #[test]
+    fn test_mpf_offsets_updated() {
+        let (bytes, gain_map) = create_mpf_image(None, 0);
+        let mut image = CameraImage::try_from(bytes.as_slice()).unwrap();
+        image
+            .set_xmp("<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about=''/></rdf:RDF></x:xmpmeta>")
+            .unwrap();
+
+        let rewritten = image.as_bytes(&mut Cursor::new(bytes.as_slice())).unwrap();
+        let jpeg = JpegImage::try_from(rewritten.as_slice()).unwrap();
+        let (position, index) = jpeg.get_mpf_index().unwrap();
+        assert_eq!(
+            usize::try_from(index.entries[0].size).unwrap(),
+            jpeg.image_size()
+        );
+        assert_eq!(
+            position + usize::try_from(index.entries[1].offset).unwrap(),
+            jpeg.image_size()
+        );
+        assert_eq!(
+            CameraImage::try_from(rewritten)
+                .unwrap()
+                .get_resource_by_type(SemanticType::GainMap)
+                .unwrap()
+                .data,
+            ResourceData::Loaded(gain_map)
+        );
+    }
+
+    /// Test that an MPF index is not updated when its images cannot be
+    /// matched with the resources.
+    #[test]
+    fn test_mpf_index_mismatch() {
+        let (bytes, _) = create_mpf_image(None, 0);
+        let mut image = CameraImage::try_from(bytes.as_slice()).unwrap();
+        let (position, index) = image.image.get_mpf_index().unwrap();
+        let mut copy = bytes.clone();
+        assert_eq!(
+            update_mpf_index(&mut copy, position, &index, 0, &[(position - 1, 1)]),
+            Err(GCameraError::InvalidMpfIndex)
+        );
+
+        image.resources.push(image.resources[0].clone());
+        assert_eq!(
+            image.as_bytes(&mut Cursor::new(bytes.as_slice())),
+            Err(GCameraError::InvalidMpfIndex)
+        );
+    }
+
+    /// Test that saving only the JPEG image of an MPF image removes the index.
+    #[test]
+    fn test_save_image_mpf() {
+        let (bytes, _) = create_mpf_image(None, 0);
+        let image = CameraImage::try_from(bytes.as_slice()).unwrap();
+        let path = env::temp_dir().join("gcamera_tools_test_save_image_mpf.jpg");
+        image.save_image(path.clone()).unwrap();
+        let saved = fs::read(&path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(
+            JpegImage::try_from(saved.as_slice())
+                .unwrap()
+                .get_mpf_index(),
+            Err(GCameraError::NoMpfIndex)
+        );
+        let reparsed = CameraImage::try_from(saved.as_slice()).unwrap();
+        assert!(reparsed.resources.is_empty());
+        assert_eq!(reparsed.total_size, reparsed.image.image_size());
+    }
+
+    /// Create little endian EXIF data holding the camera make and model.
+    ///
+    /// # Returns
+    /// The TIFF structure of the EXIF data.
+    fn create_exif() -> Vec<u8> {
+        let mut data = vec![0x49, 0x49, 0x2A, 0x00, 0x08, 0x00, 0x00, 0x00, 0x02, 0x00];
+        // Make, stored after the IFD.
+        data.extend([
+            0x0F, 0x01, 0x02, 0x00, 0x07, 0x00, 0x00, 0x00, 0x26, 0x00, 0x00, 0x00,
+        ]);
+        // Model, stored in the entry.
+        data.extend([0x10, 0x01, 0x02, 0x00, 0x04, 0x00, 0x00, 0x00]);
+        data.extend("Pix\0".as_bytes());
+        data.extend([0x00; 4]);
+        data.extend("Google\0".as_bytes());
+        return data;
+    }
+
+    /// Test that the camera settings from the EXIF data are in the debug info.
+    #[test]
+    fn test_debug_info_exif() {
+        let exif = [EXIF_MARKER, &create_exif()].concat();
+        let bytes = [
+            vec![0xFF, 0xD8, 0xFF, 0xE1],
+            u16::try_from(exif.len() + 2)
+                .unwrap()
+                .to_be_bytes()
+                .to_vec(),
+            exif,
+            vec![0xFF, 0xD9],
+        ]
+        .concat();
+        let image = CameraImage::try_from(bytes.as_slice()).unwrap();
+
+        assert!(image.get_debug_info().contains(
+            "Number of resources:     0\nCamera make:             Google\nCamera model:            Pix"
+        ));
+    }
+
+    /// Test that editing the EXIF data keeps the gain map, and its offset in
+    /// the MPF index.
+    #[test]
+    fn test_set_exif_keeps_resources() {
+        let (bytes, gain_map) = create_mpf_image(None, 0);
+        let mut image = CameraImage::try_from(bytes.as_slice()).unwrap();
+        let mut exif = ExifData::try_from(create_exif().as_slice()).unwrap();
+        exif.set_by_name("Copyright=Jane Doe").unwrap();
+        image.set_exif(&exif).unwrap();
+
+        let rewritten =
+            CameraImage::try_from(image.as_bytes(&mut Cursor::new(bytes.as_slice())).unwrap())
+                .unwrap();
+        assert_eq!(rewritten.get_exif(), Ok(exif));
+        assert_eq!(
+            rewritten
+                .get_resource_by_type(SemanticType::GainMap)
+                .unwrap()
+                .data,
+            ResourceData::Loaded(gain_map)
+        );
+    }
+
+    /// Test that the image size and gain map scale are in the debug info.
+    #[test]
+    fn test_gain_map_scale() {
+        let image_bytes = |width: u16, height: u16| {
+            return [
+                vec![0xFF, 0xD8, 0xFF, 0xC0, 0x00, 0x11, 0x08],
+                height.to_be_bytes().to_vec(),
+                width.to_be_bytes().to_vec(),
+                vec![0x03, 0x01, 0x22, 0x00, 0x02, 0x11, 0x01, 0x03, 0x11, 0x01],
+                vec![0xFF, 0xD9],
+            ]
+            .concat();
+        };
+        let mut image = get_test_image();
+        image.image = JpegImage::try_from(image_bytes(4032, 3024).as_slice()).unwrap();
+        image.resources[1].data = ResourceData::Loaded(image_bytes(1008, 756));
+
+        assert_eq!(image.get_gain_map_scale(), Ok(4));
+        let info = image.get_debug_info();
+        assert!(info.contains("Number of resources:     2\nImage dimensions:        4032x3024\n"));
+        assert!(info.contains("Gain map scale:          1/4"));
+
+        image.resources[1].data = ResourceData::Loaded(image_bytes(1008, 1008));
+        assert!(image
+            .get_debug_info()
+            .contains("A gain map of 1008x1008 cannot be scaled to an image of 4032x3024."));
+    }
+
+    /// Test the inputs that the fuzz targets found problems with.
+    ///
+    /// Each file in `fuzz/regressions` must be parsed without panicking,
+    /// and serializing and parsing it again must give the same result.
+    #[test]
+    fn test_fuzz_regressions() {
+        let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fuzz/regressions");
+        for entry in fs::read_dir(directory).unwrap() {
+            let data = fs::read(entry.unwrap().path()).unwrap();
+
+            if let Ok(text) = String::from_utf8(data.clone()) {
+                drop(XMPData::try_from(text));
+            }
+            drop(DebugComponents::try_from(data.as_slice()));
+            if let Ok(image) = JpegImage::try_from(data.as_slice()) {
+                assert_eq!(JpegImage::try_from(image.as_bytes().as_slice()), Ok(image));
+            }
+            if let Ok(bytes) = CameraImage::try_from(data.as_slice()).and_then(|image| {
+                return image.as_bytes(&mut Cursor::new(data.as_slice()));
+            }) {
+                assert_eq!(
+                    CameraImage::try_from(bytes.as_slice()).and_then(|reparsed| {
+                        return reparsed.as_bytes(&mut Cursor::new(bytes.as_slice()));
+                    }),
+                    Ok(bytes)
+                );
+            }
+        }
+    }
 }
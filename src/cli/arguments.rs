@@ -9,10 +9,12 @@
 //! arguments from the command line.
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{ArgGroup, Parser};
 
 #[derive(Parser, Debug, Eq, PartialEq)]
 #[command(author, version, about = "Utility for working with photos take with Google Camera", long_about = None)]
+#[command(group(ArgGroup::new("exif_edit").args(["set_exif", "delete_exif"]).multiple(true)))]
+#[command(group(ArgGroup::new("thumbnail_edit").args(["remove_thumbnails", "replace_thumbnail"])))]
 pub struct Arguments {
     /// Path to the image to process
     #[arg(index = 1)]
@@ -42,6 +44,90 @@ pub struct Arguments {
     #[arg(long, requires = "save_motion")]
     pub motion_path: Option<PathBuf>,
 
+    /// Save the original image of a lens blur photo.
+    #[arg(short = 'o', long)]
+    pub save_original: bool,
+
+    /// Optional path to save the original image to.
+    #[arg(long, requires = "save_original")]
+    pub original_path: Option<PathBuf>,
+
+    /// Save the depth map of a portrait photo, and its parameters.
+    #[arg(short = 'D', long)]
+    pub save_depth: bool,
+
+    /// Optional path to save the depth map to.
+    #[arg(long, requires = "save_depth")]
+    pub depth_path: Option<PathBuf>,
+
+    /// Convert a legacy micro video motion photo to the current format.
+    #[arg(short = 'c', long)]
+    pub convert_micro_video: bool,
+
+    /// Optional path to save the converted image to.
+    #[arg(long, requires = "convert_micro_video")]
+    pub converted_path: Option<PathBuf>,
+
+    /// Set an EXIF tag, such as Orientation=1. Can be given more than once.
+    #[arg(short = 'e', long, value_name = "TAG=VALUE")]
+    pub set_exif: Vec<String>,
+
+    /// Delete an EXIF tag by name. Can be given more than once.
+    #[arg(long, value_name = "TAG")]
+    pub delete_exif: Vec<String>,
+
+    /// Optional path to save the image with the edited EXIF data to.
+    #[arg(long, requires = "exif_edit")]
+    pub edited_path: Option<PathBuf>,
+
+    /// Save the embedded EXIF and JFXX thumbnails to new files.
+    #[arg(short = 't', long)]
+    pub save_thumbnails: bool,
+
+    /// Optional path to save the thumbnails to. The source of each thumbnail is added to its name.
+    #[arg(long, requires = "save_thumbnails")]
+    pub thumbnail_path: Option<PathBuf>,
+
+    /// Remove the embedded thumbnails, and save the result to a new file.
+    #[arg(long)]
+    pub remove_thumbnails: bool,
+
+    /// Replace the embedded thumbnails with a JPEG file, and save the result to a new file.
+    #[arg(long, value_name = "THUMBNAIL")]
+    pub replace_thumbnail: Option<PathBuf>,
+
+    /// Optional path to save the image with the edited thumbnails to.
+    #[arg(long, requires = "thumbnail_edit")]
+    pub thumbnail_edit_path: Option<PathBuf>,
+
+    /// Save the embedded ICC color profile to a new file.
+    #[arg(long)]
+    pub save_icc: bool,
+
+    /// Optional path to save the ICC profile to.
+    #[arg(long, requires = "save_icc")]
+    pub icc_path: Option<PathBuf>,
+
+    /// Replace the embedded ICC color profile, and save the result to a new file.
+    #[arg(long, value_name = "PROFILE")]
+    pub replace_icc: Option<PathBuf>,
+
+    /// Optional path to save the image with the replaced ICC profile to.
+    #[arg(long, requires = "replace_icc")]
+    pub icc_edit_path: Option<PathBuf>,
+
+    /// Remove location and identifying data, and save the result to a new file.
+    #[arg(short = 's', long)]
+    pub scrub: bool,
+
+    /// Keep the motion photo video when scrubbing.
+    #[arg(long, requires = "scrub")]
+    pub keep_motion: bool,
+
+    /// Optional path to save the scrubbed image to.
+    #[arg(long, requires = "scrub")]
+    pub scrubbed_path: Option<PathBuf>,
+
     /// Print out some information about the file
     #[arg(short = 'I', long)]
     pub info: bool,
@@ -146,6 +232,57 @@ mod tests {
         );
     }
 
+    /// Test that the `original_path` arg without `save_original` fails.
+    #[test]
+    fn test_original_path_missing_flag() {
+        let input_args = vec![
+            "/bin/gcamera_tools",
+            "portrait.jpg",
+            "--original-path",
+            "original.jpg",
+        ];
+        let parsed_args = Arguments::try_parse_from(input_args);
+        assert!(parsed_args.is_err());
+        assert_eq!(
+            parsed_args.unwrap_err().kind(),
+            ErrorKind::MissingRequiredArgument
+        );
+    }
+
+    /// Test that the `depth_path` arg without `save_depth` fails.
+    #[test]
+    fn test_depth_path_missing_flag() {
+        let input_args = vec![
+            "/bin/gcamera_tools",
+            "portrait.jpg",
+            "--depth-path",
+            "depth.png",
+        ];
+        let parsed_args = Arguments::try_parse_from(input_args);
+        assert!(parsed_args.is_err());
+        assert_eq!(
+            parsed_args.unwrap_err().kind(),
+            ErrorKind::MissingRequiredArgument
+        );
+    }
+
+    /// Test that the `converted_path` arg without `convert_micro_video` fails.
+    #[test]
+    fn test_converted_path_missing_flag() {
+        let input_args = vec![
+            "/bin/gcamera_tools",
+            "motion_photo.jpg",
+            "--converted-path",
+            "converted.jpg",
+        ];
+        let parsed_args = Arguments::try_parse_from(input_args);
+        assert!(parsed_args.is_err());
+        assert_eq!(
+            parsed_args.unwrap_err().kind(),
+            ErrorKind::MissingRequiredArgument
+        );
+    }
+
     /// Test `create_output_path` when the default should be used
     #[test]
     fn test_create_output_path_default() {
@@ -169,4 +306,143 @@ mod tests {
         use clap::CommandFactory;
         Arguments::command().debug_assert();
     }
+
+    /// Test that the `edited_path` arg without an EXIF edit fails.
+    #[test]
+    fn test_edited_path_missing_flag() {
+        let input_args = vec![
+            "/bin/gcamera_tools",
+            "photo.jpg",
+            "--edited-path",
+            "edited.jpg",
+        ];
+        let parsed_args = Arguments::try_parse_from(input_args);
+        assert!(parsed_args.is_err());
+        assert_eq!(
+            parsed_args.unwrap_err().kind(),
+            ErrorKind::MissingRequiredArgument
+        );
+    }
+
+    /// Test that the EXIF edits can be repeated.
+    #[test]
+    fn test_exif_edits() {
+        let input_args = vec![
+            "/bin/gcamera_tools",
+            "photo.jpg",
+            "-e",
+            "Orientation=1",
+            "--set-exif",
+            "Copyright=Jane Doe",
+            "--delete-exif",
+            "Software",
+            "--edited-path",
+            "edited.jpg",
+        ];
+        let parsed_args = Arguments::try_parse_from(input_args).unwrap();
+        assert_eq!(
+            parsed_args.set_exif,
+            vec![
+                String::from("Orientation=1"),
+                String::from("Copyright=Jane Doe")
+            ]
+        );
+        assert_eq!(parsed_args.delete_exif, vec![String::from("Software")]);
+        assert_eq!(parsed_args.edited_path, Some(PathBuf::from("edited.jpg")));
+    }
+
+    /// Test that the `keep_motion` arg without `scrub` fails.
+    #[test]
+    fn test_keep_motion_missing_flag() {
+        let input_args = vec!["/bin/gcamera_tools", "motion_photo.jpg", "--keep-motion"];
+        let parsed_args = Arguments::try_parse_from(input_args);
+        assert!(parsed_args.is_err());
+        assert_eq!(
+            parsed_args.unwrap_err().kind(),
+            ErrorKind::MissingRequiredArgument
+        );
+    }
+
+    /// Test that the `scrubbed_path` arg without `scrub` fails.
+    #[test]
+    fn test_scrubbed_path_missing_flag() {
+        let input_args = vec![
+            "/bin/gcamera_tools",
+            "motion_photo.jpg",
+            "--scrubbed-path",
+            "shared.jpg",
+        ];
+        let parsed_args = Arguments::try_parse_from(input_args);
+        assert!(parsed_args.is_err());
+        assert_eq!(
+            parsed_args.unwrap_err().kind(),
+            ErrorKind::MissingRequiredArgument
+        );
+    }
+
+    /// Test that the `thumbnail_path` arg without `save_thumbnails` fails.
+    #[test]
+    fn test_thumbnail_path_missing_flag() {
+        let input_args = vec![
+            "/bin/gcamera_tools",
+            "photo.jpg",
+            "--thumbnail-path",
+            "thumbnail.jpg",
+        ];
+        let parsed_args = Arguments::try_parse_from(input_args);
+        assert!(parsed_args.is_err());
+        assert_eq!(
+            parsed_args.unwrap_err().kind(),
+            ErrorKind::MissingRequiredArgument
+        );
+    }
+
+    /// Test that the `thumbnail_edit_path` arg without a thumbnail edit fails.
+    #[test]
+    fn test_thumbnail_edit_path_missing_flag() {
+        let input_args = vec![
+            "/bin/gcamera_tools",
+            "photo.jpg",
+            "--thumbnail-edit-path",
+            "edited.jpg",
+        ];
+        let parsed_args = Arguments::try_parse_from(input_args);
+        assert!(parsed_args.is_err());
+        assert_eq!(
+            parsed_args.unwrap_err().kind(),
+            ErrorKind::MissingRequiredArgument
+        );
+    }
+
+    /// Test that thumbnails cannot be removed and replaced at the same time.
+    #[test]
+    fn test_thumbnail_edit_conflict() {
+        let input_args = vec![
+            "/bin/gcamera_tools",
+            "photo.jpg",
+            "--remove-thumbnails",
+            "--replace-thumbnail",
+            "thumbnail.jpg",
+        ];
+        let parsed_args = Arguments::try_parse_from(input_args);
+        assert!(parsed_args.is_err());
+        assert_eq!(parsed_args.unwrap_err().kind(), ErrorKind::ArgumentConflict);
+    }
+
+    /// Test that the `icc_path` arg without `save_icc` fails.
+    #[test]
+    fn test_icc_path_missing_flag() {
+        let input_args = vec![
+            "/bin/gcamera_tools",
+            "photo.jpg",
+            "--icc-path",
+            "profile.icc",
+        ];
+        let parsed_args = Arguments::try_parse_from(input_args);
+        assert!(parsed_args.is_err());
+        assert_eq!(
+            parsed_args.unwrap_err().kind(),
+            ErrorKind::MissingRequiredArgument
+        );
+    }
 }
@@ -10,7 +10,95 @@
 use crate::camera_image::CameraImage;
 use crate::cli::arguments::Arguments;
 use crate::errors::GCameraError;
+use crate::image_index::ImageIndex;
+use crate::jpeg::exif::ExifData;
+use crate::jpeg::tiff::ByteOrder;
 use clap::Parser;
+use std::fs;
+use std::fs::File;
+
+/// Apply the requested edits, saving each edited image to a new file.
+///
+/// The image is only loaded for an edit, and every edit loads it again from
+/// the file, so each output file only holds the changes of its own edit. The
+/// video of a motion photo is copied straight from the file when the edited
+/// image is saved.
+///
+/// # Arguments
+/// * `args`: The parsed command line arguments.
+/// * `index`: The index of the image to edit.
+/// * `file`: The file the image was indexed from.
+///
+/// # Errors
+/// Will return an error if any of the edits fail, or an edited image cannot
+/// be loaded or saved.
+fn edit_image(args: &Arguments, index: &ImageIndex, file: &mut File) -> Result<(), GCameraError> {
+    // Convert a legacy motion photo if requested
+    if args.convert_micro_video {
+        let mut edited = CameraImage::from_index(index, file)?;
+        let output_path = args.create_output_path(&args.converted_path, "converted.jpg");
+        edited.convert_micro_video()?;
+        edited.save(file, output_path)?;
+    }
+
+    // Edit the EXIF data if requested
+    if !args.set_exif.is_empty() || !args.delete_exif.is_empty() {
+        let mut edited = CameraImage::from_index(index, file)?;
+        let mut exif = match edited.get_exif() {
+            Err(GCameraError::NoExifData) => ExifData::new(ByteOrder::BigEndian),
+            result => result?,
+        };
+        for assignment in &args.set_exif {
+            exif.set_by_name(assignment)?;
+        }
+        for name in &args.delete_exif {
+            exif.remove_by_name(name)?;
+        }
+        let output_path = args.create_output_path(&args.edited_path, "edited.jpg");
+        edited.set_exif(&exif)?;
+        edited.save(file, output_path)?;
+    }
+
+    // Remove or replace the thumbnails if requested
+    if args.remove_thumbnails || args.replace_thumbnail.is_some() {
+        let mut edited = CameraImage::from_index(index, file)?;
+        if let Some(thumbnail_path) = &args.replace_thumbnail {
+            let thumbnail = fs::read(thumbnail_path)
+                .map_err(|error| return GCameraError::ImageReadError { kind: error.kind() })?;
+            edited.set_thumbnail(&thumbnail)?;
+        } else {
+            edited.remove_thumbnails()?;
+        }
+        let output_path = args.create_output_path(&args.thumbnail_edit_path, "thumbnail-edit.jpg");
+        edited.save(file, output_path)?;
+    }
+
+    // Replace the ICC profile if requested
+    if let Some(profile_path) = &args.replace_icc {
+        let mut edited = CameraImage::from_index(index, file)?;
+        let profile = fs::read(profile_path)
+            .map_err(|error| return GCameraError::ImageReadError { kind: error.kind() })?;
+        let output_path = args.create_output_path(&args.icc_edit_path, "icc-edit.jpg");
+        edited.set_icc_profile(&profile)?;
+        edited.save(file, output_path)?;
+    }
+
+    // Remove the private data if requested
+    if args.scrub {
+        let mut edited = CameraImage::from_index(index, file)?;
+        let output_path = args.create_output_path(&args.scrubbed_path, "scrubbed.jpg");
+        let removed = edited.scrub(args.keep_motion)?;
+        edited.save(file, output_path)?;
+        if removed.is_empty() {
+            println!("Nothing was removed");
+        }
+        for item in removed {
+            println!("Removed {item}");
+        }
+    }
+
+    return Ok(());
+}
 
 /// Main function to be called when running the tool.
 ///
@@ -20,32 +108,83 @@ pub fn tool_main() -> Result<(), GCameraError> {
     // Parse command line arguments
     let args = Arguments::parse();
 
-    let image = CameraImage::from_file(&args.input_path)?;
+    // Index the image, so that everything that is only read is taken
+    // straight from the file, without loading the whole image.
+    let mut file = File::open(&args.input_path)
+        .map_err(|error| return GCameraError::ImageReadError { kind: error.kind() })?;
+    let index = ImageIndex::from_reader(&mut file)?;
 
-    // Save the JPEG image if requested
-    if args.save_image {
-        let output_path = args.create_output_path(&args.image_path, "image.jpg");
-        image.save_image(output_path)?;
+    // Print the information before any edits are made
+    if args.info {
+        index.print_debug_info(&mut file)?;
+    }
+
+    if args.list_resources {
+        index.print_resource_list();
     }
 
     // Save the debug data if requested.
     if args.save_debug {
         let output_path = args.create_output_path(&args.debug_path, "debug.bin");
-        image.save_debug_data(output_path)?;
+        let mut output = File::create(output_path)
+            .map_err(|error| return GCameraError::DebugDataWriteError { kind: error.kind() })?;
+        index.copy_debug_data(&mut file, &mut output)?;
     }
+
     // Save the motion photo if requested
     if args.save_motion {
         let output_path = args.create_output_path(&args.motion_path, "motion.mp4");
-        image.save_motion_video(output_path)?;
+        let mut output = File::create(output_path)
+            .map_err(|error| return GCameraError::MotionVideoWriteError { kind: error.kind() })?;
+        index.copy_motion_video(&mut file, &mut output)?;
     }
 
-    if args.info {
-        image.print_debug_info();
+    // Save the JPEG image if requested
+    if args.save_image {
+        let output_path = args.create_output_path(&args.image_path, "image.jpg");
+        index.save_image(&mut file, output_path)?;
     }
 
-    if args.list_resources {
-        image.print_resource_list();
+    // Save the original image if requested
+    if args.save_original {
+        let original = index.metadata.get_original_image()?;
+        let extension = format!("original.{}", original.mimetype.extension());
+        let output_path = args.create_output_path(&args.original_path, &extension);
+        original.save(&output_path)?;
     }
 
+    // Save the depth map if requested
+    if args.save_depth {
+        let depth_map = index.metadata.get_depth_map()?;
+        let extension = format!("depth.{}", depth_map.mimetype.extension());
+        let output_path = args.create_output_path(&args.depth_path, &extension);
+        depth_map.save(&output_path)?;
+    }
+
+    // Save the thumbnails if requested
+    if args.save_thumbnails {
+        let thumbnails = index.metadata.get_thumbnails()?;
+        if thumbnails.is_empty() {
+            return Err(GCameraError::NoThumbnail);
+        }
+        for thumbnail in thumbnails {
+            let extension = format!("{}-thumbnail.jpg", thumbnail.source.as_str());
+            let thumbnail_path = args
+                .thumbnail_path
+                .as_ref()
+                .map(|path| return path.with_extension(&extension));
+            let output_path = args.create_output_path(&thumbnail_path, &extension);
+            thumbnail.save(&output_path)?;
+        }
+    }
+
+    // Save the ICC profile if requested
+    if args.save_icc {
+        let output_path = args.create_output_path(&args.icc_path, "icc");
+        index.metadata.get_icc_profile()?.save(&output_path)?;
+    }
+
+    edit_image(&args, &index, &mut file)?;
+
     return Ok(());
 }
@@ -10,8 +10,11 @@ use std::{io::Write, path::PathBuf};
 
 use crate::errors::GCameraError;
 
+/// The magic at the start of each debug chunk, in the order they are stored.
+const DEBUG_MAGICS: [&str; 3] = ["aecDebug", "afDebug", "awbDebug"];
+
 /// A single chunk of debug data.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct DebugChunk {
     /// The magic at the start of the chunk
     pub magic: String,
@@ -45,14 +48,55 @@ impl DebugChunk {
     ///
     /// Returns:
     ///     The created chunk
-    pub fn create_chunk(bytes: &[u8], magic_len: usize) -> Self {
-        return Self {
-            magic: String::from_utf8(bytes[..magic_len].to_vec()).unwrap(),
-            data: bytes[magic_len..].to_vec(),
-        };
+    ///
+    /// # Errors
+    /// Will error if the slice is shorter than the magic, or if the magic
+    /// is not valid UTF-8. The offsets are from the start of the slice.
+    pub fn create_chunk(bytes: &[u8], magic_len: usize) -> Result<Self, GCameraError> {
+        let (magic_bytes, data) =
+            bytes
+                .split_at_checked(magic_len)
+                .ok_or(GCameraError::TruncatedData {
+                    structure: "debug chunk magic",
+                    offset: 0,
+                })?;
+        let magic = String::from_utf8(magic_bytes.to_vec()).map_err(|error| {
+            return GCameraError::InvalidUtf8 {
+                structure: "debug chunk magic",
+                offset: error.utf8_error().valid_up_to(),
+            };
+        })?;
+        return Ok(Self {
+            magic,
+            data: data.to_vec(),
+        });
     }
 }
 
+/// Find where the debug data starts, without holding all of the bytes.
+///
+/// The debug data starts at the first chunk magic, and everything before
+/// it is padding. Only a window as long as the longest magic is kept, so
+/// the bytes can be read straight from a file.
+///
+/// # Arguments
+/// * `bytes`: The bytes that may hold the debug data. This can be a slice,
+///   or the bytes of a reader.
+///
+/// # Returns
+/// The offset of the first chunk magic, or None if there is no debug data.
+pub fn find_debug_start<I: IntoIterator<Item = u8>>(bytes: I) -> Option<usize> {
+    let mut window = [0; 8];
+    return bytes.into_iter().enumerate().find_map(|(position, byte)| {
+        window.rotate_left(1);
+        window[7] = byte;
+        return DEBUG_MAGICS.iter().find_map(|magic| {
+            let start = (position + 1).checked_sub(magic.len())?;
+            return window.ends_with(magic.as_bytes()).then_some(start);
+        });
+    });
+}
+
 /// Find the start index of the given magic using a linear search.
 ///
 /// # Arguments
@@ -77,7 +121,7 @@ fn find_magic_start(data: &[u8], magic: &str) -> Option<usize> {
 }
 
 /// All of the debug information from the image.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct DebugComponents {
     /// Contents of the aecDebug portion
     pub aecdebug: Option<DebugChunk>,
@@ -136,6 +180,21 @@ impl DebugComponents {
         .concat();
     }
 
+    /// Remove all of the debug components.
+    ///
+    /// # Returns
+    /// The chunks that were removed.
+    pub fn clear(&mut self) -> Vec<DebugChunk> {
+        return [
+            self.aecdebug.take(),
+            self.afdebug.take(),
+            self.awbdebug.take(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+    }
+
     /// Get the size of all of the debug components.
     ///
     /// # Returns
@@ -148,15 +207,22 @@ impl DebugComponents {
 }
 
 /// Implementation to create debug components from a slice of bytes.
-impl From<&[u8]> for DebugComponents {
+impl TryFrom<&[u8]> for DebugComponents {
+    type Error = GCameraError;
+
     /// Create an instance from the bytes.
     ///
     /// # Arguments
     /// * `bytes`: The bytes to create the instance from.
     ///
     /// # Returns
-    /// The created `DebugComponents` struct
-    fn from(bytes: &[u8]) -> Self {
+    /// Result holding the created `DebugComponents` struct, or an error.
+    ///
+    /// # Errors
+    /// Will error if a chunk is out of order, or its magic is not valid.
+    /// The offsets are from the start of the bytes, so the caller moves
+    /// them to the start of the debug data in the file.
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
         // TODO: use slice.split_array_ref instead of find_magic_start.
         // slice.split_array_ref is still in nightly only
 
@@ -166,26 +232,39 @@ impl From<&[u8]> for DebugComponents {
         let af_start = find_magic_start(bytes, "afDebug");
         let awb_start = find_magic_start(bytes, "awbDebug");
 
-        let awb_chunk = awb_start.map(|start| return DebugChunk::create_chunk(&bytes[start..], 8));
+        // A chunk runs until the start of the chunk after it, so a chunk
+        // that starts after its end is out of order.
+        let read_chunk = |start: usize, end: usize, magic_len: usize| {
+            let chunk_bytes = bytes
+                .get(start..end)
+                .ok_or(GCameraError::DebugChunkOutOfOrder { offset: start })?;
+            return DebugChunk::create_chunk(chunk_bytes, magic_len)
+                .map_err(|error| return error.offset_by(start));
+        };
+
+        let awb_chunk = awb_start
+            .map(|start| return read_chunk(start, bytes.len(), 8))
+            .transpose()?;
 
         // End point of AF is the start of AWB, or if there is no AWB, the end of the binary.
         let af_end = bytes.len() - awb_chunk.as_ref().map_or(0, |chunk| return chunk.size());
 
-        let af_chunk = af_start.map(|start| {
-            return DebugChunk::create_chunk(&bytes[start..af_end], 7);
-        });
+        let af_chunk = af_start
+            .map(|start| return read_chunk(start, af_end, 7))
+            .transpose()?;
 
         // Subtract the af size from the AF end if it exists, otherwise, we propagate af_end.
         let aec_end = af_end - af_chunk.as_ref().map_or(0, |chunk| return chunk.size());
 
-        let aec_chunk =
-            aec_start.map(|start| return DebugChunk::create_chunk(&bytes[start..aec_end], 8));
+        let aec_chunk = aec_start
+            .map(|start| return read_chunk(start, aec_end, 8))
+            .transpose()?;
 
-        return DebugComponents {
+        return Ok(DebugComponents {
             aecdebug: aec_chunk,
             afdebug: af_chunk,
             awbdebug: awb_chunk,
-        };
+        });
     }
 }
 
@@ -206,10 +285,30 @@ mod tests {
 
             assert_eq!(
                 created_chunk,
-                DebugChunk {
+                Ok(DebugChunk {
                     magic: String::from("hello"),
                     data: vec![0x01, 0x02, 0x03, 0xFF, 0xAB],
-                }
+                })
+            );
+        }
+
+        /// Test creating a chunk that is shorter than its magic, or whose
+        /// magic is not valid UTF-8.
+        #[test]
+        fn test_create_invalid_chunk() {
+            assert_eq!(
+                DebugChunk::create_chunk(&[0x68, 0x65], 5),
+                Err(GCameraError::TruncatedData {
+                    structure: "debug chunk magic",
+                    offset: 0,
+                })
+            );
+            assert_eq!(
+                DebugChunk::create_chunk(&[0x68, 0xFF, 0x01], 2),
+                Err(GCameraError::InvalidUtf8 {
+                    structure: "debug chunk magic",
+                    offset: 1,
+                })
             );
         }
 
@@ -261,6 +360,16 @@ mod tests {
 
             assert_eq!(function_result, None);
         }
+
+        /// Test finding the start of the debug data, after some padding.
+        #[test]
+        fn test_debug_start() {
+            let test_bytes = "\0\0afDebug def awbDebug ghi".as_bytes();
+
+            assert_eq!(find_debug_start(test_bytes.iter().copied()), Some(2));
+            assert_eq!(find_debug_start(test_bytes[..8].iter().copied()), None);
+            assert_eq!(find_debug_start("hello".bytes()), None);
+        }
     }
 
     mod test_debug_components {
@@ -270,15 +379,15 @@ mod tests {
         #[test]
         fn test_no_magic_found() {
             let test_bytes = "hello how are you".as_bytes();
-            let result = DebugComponents::from(test_bytes);
+            let result = DebugComponents::try_from(test_bytes);
 
             assert_eq!(
                 result,
-                DebugComponents {
+                Ok(DebugComponents {
                     aecdebug: None,
                     afdebug: None,
                     awbdebug: None
-                }
+                })
             );
         }
 
@@ -286,7 +395,7 @@ mod tests {
         #[test]
         fn test_successful_creation() {
             let test_bytes = "aecDebug abc afDebug def awbDebug ghi".as_bytes();
-            let result = DebugComponents::from(test_bytes);
+            let result = DebugComponents::try_from(test_bytes);
 
             let expected_struct = DebugComponents {
                 aecdebug: Some(DebugChunk {
@@ -303,7 +412,18 @@ mod tests {
                 }),
             };
 
-            assert_eq!(result, expected_struct);
+            assert_eq!(result, Ok(expected_struct));
+        }
+
+        /// Test chunks that are out of order.
+        #[test]
+        fn test_out_of_order_chunks() {
+            let test_bytes = "awbDebug ghi afDebug def".as_bytes();
+
+            assert_eq!(
+                DebugComponents::try_from(test_bytes),
+                Err(GCameraError::DebugChunkOutOfOrder { offset: 13 })
+            );
         }
 
         /// Test converting to bytes.
@@ -351,5 +471,23 @@ mod tests {
 
             assert_eq!(debug_components.size(), 37);
         }
+
+        /// Test removing all of the debug components.
+        #[test]
+        fn test_clear() {
+            let mut debug_components =
+                DebugComponents::try_from("aecDebug abc awbDebug ghi".as_bytes()).unwrap();
+            let removed = debug_components.clear();
+
+            assert_eq!(
+                removed
+                    .iter()
+                    .map(|chunk| return chunk.magic.as_str())
+                    .collect::<Vec<&str>>(),
+                vec!["aecDebug", "awbDebug"]
+            );
+            assert_eq!(debug_components.size(), 0);
+            assert!(debug_components.clear().is_empty());
+        }
     }
 }
@@ -20,11 +20,11 @@ pub enum GCameraError {
     #[error("Error writing the image. Kind: {kind}")]
     ImageWriteError { kind: ErrorKind },
 
-    ///Indicates something went wrong saving the debug data
+    /// Indicates something went wrong saving the debug data
     #[error("Error writing the debug data. Kind: {kind}")]
     DebugDataWriteError { kind: ErrorKind },
 
-    /// Indicates something went wro ng with saving the motion video
+    /// Indicates something went wrong with saving the motion video
     #[error("Error writing motion video. Kind: {kind}")]
     MotionVideoWriteError { kind: ErrorKind },
 
@@ -59,6 +59,44 @@ pub enum GCameraError {
     #[error("No XMP Data found in the image.")]
     NoXMPData,
 
+    /// Indicates that Extended XMP Data could not be found in the image.
+    #[error("No Extended XMP Data found in the image.")]
+    NoExtendedXMPData,
+
+    /// Indicates that an Extended XMP chunk is too short, or has an invalid header.
+    #[error("The Extended XMP chunk in the segment at offset {offset} is invalid.")]
+    InvalidExtendedXMPChunk {
+        /// The offset of the segment holding the chunk.
+        offset: usize,
+    },
+
+    /// Indicates that an Extended XMP chunk does not start where the previous chunk ended.
+    #[error("Extended XMP chunk expected at offset {expected}, but found at {found}.")]
+    ExtendedXMPOffsetMismatch {
+        /// The offset the chunk was expected at.
+        expected: usize,
+        /// The offset of the chunk that was found.
+        found: usize,
+    },
+
+    /// Indicates that the Extended XMP chunks do not add up to the full length.
+    #[error("Extended XMP should be {expected} bytes long, but chunks hold {found} bytes.")]
+    ExtendedXMPLengthMismatch {
+        /// The full length given in the chunk headers.
+        expected: usize,
+        /// The number of bytes held in the chunks.
+        found: usize,
+    },
+
+    /// Indicates that the digest of the Extended XMP does not match its GUID.
+    #[error("Extended XMP has a GUID of '{guid}', but a digest of '{digest}'.")]
+    ExtendedXMPDigestMismatch {
+        /// The GUID of the Extended XMP.
+        guid: String,
+        /// The MD5 digest of the merged Extended XMP.
+        digest: String,
+    },
+
     /// Indicates that the Description Node could not be found in the XML
     #[error("Description not found in XMP data.")]
     DescriptionNodeNotFound,
@@ -82,12 +120,298 @@ pub enum GCameraError {
     JpegMarkerNotFound,
 
     /// Indicates that the type of JPEG Marker is not known.
-    #[error("JPEG Marker with bytes '{marker_byte:02x}' is not known.")]
+    #[error("JPEG Marker with bytes '{marker_byte:02x}' at offset {offset} is not known.")]
     UnknownJpegMarker {
         /// The bytes of the unknown marker.
         marker_byte: u8,
+        /// The offset of the segment with the marker.
+        offset: usize,
+    },
+
+    /// Indicates that a segment does not start with a `0xFF` byte.
+    #[error("Expected a JPEG marker at offset {offset}, but found byte '{byte:02x}'.")]
+    MissingMarkerPrefix {
+        /// The byte found instead of `0xFF`.
+        byte: u8,
+        /// The offset of the segment.
+        offset: usize,
+    },
+
+    /// Indicates that there is too much data to fit in a single JPEG segment.
+    #[error("Segment data is {length} bytes long, which is too large to fit in a segment.")]
+    SegmentTooLarge {
+        /// The length of the data.
+        length: usize,
+    },
+
+    /// Indicates that the data ended before the end of a structure.
+    #[error("The {structure} at offset {offset} extends past the end of the data.")]
+    TruncatedData {
+        /// The structure that was being parsed.
+        structure: &'static str,
+        /// The offset of the structure.
+        offset: usize,
+    },
+
+    /// Indicates that a debug chunk starts after the chunk that follows it.
+    #[error("The debug chunk at offset {offset} is out of order.")]
+    DebugChunkOutOfOrder {
+        /// The offset of the chunk.
+        offset: usize,
+    },
+
+    /// Indicates that a structure has a length that is not possible.
+    #[error("The {structure} at offset {offset} has a length that is not valid.")]
+    InvalidLength {
+        /// The structure that was being parsed.
+        structure: &'static str,
+        /// The offset of the structure.
+        offset: usize,
+    },
+
+    /// Indicates that text in a structure is not valid UTF-8.
+    #[error("The {structure} holds text that is not valid UTF-8 at offset {offset}.")]
+    InvalidUtf8 {
+        /// The structure that was being parsed.
+        structure: &'static str,
+        /// The offset of the first byte that is not valid, from the start
+        /// of the structure.
+        offset: usize,
+    },
+
+    /// Indicates that XMP data is too large to split into main and Extended XMP.
+    #[error("XMP data is too large to fit in a single segment, even after moving properties into Extended XMP.")]
+    XMPTooLarge,
+
+    /// Indicates that the micro video offset points outside of the image.
+    #[error("MicroVideo offset of {offset} is larger than the image size of {size}.")]
+    InvalidMicroVideoOffset {
+        /// The offset of the video from the end of the file.
+        offset: usize,
+        /// The size of the image.
+        size: usize,
+    },
+
+    /// Indicates that the image is not a legacy micro video motion photo.
+    #[error("The image is not a MicroVideo motion photo.")]
+    NotMicroVideo,
+
+    /// Indicates that the image does not contain a depth map.
+    #[error("The image does not contain a depth map.")]
+    NoDepthMap,
+
+    /// Indicates that the image does not contain gain map metadata.
+    #[error("The image does not contain gain map metadata.")]
+    NoGainMapMetadata,
+
+    /// Indicates that a per channel gain map property has the wrong number of values.
+    #[error("Gain map property '{property}' has {count} values, but should have 1 or 3.")]
+    InvalidGainMapChannels {
+        /// The name of the property.
+        property: String,
+        /// The number of values the property has.
+        count: usize,
+    },
+
+    /// Indicates that the ISO 21496-1 gain map metadata is truncated or has a zero denominator.
+    #[error("The ISO 21496-1 gain map metadata is not valid.")]
+    InvalidIsoGainMap,
+
+    /// Indicates that the ISO 21496-1 gain map metadata has an unsupported version.
+    #[error("ISO 21496-1 gain map metadata version {version} is not supported.")]
+    UnsupportedIsoGainMapVersion {
+        /// The minimum version needed to read the metadata.
+        version: u16,
+    },
+
+    /// Indicates that a TIFF structure does not start with a valid header.
+    #[error("The TIFF header is not valid.")]
+    InvalidTiffHeader,
+
+    /// Indicates that a value in a TIFF structure is past the end of the data.
+    #[error("TIFF data at offset {offset} is past the end of the structure.")]
+    InvalidTiffOffset {
+        /// The offset of the value from the start of the TIFF header.
+        offset: usize,
+    },
+
+    /// Indicates that the image does not contain EXIF data.
+    #[error("The image does not contain EXIF data.")]
+    NoExifData,
+
+    /// Indicates that an EXIF tag name is not known.
+    #[error("Unknown EXIF tag '{name}'.")]
+    UnknownExifTag {
+        /// The name that could not be converted to a tag.
+        name: String,
+    },
+
+    /// Indicates that a value cannot be stored in an EXIF tag.
+    #[error("Invalid value '{value}' for EXIF tag '{tag}'.")]
+    InvalidExifValue {
+        /// The name of the tag.
+        tag: String,
+
+        /// The value that could not be converted.
+        value: String,
+    },
+
+    /// Indicates that a SOS segment does not hold a valid scan header.
+    #[error("The SOS scan header in the segment at offset {offset} is not valid.")]
+    InvalidScanHeader {
+        /// The offset of the segment holding the header.
+        offset: usize,
     },
 
+    /// Indicates that a scan uses a component that is not in the frame.
+    #[error("The scan uses component {selector}, which is not in the frame header.")]
+    UnknownScanComponent {
+        /// The component selector of the scan.
+        selector: u8,
+    },
+
+    /// Indicates that a DRI segment does not hold a two byte restart interval.
+    #[error("The DRI segment does not hold a valid restart interval.")]
+    InvalidRestartInterval,
+
+    /// Indicates that a scan has a restart marker without a restart interval.
+    #[error("Found a restart marker at offset {offset} in a scan with a restart interval of 0.")]
+    UnexpectedRestartMarker {
+        /// The offset of the restart marker.
+        offset: usize,
+    },
+
+    /// Indicates that the restart markers of a scan are not in sequence.
+    #[error("Expected restart marker RST{expected}, but found RST{found} at offset {offset}.")]
+    RestartMarkerOutOfOrder {
+        /// The number of the restart marker that should come next.
+        expected: u8,
+
+        /// The number of the restart marker that was found.
+        found: u8,
+
+        /// The offset of the restart marker that was found.
+        offset: usize,
+    },
+
+    /// Indicates that the image does not contain a frame header.
+    #[error("The image does not contain a SOF frame header.")]
+    NoFrameHeader,
+
+    /// Indicates that a SOF segment does not hold a valid frame header.
+    #[error("The SOF frame header in the segment at offset {offset} is not valid.")]
+    InvalidFrameHeader {
+        /// The offset of the segment holding the header.
+        offset: usize,
+    },
+
+    /// Indicates that the image does not contain a quantization table.
+    #[error("The image does not contain a quantization table.")]
+    NoQuantizationTable,
+
+    /// Indicates that a DQT segment does not hold valid quantization tables.
+    #[error("The DQT segment does not hold valid quantization tables.")]
+    InvalidQuantizationTable,
+
+    /// Indicates that a DHT segment does not hold valid Huffman tables.
+    #[error("The DHT segment does not hold valid Huffman tables.")]
+    InvalidHuffmanTable,
+
+    /// Indicates that the size of the gain map does not fit the primary image.
+    #[error("A gain map of {gain_map_width}x{gain_map_height} cannot be scaled to an image of {width}x{height}.")]
+    IncompatibleGainMapSize {
+        /// The width of the primary image.
+        width: u16,
+
+        /// The height of the primary image.
+        height: u16,
+
+        /// The width of the gain map.
+        gain_map_width: u16,
+
+        /// The height of the gain map.
+        gain_map_height: u16,
+    },
+
+    /// Indicates that the image does not contain an MPF index.
+    #[error("The image does not contain an MPF index.")]
+    NoMpfIndex,
+
+    /// Indicates that the MPF index does not hold a valid list of images.
+    #[error("The MPF index does not hold a valid list of images.")]
+    InvalidMpfIndex,
+
+    /// Indicates that the image does not contain a thumbnail.
+    #[error("The image does not contain a thumbnail.")]
+    NoThumbnail,
+
+    /// Indicates that a thumbnail is not a JPEG image.
+    #[error("The thumbnail is not a JPEG image.")]
+    InvalidThumbnail,
+
+    /// Indicates that writing a thumbnail to the disk failed.
+    #[error("Error writing the thumbnail. Kind: {kind}")]
+    ThumbnailWriteError { kind: ErrorKind },
+
+    /// Indicates that the image does not contain an ICC profile.
+    #[error("The image does not contain an ICC profile.")]
+    NoIccProfile,
+
+    /// Indicates that the ICC profile chunks have invalid sequence numbers.
+    #[error("The ICC profile chunk in the segment at offset {offset} is not valid.")]
+    InvalidIccChunk {
+        /// The offset of the segment holding the chunk.
+        offset: usize,
+    },
+
+    /// Indicates that a chunk of the ICC profile is missing.
+    #[error("Chunk {sequence} of the ICC profile is missing.")]
+    MissingIccChunk {
+        /// The sequence number of the missing chunk.
+        sequence: u8,
+    },
+
+    /// Indicates that the ICC profile does not have a valid header.
+    #[error("The ICC profile does not have a valid header.")]
+    InvalidIccProfile,
+
+    /// Indicates that an ICC profile needs more than 255 chunks.
+    #[error("The ICC profile of {length} bytes is too large to store in the image.")]
+    IccProfileTooLarge {
+        /// The length of the profile.
+        length: usize,
+    },
+
+    /// Indicates that writing the ICC profile to the disk failed.
+    #[error("Error writing the ICC profile. Kind: {kind}")]
+    IccProfileWriteError { kind: ErrorKind },
+
+    /// Indicates that the image does not contain an original image.
+    #[error("The image does not contain an original image.")]
+    NoOriginalImage,
+
+    /// Indicates that writing the original image to the disk failed.
+    #[error("Error writing the original image. Kind: {kind}")]
+    OriginalImageWriteError { kind: ErrorKind },
+
+    /// Indicates that the format of the depth map is not known.
+    #[error("Depth map has an unknown format of '{format}'")]
+    UnknownDepthFormat {
+        /// The string that could not be converted to a depth format.
+        format: String,
+    },
+
+    /// Indicates that a property holding binary data is not valid base64.
+    #[error("Property '{property}' does not hold valid base64 data.")]
+    InvalidBase64 {
+        /// The name of the property.
+        property: String,
+    },
+
+    /// Indicates that writing the depth map to the disk failed.
+    #[error("Error writing the depth map. Kind: {kind}")]
+    DepthWriteError { kind: ErrorKind },
+
     /// Indicates that the image contains no resources of the given type
     #[error("The image contains no resources of type {semantic_type:?}")]
     NoResourcesOfType {
@@ -95,3 +419,71 @@ pub enum GCameraError {
         semantic_type: SemanticType,
     },
 }
+
+impl GCameraError {
+    /// Move the offset of an error to the data containing the structure.
+    ///
+    /// Structures that are parsed on their own give offsets from their own
+    /// start, so this is used to give the offset in the whole file. The
+    /// offsets of the UTF-8 and TIFF errors are always from the start of
+    /// their structure, so they are not changed.
+    ///
+    /// # Arguments
+    /// * `base`: The offset of the structure in the containing data.
+    ///
+    /// # Returns
+    /// The error, with `base` added to its offset if it has one.
+    #[must_use]
+    #[allow(clippy::wildcard_enum_match_arm)] // Most errors have no offset
+    pub fn offset_by(self, base: usize) -> Self {
+        return match self {
+            Self::InvalidExtendedXMPChunk { offset } => Self::InvalidExtendedXMPChunk {
+                offset: base + offset,
+            },
+            Self::UnknownJpegMarker {
+                marker_byte,
+                offset,
+            } => Self::UnknownJpegMarker {
+                marker_byte,
+                offset: base + offset,
+            },
+            Self::MissingMarkerPrefix { byte, offset } => Self::MissingMarkerPrefix {
+                byte,
+                offset: base + offset,
+            },
+            Self::TruncatedData { structure, offset } => Self::TruncatedData {
+                structure,
+                offset: base + offset,
+            },
+            Self::DebugChunkOutOfOrder { offset } => Self::DebugChunkOutOfOrder {
+                offset: base + offset,
+            },
+            Self::InvalidLength { structure, offset } => Self::InvalidLength {
+                structure,
+                offset: base + offset,
+            },
+            Self::InvalidScanHeader { offset } => Self::InvalidScanHeader {
+                offset: base + offset,
+            },
+            Self::UnexpectedRestartMarker { offset } => Self::UnexpectedRestartMarker {
+                offset: base + offset,
+            },
+            Self::RestartMarkerOutOfOrder {
+                expected,
+                found,
+                offset,
+            } => Self::RestartMarkerOutOfOrder {
+                expected,
+                found,
+                offset: base + offset,
+            },
+            Self::InvalidFrameHeader { offset } => Self::InvalidFrameHeader {
+                offset: base + offset,
+            },
+            Self::InvalidIccChunk { offset } => Self::InvalidIccChunk {
+                offset: base + offset,
+            },
+            error => error,
+        };
+    }
+}
@@ -0,0 +1,1015 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Logic for indexing an image without loading all of it into memory.
+//!
+//! The index records where each JPEG segment, the debug data, and each
+//! resource are stored in the file. Only the segments holding metadata, the
+//! debug data, and the metadata segments of any images listed in an MPF
+//! index are read into memory while indexing, so the scan data and the
+//! resources, such as the video of a motion photo, can be streamed straight
+//! from the file to their destination.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+
+use crate::camera_image::{debug_info, get_resource_ranges_from_xmp, resource_list};
+use crate::debug_components::{find_debug_start, DebugComponents};
+use crate::errors::GCameraError;
+use crate::jpeg::jpeg_components::{find_scan_end, JpegSegment};
+use crate::jpeg::jpeg_image::JpegImage;
+use crate::jpeg::marker::JpegMarker;
+use crate::jpeg::mpf::MpIndex;
+use crate::jpeg::xmp::gain_map::HDRGM_NS;
+use crate::jpeg::xmp::{Item, MimeType, SemanticType};
+
+/// The position of a single JPEG segment in the file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SegmentRange {
+    /// The marker indicating the segment type.
+    pub marker: JpegMarker,
+
+    /// The bytes of the segment, including the marker. For the SOS
+    /// segment, this includes the scan data.
+    pub range: Range<usize>,
+}
+
+/// The position of a single non-primary resource in the file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ResourceRange {
+    /// Information about the resource.
+    pub info: Item,
+
+    /// The bytes of the resource, without any padding before it.
+    pub range: Range<usize>,
+}
+
+/// The positions of all of the parts of an image in a file.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ImageIndex {
+    /// The position of each segment of the JPEG image.
+    pub segments: Vec<SegmentRange>,
+
+    /// The segments of the JPEG image, except for the SOS segments.
+    pub metadata: JpegImage,
+
+    /// The position of the camera debug data.
+    pub debug_data: Range<usize>,
+
+    /// The position of each resource listed in the XMP data or the MPF
+    /// index.
+    pub resources: Vec<ResourceRange>,
+
+    /// The total size of the file.
+    pub total_size: usize,
+}
+
+impl ImageIndex {
+    /// Index an image by reading through it.
+    ///
+    /// The resources are found from the XMP data, or from the MPF index
+    /// when the XMP data does not describe its images. The original image
+    /// of a lens blur photo is stored in the XMP data, so it is read from
+    /// the metadata instead.
+    ///
+    /// # Arguments
+    /// * `reader`: The reader to index the image from.
+    ///
+    /// # Returns
+    /// The index of the image.
+    ///
+    /// # Errors
+    /// Will error if reading fails, if the JPEG image or debug data cannot
+    /// be parsed, or if the resources do not fit in the file.
+    pub fn from_reader<R: Read + Seek>(reader: &mut R) -> Result<Self, GCameraError> {
+        let read_error =
+            |error: io::Error| return GCameraError::ImageReadError { kind: error.kind() };
+        let total_size = usize::try_from(reader.seek(SeekFrom::End(0)).map_err(read_error)?)
+            .map_err(|_| {
+                return GCameraError::ImageReadError {
+                    kind: ErrorKind::FileTooLarge,
+                };
+            })?;
+        reader.rewind().map_err(read_error)?;
+
+        let mut buffered = BufReader::new(reader);
+        let (segments, metadata) = read_segments(&mut buffered)?;
+        let image_size = segments
+            .last()
+            .map_or(0, |segment| return segment.range.end);
+
+        let (mut resources, mut resources_start) = match metadata.get_xmp() {
+            Ok(xmp) => get_resource_ranges_from_xmp(&xmp, total_size)?,
+            Err(_) => (Vec::new(), total_size),
+        };
+
+        // An MPF index that is not valid is ignored, and the XMP data is
+        // used. The index is stored before the scans, so its position in the
+        // metadata is its position in the file.
+        let mpf = metadata.get_mpf_index().ok().and_then(|(position, index)| {
+            return get_resource_ranges_from_mpf(
+                &mut buffered,
+                position,
+                &index,
+                image_size,
+                total_size,
+            )
+            .ok()
+            .flatten();
+        });
+        if let Some((mpf_resources, mpf_start)) = mpf {
+            if mpf_overrides_xmp(&resources, &mpf_resources) {
+                resources = mpf_resources;
+                resources_start = mpf_start;
+            }
+        }
+
+        // The resources cannot start inside the primary image.
+        let debug_length =
+            resources_start
+                .checked_sub(image_size)
+                .ok_or(GCameraError::InvalidLength {
+                    structure: "resource",
+                    offset: resources_start,
+                })?;
+        buffered
+            .seek(SeekFrom::Start(image_size as u64))
+            .map_err(read_error)?;
+        let debug_start = image_size
+            + read_debug_start(&mut buffered.by_ref().take(debug_length as u64))?
+                .unwrap_or(debug_length);
+
+        let index = Self {
+            segments,
+            metadata,
+            debug_data: debug_start..resources_start,
+            resources,
+            total_size,
+        };
+
+        // Only the debug chunks are read, so that they can be checked.
+        index.read_debug_data(&mut buffered)?;
+        return Ok(index);
+    }
+
+    /// Get the size of the JPEG image.
+    ///
+    /// # Returns
+    /// The size of the JPEG image, in bytes.
+    pub fn image_size(&self) -> usize {
+        return self
+            .segments
+            .last()
+            .map_or(0, |segment| return segment.range.end);
+    }
+
+    /// Get the first resource of the given semantic type.
+    ///
+    /// # Arguments
+    /// * `semantic_type`: The semantic type of the resource to get.
+    ///
+    /// # Returns
+    /// The first resource that has the matching semantic type.
+    ///
+    /// # Errors
+    /// Will error if there are no resources of the given semantic type.
+    pub fn get_resource_by_type(
+        &self,
+        semantic_type: SemanticType,
+    ) -> Result<&ResourceRange, GCameraError> {
+        return self
+            .resources
+            .iter()
+            .find(|resource| return resource.info.semantic == semantic_type)
+            .ok_or(GCameraError::NoResourcesOfType { semantic_type });
+    }
+
+    /// Copy the debug data from the image.
+    ///
+    /// # Arguments
+    /// * `reader`: The reader the image was indexed from.
+    /// * `writer`: The writer to copy the debug data to.
+    ///
+    /// # Returns
+    /// Result of copying the data.
+    ///
+    /// # Errors
+    /// Will error if reading the image or writing the data fails.
+    pub fn copy_debug_data<R: Read + Seek, W: Write>(
+        &self,
+        reader: &mut R,
+        writer: &mut W,
+    ) -> Result<(), GCameraError> {
+        return copy_range(reader, &self.debug_data, writer)
+            .map_err(|error| return GCameraError::DebugDataWriteError { kind: error.kind() });
+    }
+
+    /// Copy the motion photo from the image.
+    ///
+    /// # Arguments
+    /// * `reader`: The reader the image was indexed from.
+    /// * `writer`: The writer to copy the video to.
+    ///
+    /// # Returns
+    /// Result of copying the video.
+    ///
+    /// # Errors
+    /// Will error if the image has no motion photo, or reading the image or
+    /// writing the video fails.
+    pub fn copy_motion_video<R: Read + Seek, W: Write>(
+        &self,
+        reader: &mut R,
+        writer: &mut W,
+    ) -> Result<(), GCameraError> {
+        let video = self.get_resource_by_type(SemanticType::MotionPhoto)?;
+        return copy_range(reader, &video.range, writer)
+            .map_err(|error| return GCameraError::MotionVideoWriteError { kind: error.kind() });
+    }
+
+    /// Get a string of the debug info.
+    ///
+    /// Only the JPEG image and the gain map are read, so the other
+    /// resources are never held in memory.
+    ///
+    /// # Arguments
+    /// * `reader`: The reader the image was indexed from.
+    ///
+    /// # Returns
+    /// A string with the debug info to print.
+    ///
+    /// # Errors
+    /// Will error if reading the image fails, or the JPEG image cannot be
+    /// parsed.
+    pub fn get_debug_info<R: Read + Seek>(&self, reader: &mut R) -> Result<String, GCameraError> {
+        let image = self.read_image(reader)?;
+        let gain_map = match self.get_resource_by_type(SemanticType::GainMap) {
+            Ok(resource) => Some(read_range(reader, &resource.range, "resource")?),
+            Err(_) => None,
+        };
+        return Ok(debug_info(
+            &image,
+            self.debug_data.len(),
+            self.resources.len(),
+            gain_map.as_deref(),
+        ));
+    }
+
+    /// Print out some information about the file.
+    ///
+    /// # Arguments
+    /// * `reader`: The reader the image was indexed from.
+    ///
+    /// # Errors
+    /// Will error if reading the image fails, or the JPEG image cannot be
+    /// parsed.
+    pub fn print_debug_info<R: Read + Seek>(&self, reader: &mut R) -> Result<(), GCameraError> {
+        println!("{}", self.get_debug_info(reader)?);
+        return Ok(());
+    }
+
+    /// Print out a list of the additional resources.
+    pub fn print_resource_list(&self) {
+        print!(
+            "{}",
+            resource_list(
+                self.resources
+                    .iter()
+                    .map(|resource| return (resource.range.len(), resource.info.semantic)),
+            )
+        );
+    }
+
+    /// Read the debug data.
+    ///
+    /// # Arguments
+    /// * `reader`: The reader the image was indexed from.
+    ///
+    /// # Returns
+    /// The parsed debug data.
+    ///
+    /// # Errors
+    /// Will error if reading the image fails, or the debug data cannot be
+    /// parsed.
+    pub fn read_debug_data<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+    ) -> Result<DebugComponents, GCameraError> {
+        let bytes = read_range(reader, &self.debug_data, "debug data")?;
+        return DebugComponents::try_from(bytes.as_slice())
+            .map_err(|error| return error.offset_by(self.debug_data.start));
+    }
+
+    /// Read the JPEG image, including its scan data.
+    ///
+    /// # Arguments
+    /// * `reader`: The reader the image was indexed from.
+    ///
+    /// # Returns
+    /// The parsed JPEG image.
+    ///
+    /// # Errors
+    /// Will error if reading the image fails, or it cannot be parsed.
+    pub fn read_image<R: Read + Seek>(&self, reader: &mut R) -> Result<JpegImage, GCameraError> {
+        let bytes = read_range(reader, &(0..self.image_size()), "JPEG image")?;
+        return JpegImage::try_from(bytes.as_slice());
+    }
+
+    /// Save the JPEG component of the image.
+    ///
+    /// # Arguments
+    /// * `reader`: The reader the image was indexed from.
+    /// * `filepath`: Path to save the image to.
+    ///
+    /// # Returns
+    /// Result of saving the file.
+    ///
+    /// # Errors
+    /// Will error if reading the image fails, or writing the data to disk
+    /// fails.
+    pub fn save_image<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        filepath: PathBuf,
+    ) -> Result<(), GCameraError> {
+        let bytes = self.read_image(reader)?.as_resourceless_bytes()?;
+        return File::create(filepath)
+            .map_err(|error| return GCameraError::ImageWriteError { kind: error.kind() })?
+            .write_all(&bytes)
+            .map_err(|error| return GCameraError::ImageWriteError { kind: error.kind() });
+    }
+}
+
+/// Copy a range of bytes from a reader to a writer, without holding all of
+/// them in memory.
+///
+/// # Arguments
+/// * `reader`: The reader to copy the bytes from.
+/// * `range`: The range of bytes to copy.
+/// * `writer`: The writer to copy the bytes to.
+///
+/// # Returns
+/// Result of copying the bytes.
+///
+/// # Errors
+/// Will error if seeking, reading or writing fails, or if the reader ends
+/// before the end of the range.
+pub fn copy_range<R: Read + Seek, W: Write>(
+    reader: &mut R,
+    range: &Range<usize>,
+    writer: &mut W,
+) -> io::Result<()> {
+    reader.seek(SeekFrom::Start(range.start as u64))?;
+    let length = range.len() as u64;
+    if io::copy(&mut reader.by_ref().take(length), writer)? != length {
+        return Err(io::Error::from(ErrorKind::UnexpectedEof));
+    }
+    return Ok(());
+}
+
+/// Find where each image listed in the MPF index is stored.
+///
+/// Images that are not JPEG data are still listed as JPEG resources, since
+/// the MPF index does not give the type of the data.
+///
+/// # Arguments
+/// * `reader`: The reader the image is being indexed from.
+/// * `position`: The offset of the TIFF header of the index in the file.
+/// * `index`: The MPF index of the image.
+/// * `image_size`: The size of the primary JPEG image.
+/// * `total_size`: The total size of the file.
+///
+/// # Returns
+/// Tuple of the position of each secondary image, in the order they are
+/// stored, and the offset where the resources start, including any padding
+/// before the first image. None if the index does not list any secondary
+/// images.
+///
+/// # Errors
+/// Will error if an image is not within the file, starts inside the primary
+/// image, or overlaps another image, or if reading fails.
+fn get_resource_ranges_from_mpf<R: BufRead + Seek>(
+    reader: &mut R,
+    position: usize,
+    index: &MpIndex,
+    image_size: usize,
+    total_size: usize,
+) -> Result<Option<(Vec<ResourceRange>, usize)>, GCameraError> {
+    let mut ranges = index
+        .entries
+        .iter()
+        .filter(|entry| return entry.offset != 0)
+        .map(|entry| {
+            let start = usize::try_from(entry.offset)
+                .ok()
+                .and_then(|offset| return offset.checked_add(position));
+            let size = usize::try_from(entry.size).ok();
+            return start.zip(size).ok_or(GCameraError::InvalidMpfIndex);
+        })
+        .collect::<Result<Vec<(usize, usize)>, GCameraError>>()?;
+    ranges.sort_unstable();
+    let Some(&(first_start, _)) = ranges.first() else {
+        return Ok(None);
+    };
+    if first_start < image_size || first_start > total_size {
+        return Err(GCameraError::InvalidMpfIndex);
+    }
+
+    // A gap of zero bytes after the primary image is padding, while
+    // anything else is left to be read as debug data.
+    reader
+        .seek(SeekFrom::Start(image_size as u64))
+        .map_err(|error| return GCameraError::ImageReadError { kind: error.kind() })?;
+    let gap_length = (first_start - image_size) as u64;
+    let resources_start = match reader
+        .by_ref()
+        .take(gap_length)
+        .bytes()
+        .find(|byte| return !matches!(byte, Ok(0x00)))
+    {
+        None => image_size,
+        Some(Ok(_)) => first_start,
+        Some(Err(error)) => return Err(GCameraError::ImageReadError { kind: error.kind() }),
+    };
+    let mut previous_end = resources_start;
+    let mut resources = Vec::with_capacity(ranges.len());
+    for (start, size) in ranges {
+        if start < previous_end {
+            return Err(GCameraError::InvalidMpfIndex);
+        }
+        let range = start
+            .checked_add(size)
+            .filter(|&end| return end <= total_size)
+            .map(|end| return start..end)
+            .ok_or(GCameraError::InvalidMpfIndex)?;
+        reader
+            .seek(SeekFrom::Start(start as u64))
+            .map_err(|error| return GCameraError::ImageReadError { kind: error.kind() })?;
+        let semantic = if is_gain_map(&mut reader.by_ref().take(size as u64))? {
+            SemanticType::GainMap
+        } else {
+            SemanticType::Secondary
+        };
+        resources.push(ResourceRange {
+            info: Item {
+                mimetype: MimeType::Jpeg,
+                length: Some(size),
+                padding: start - previous_end,
+                semantic,
+                label: None,
+                uri: None,
+            },
+            range,
+        });
+        previous_end = start + size;
+    }
+    return Ok(Some((resources, resources_start)));
+}
+
+/// Check whether an image is an `UltraHDR` gain map.
+///
+/// Only the segments before the scan data are read, since they hold the
+/// gain map metadata.
+///
+/// # Arguments
+/// * `reader`: The reader, positioned at the start of the image and limited
+///   to its bytes.
+///
+/// # Returns
+/// Whether the image is a JPEG holding gain map metadata.
+///
+/// # Errors
+/// Will error if reading fails.
+fn is_gain_map<R: BufRead>(reader: &mut R) -> Result<bool, GCameraError> {
+    let image = match read_image_header(reader) {
+        Ok(image) => image,
+        Err(error @ GCameraError::ImageReadError { .. }) => return Err(error),
+        Err(_) => return Ok(false),
+    };
+    return Ok(image.get_iso_gain_map_metadata().is_ok()
+        || image
+            .get_xmp()
+            .is_ok_and(|xmp| return xmp.tree.get(HDRGM_NS, "Version").is_some()));
+}
+
+/// Check whether the MPF index should be used instead of the XMP data.
+///
+/// This is the case when the XMP data does not describe any resources, or
+/// when the JPEG resources it describes are not the images in the index.
+///
+/// # Arguments
+/// * `xmp_resources`: The resources from the XMP data.
+/// * `mpf_resources`: The resources from the MPF index.
+///
+/// # Returns
+/// Whether the resources from the MPF index should be used.
+fn mpf_overrides_xmp(xmp_resources: &[ResourceRange], mpf_resources: &[ResourceRange]) -> bool {
+    if xmp_resources.is_empty() {
+        return true;
+    }
+    let image_ranges = |resources: &[ResourceRange]| {
+        return resources
+            .iter()
+            .filter(|resource| return resource.info.mimetype == MimeType::Jpeg)
+            .map(|resource| return resource.range.clone())
+            .collect::<Vec<Range<usize>>>();
+    };
+    let xmp_images = image_ranges(xmp_resources);
+    return !xmp_images.is_empty() && xmp_images != image_ranges(mpf_resources);
+}
+
+/// Fill a buffer with bytes from the reader.
+///
+/// # Arguments
+/// * `reader`: The reader to read the bytes from.
+/// * `buffer`: The buffer to fill.
+/// * `structure`: The structure being read, for the error.
+/// * `offset`: The offset of the structure, for the error.
+///
+/// # Returns
+/// Result of reading the bytes.
+///
+/// # Errors
+/// Will error if the reader ends before the buffer is full, or if reading
+/// fails.
+fn read_bytes<R: Read>(
+    reader: &mut R,
+    buffer: &mut [u8],
+    structure: &'static str,
+    offset: usize,
+) -> Result<(), GCameraError> {
+    return reader.read_exact(buffer).map_err(|error| {
+        if error.kind() == ErrorKind::UnexpectedEof {
+            return GCameraError::TruncatedData { structure, offset };
+        }
+        return GCameraError::ImageReadError { kind: error.kind() };
+    });
+}
+
+/// Read a range of bytes from a reader.
+///
+/// # Arguments
+/// * `reader`: The reader to read the bytes from.
+/// * `range`: The range of bytes to read.
+/// * `structure`: The structure being read, for the error.
+///
+/// # Returns
+/// The bytes in the range.
+///
+/// # Errors
+/// Will error if seeking or reading fails, or if the reader ends before
+/// the end of the range.
+pub fn read_range<R: Read + Seek>(
+    reader: &mut R,
+    range: &Range<usize>,
+    structure: &'static str,
+) -> Result<Vec<u8>, GCameraError> {
+    reader
+        .seek(SeekFrom::Start(range.start as u64))
+        .map_err(|error| return GCameraError::ImageReadError { kind: error.kind() })?;
+    let mut bytes = vec![0; range.len()];
+    read_bytes(reader, &mut bytes, structure, range.start)?;
+    return Ok(bytes);
+}
+
+/// Find where the debug data starts in a reader.
+///
+/// # Arguments
+/// * `reader`: The reader, positioned at the end of the JPEG image and
+///   limited to the bytes before the resources.
+///
+/// # Returns
+/// The offset of the debug data from the end of the JPEG image, or None if
+/// there is no debug data.
+///
+/// # Errors
+/// Will error if reading fails.
+fn read_debug_start<R: BufRead>(reader: &mut R) -> Result<Option<usize>, GCameraError> {
+    let mut read_error = None;
+    let bytes = reader.bytes().map_while(|byte| {
+        return byte
+            .map_err(|error| {
+                read_error = Some(error.kind());
+            })
+            .ok();
+    });
+    let start = find_debug_start(bytes);
+    if let Some(kind) = read_error {
+        return Err(GCameraError::ImageReadError { kind });
+    }
+    return Ok(start);
+}
+
+/// Find the length of the entropy-coded data of a scan in a reader.
+///
+/// The reader is left somewhere after the end of the scan.
+///
+/// # Arguments
+/// * `reader`: The reader, positioned at the start of the scan data.
+/// * `offset`: The offset of the scan data, for the error.
+///
+/// # Returns
+/// The length of the scan data.
+///
+/// # Errors
+/// Will error if reading fails, or if no marker follows the scan.
+fn read_scan_length<R: BufRead>(reader: &mut R, offset: usize) -> Result<usize, GCameraError> {
+    let mut read_error = None;
+    let bytes = reader.by_ref().bytes().map_while(|byte| {
+        return byte
+            .map_err(|error| {
+                read_error = Some(error.kind());
+            })
+            .ok();
+    });
+    let length = find_scan_end(bytes);
+    if let Some(kind) = read_error {
+        return Err(GCameraError::ImageReadError { kind });
+    }
+    return length.map_err(|error| {
+        if error == GCameraError::JpegMarkerNotFound {
+            return GCameraError::TruncatedData {
+                structure: "scan",
+                offset,
+            };
+        }
+        return error;
+    });
+}
+
+/// Read the JPEG magic at the start of an image.
+///
+/// # Arguments
+/// * `reader`: The reader, positioned at the start of the image.
+///
+/// # Errors
+/// Will error if reading fails, or if the image does not start with the
+/// SOI marker.
+fn read_magic<R: Read>(reader: &mut R) -> Result<(), GCameraError> {
+    let mut magic = [0; 2];
+    read_bytes(reader, &mut magic, "segment marker", 0).map_err(|error| {
+        if matches!(error, GCameraError::TruncatedData { .. }) {
+            return GCameraError::InvalidJpegMagic;
+        }
+        return error;
+    })?;
+    if magic != [0xFF, 0xD8] {
+        return Err(GCameraError::InvalidJpegMagic);
+    }
+    return Ok(());
+}
+
+/// Read the next segment of the JPEG image, without any scan data.
+///
+/// # Arguments
+/// * `reader`: The reader, positioned at the start of the segment.
+/// * `offset`: The offset of the segment, for the errors.
+///
+/// # Returns
+/// Tuple of the marker, the offset of the marker after any 0xFF fill
+/// bytes, and the bytes of the segment, including the fill bytes.
+///
+/// # Errors
+/// Will error if reading fails, or if the marker or length is not valid.
+fn read_segment<R: BufRead>(
+    reader: &mut R,
+    mut offset: usize,
+) -> Result<(JpegMarker, usize, Vec<u8>), GCameraError> {
+    let mut header = [0; 2];
+    read_bytes(reader, &mut header, "segment marker", offset)?;
+    // Skip any 0xFF fill bytes before the marker, keeping them in the
+    // segment so that its offsets match the file.
+    let segment_start = offset;
+    while header == [0xFF, 0xFF] {
+        offset += 1;
+        read_bytes(reader, &mut header[1..], "segment marker", offset)?;
+    }
+    let fill = vec![0xFF; offset - segment_start];
+    if header[0] != 0xFF {
+        return Err(GCameraError::MissingMarkerPrefix {
+            byte: header[0],
+            offset,
+        });
+    }
+    let marker = JpegMarker::try_from(header[1]).map_err(|error| return error.offset_by(offset))?;
+    if !marker.has_length() {
+        return Ok((marker, offset, [fill.as_slice(), &header].concat()));
+    }
+
+    let mut length_bytes = [0; 2];
+    read_bytes(reader, &mut length_bytes, "segment length", offset)?;
+    let length = usize::from(u16::from_be_bytes(length_bytes));
+    let data_length = length.checked_sub(2).ok_or(GCameraError::InvalidLength {
+        structure: "segment",
+        offset,
+    })?;
+    let mut data = vec![0; data_length];
+    read_bytes(reader, &mut data, "segment", offset)?;
+    return Ok((
+        marker,
+        offset,
+        [fill.as_slice(), &header, &length_bytes, &data].concat(),
+    ));
+}
+
+/// Read the segments of a JPEG image that come before its scan data.
+///
+/// # Arguments
+/// * `reader`: The reader, positioned at the start of the image.
+///
+/// # Returns
+/// The image holding every segment before the first SOS segment.
+///
+/// # Errors
+/// Will error if reading fails, or if a segment cannot be parsed.
+fn read_image_header<R: BufRead>(reader: &mut R) -> Result<JpegImage, GCameraError> {
+    read_magic(reader)?;
+    let mut image = JpegImage {
+        segments: vec![JpegSegment::new(JpegMarker::SOI, &[])?],
+    };
+    let mut offset = 2;
+    loop {
+        let (marker, _, bytes) = read_segment(reader, offset)?;
+        if matches!(marker, JpegMarker::SOS | JpegMarker::EOI) {
+            return Ok(image);
+        }
+        offset += bytes.len();
+        image.segments.push(JpegSegment::from_bytes(&bytes)?);
+    }
+}
+
+/// Read through the segments of the JPEG image.
+///
+/// # Arguments
+/// * `reader`: The reader, positioned at the start of the image.
+///
+/// # Returns
+/// Tuple of the position of each segment, and the image holding every
+/// segment except for the SOS segments.
+///
+/// # Errors
+/// Will error if reading fails, or if a segment cannot be parsed. These
+/// errors give the offset of the segment, in the same way as parsing the
+/// image from bytes.
+fn read_segments<R: BufRead + Seek>(
+    reader: &mut R,
+) -> Result<(Vec<SegmentRange>, JpegImage), GCameraError> {
+    read_magic(reader)?;
+    let mut segments = vec![SegmentRange {
+        marker: JpegMarker::SOI,
+        range: 0..2,
+    }];
+    let mut metadata = JpegImage {
+        segments: vec![JpegSegment::new(JpegMarker::SOI, &[])?],
+    };
+    let mut offset = 2;
+
+    loop {
+        let (marker, marker_offset, bytes) = read_segment(reader, offset)?;
+        let header_end = offset + bytes.len();
+        let end = if marker == JpegMarker::SOS {
+            let scan_end = header_end + read_scan_length(reader, header_end)?;
+            reader
+                .seek(SeekFrom::Start(scan_end as u64))
+                .map_err(|error| return GCameraError::ImageReadError { kind: error.kind() })?;
+            scan_end
+        } else {
+            metadata.segments.push(JpegSegment::from_bytes(&bytes)?);
+            header_end
+        };
+        segments.push(SegmentRange {
+            marker,
+            range: marker_offset..end,
+        });
+        offset = end;
+        if marker == JpegMarker::EOI {
+            break;
+        }
+    }
+
+    return Ok((segments, metadata));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::camera_image::CameraImage;
+    use crate::jpeg::xmp::create_xmp_segment;
+
+    /// Create a motion photo.
+    ///
+    /// # Returns
+    /// The bytes of the image, the bytes of the debug data, and the bytes of
+    /// the video.
+    fn create_motion_photo() -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let debug = "aecDebug abc afDebug def awbDebug ghi".as_bytes().to_vec();
+        let video = vec![0x00, 0x00, 0x00, 0x08, 0x66, 0x74, 0x79, 0x70];
+        let xmp = format!("<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:Container='http://ns.google.com/photos/1.0/container/' xmlns:Item='http://ns.google.com/photos/1.0/container/item/'><Container:Directory><rdf:Seq><rdf:li rdf:parseType='Resource'><Container:Item Item:Semantic='Primary' Item:Mime='image/jpeg'/></rdf:li><rdf:li rdf:parseType='Resource'><Container:Item Item:Semantic='MotionPhoto' Item:Mime='video/mp4' Item:Length='{}' Item:Padding='2'/></rdf:li></rdf:Seq></Container:Directory></rdf:Description></rdf:RDF></x:xmpmeta>", video.len());
+        let bytes = [
+            vec![0xFF, 0xD8],
+            create_xmp_segment(&xmp).unwrap().as_bytes(),
+            vec![0xFF, 0xDD, 0x00, 0x04, 0x00, 0x01],
+            vec![0xFF, 0xDA, 0x00, 0x03, 0x01],
+            vec![0x12, 0xFF, 0x00, 0xFF, 0xD0, 0x34, 0xFF, 0xD1, 0x56],
+            vec![0xFF, 0xD9],
+            debug.clone(),
+            vec![0x00, 0x00],
+            video.clone(),
+        ]
+        .concat();
+        return (bytes, debug, video);
+    }
+
+    /// Test indexing a motion photo, and copying out its parts.
+    #[test]
+    fn test_index_motion_photo() {
+        let (bytes, debug, video) = create_motion_photo();
+        let mut reader = Cursor::new(bytes.as_slice());
+        let index = ImageIndex::from_reader(&mut reader).unwrap();
+
+        let image = CameraImage::try_from(bytes.as_slice()).unwrap();
+        let image_size = bytes.len() - debug.len() - video.len() - 2;
+        assert_eq!(index.image_size(), image_size);
+        assert_eq!(index.total_size, bytes.len());
+        assert_eq!(
+            index
+                .segments
+                .iter()
+                .map(|segment| return segment.marker)
+                .collect::<Vec<JpegMarker>>(),
+            vec![
+                JpegMarker::SOI,
+                JpegMarker::APP1,
+                JpegMarker::DRI,
+                JpegMarker::SOS,
+                JpegMarker::EOI
+            ]
+        );
+        assert_eq!(index.segments[3].range, (image_size - 16)..(image_size - 2));
+        assert_eq!(index.metadata.segments.len(), 4);
+        assert_eq!(index.metadata.get_xmp(), image.get_xmp());
+        assert_eq!(index.debug_data, image_size..(image_size + debug.len()));
+        assert_eq!(
+            index
+                .get_resource_by_type(SemanticType::MotionPhoto)
+                .unwrap(),
+            &ResourceRange {
+                info: image
+                    .get_resource_by_type(SemanticType::MotionPhoto)
+                    .unwrap()
+                    .info
+                    .clone(),
+                range: (bytes.len() - video.len())..bytes.len(),
+            }
+        );
+
+        let mut copied_debug = Vec::new();
+        index
+            .copy_debug_data(&mut reader, &mut copied_debug)
+            .unwrap();
+        assert_eq!(copied_debug, debug);
+
+        let mut copied_video = Vec::new();
+        index
+            .copy_motion_video(&mut reader, &mut copied_video)
+            .unwrap();
+        assert_eq!(copied_video, video);
+    }
+
+    /// Test saving the JPEG image of an indexed motion photo.
+    #[test]
+    fn test_index_save_image() {
+        let (bytes, _, _) = create_motion_photo();
+        let mut reader = Cursor::new(bytes.as_slice());
+        let index = ImageIndex::from_reader(&mut reader).unwrap();
+        let path = env::temp_dir().join("gcamera_tools_test_index_save_image.jpg");
+        index.save_image(&mut reader, path.clone()).unwrap();
+        let saved = fs::read(&path).unwrap();
+        CameraImage::try_from(bytes.as_slice())
+            .unwrap()
+            .save_image(path.clone())
+            .unwrap();
+        let expected = fs::read(&path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(saved, expected);
+        assert_eq!(
+            ImageIndex::from_reader(&mut Cursor::new(saved.as_slice()))
+                .unwrap()
+                .resources,
+            Vec::new()
+        );
+    }
+
+    /// Test indexing an image without any resources.
+    #[test]
+    fn test_index_without_resources() {
+        let bytes = [0xFF, 0xD8, 0xFF, 0xD9];
+        let mut reader = Cursor::new(bytes.as_slice());
+        let index = ImageIndex::from_reader(&mut reader).unwrap();
+
+        assert_eq!(index.image_size(), 4);
+        assert_eq!(index.debug_data, 4..4);
+        assert!(index.resources.is_empty());
+        assert_eq!(
+            index.copy_motion_video(&mut reader, &mut Vec::new()),
+            Err(GCameraError::NoResourcesOfType {
+                semantic_type: SemanticType::MotionPhoto
+            })
+        );
+    }
+
+    /// Test that padding before the debug data is not part of it.
+    #[test]
+    fn test_index_debug_padding() {
+        let bytes = [
+            [0xFF, 0xD8, 0xFF, 0xD9, 0x00, 0x00, 0x00].as_slice(),
+            "afDebug def".as_bytes(),
+        ]
+        .concat();
+        let mut reader = Cursor::new(bytes.as_slice());
+        let index = ImageIndex::from_reader(&mut reader).unwrap();
+
+        assert_eq!(index.debug_data, 7..bytes.len());
+        let mut copied_debug = Vec::new();
+        index
+            .copy_debug_data(&mut reader, &mut copied_debug)
+            .unwrap();
+        assert_eq!(copied_debug, "afDebug def".as_bytes());
+    }
+
+    /// Test indexing an image with fill bytes before its markers.
+    #[test]
+    fn test_index_fill_bytes() {
+        let bytes = [
+            0xFF, 0xD8, 0xFF, 0xFF, 0xFE, 0x00, 0x03, 0x01, 0xFF, 0xFF, 0xFF, 0xD9,
+        ];
+        let index = ImageIndex::from_reader(&mut Cursor::new(bytes.as_slice())).unwrap();
+
+        assert_eq!(index.image_size(), bytes.len());
+        assert_eq!(index.segments[1].marker, JpegMarker::COM);
+        assert_eq!(index.segments[1].range, 3..8);
+        assert_eq!(index.segments[2].range, 10..12);
+        assert_eq!(
+            index.metadata,
+            JpegImage::try_from(bytes.as_slice()).unwrap()
+        );
+    }
+
+    /// Test that indexing a truncated image gives the same errors as
+    /// parsing it from bytes.
+    #[test]
+    fn test_index_truncated() {
+        let (bytes, _, _) = create_motion_photo();
+        for length in [0, 1, 3, 5, 40, bytes.len() - 40] {
+            let truncated = &bytes[..length];
+            assert_eq!(
+                ImageIndex::from_reader(&mut Cursor::new(truncated)).map(drop),
+                CameraImage::try_from(truncated).map(drop)
+            );
+        }
+    }
+
+    /// Test that indexing an image with a marker that is not valid gives the
+    /// same errors as parsing it from bytes.
+    #[test]
+    fn test_index_invalid_marker() {
+        for segment in [[0x00, 0xFE], [0xFF, 0x00]] {
+            let bytes = [[0xFF, 0xD8], segment, [0xFF, 0xD9]].concat();
+            let expected = CameraImage::try_from(bytes.as_slice()).map(drop);
+            assert!(matches!(
+                expected,
+                Err(GCameraError::MissingMarkerPrefix { offset: 2, .. }
+                    | GCameraError::UnknownJpegMarker { offset: 2, .. })
+            ));
+            assert_eq!(
+                ImageIndex::from_reader(&mut Cursor::new(bytes.as_slice())).map(drop),
+                expected
+            );
+        }
+    }
+
+    /// Test that copying a range fails if the reader ends before the range.
+    #[test]
+    fn test_copy_range_truncated() {
+        let mut reader = Cursor::new([0x01, 0x02, 0x03].as_slice());
+        let mut copied = Vec::new();
+
+        assert_eq!(
+            copy_range(&mut reader, &(1..3), &mut copied).map_err(|error| return error.kind()),
+            Ok(())
+        );
+        assert_eq!(copied, vec![0x02, 0x03]);
+        assert_eq!(
+            copy_range(&mut reader, &(2..5), &mut copied).map_err(|error| return error.kind()),
+            Err(ErrorKind::UnexpectedEof)
+        );
+    }
+}
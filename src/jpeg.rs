@@ -1,5 +1,15 @@
 //! JPEG Image parsing logic
+pub mod exif;
+pub mod extended_xmp;
+pub mod frame;
+pub mod icc;
+pub mod iso_gain_map;
 pub mod jpeg_components;
 pub mod jpeg_image;
-mod marker;
+pub mod marker;
+pub mod mpf;
+pub mod scan;
+pub mod tables;
+pub mod thumbnail;
+pub mod tiff;
 pub mod xmp;
@@ -0,0 +1,1488 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Logic for reading and writing EXIF data.
+//!
+//! EXIF data is stored in an APP1 segment as a TIFF structure. IFD0 holds
+//! the tags describing the image, and links to the Exif and GPS IFDs. The
+//! Exif IFD in turn links to the Interoperability IFD, and IFD0 is followed
+//! by IFD1, which describes the embedded thumbnail.
+//!
+//! When the data is written back, every IFD is laid out again and all of
+//! the links between them are recalculated. Values holding offsets of their
+//! own, such as maker notes, are copied as they are.
+use std::str::FromStr;
+
+use crate::errors::GCameraError;
+use crate::jpeg::tiff::{
+    create_header, next_offset_position, parse_header, parse_ifd, to_offset, to_usize, write_ifd,
+    ByteOrder, Ifd, IfdEntry, TYPE_ASCII, TYPE_BYTE, TYPE_DOUBLE, TYPE_FLOAT, TYPE_LONG,
+    TYPE_RATIONAL, TYPE_SBYTE, TYPE_SHORT, TYPE_SLONG, TYPE_SRATIONAL, TYPE_SSHORT, TYPE_UNDEFINED,
+};
+
+/// String that occurs at the start of the EXIF APP1 segment.
+pub const EXIF_MARKER: &[u8] = "Exif\0\0".as_bytes();
+
+/// Width of the image in pixels, in IFD0 or IFD1.
+pub const TAG_IMAGE_WIDTH: u16 = 0x0100;
+
+/// Height of the image in pixels, in IFD0 or IFD1.
+pub const TAG_IMAGE_LENGTH: u16 = 0x0101;
+
+/// Compression of the thumbnail, in IFD1.
+pub const TAG_COMPRESSION: u16 = 0x0103;
+
+/// Description of the image, in IFD0.
+pub const TAG_IMAGE_DESCRIPTION: u16 = 0x010E;
+
+/// Manufacturer of the camera, in IFD0.
+pub const TAG_MAKE: u16 = 0x010F;
+
+/// Model of the camera, in IFD0.
+pub const TAG_MODEL: u16 = 0x0110;
+
+/// Orientation of the image, in IFD0.
+pub const TAG_ORIENTATION: u16 = 0x0112;
+
+/// Horizontal resolution of the image, in IFD0 or IFD1.
+pub const TAG_X_RESOLUTION: u16 = 0x011A;
+
+/// Vertical resolution of the image, in IFD0 or IFD1.
+pub const TAG_Y_RESOLUTION: u16 = 0x011B;
+
+/// Unit of the resolution of the image, in IFD0 or IFD1.
+pub const TAG_RESOLUTION_UNIT: u16 = 0x0128;
+
+/// Software used to create the image, in IFD0.
+pub const TAG_SOFTWARE: u16 = 0x0131;
+
+/// Date and time the image was last changed, in IFD0.
+pub const TAG_DATE_TIME: u16 = 0x0132;
+
+/// Name of the photographer, in IFD0.
+pub const TAG_ARTIST: u16 = 0x013B;
+
+/// Offset of the JPEG thumbnail, in IFD1.
+pub const TAG_THUMBNAIL_OFFSET: u16 = 0x0201;
+
+/// Size of the JPEG thumbnail in bytes, in IFD1.
+pub const TAG_THUMBNAIL_LENGTH: u16 = 0x0202;
+
+/// Copyright notice of the image, in IFD0.
+pub const TAG_COPYRIGHT: u16 = 0x8298;
+
+/// Offset of the Exif IFD, in IFD0.
+pub const TAG_EXIF_POINTER: u16 = 0x8769;
+
+/// Offset of the GPS IFD, in IFD0.
+pub const TAG_GPS_POINTER: u16 = 0x8825;
+
+/// Exposure time in seconds, in the Exif IFD.
+pub const TAG_EXPOSURE_TIME: u16 = 0x829A;
+
+/// F-number of the lens, in the Exif IFD.
+pub const TAG_F_NUMBER: u16 = 0x829D;
+
+/// ISO sensitivity, in the Exif IFD.
+pub const TAG_ISO: u16 = 0x8827;
+
+/// Date and time the image was taken, in the Exif IFD.
+pub const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+
+/// Date and time the image was digitized, in the Exif IFD.
+pub const TAG_DATE_TIME_DIGITIZED: u16 = 0x9004;
+
+/// Time zone offset of `DateTime`, in the Exif IFD.
+pub const TAG_OFFSET_TIME: u16 = 0x9010;
+
+/// Time zone offset of `DateTimeOriginal`, in the Exif IFD.
+pub const TAG_OFFSET_TIME_ORIGINAL: u16 = 0x9011;
+
+/// Time zone offset of `DateTimeDigitized`, in the Exif IFD.
+pub const TAG_OFFSET_TIME_DIGITIZED: u16 = 0x9012;
+
+/// Focal length of the lens in millimeters, in the Exif IFD.
+pub const TAG_FOCAL_LENGTH: u16 = 0x920A;
+
+/// Fractions of a second of `DateTime`, in the Exif IFD.
+pub const TAG_SUB_SEC_TIME: u16 = 0x9290;
+
+/// Fractions of a second of `DateTimeOriginal`, in the Exif IFD.
+pub const TAG_SUB_SEC_TIME_ORIGINAL: u16 = 0x9291;
+
+/// Data specific to the camera manufacturer, in the Exif IFD.
+pub const TAG_MAKER_NOTE: u16 = 0x927C;
+
+/// Fractions of a second of `DateTimeDigitized`, in the Exif IFD.
+pub const TAG_SUB_SEC_TIME_DIGITIZED: u16 = 0x9292;
+
+/// Offset of the Interoperability IFD, in the Exif IFD.
+pub const TAG_INTEROP_POINTER: u16 = 0xA005;
+
+/// Unique identifier of the image, in the Exif IFD.
+pub const TAG_IMAGE_UNIQUE_ID: u16 = 0xA420;
+
+/// Name of the owner of the camera, in the Exif IFD.
+pub const TAG_CAMERA_OWNER_NAME: u16 = 0xA430;
+
+/// Serial number of the camera, in the Exif IFD.
+pub const TAG_BODY_SERIAL_NUMBER: u16 = 0xA431;
+
+/// Model of the lens, in the Exif IFD.
+pub const TAG_LENS_MODEL: u16 = 0xA434;
+
+/// Serial number of the lens, in the Exif IFD.
+pub const TAG_LENS_SERIAL_NUMBER: u16 = 0xA435;
+
+/// The IFDs that EXIF data is made up of.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IfdKind {
+    /// IFD0, describing the primary image.
+    Primary,
+
+    /// The Exif IFD, describing the camera settings.
+    Exif,
+
+    /// The GPS IFD, describing where the image was taken.
+    Gps,
+
+    /// The Interoperability IFD.
+    Interop,
+
+    /// IFD1, describing the embedded thumbnail.
+    Thumbnail,
+}
+
+/// The tags that can be edited by name, with their IFD and type.
+const TAG_NAMES: [(&str, IfdKind, u16, u16); 25] = [
+    (
+        "ImageDescription",
+        IfdKind::Primary,
+        TAG_IMAGE_DESCRIPTION,
+        TYPE_ASCII,
+    ),
+    ("Make", IfdKind::Primary, TAG_MAKE, TYPE_ASCII),
+    ("Model", IfdKind::Primary, TAG_MODEL, TYPE_ASCII),
+    ("Orientation", IfdKind::Primary, TAG_ORIENTATION, TYPE_SHORT),
+    ("Software", IfdKind::Primary, TAG_SOFTWARE, TYPE_ASCII),
+    ("DateTime", IfdKind::Primary, TAG_DATE_TIME, TYPE_ASCII),
+    ("Artist", IfdKind::Primary, TAG_ARTIST, TYPE_ASCII),
+    ("Copyright", IfdKind::Primary, TAG_COPYRIGHT, TYPE_ASCII),
+    (
+        "ExposureTime",
+        IfdKind::Exif,
+        TAG_EXPOSURE_TIME,
+        TYPE_RATIONAL,
+    ),
+    ("FNumber", IfdKind::Exif, TAG_F_NUMBER, TYPE_RATIONAL),
+    ("ISO", IfdKind::Exif, TAG_ISO, TYPE_SHORT),
+    (
+        "DateTimeOriginal",
+        IfdKind::Exif,
+        TAG_DATE_TIME_ORIGINAL,
+        TYPE_ASCII,
+    ),
+    (
+        "DateTimeDigitized",
+        IfdKind::Exif,
+        TAG_DATE_TIME_DIGITIZED,
+        TYPE_ASCII,
+    ),
+    ("OffsetTime", IfdKind::Exif, TAG_OFFSET_TIME, TYPE_ASCII),
+    (
+        "OffsetTimeOriginal",
+        IfdKind::Exif,
+        TAG_OFFSET_TIME_ORIGINAL,
+        TYPE_ASCII,
+    ),
+    (
+        "OffsetTimeDigitized",
+        IfdKind::Exif,
+        TAG_OFFSET_TIME_DIGITIZED,
+        TYPE_ASCII,
+    ),
+    (
+        "FocalLength",
+        IfdKind::Exif,
+        TAG_FOCAL_LENGTH,
+        TYPE_RATIONAL,
+    ),
+    ("SubSecTime", IfdKind::Exif, TAG_SUB_SEC_TIME, TYPE_ASCII),
+    (
+        "SubSecTimeOriginal",
+        IfdKind::Exif,
+        TAG_SUB_SEC_TIME_ORIGINAL,
+        TYPE_ASCII,
+    ),
+    (
+        "SubSecTimeDigitized",
+        IfdKind::Exif,
+        TAG_SUB_SEC_TIME_DIGITIZED,
+        TYPE_ASCII,
+    ),
+    (
+        "ImageUniqueID",
+        IfdKind::Exif,
+        TAG_IMAGE_UNIQUE_ID,
+        TYPE_ASCII,
+    ),
+    (
+        "CameraOwnerName",
+        IfdKind::Exif,
+        TAG_CAMERA_OWNER_NAME,
+        TYPE_ASCII,
+    ),
+    (
+        "BodySerialNumber",
+        IfdKind::Exif,
+        TAG_BODY_SERIAL_NUMBER,
+        TYPE_ASCII,
+    ),
+    ("LensModel", IfdKind::Exif, TAG_LENS_MODEL, TYPE_ASCII),
+    (
+        "LensSerialNumber",
+        IfdKind::Exif,
+        TAG_LENS_SERIAL_NUMBER,
+        TYPE_ASCII,
+    ),
+];
+
+/// The tags removed when scrubbing identifying data, with their names.
+///
+/// Maker notes are included, since they often hold serial numbers in a
+/// format that cannot be edited.
+const PRIVATE_TAGS: [(&str, IfdKind, u16); 6] = [
+    ("Artist", IfdKind::Primary, TAG_ARTIST),
+    ("MakerNote", IfdKind::Exif, TAG_MAKER_NOTE),
+    ("ImageUniqueID", IfdKind::Exif, TAG_IMAGE_UNIQUE_ID),
+    ("CameraOwnerName", IfdKind::Exif, TAG_CAMERA_OWNER_NAME),
+    ("BodySerialNumber", IfdKind::Exif, TAG_BODY_SERIAL_NUMBER),
+    ("LensSerialNumber", IfdKind::Exif, TAG_LENS_SERIAL_NUMBER),
+];
+
+/// The typed values of an EXIF tag.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ExifValue {
+    /// 8-bit unsigned integers.
+    Byte(Vec<u8>),
+
+    /// A string, without the null terminator.
+    Ascii(String),
+
+    /// 16-bit unsigned integers.
+    Short(Vec<u16>),
+
+    /// 32-bit unsigned integers.
+    Long(Vec<u32>),
+
+    /// Fractions of 32-bit unsigned integers, as numerator and denominator.
+    Rational(Vec<(u32, u32)>),
+
+    /// 8-bit signed integers.
+    SByte(Vec<i8>),
+
+    /// Bytes that are interpreted based on the tag.
+    Undefined(Vec<u8>),
+
+    /// 16-bit signed integers.
+    SShort(Vec<i16>),
+
+    /// 32-bit signed integers.
+    SLong(Vec<i32>),
+
+    /// Fractions of 32-bit signed integers, as numerator and denominator.
+    SRational(Vec<(i32, i32)>),
+
+    /// Single precision floating point numbers.
+    Float(Vec<f32>),
+
+    /// Double precision floating point numbers.
+    Double(Vec<f64>),
+}
+
+/// Read every value of an entry.
+///
+/// # Arguments
+/// * `value`: The raw bytes of the entry.
+/// * `size`: The size of a single value.
+/// * `read`: Function reading a single value from its bytes.
+///
+/// # Returns
+/// The values of the entry.
+///
+/// # Errors
+/// Will error if a value cannot be read.
+fn read_values<T>(
+    value: &[u8],
+    size: usize,
+    read: impl Fn(&[u8]) -> Result<T, GCameraError>,
+) -> Result<Vec<T>, GCameraError> {
+    return value.chunks_exact(size).map(read).collect();
+}
+
+/// Parse a comma separated list of numbers.
+///
+/// # Arguments
+/// * `text`: The list to parse.
+///
+/// # Returns
+/// The numbers, or None if any of them cannot be parsed.
+fn parse_list<T: FromStr>(text: &str) -> Option<Vec<T>> {
+    return text
+        .split(',')
+        .map(|part| return part.trim().parse().ok())
+        .collect();
+}
+
+/// Parse a comma separated list of fractions.
+///
+/// # Arguments
+/// * `text`: The list to parse, with each fraction written as `n/d`, or as
+///   `n` for a denominator of 1.
+///
+/// # Returns
+/// The numerators and denominators, or None if any of them cannot be parsed.
+fn parse_fractions<T: FromStr>(text: &str) -> Option<Vec<(T, T)>> {
+    return text
+        .split(',')
+        .map(|part| {
+            let (numerator, denominator) = part.split_once('/').unwrap_or((part, "1"));
+            return Some((
+                numerator.trim().parse().ok()?,
+                denominator.trim().parse().ok()?,
+            ));
+        })
+        .collect();
+}
+
+/// Encode a list of values.
+///
+/// # Arguments
+/// * `values`: The values to encode.
+/// * `bytes`: Function encoding a single value.
+///
+/// # Returns
+/// The number of values, and their bytes.
+fn encode_values<T: Copy, const SIZE: usize>(
+    values: &[T],
+    bytes: impl Fn(T) -> [u8; SIZE],
+) -> (usize, Vec<u8>) {
+    return (
+        values.len(),
+        values
+            .iter()
+            .flat_map(|&value| return bytes(value))
+            .collect(),
+    );
+}
+
+impl ExifValue {
+    /// Decode the values of an IFD entry.
+    ///
+    /// # Arguments
+    /// * `entry`: The entry to decode.
+    /// * `order`: The byte order of the TIFF structure.
+    ///
+    /// # Returns
+    /// The typed values of the entry.
+    ///
+    /// # Errors
+    /// Will error if the values cannot be read.
+    pub fn from_entry(entry: &IfdEntry, order: ByteOrder) -> Result<Self, GCameraError> {
+        let value = entry.value.as_slice();
+        let read_u16 = |chunk: &[u8]| return order.read_u16(chunk, 0);
+        let read_u32 = |chunk: &[u8]| return order.read_u32(chunk, 0);
+        return Ok(match entry.field_type {
+            TYPE_BYTE => Self::Byte(value.to_vec()),
+            TYPE_ASCII => Self::Ascii(
+                String::from_utf8_lossy(value)
+                    .trim_end_matches('\0')
+                    .to_owned(),
+            ),
+            TYPE_SHORT => Self::Short(read_values(value, 2, read_u16)?),
+            TYPE_LONG => Self::Long(read_values(value, 4, read_u32)?),
+            TYPE_RATIONAL => Self::Rational(read_values(value, 8, |chunk| {
+                return Ok((order.read_u32(chunk, 0)?, order.read_u32(chunk, 4)?));
+            })?),
+            TYPE_SBYTE => Self::SByte(value.iter().map(|byte| return byte.cast_signed()).collect()),
+            TYPE_SSHORT => Self::SShort(read_values(value, 2, |chunk| {
+                return Ok(read_u16(chunk)?.cast_signed());
+            })?),
+            TYPE_SLONG => Self::SLong(read_values(value, 4, |chunk| {
+                return Ok(read_u32(chunk)?.cast_signed());
+            })?),
+            TYPE_SRATIONAL => Self::SRational(read_values(value, 8, |chunk| {
+                return Ok((
+                    order.read_u32(chunk, 0)?.cast_signed(),
+                    order.read_u32(chunk, 4)?.cast_signed(),
+                ));
+            })?),
+            TYPE_FLOAT => Self::Float(read_values(value, 4, |chunk| {
+                return Ok(f32::from_bits(read_u32(chunk)?));
+            })?),
+            TYPE_DOUBLE => Self::Double(read_values(value, 8, |chunk| {
+                return Ok(f64::from_bits(order.read_u64(chunk, 0)?));
+            })?),
+            _ => Self::Undefined(value.to_vec()),
+        });
+    }
+
+    /// Parse a value from text.
+    ///
+    /// # Arguments
+    /// * `field_type`: The TIFF type of the value.
+    /// * `text`: The text to parse. Integers and fractions can be given as a
+    ///   comma separated list, and fractions are written as `n/d`.
+    ///
+    /// # Returns
+    /// The parsed value, or None if the text is not valid for the type, or
+    /// the type cannot be parsed from text.
+    pub fn parse(field_type: u16, text: &str) -> Option<Self> {
+        return match field_type {
+            TYPE_ASCII if !text.contains('\0') => Some(Self::Ascii(text.to_owned())),
+            TYPE_SHORT => parse_list(text).map(Self::Short),
+            TYPE_LONG => parse_list(text).map(Self::Long),
+            TYPE_RATIONAL => parse_fractions(text).map(Self::Rational),
+            TYPE_SRATIONAL => parse_fractions(text).map(Self::SRational),
+            _ => None,
+        };
+    }
+
+    /// Get the TIFF type of the value.
+    ///
+    /// # Returns
+    /// The type code of the value.
+    pub fn field_type(&self) -> u16 {
+        return match self {
+            Self::Byte(_) => TYPE_BYTE,
+            Self::Ascii(_) => TYPE_ASCII,
+            Self::Short(_) => TYPE_SHORT,
+            Self::Long(_) => TYPE_LONG,
+            Self::Rational(_) => TYPE_RATIONAL,
+            Self::SByte(_) => TYPE_SBYTE,
+            Self::Undefined(_) => TYPE_UNDEFINED,
+            Self::SShort(_) => TYPE_SSHORT,
+            Self::SLong(_) => TYPE_SLONG,
+            Self::SRational(_) => TYPE_SRATIONAL,
+            Self::Float(_) => TYPE_FLOAT,
+            Self::Double(_) => TYPE_DOUBLE,
+        };
+    }
+
+    /// Encode the value as an IFD entry.
+    ///
+    /// # Arguments
+    /// * `tag`: The tag of the entry.
+    /// * `order`: The byte order of the TIFF structure.
+    ///
+    /// # Returns
+    /// The entry, with a value offset of 0.
+    ///
+    /// # Errors
+    /// Will error if there are too many values for an entry.
+    pub fn to_entry(&self, tag: u16, order: ByteOrder) -> Result<IfdEntry, GCameraError> {
+        let (count, value) = match self {
+            Self::Byte(values) | Self::Undefined(values) => (values.len(), values.clone()),
+            Self::Ascii(text) => (text.len() + 1, [text.as_bytes(), &[0x00]].concat()),
+            Self::Short(values) => encode_values(values, |value| return order.u16_bytes(value)),
+            Self::Long(values) => encode_values(values, |value| return order.u32_bytes(value)),
+            Self::Rational(values) => encode_values(values, |(numerator, denominator)| {
+                let mut bytes = [0; 8];
+                bytes[..4].copy_from_slice(&order.u32_bytes(numerator));
+                bytes[4..].copy_from_slice(&order.u32_bytes(denominator));
+                return bytes;
+            }),
+            Self::SByte(values) => encode_values(values, |value| return [value.cast_unsigned()]),
+            Self::SShort(values) => encode_values(values, |value| {
+                return order.u16_bytes(value.cast_unsigned());
+            }),
+            Self::SLong(values) => encode_values(values, |value| {
+                return order.u32_bytes(value.cast_unsigned());
+            }),
+            Self::SRational(values) => encode_values(values, |(numerator, denominator)| {
+                let mut bytes = [0; 8];
+                bytes[..4].copy_from_slice(&order.u32_bytes(numerator.cast_unsigned()));
+                bytes[4..].copy_from_slice(&order.u32_bytes(denominator.cast_unsigned()));
+                return bytes;
+            }),
+            Self::Float(values) => {
+                encode_values(values, |value| return order.u32_bytes(value.to_bits()))
+            }
+            Self::Double(values) => {
+                encode_values(values, |value| return order.u64_bytes(value.to_bits()))
+            }
+        };
+        return Ok(IfdEntry {
+            tag,
+            field_type: self.field_type(),
+            count: to_offset(count)?,
+            value_offset: 0,
+            value,
+        });
+    }
+
+    /// Get the value as a string.
+    ///
+    /// # Returns
+    /// The string, or None if the value is not an ASCII string.
+    pub fn as_str(&self) -> Option<&str> {
+        if let Self::Ascii(text) = self {
+            return Some(text.as_str());
+        }
+        return None;
+    }
+
+    /// Get the first value as an unsigned integer.
+    ///
+    /// # Returns
+    /// The first value, or None if the value is not an unsigned integer.
+    pub fn as_u32(&self) -> Option<u32> {
+        return match self {
+            Self::Byte(values) => values.first().copied().map(u32::from),
+            Self::Short(values) => values.first().copied().map(u32::from),
+            Self::Long(values) => values.first().copied(),
+            Self::Ascii(_)
+            | Self::Rational(_)
+            | Self::SByte(_)
+            | Self::Undefined(_)
+            | Self::SShort(_)
+            | Self::SLong(_)
+            | Self::SRational(_)
+            | Self::Float(_)
+            | Self::Double(_) => None,
+        };
+    }
+
+    /// Get the first value as a fraction.
+    ///
+    /// # Returns
+    /// The numerator and denominator of the first value, or None if the
+    /// value is not an unsigned fraction.
+    pub fn as_rational(&self) -> Option<(u32, u32)> {
+        if let Self::Rational(values) = self {
+            return values.first().copied();
+        }
+        return None;
+    }
+
+    /// Get the first value as a floating point number.
+    ///
+    /// # Returns
+    /// The first value, or None if the value is not a number, or is a
+    /// fraction with a denominator of zero.
+    #[allow(clippy::float_arithmetic)] // Fractions have to be divided to get their value
+    pub fn as_f64(&self) -> Option<f64> {
+        return match self {
+            Self::Rational(values) => values
+                .first()
+                .filter(|&&(_, denominator)| return denominator != 0)
+                .map(|&(numerator, denominator)| {
+                    return f64::from(numerator) / f64::from(denominator);
+                }),
+            Self::SRational(values) => values
+                .first()
+                .filter(|&&(_, denominator)| return denominator != 0)
+                .map(|&(numerator, denominator)| {
+                    return f64::from(numerator) / f64::from(denominator);
+                }),
+            Self::Float(values) => values.first().copied().map(f64::from),
+            Self::Double(values) => values.first().copied(),
+            Self::Byte(_) | Self::Short(_) | Self::Long(_) => self.as_u32().map(f64::from),
+            Self::Ascii(_)
+            | Self::SByte(_)
+            | Self::Undefined(_)
+            | Self::SShort(_)
+            | Self::SLong(_) => None,
+        };
+    }
+}
+
+/// A single tag of the EXIF data.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExifField {
+    /// The tag identifying the field.
+    pub tag: u16,
+
+    /// The values of the field.
+    pub value: ExifValue,
+}
+
+/// The decoded EXIF data of an image.
+///
+/// The tags holding the offsets of the other IFDs are not kept, since the
+/// IFDs they point to are parsed into their own lists. The same goes for the
+/// tags locating the thumbnail, which is kept as its own field.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExifData {
+    /// The byte order of the TIFF structure.
+    pub byte_order: ByteOrder,
+
+    /// The fields of IFD0.
+    pub primary: Vec<ExifField>,
+
+    /// The fields of the Exif IFD.
+    pub exif: Vec<ExifField>,
+
+    /// The fields of the GPS IFD.
+    pub gps: Vec<ExifField>,
+
+    /// The fields of the Interoperability IFD.
+    pub interop: Vec<ExifField>,
+
+    /// The fields of IFD1.
+    pub thumbnail: Vec<ExifField>,
+
+    /// The JPEG thumbnail described by IFD1.
+    pub thumbnail_image: Option<Vec<u8>>,
+}
+
+/// The camera settings from the EXIF data.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct CameraSettings {
+    /// Manufacturer of the camera.
+    pub make: Option<String>,
+
+    /// Model of the camera.
+    pub model: Option<String>,
+
+    /// Model of the lens.
+    pub lens_model: Option<String>,
+
+    /// Date and time the image was taken.
+    pub date_time_original: Option<String>,
+
+    /// Fractions of a second of the time the image was taken.
+    pub sub_sec_time_original: Option<String>,
+
+    /// Exposure time in seconds, as numerator and denominator.
+    pub exposure_time: Option<(u32, u32)>,
+
+    /// F-number of the lens.
+    pub f_number: Option<f64>,
+
+    /// ISO sensitivity.
+    pub iso: Option<u32>,
+
+    /// Focal length of the lens in millimeters.
+    pub focal_length: Option<f64>,
+
+    /// Orientation of the image, from 1 to 8.
+    pub orientation: Option<u32>,
+}
+
+impl CameraSettings {
+    /// Get a description of the camera settings.
+    ///
+    /// # Returns
+    /// String with one line for each setting that is present.
+    pub fn info_str(&self) -> String {
+        let mut lines = Vec::new();
+        if let Some(make) = &self.make {
+            lines.push(format!("Camera make:             {make}"));
+        }
+        if let Some(model) = &self.model {
+            lines.push(format!("Camera model:            {model}"));
+        }
+        if let Some(lens_model) = &self.lens_model {
+            lines.push(format!("Lens model:              {lens_model}"));
+        }
+        if let Some(date_time) = &self.date_time_original {
+            let sub_sec = self
+                .sub_sec_time_original
+                .as_ref()
+                .map_or(String::new(), |fraction| return format!(".{fraction}"));
+            lines.push(format!("Date taken:              {date_time}{sub_sec}"));
+        }
+        if let Some((numerator, denominator)) = self.exposure_time {
+            lines.push(format!(
+                "Exposure time:           {numerator}/{denominator} s"
+            ));
+        }
+        if let Some(f_number) = self.f_number {
+            lines.push(format!("F-number:                f/{f_number}"));
+        }
+        if let Some(iso) = self.iso {
+            lines.push(format!("ISO:                     {iso}"));
+        }
+        if let Some(focal_length) = self.focal_length {
+            lines.push(format!("Focal length:            {focal_length} mm"));
+        }
+        if let Some(orientation) = self.orientation {
+            lines.push(format!("Orientation:             {orientation}"));
+        }
+        return lines.join("\n");
+    }
+}
+
+/// Decode the fields of an IFD.
+///
+/// # Arguments
+/// * `ifd`: The IFD to decode.
+/// * `order`: The byte order of the TIFF structure.
+///
+/// # Returns
+/// The fields of the IFD, without the tags pointing to other IFDs or to the
+/// thumbnail.
+///
+/// # Errors
+/// Will error if any of the values cannot be read.
+fn decode_fields(ifd: &Ifd, order: ByteOrder) -> Result<Vec<ExifField>, GCameraError> {
+    return ifd
+        .entries
+        .iter()
+        .filter(|entry| {
+            return !matches!(
+                entry.tag,
+                TAG_EXIF_POINTER
+                    | TAG_GPS_POINTER
+                    | TAG_INTEROP_POINTER
+                    | TAG_THUMBNAIL_OFFSET
+                    | TAG_THUMBNAIL_LENGTH
+            );
+        })
+        .map(|entry| {
+            return Ok(ExifField {
+                tag: entry.tag,
+                value: ExifValue::from_entry(entry, order)?,
+            });
+        })
+        .collect();
+}
+
+/// Parse the IFD a pointer tag points to.
+///
+/// # Arguments
+/// * `data`: The TIFF structure.
+/// * `order`: The byte order of the structure.
+/// * `parent`: The IFD holding the pointer tag.
+/// * `tag`: The pointer tag.
+///
+/// # Returns
+/// The linked IFD, or None if the parent does not have the pointer tag.
+///
+/// # Errors
+/// Will error if the linked IFD cannot be parsed.
+fn parse_linked_ifd(
+    data: &[u8],
+    order: ByteOrder,
+    parent: &Ifd,
+    tag: u16,
+) -> Result<Option<Ifd>, GCameraError> {
+    return parent
+        .get(tag)
+        .and_then(|entry| return entry.as_u32(order))
+        .map(|offset| return parse_ifd(data, order, offset))
+        .transpose();
+}
+
+/// Decode the fields of an IFD that may be missing.
+///
+/// # Arguments
+/// * `ifd`: The IFD to decode, if there is one.
+/// * `order`: The byte order of the TIFF structure.
+///
+/// # Returns
+/// The fields of the IFD, or an empty list if there is no IFD.
+///
+/// # Errors
+/// Will error if any of the values cannot be read.
+fn decode_optional(ifd: Option<&Ifd>, order: ByteOrder) -> Result<Vec<ExifField>, GCameraError> {
+    return match ifd {
+        Some(linked) => decode_fields(linked, order),
+        None => Ok(Vec::new()),
+    };
+}
+
+/// Read the JPEG thumbnail described by IFD1.
+///
+/// # Arguments
+/// * `data`: The TIFF structure.
+/// * `order`: The byte order of the structure.
+/// * `ifd`: IFD1 of the structure.
+///
+/// # Returns
+/// The thumbnail, or None if the IFD does not locate one.
+///
+/// # Errors
+/// Will error if the thumbnail is past the end of the structure.
+fn read_thumbnail(
+    data: &[u8],
+    order: ByteOrder,
+    ifd: &Ifd,
+) -> Result<Option<Vec<u8>>, GCameraError> {
+    let offset = ifd
+        .get(TAG_THUMBNAIL_OFFSET)
+        .and_then(|entry| return entry.as_u32(order));
+    let length = ifd
+        .get(TAG_THUMBNAIL_LENGTH)
+        .and_then(|entry| return entry.as_u32(order));
+    let (Some(start), Some(size)) = (offset.map(to_usize), length.map(to_usize)) else {
+        return Ok(None);
+    };
+    return data
+        .get(start..start.saturating_add(size))
+        .map(|image| return Some(image.to_vec()))
+        .ok_or(GCameraError::InvalidTiffOffset { offset: start });
+}
+
+/// Point a tag of a written IFD at an offset.
+///
+/// # Arguments
+/// * `data`: The TIFF structure holding the IFD.
+/// * `order`: The byte order of the structure.
+/// * `ifd`: The written IFD.
+/// * `tag`: The tag holding the offset.
+/// * `offset`: The offset to store in the tag.
+///
+/// # Errors
+/// Will error if the offset does not fit in the tag.
+fn write_pointer(
+    data: &mut [u8],
+    order: ByteOrder,
+    ifd: &Ifd,
+    tag: u16,
+    offset: usize,
+) -> Result<(), GCameraError> {
+    let target = to_offset(offset)?;
+    return match ifd.get(tag) {
+        Some(entry) => order.write_u32(data, entry.value_offset, target),
+        None => Ok(()),
+    };
+}
+
+/// Find an editable tag by its name.
+///
+/// # Arguments
+/// * `name`: The name of the tag, ignoring case.
+///
+/// # Returns
+/// The IFD, tag and type of the tag.
+///
+/// # Errors
+/// Will error if the name is not known.
+fn find_tag(name: &str) -> Result<(IfdKind, u16, u16), GCameraError> {
+    return TAG_NAMES
+        .iter()
+        .find(|(tag_name, ..)| return tag_name.eq_ignore_ascii_case(name.trim()))
+        .map(|&(_, kind, tag, field_type)| return (kind, tag, field_type))
+        .ok_or_else(|| {
+            return GCameraError::UnknownExifTag {
+                name: name.to_owned(),
+            };
+        });
+}
+
+impl ExifData {
+    /// Create empty EXIF data.
+    ///
+    /// # Arguments
+    /// * `byte_order`: The byte order to write the TIFF structure in.
+    ///
+    /// # Returns
+    /// EXIF data without any fields.
+    pub const fn new(byte_order: ByteOrder) -> Self {
+        return Self {
+            byte_order,
+            primary: Vec::new(),
+            exif: Vec::new(),
+            gps: Vec::new(),
+            interop: Vec::new(),
+            thumbnail: Vec::new(),
+            thumbnail_image: None,
+        };
+    }
+
+    /// Get the fields of an IFD.
+    ///
+    /// # Arguments
+    /// * `kind`: The IFD to get the fields of.
+    ///
+    /// # Returns
+    /// The fields of the IFD.
+    pub fn fields(&self, kind: IfdKind) -> &[ExifField] {
+        return match kind {
+            IfdKind::Primary => &self.primary,
+            IfdKind::Exif => &self.exif,
+            IfdKind::Gps => &self.gps,
+            IfdKind::Interop => &self.interop,
+            IfdKind::Thumbnail => &self.thumbnail,
+        };
+    }
+
+    /// Get the mutable fields of an IFD.
+    ///
+    /// # Arguments
+    /// * `kind`: The IFD to get the fields of.
+    ///
+    /// # Returns
+    /// The fields of the IFD.
+    fn fields_mut(&mut self, kind: IfdKind) -> &mut Vec<ExifField> {
+        return match kind {
+            IfdKind::Primary => &mut self.primary,
+            IfdKind::Exif => &mut self.exif,
+            IfdKind::Gps => &mut self.gps,
+            IfdKind::Interop => &mut self.interop,
+            IfdKind::Thumbnail => &mut self.thumbnail,
+        };
+    }
+
+    /// Set the value of a tag, adding it if the IFD does not have it.
+    ///
+    /// # Arguments
+    /// * `kind`: The IFD holding the tag.
+    /// * `tag`: The tag to set.
+    /// * `value`: The new value of the tag.
+    pub fn set(&mut self, kind: IfdKind, tag: u16, value: ExifValue) {
+        let fields = self.fields_mut(kind);
+        if let Some(field) = fields.iter_mut().find(|field| return field.tag == tag) {
+            field.value = value;
+        } else {
+            fields.push(ExifField { tag, value });
+        }
+    }
+
+    /// Remove a tag.
+    ///
+    /// # Arguments
+    /// * `kind`: The IFD holding the tag.
+    /// * `tag`: The tag to remove.
+    ///
+    /// # Returns
+    /// Whether the IFD had the tag.
+    pub fn remove(&mut self, kind: IfdKind, tag: u16) -> bool {
+        let fields = self.fields_mut(kind);
+        let count = fields.len();
+        fields.retain(|field| return field.tag != tag);
+        return fields.len() != count;
+    }
+
+    /// Set a tag from an assignment such as `Orientation=1`.
+    ///
+    /// # Arguments
+    /// * `assignment`: The name of the tag and its new value, separated by
+    ///   `=`. The name is matched ignoring case.
+    ///
+    /// # Errors
+    /// Will error if the tag is not known, or the value is not valid for it.
+    pub fn set_by_name(&mut self, assignment: &str) -> Result<(), GCameraError> {
+        let invalid = |name: &str, text: &str| {
+            return GCameraError::InvalidExifValue {
+                tag: name.to_owned(),
+                value: text.to_owned(),
+            };
+        };
+        let (name, text) = assignment
+            .split_once('=')
+            .ok_or_else(|| return invalid(assignment, ""))?;
+        let (kind, tag, field_type) = find_tag(name)?;
+        let value = ExifValue::parse(field_type, text).ok_or_else(|| return invalid(name, text))?;
+        self.set(kind, tag, value);
+        return Ok(());
+    }
+
+    /// Remove a tag by its name.
+    ///
+    /// # Arguments
+    /// * `name`: The name of the tag, matched ignoring case.
+    ///
+    /// # Returns
+    /// Whether the tag was present.
+    ///
+    /// # Errors
+    /// Will error if the tag is not known.
+    pub fn remove_by_name(&mut self, name: &str) -> Result<bool, GCameraError> {
+        let (kind, tag, _) = find_tag(name)?;
+        return Ok(self.remove(kind, tag));
+    }
+
+    /// Remove the location and identifying data.
+    ///
+    /// The whole GPS IFD is removed, along with the tags holding serial
+    /// numbers, the owner of the camera and the maker notes.
+    ///
+    /// # Returns
+    /// A description of each item that was removed.
+    pub fn scrub(&mut self) -> Vec<String> {
+        let mut removed = Vec::new();
+        if !self.gps.is_empty() {
+            removed.push(format!("EXIF GPS data ({} tags)", self.gps.len()));
+            self.gps.clear();
+        }
+        for (name, kind, tag) in PRIVATE_TAGS {
+            if self.remove(kind, tag) {
+                removed.push(format!("EXIF tag {name}"));
+            }
+        }
+        return removed;
+    }
+
+    /// Write the EXIF data as a TIFF structure.
+    ///
+    /// IFD0 is written first, followed by the Exif, Interoperability, GPS
+    /// and IFD1 directories that have fields, and then the thumbnail.
+    ///
+    /// # Returns
+    /// The TIFF structure, to be stored after the `Exif` marker of the
+    /// segment.
+    ///
+    /// # Errors
+    /// Will error if a field has too many values, or the structure is too
+    /// large for 32-bit offsets.
+    pub fn as_bytes(&self) -> Result<Vec<u8>, GCameraError> {
+        let order = self.byte_order;
+        let entries = |fields: &[ExifField]| {
+            return fields
+                .iter()
+                .map(|field| return field.value.to_entry(field.tag, order))
+                .collect::<Result<Vec<IfdEntry>, GCameraError>>();
+        };
+        let pointer = |tag: u16| return ExifValue::Long(vec![0]).to_entry(tag, order);
+        let has_exif = !self.exif.is_empty() || !self.interop.is_empty();
+
+        let mut primary = entries(&self.primary)?;
+        if has_exif {
+            primary.push(pointer(TAG_EXIF_POINTER)?);
+        }
+        if !self.gps.is_empty() {
+            primary.push(pointer(TAG_GPS_POINTER)?);
+        }
+        let mut data = create_header(order);
+        let (primary_start, primary_ifd) = write_ifd(&mut data, order, &primary)?;
+
+        if has_exif {
+            let mut exif = entries(&self.exif)?;
+            if !self.interop.is_empty() {
+                exif.push(pointer(TAG_INTEROP_POINTER)?);
+            }
+            let (exif_start, exif_ifd) = write_ifd(&mut data, order, &exif)?;
+            write_pointer(&mut data, order, &primary_ifd, TAG_EXIF_POINTER, exif_start)?;
+            if !self.interop.is_empty() {
+                let (interop_start, _) = write_ifd(&mut data, order, &entries(&self.interop)?)?;
+                write_pointer(
+                    &mut data,
+                    order,
+                    &exif_ifd,
+                    TAG_INTEROP_POINTER,
+                    interop_start,
+                )?;
+            }
+        }
+        if !self.gps.is_empty() {
+            let (gps_start, _) = write_ifd(&mut data, order, &entries(&self.gps)?)?;
+            write_pointer(&mut data, order, &primary_ifd, TAG_GPS_POINTER, gps_start)?;
+        }
+
+        if !self.thumbnail.is_empty() || self.thumbnail_image.is_some() {
+            let mut thumbnail = entries(&self.thumbnail)?;
+            if let Some(image) = &self.thumbnail_image {
+                thumbnail.push(pointer(TAG_THUMBNAIL_OFFSET)?);
+                let length = ExifValue::Long(vec![to_offset(image.len())?]);
+                thumbnail.push(length.to_entry(TAG_THUMBNAIL_LENGTH, order)?);
+            }
+            let (thumbnail_start, thumbnail_ifd) = write_ifd(&mut data, order, &thumbnail)?;
+            order.write_u32(
+                &mut data,
+                next_offset_position(primary_start, &primary_ifd),
+                to_offset(thumbnail_start)?,
+            )?;
+            if let Some(image) = &self.thumbnail_image {
+                let image_start = data.len();
+                data.extend(image);
+                to_offset(data.len())?;
+                write_pointer(
+                    &mut data,
+                    order,
+                    &thumbnail_ifd,
+                    TAG_THUMBNAIL_OFFSET,
+                    image_start,
+                )?;
+            }
+        }
+        return Ok(data);
+    }
+
+    /// Get the value of a tag.
+    ///
+    /// # Arguments
+    /// * `kind`: The IFD holding the tag.
+    /// * `tag`: The tag to find.
+    ///
+    /// # Returns
+    /// The value of the tag, or None if the IFD does not have it.
+    pub fn get(&self, kind: IfdKind, tag: u16) -> Option<&ExifValue> {
+        return self
+            .fields(kind)
+            .iter()
+            .find(|field| return field.tag == tag)
+            .map(|field| return &field.value);
+    }
+
+    /// Get the camera settings from the EXIF data.
+    ///
+    /// # Returns
+    /// The camera settings, with None for any that are missing.
+    pub fn camera_settings(&self) -> CameraSettings {
+        let string = |kind: IfdKind, tag: u16| {
+            return self
+                .get(kind, tag)
+                .and_then(ExifValue::as_str)
+                .map(|text| return String::from(text.trim()));
+        };
+        return CameraSettings {
+            make: string(IfdKind::Primary, TAG_MAKE),
+            model: string(IfdKind::Primary, TAG_MODEL),
+            lens_model: string(IfdKind::Exif, TAG_LENS_MODEL),
+            date_time_original: string(IfdKind::Exif, TAG_DATE_TIME_ORIGINAL),
+            sub_sec_time_original: string(IfdKind::Exif, TAG_SUB_SEC_TIME_ORIGINAL),
+            exposure_time: self
+                .get(IfdKind::Exif, TAG_EXPOSURE_TIME)
+                .and_then(ExifValue::as_rational),
+            f_number: self
+                .get(IfdKind::Exif, TAG_F_NUMBER)
+                .and_then(ExifValue::as_f64),
+            iso: self.get(IfdKind::Exif, TAG_ISO).and_then(ExifValue::as_u32),
+            focal_length: self
+                .get(IfdKind::Exif, TAG_FOCAL_LENGTH)
+                .and_then(ExifValue::as_f64),
+            orientation: self
+                .get(IfdKind::Primary, TAG_ORIENTATION)
+                .and_then(ExifValue::as_u32),
+        };
+    }
+}
+
+impl TryFrom<&[u8]> for ExifData {
+    type Error = GCameraError;
+
+    /// Parse the EXIF data.
+    ///
+    /// # Arguments
+    /// * `data`: The TIFF structure, after the `Exif` marker of the segment.
+    ///
+    /// # Returns
+    /// The parsed EXIF data.
+    ///
+    /// # Errors
+    /// Will error if the TIFF structure, or any of its IFDs, are not valid.
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let (order, offset) = parse_header(data)?;
+        let primary = parse_ifd(data, order, offset)?;
+        let exif = parse_linked_ifd(data, order, &primary, TAG_EXIF_POINTER)?;
+        let gps = parse_linked_ifd(data, order, &primary, TAG_GPS_POINTER)?;
+        let interop = match &exif {
+            Some(exif_ifd) => parse_linked_ifd(data, order, exif_ifd, TAG_INTEROP_POINTER)?,
+            None => None,
+        };
+        let thumbnail = if primary.next_offset == 0 {
+            None
+        } else {
+            Some(parse_ifd(data, order, primary.next_offset)?)
+        };
+        let thumbnail_image = match &thumbnail {
+            Some(thumbnail_ifd) => read_thumbnail(data, order, thumbnail_ifd)?,
+            None => None,
+        };
+
+        return Ok(Self {
+            byte_order: order,
+            primary: decode_fields(&primary, order)?,
+            exif: decode_optional(exif.as_ref(), order)?,
+            gps: decode_optional(gps.as_ref(), order)?,
+            interop: decode_optional(interop.as_ref(), order)?,
+            thumbnail: decode_optional(thumbnail.as_ref(), order)?,
+            thumbnail_image,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Add an IFD to a little endian TIFF structure.
+    ///
+    /// # Arguments
+    /// * `data`: The structure to add the IFD to.
+    /// * `entries`: The tag, type, count and inline value of each entry.
+    /// * `next_offset`: The offset of the next IFD.
+    ///
+    /// # Panics
+    /// Will panic if there are too many entries for an IFD.
+    #[allow(clippy::little_endian_bytes)] // The test data is a little endian TIFF structure
+    fn push_ifd(data: &mut Vec<u8>, entries: &[(u16, u16, u32, [u8; 4])], next_offset: u32) {
+        data.extend(u16::try_from(entries.len()).unwrap().to_le_bytes());
+        for (tag, field_type, count, value) in entries {
+            data.extend(tag.to_le_bytes());
+            data.extend(field_type.to_le_bytes());
+            data.extend(count.to_le_bytes());
+            data.extend(value);
+        }
+        data.extend(next_offset.to_le_bytes());
+    }
+
+    /// Create little endian EXIF data, with every IFD except the GPS IFD.
+    ///
+    /// # Returns
+    /// The TIFF structure of the EXIF data.
+    #[allow(clippy::little_endian_bytes)] // The test data is a little endian TIFF structure
+    fn create_exif() -> Vec<u8> {
+        let offset = |value: u32| return value.to_le_bytes();
+        let mut data = vec![0x49, 0x49, 0x2A, 0x00, 0x08, 0x00, 0x00, 0x00];
+        push_ifd(
+            &mut data,
+            &[
+                (TAG_MAKE, TYPE_ASCII, 7, offset(0xB0)),
+                (TAG_MODEL, TYPE_ASCII, 4, *b"Pix\0"),
+                (TAG_ORIENTATION, TYPE_SHORT, 1, [0x06, 0x00, 0x00, 0x00]),
+                (TAG_EXIF_POINTER, TYPE_LONG, 1, offset(0x3E)),
+            ],
+            0x9E,
+        );
+        push_ifd(
+            &mut data,
+            &[
+                (TAG_EXPOSURE_TIME, TYPE_RATIONAL, 1, offset(0xB8)),
+                (TAG_F_NUMBER, TYPE_RATIONAL, 1, offset(0xC0)),
+                (TAG_ISO, TYPE_SHORT, 1, [0x32, 0x00, 0x00, 0x00]),
+                (TAG_DATE_TIME_ORIGINAL, TYPE_ASCII, 20, offset(0xC8)),
+                (TAG_SUB_SEC_TIME_ORIGINAL, TYPE_ASCII, 4, *b"123\0"),
+                (TAG_INTEROP_POINTER, TYPE_LONG, 1, offset(0x8C)),
+            ],
+            0,
+        );
+        push_ifd(&mut data, &[(0x0001, TYPE_ASCII, 4, *b"R98\0")], 0);
+        push_ifd(
+            &mut data,
+            &[(0x0103, TYPE_SHORT, 1, [0x06, 0x00, 0x00, 0x00])],
+            0,
+        );
+        data.extend("Google\0\0".as_bytes());
+        for value in [1, 120, 195, 100] {
+            data.extend(offset(value));
+        }
+        data.extend("2024:01:02 03:04:05\0".as_bytes());
+        return data;
+    }
+
+    /// Test parsing EXIF data with linked IFDs.
+    #[test]
+    fn test_parse_exif() {
+        let exif = ExifData::try_from(create_exif().as_slice()).unwrap();
+
+        assert_eq!(exif.byte_order, ByteOrder::LittleEndian);
+        assert_eq!(
+            exif.get(IfdKind::Primary, TAG_MAKE),
+            Some(&ExifValue::Ascii(String::from("Google")))
+        );
+        assert_eq!(exif.get(IfdKind::Primary, TAG_EXIF_POINTER), None);
+        assert_eq!(
+            exif.get(IfdKind::Exif, TAG_EXPOSURE_TIME),
+            Some(&ExifValue::Rational(vec![(1, 120)]))
+        );
+        assert_eq!(
+            exif.get(IfdKind::Interop, 0x0001),
+            Some(&ExifValue::Ascii(String::from("R98")))
+        );
+        assert_eq!(
+            exif.get(IfdKind::Thumbnail, 0x0103),
+            Some(&ExifValue::Short(vec![6]))
+        );
+        assert!(exif.fields(IfdKind::Gps).is_empty());
+    }
+
+    /// Test reading the camera settings.
+    #[test]
+    fn test_camera_settings() {
+        let settings = ExifData::try_from(create_exif().as_slice())
+            .unwrap()
+            .camera_settings();
+
+        assert_eq!(
+            settings,
+            CameraSettings {
+                make: Some(String::from("Google")),
+                model: Some(String::from("Pix")),
+                lens_model: None,
+                date_time_original: Some(String::from("2024:01:02 03:04:05")),
+                sub_sec_time_original: Some(String::from("123")),
+                exposure_time: Some((1, 120)),
+                f_number: Some(1.95),
+                iso: Some(50),
+                focal_length: None,
+                orientation: Some(6),
+            }
+        );
+        assert_eq!(
+            settings.info_str(),
+            "\
+Camera make:             Google
+Camera model:            Pix
+Date taken:              2024:01:02 03:04:05.123
+Exposure time:           1/120 s
+F-number:                f/1.95
+ISO:                     50
+Orientation:             6"
+        );
+    }
+
+    /// Test decoding signed and floating point values.
+    #[test]
+    fn test_signed_values() {
+        let entry = IfdEntry {
+            tag: 0x9204,
+            field_type: TYPE_SRATIONAL,
+            count: 1,
+            value_offset: 0,
+            value: vec![0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x03],
+        };
+        let value = ExifValue::from_entry(&entry, ByteOrder::BigEndian).unwrap();
+        assert_eq!(value, ExifValue::SRational(vec![(-1, 3)]));
+
+        let double = IfdEntry {
+            field_type: TYPE_DOUBLE,
+            value: f64::to_bits(1.5).to_be_bytes().to_vec(),
+            ..entry
+        };
+        assert_eq!(
+            ExifValue::from_entry(&double, ByteOrder::BigEndian)
+                .unwrap()
+                .as_f64(),
+            Some(1.5)
+        );
+    }
+
+    /// Test writing parsed EXIF data and parsing it again.
+    #[test]
+    fn test_write_exif() {
+        let exif = ExifData::try_from(create_exif().as_slice()).unwrap();
+        let written = exif.as_bytes().unwrap();
+
+        assert_eq!(ExifData::try_from(written.as_slice()), Ok(exif));
+    }
+
+    /// Test editing tags by name.
+    #[test]
+    fn test_edit_by_name() {
+        let mut exif = ExifData::try_from(create_exif().as_slice()).unwrap();
+        exif.set_by_name("orientation=1").unwrap();
+        exif.set_by_name("Copyright=Jane Doe").unwrap();
+        exif.set_by_name("FocalLength=690/100").unwrap();
+        exif.set_by_name("DateTimeOriginal=2024:02:03 04:05:06")
+            .unwrap();
+        assert_eq!(exif.remove_by_name("SubSecTimeOriginal"), Ok(true));
+        assert_eq!(exif.remove_by_name("LensModel"), Ok(false));
+
+        let edited = ExifData::try_from(exif.as_bytes().unwrap().as_slice()).unwrap();
+        assert_eq!(edited, exif);
+        assert_eq!(
+            edited.get(IfdKind::Primary, TAG_ORIENTATION),
+            Some(&ExifValue::Short(vec![1]))
+        );
+        assert_eq!(
+            edited.get(IfdKind::Primary, TAG_COPYRIGHT),
+            Some(&ExifValue::Ascii(String::from("Jane Doe")))
+        );
+        assert_eq!(
+            edited.get(IfdKind::Exif, TAG_FOCAL_LENGTH),
+            Some(&ExifValue::Rational(vec![(690, 100)]))
+        );
+        assert_eq!(edited.get(IfdKind::Exif, TAG_SUB_SEC_TIME_ORIGINAL), None);
+        assert_eq!(
+            edited.get(IfdKind::Interop, 0x0001),
+            Some(&ExifValue::Ascii(String::from("R98")))
+        );
+    }
+
+    /// Test editing tags with names or values that are not valid.
+    #[test]
+    fn test_edit_invalid() {
+        let mut exif = ExifData::new(ByteOrder::LittleEndian);
+        assert_eq!(
+            exif.set_by_name("Flash=1"),
+            Err(GCameraError::UnknownExifTag {
+                name: String::from("Flash")
+            })
+        );
+        assert_eq!(
+            exif.set_by_name("Orientation=up"),
+            Err(GCameraError::InvalidExifValue {
+                tag: String::from("Orientation"),
+                value: String::from("up")
+            })
+        );
+        assert_eq!(
+            exif.set_by_name("Orientation"),
+            Err(GCameraError::InvalidExifValue {
+                tag: String::from("Orientation"),
+                value: String::new()
+            })
+        );
+        assert_eq!(exif, ExifData::new(ByteOrder::LittleEndian));
+    }
+
+    /// Test writing new EXIF data with GPS fields and a thumbnail.
+    #[test]
+    fn test_write_thumbnail() {
+        let mut exif = ExifData::new(ByteOrder::BigEndian);
+        exif.set(
+            IfdKind::Primary,
+            TAG_MAKE,
+            ExifValue::Ascii(String::from("Google")),
+        );
+        exif.set(IfdKind::Gps, 0x0001, ExifValue::Ascii(String::from("N")));
+        exif.set(IfdKind::Thumbnail, 0x0103, ExifValue::Short(vec![6]));
+        exif.set(
+            IfdKind::Gps,
+            0x0002,
+            ExifValue::Rational(vec![(52, 1), (22, 1), (0, 1)]),
+        );
+        exif.thumbnail_image = Some(vec![0xFF, 0xD8, 0xFF, 0xD9, 0x00]);
+
+        let written = exif.as_bytes().unwrap();
+        assert_eq!(written.get(..4), Some([0x4D, 0x4D, 0x00, 0x2A].as_slice()));
+        assert!(written.ends_with(&[0xFF, 0xD8, 0xFF, 0xD9, 0x00]));
+        assert_eq!(ExifData::try_from(written.as_slice()), Ok(exif));
+    }
+
+    /// Test removing the location and identifying data.
+    #[test]
+    fn test_scrub() {
+        let mut exif = ExifData::try_from(create_exif().as_slice()).unwrap();
+        exif.set(IfdKind::Gps, 0x0001, ExifValue::Ascii(String::from("N")));
+        exif.set_by_name("BodySerialNumber=12345").unwrap();
+        exif.set_by_name("Artist=Jane Doe").unwrap();
+        exif.set(
+            IfdKind::Exif,
+            TAG_MAKER_NOTE,
+            ExifValue::Undefined(vec![1, 2]),
+        );
+
+        assert_eq!(
+            exif.scrub(),
+            vec![
+                String::from("EXIF GPS data (1 tags)"),
+                String::from("EXIF tag Artist"),
+                String::from("EXIF tag MakerNote"),
+                String::from("EXIF tag BodySerialNumber"),
+            ]
+        );
+        assert!(exif.fields(IfdKind::Gps).is_empty());
+        assert_eq!(
+            exif.get(IfdKind::Primary, TAG_MAKE),
+            Some(&ExifValue::Ascii(String::from("Google")))
+        );
+        assert!(exif.scrub().is_empty());
+    }
+
+    /// Test parsing EXIF data where a linked IFD is past the end of the data.
+    #[test]
+    fn test_truncated() {
+        let data = create_exif();
+        assert_eq!(
+            ExifData::try_from(&data[..0x80]),
+            Err(GCameraError::InvalidTiffOffset { offset: 0xB0 })
+        );
+    }
+}
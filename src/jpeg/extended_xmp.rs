@@ -0,0 +1,669 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Logic for working with Extended XMP data.
+//!
+//! XMP data that does not fit in a single APP1 segment is split into
+//! chunks, and stored in additional APP1 segments. Each of these segments
+//! starts with a header containing the GUID of the extended data (the MD5
+//! digest of the complete extended XMP), the full length of the extended
+//! XMP, and the offset of the chunk within the extended XMP.
+
+use roxmltree::{Document, ExpandedName, Node};
+use std::cmp::Reverse;
+use std::ops::Range;
+
+use crate::errors::GCameraError;
+use crate::jpeg::jpeg_components::{JpegSegment, MAX_DATA_LENGTH};
+use crate::jpeg::marker::JpegMarker;
+use crate::jpeg::xmp::gain_map::HDRGM_NS;
+use crate::jpeg::xmp::{
+    attribute_range, CONTAINER_NS, GCAMERA_NS, RDF_NS, XMP_MARKER, XMP_NOTE_NS,
+};
+
+/// String that occurs at the start of each Extended XMP segment.
+pub const EXTENDED_XMP_MARKER: &[u8] = "http://ns.adobe.com/xmp/extension/".as_bytes();
+
+/// Length of the GUID in the chunk header.
+pub const GUID_LENGTH: usize = 32;
+
+/// Length of the entire chunk header (GUID, full length, and offset).
+const HEADER_LENGTH: usize = GUID_LENGTH + 8;
+
+/// Maximum number of bytes of Extended XMP data that can be stored in one chunk.
+const MAX_CHUNK_LENGTH: usize = MAX_DATA_LENGTH - EXTENDED_XMP_MARKER.len() - 1 - HEADER_LENGTH;
+
+/// Maximum length of the main XMP data, so it will fit in a single segment.
+const MAX_MAIN_XMP_LENGTH: usize = MAX_DATA_LENGTH - XMP_MARKER.len() - 1;
+
+/// Compute the GUID for the given extended XMP data.
+///
+/// # Arguments
+/// * `data`: The complete extended XMP data.
+///
+/// # Returns
+/// The GUID, which is the MD5 digest of the data as upper case hex.
+pub fn compute_guid(data: &[u8]) -> String {
+    return format!("{:X}", md5::compute(data));
+}
+
+/// A single chunk of Extended XMP data, stored in a single APP1 segment.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ExtendedXMPChunk {
+    /// The GUID of the extended XMP data the chunk is a part of.
+    pub guid: String,
+
+    /// The length of the complete extended XMP data.
+    pub full_length: u32,
+
+    /// The offset of this chunk within the complete extended XMP data.
+    pub offset: u32,
+
+    /// The data in this chunk.
+    pub data: Vec<u8>,
+}
+
+impl ExtendedXMPChunk {
+    /// Create a `JpegSegment` holding this chunk.
+    ///
+    /// # Returns
+    /// Result holding the created APP1 segment.
+    ///
+    /// # Errors
+    /// Will error if the chunk is too large to fit in a single segment.
+    pub fn as_segment(&self) -> Result<JpegSegment, GCameraError> {
+        let data = [
+            EXTENDED_XMP_MARKER,
+            &[0x00],
+            self.guid.as_bytes(),
+            &self.full_length.to_be_bytes(),
+            &self.offset.to_be_bytes(),
+            &self.data,
+        ]
+        .concat();
+        return JpegSegment::new(JpegMarker::APP1, &data);
+    }
+}
+
+/// Implementation to create a chunk from the bytes of an APP1 segment.
+impl TryFrom<&[u8]> for ExtendedXMPChunk {
+    type Error = GCameraError;
+
+    /// Create an instance from the bytes following the Extended XMP marker.
+    ///
+    /// # Arguments
+    /// * `bytes`: The bytes of the segment after the marker and its null terminator.
+    ///
+    /// # Returns
+    /// Result holding the created chunk, or an error.
+    ///
+    /// # Errors
+    /// Will error if the bytes are too short to hold the chunk header, or
+    /// the GUID is not valid UTF-8. The error gives an offset of 0, since
+    /// the position of the segment is not known here.
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        if bytes.len() < HEADER_LENGTH {
+            return Err(GCameraError::InvalidExtendedXMPChunk { offset: 0 });
+        }
+
+        let guid = String::from_utf8(bytes[..GUID_LENGTH].to_vec())
+            .map_err(|_| return GCameraError::InvalidExtendedXMPChunk { offset: 0 })?;
+
+        return Ok(Self {
+            guid,
+            full_length: u32::from_be_bytes(
+                bytes[GUID_LENGTH..GUID_LENGTH + 4].try_into().unwrap(),
+            ),
+            offset: u32::from_be_bytes(bytes[GUID_LENGTH + 4..HEADER_LENGTH].try_into().unwrap()),
+            data: bytes[HEADER_LENGTH..].to_vec(),
+        });
+    }
+}
+
+/// Merge chunks of Extended XMP data back into a single document.
+///
+/// Only chunks matching the given GUID are used. The chunks are ordered by
+/// their offsets, and the merged data is checked against the GUID.
+///
+/// # Arguments
+/// * `guid`: The GUID of the extended XMP, as given by `xmpNote:HasExtendedXMP`.
+/// * `chunks`: The chunks found in the image.
+///
+/// # Returns
+/// The merged extended XMP document as a string.
+///
+/// # Errors
+/// Will error if no chunks match the GUID, if the chunks do not cover the
+/// full length of the data, if the MD5 digest does not match the GUID, or
+/// if the merged data is not a valid UTF-8 string.
+pub fn merge_chunks(guid: &str, chunks: &[ExtendedXMPChunk]) -> Result<String, GCameraError> {
+    let mut matching: Vec<&ExtendedXMPChunk> = chunks
+        .iter()
+        .filter(|chunk| return chunk.guid.eq_ignore_ascii_case(guid))
+        .collect();
+
+    if matching.is_empty() {
+        return Err(GCameraError::NoExtendedXMPData);
+    }
+
+    matching.sort_by_key(|chunk| return chunk.offset);
+
+    // The full length comes from the chunk headers, so only the data that is
+    // actually present is preallocated.
+    let full_length = matching[0].full_length;
+    let mut merged: Vec<u8> =
+        Vec::with_capacity(matching.iter().map(|chunk| return chunk.data.len()).sum());
+    for chunk in matching {
+        if chunk.offset as usize != merged.len() {
+            return Err(GCameraError::ExtendedXMPOffsetMismatch {
+                expected: merged.len(),
+                found: chunk.offset as usize,
+            });
+        }
+        merged.extend(&chunk.data);
+    }
+
+    if merged.len() != full_length as usize {
+        return Err(GCameraError::ExtendedXMPLengthMismatch {
+            expected: full_length as usize,
+            found: merged.len(),
+        });
+    }
+
+    let digest = compute_guid(&merged);
+    if !digest.eq_ignore_ascii_case(guid) {
+        return Err(GCameraError::ExtendedXMPDigestMismatch {
+            guid: String::from(guid),
+            digest,
+        });
+    }
+
+    return String::from_utf8(merged).map_err(|error| {
+        return GCameraError::InvalidUtf8 {
+            structure: "Extended XMP data",
+            offset: error.utf8_error().valid_up_to(),
+        };
+    });
+}
+
+/// Split Extended XMP data into chunks that will each fit in a single segment.
+///
+/// # Arguments
+/// * `extended_xmp`: The complete Extended XMP document.
+///
+/// # Returns
+/// Vector of the chunks of the Extended XMP data.
+///
+/// # Errors
+/// Will error if the Extended XMP is longer than can be described in the
+/// chunk headers.
+pub fn split_into_chunks(extended_xmp: &str) -> Result<Vec<ExtendedXMPChunk>, GCameraError> {
+    let bytes = extended_xmp.as_bytes();
+    let full_length = u32::try_from(bytes.len()).map_err(|_| return GCameraError::XMPTooLarge)?;
+    let guid = compute_guid(bytes);
+
+    return bytes
+        .chunks(MAX_CHUNK_LENGTH)
+        .enumerate()
+        .map(|(index, data)| {
+            return Ok(ExtendedXMPChunk {
+                guid: guid.clone(),
+                full_length,
+                offset: u32::try_from(index * MAX_CHUNK_LENGTH)
+                    .map_err(|_| return GCameraError::XMPTooLarge)?,
+                data: data.to_vec(),
+            });
+        })
+        .collect();
+}
+
+/// Create the segments holding the given Extended XMP data.
+///
+/// # Arguments
+/// * `extended_xmp`: The complete Extended XMP document.
+///
+/// # Returns
+/// Vector of APP1 segments holding the chunks of the Extended XMP data.
+///
+/// # Errors
+/// Will error if the Extended XMP data is too large.
+pub fn create_segments(extended_xmp: &str) -> Result<Vec<JpegSegment>, GCameraError> {
+    return split_into_chunks(extended_xmp)?
+        .iter()
+        .map(|chunk| return chunk.as_segment())
+        .collect();
+}
+
+/// Check if a property has to stay in the main XMP data.
+///
+/// The resources of an image, and the gain map metadata, are only read from
+/// the main XMP data, so the container directory, the motion photo
+/// properties, and the gain map properties must never be moved.
+///
+/// # Arguments
+/// * `namespace`: The namespace of the property.
+/// * `name`: The local name of the property.
+///
+/// # Returns
+/// Boolean indicating if the property must stay in the main XMP data.
+fn is_main_only(namespace: Option<&str>, name: &str) -> bool {
+    return namespace == Some(CONTAINER_NS)
+        || namespace == Some(HDRGM_NS)
+        || (namespace == Some(GCAMERA_NS)
+            && (name.starts_with("MotionPhoto") || name.starts_with("MicroVideo")));
+}
+
+/// Get the ranges of all of the movable properties of a description node.
+///
+/// This includes both properties stored as attributes, and properties
+/// stored as child elements. Attributes from the RDF namespace, the
+/// `xmpNote:HasExtendedXMP` attribute, and the properties that must stay in
+/// the main XMP data are not included.
+///
+/// # Arguments
+/// * `text`: The original XML text.
+/// * `description`: The description node to get the properties of.
+///
+/// # Returns
+/// Tuple of the attribute ranges and the child element ranges.
+fn property_ranges(text: &str, description: Node) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    let attributes = description
+        .attributes()
+        .filter(|attribute| {
+            return attribute.namespace() != Some(RDF_NS)
+                && ExpandedName::from((attribute.namespace().unwrap_or(""), attribute.name()))
+                    != ExpandedName::from((XMP_NOTE_NS, "HasExtendedXMP"))
+                && !is_main_only(attribute.namespace(), attribute.name());
+        })
+        .map(|attribute| return attribute_range(text, attribute.position()))
+        .collect();
+    let elements = description
+        .children()
+        .filter(|child| {
+            return child.is_element()
+                && !is_main_only(child.tag_name().namespace(), child.tag_name().name());
+        })
+        .map(|child| return child.range())
+        .collect();
+    return (attributes, elements);
+}
+
+/// Split XMP data into main and Extended XMP, if it is too large for one segment.
+///
+/// If the XMP fits within a single segment, it is returned unchanged.
+/// Otherwise, the largest properties are moved into an Extended XMP document
+/// until the main XMP fits, and an `xmpNote:HasExtendedXMP` attribute
+/// holding the GUID of the Extended XMP is added to the main XMP. The
+/// resource and gain map properties are always kept in the main XMP.
+///
+/// # Arguments
+/// * `xmp`: The XMP data to split.
+///
+/// # Returns
+/// Tuple of the main XMP, and the Extended XMP, if one was needed.
+///
+/// # Errors
+/// Will error if the XMP cannot be parsed, or if the main XMP cannot be
+/// made small enough to fit in a single segment.
+pub fn split_xmp(xmp: &str) -> Result<(String, Option<String>), GCameraError> {
+    if xmp.len() <= MAX_MAIN_XMP_LENGTH {
+        return Ok((String::from(xmp), None));
+    }
+
+    let document = Document::parse(xmp)
+        .map_err(|xml_error| return GCameraError::XMLParsingError { xml_error })?;
+    let description = document
+        .descendants()
+        .find(|n| return n.tag_name() == ExpandedName::from((RDF_NS, "Description")))
+        .ok_or(GCameraError::DescriptionNodeNotFound)?;
+
+    // Any existing note is always removed, since the GUID will be changing.
+    let existing_note = description
+        .attributes()
+        .find(|attribute| {
+            return attribute.namespace() == Some(XMP_NOTE_NS)
+                && attribute.name() == "HasExtendedXMP";
+        })
+        .map(|attribute| return attribute_range(xmp, attribute.position()));
+
+    let note_prefix = description.lookup_prefix(XMP_NOTE_NS);
+    let namespace_declaration = match note_prefix {
+        Some(_) => String::new(),
+        None => format!(" xmlns:xmpNote=\"{XMP_NOTE_NS}\""),
+    };
+    let create_note = |guid: &str| {
+        return format!(
+            "{namespace_declaration} {}:HasExtendedXMP=\"{guid}\"",
+            note_prefix.unwrap_or("xmpNote")
+        );
+    };
+    let note_length = create_note("").len() + GUID_LENGTH;
+
+    // Move the largest properties first, until the main XMP is small enough.
+    let (attributes, elements) = property_ranges(xmp, description);
+    let mut candidates: Vec<(bool, Range<usize>)> = attributes
+        .into_iter()
+        .map(|range| return (true, range))
+        .chain(elements.into_iter().map(|range| return (false, range)))
+        .collect();
+    candidates.sort_by_key(|(_, range)| return Reverse(range.len()));
+
+    let mut main_length =
+        xmp.len() + note_length - existing_note.as_ref().map_or(0, |range| return range.len());
+    let mut moved: Vec<(bool, Range<usize>)> = Vec::new();
+    for candidate in candidates {
+        if main_length <= MAX_MAIN_XMP_LENGTH {
+            break;
+        }
+        main_length -= candidate.1.len();
+        moved.push(candidate);
+    }
+    if main_length > MAX_MAIN_XMP_LENGTH {
+        return Err(GCameraError::XMPTooLarge);
+    }
+
+    // Build the extended XMP from the moved properties, keeping document order.
+    moved.sort_by_key(|(_, range)| return range.start);
+    let namespaces: String = description
+        .namespaces()
+        .filter(|namespace| return namespace.name() != Some("xml"))
+        .map(|namespace| {
+            return match namespace.name() {
+                Some(prefix) => format!(" xmlns:{prefix}=\"{}\"", namespace.uri()),
+                None => format!(" xmlns=\"{}\"", namespace.uri()),
+            };
+        })
+        .collect();
+    let moved_attributes: String = moved
+        .iter()
+        .filter(|(is_attribute, _)| return *is_attribute)
+        .map(|(_, range)| return format!(" {}", xmp.get(range.clone()).unwrap_or_default()))
+        .collect();
+    let moved_elements: String = moved
+        .iter()
+        .filter(|(is_attribute, _)| return !*is_attribute)
+        .map(|(_, range)| return xmp.get(range.clone()).unwrap_or_default())
+        .collect();
+    let extended = format!(
+        "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><rdf:RDF xmlns:rdf=\"{RDF_NS}\"><rdf:Description rdf:about=\"\"{namespaces}{moved_attributes}>{moved_elements}</rdf:Description></rdf:RDF></x:xmpmeta>"
+    );
+
+    // Build the main XMP by removing the moved properties, working backwards
+    // so that the earlier ranges remain valid.
+    let mut removals: Vec<Range<usize>> =
+        moved.into_iter().map(|(_, range)| return range).collect();
+    removals.extend(existing_note);
+    removals.sort_by_key(|range| return Reverse(range.start));
+    let mut main = String::from(xmp);
+    for range in removals {
+        main.replace_range(range, "");
+    }
+
+    // Insert the note directly after the name of the description tag.
+    let tag_start = description.range().start;
+    let insert_at = tag_start
+        + xmp
+            .get(tag_start..)
+            .unwrap_or_default()
+            .find(|c: char| return c.is_whitespace() || c == '/' || c == '>')
+            .unwrap_or(0);
+    main.insert_str(insert_at, &create_note(&compute_guid(extended.as_bytes())));
+
+    return Ok((main, Some(extended)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create a chunk for the given data, as would be stored in a segment.
+    ///
+    /// # Arguments
+    /// * `guid`: The GUID to write into the chunk header
+    /// * `full_length`: The full length to write into the chunk header
+    /// * `offset`: The offset to write into the chunk header
+    /// * `data`: The chunk data.
+    ///
+    /// # Returns
+    /// The bytes of the chunk.
+    fn chunk_bytes(guid: &str, full_length: u32, offset: u32, data: &[u8]) -> Vec<u8> {
+        return [
+            guid.as_bytes(),
+            &full_length.to_be_bytes(),
+            &offset.to_be_bytes(),
+            data,
+        ]
+        .concat();
+    }
+
+    /// Test computing the GUID.
+    #[test]
+    fn test_compute_guid() {
+        assert_eq!(compute_guid(b"hello"), "5D41402ABC4B2A76B9719D911017C592");
+    }
+
+    /// Tests for creating a chunk from bytes.
+    mod test_chunk_try_from {
+        use super::*;
+
+        /// Test successfully parsing a chunk.
+        #[test]
+        fn test_valid() {
+            let bytes = chunk_bytes("5D41402ABC4B2A76B9719D911017C592", 5, 2, b"llo");
+            assert_eq!(
+                ExtendedXMPChunk::try_from(bytes.as_slice()),
+                Ok(ExtendedXMPChunk {
+                    guid: String::from("5D41402ABC4B2A76B9719D911017C592"),
+                    full_length: 5,
+                    offset: 2,
+                    data: b"llo".to_vec(),
+                })
+            );
+        }
+
+        /// Test a chunk that is too short to hold the header.
+        #[test]
+        fn test_too_short() {
+            let bytes: &[u8] = b"5D41402ABC4B2A76B9719D911017C592";
+            assert_eq!(
+                ExtendedXMPChunk::try_from(bytes),
+                Err(GCameraError::InvalidExtendedXMPChunk { offset: 0 })
+            );
+        }
+    }
+
+    /// Tests for the `merge_chunks` function.
+    mod test_merge_chunks {
+        use super::*;
+
+        /// GUID of the string "hello".
+        const HELLO_GUID: &str = "5D41402ABC4B2A76B9719D911017C592";
+
+        /// Create a chunk of the string "hello".
+        ///
+        /// # Arguments
+        /// * `guid`: The GUID of the chunk.
+        /// * `offset`: The offset of the chunk in the full string.
+        /// * `data`: The data in the chunk.
+        ///
+        /// # Returns
+        /// The created chunk.
+        fn hello_chunk(guid: &str, offset: u32, data: &str) -> ExtendedXMPChunk {
+            return ExtendedXMPChunk {
+                guid: String::from(guid),
+                full_length: 5,
+                offset,
+                data: data.as_bytes().to_vec(),
+            };
+        }
+
+        /// Test merging chunks that are out of order, and have an unrelated chunk.
+        #[test]
+        fn test_merge_out_of_order() {
+            let chunks = vec![
+                hello_chunk(HELLO_GUID, 3, "lo"),
+                hello_chunk("00000000000000000000000000000000", 0, "bye"),
+                hello_chunk(HELLO_GUID, 0, "hel"),
+            ];
+            assert_eq!(merge_chunks(HELLO_GUID, &chunks), Ok(String::from("hello")));
+        }
+
+        /// Test when no chunks have a matching GUID.
+        #[test]
+        fn test_no_matching_chunks() {
+            let chunks = vec![hello_chunk("00000000000000000000000000000000", 0, "hello")];
+            assert_eq!(
+                merge_chunks(HELLO_GUID, &chunks),
+                Err(GCameraError::NoExtendedXMPData)
+            );
+        }
+
+        /// Test when a chunk is missing from the middle of the data.
+        #[test]
+        fn test_missing_chunk() {
+            let chunks = vec![
+                hello_chunk(HELLO_GUID, 0, "he"),
+                hello_chunk(HELLO_GUID, 3, "lo"),
+            ];
+            assert_eq!(
+                merge_chunks(HELLO_GUID, &chunks),
+                Err(GCameraError::ExtendedXMPOffsetMismatch {
+                    expected: 2,
+                    found: 3
+                })
+            );
+        }
+
+        /// Test when the chunks do not add up to the full length.
+        #[test]
+        fn test_truncated() {
+            let chunks = vec![hello_chunk(HELLO_GUID, 0, "hel")];
+            assert_eq!(
+                merge_chunks(HELLO_GUID, &chunks),
+                Err(GCameraError::ExtendedXMPLengthMismatch {
+                    expected: 5,
+                    found: 3
+                })
+            );
+        }
+
+        /// Test when the header claims a much larger length than the chunks hold.
+        #[test]
+        fn test_huge_full_length() {
+            let mut chunk = hello_chunk(HELLO_GUID, 0, "hello");
+            chunk.full_length = u32::MAX;
+            assert_eq!(
+                merge_chunks(HELLO_GUID, &[chunk]),
+                Err(GCameraError::ExtendedXMPLengthMismatch {
+                    expected: u32::MAX as usize,
+                    found: 5
+                })
+            );
+        }
+
+        /// Test when the data does not match the GUID.
+        #[test]
+        fn test_bad_digest() {
+            let chunks = vec![hello_chunk(HELLO_GUID, 0, "jello")];
+            assert_eq!(
+                merge_chunks(HELLO_GUID, &chunks),
+                Err(GCameraError::ExtendedXMPDigestMismatch {
+                    guid: String::from(HELLO_GUID),
+                    digest: String::from("7AA6991A62353DD2761280CF592542DC")
+                })
+            );
+        }
+    }
+
+    /// Tests for splitting Extended XMP into chunks.
+    mod test_split_into_chunks {
+        use super::*;
+
+        /// Test splitting data that needs multiple chunks.
+        #[test]
+        fn test_multiple_chunks() {
+            let data = "A".repeat(MAX_CHUNK_LENGTH + 10);
+            let chunks = split_into_chunks(&data).unwrap();
+
+            assert_eq!(chunks.len(), 2);
+            assert_eq!(chunks[0].data.len(), MAX_CHUNK_LENGTH);
+            assert_eq!(chunks[1].offset as usize, MAX_CHUNK_LENGTH);
+            assert_eq!(chunks[1].data.len(), 10);
+            assert!(chunks
+                .iter()
+                .all(|chunk| return chunk.full_length as usize == data.len()));
+            assert_eq!(
+                merge_chunks(&compute_guid(data.as_bytes()), &chunks),
+                Ok(data)
+            );
+        }
+
+        /// Test that the created segments round trip back into chunks.
+        #[test]
+        fn test_create_segments() {
+            let data = "B".repeat(MAX_CHUNK_LENGTH * 2);
+            let segments = create_segments(&data).unwrap();
+            let chunks: Vec<ExtendedXMPChunk> = segments
+                .iter()
+                .map(|segment| return segment.as_extended_xmp_chunk().unwrap().unwrap())
+                .collect();
+
+            assert_eq!(chunks, split_into_chunks(&data).unwrap());
+            assert!(segments
+                .iter()
+                .all(|segment| return segment.data.as_ref().unwrap().len() <= MAX_DATA_LENGTH));
+        }
+    }
+
+    /// Tests for the `split_xmp` function.
+    mod test_split_xmp {
+        use super::*;
+
+        /// Test XMP data that is small enough to not be split.
+        #[test]
+        fn test_small_xmp() {
+            let xmp = "<x:xmpmeta xmlns:x='adobe:ns:meta/'/>";
+            assert_eq!(split_xmp(xmp), Ok((String::from(xmp), None)));
+        }
+
+        /// Test XMP data with a large child element property.
+        #[test]
+        fn test_large_element() {
+            let big_text = "C".repeat(MAX_MAIN_XMP_LENGTH);
+            let xmp = format!("<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:xmpNote='http://ns.adobe.com/xmp/note/' xmlns:dc='http://purl.org/dc/elements/1.1/' xmpNote:HasExtendedXMP='OLD' dc:format='image/jpeg'><dc:description>{big_text}</dc:description></rdf:Description></rdf:RDF></x:xmpmeta>");
+            let (main, extended) = split_xmp(&xmp).unwrap();
+            let extended_xmp = extended.unwrap();
+            let guid = compute_guid(extended_xmp.as_bytes());
+
+            assert_eq!(
+                main,
+                format!("<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description xmpNote:HasExtendedXMP=\"{guid}\" rdf:about='' xmlns:xmpNote='http://ns.adobe.com/xmp/note/' xmlns:dc='http://purl.org/dc/elements/1.1/'  dc:format='image/jpeg'></rdf:Description></rdf:RDF></x:xmpmeta>")
+            );
+            let document = Document::parse(&extended_xmp).unwrap();
+            let description = document
+                .descendants()
+                .find(|n| return n.has_tag_name((RDF_NS, "Description")))
+                .unwrap();
+            assert_eq!(
+                description
+                    .children()
+                    .find(|n| return n
+                        .has_tag_name(("http://purl.org/dc/elements/1.1/", "description")))
+                    .and_then(|n| return n.text()),
+                Some(big_text.as_str())
+            );
+        }
+
+        /// Test XMP data where a single property is too large to fit anywhere.
+        #[test]
+        fn test_too_large() {
+            let xmp = format!(
+                "<x:xmpmeta xmlns:x='adobe:ns:meta/'><!-- {} --><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about=''/></rdf:RDF></x:xmpmeta>",
+                "D".repeat(MAX_MAIN_XMP_LENGTH)
+            );
+            assert_eq!(split_xmp(&xmp), Err(GCameraError::XMPTooLarge));
+        }
+    }
+}
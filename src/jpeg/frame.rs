@@ -0,0 +1,380 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Logic for parsing the frame header of a JPEG image.
+//!
+//! The frame header is stored in one of the SOF segments, and its marker
+//! gives the coding process of the image. It holds the sample precision,
+//! the size of the image, and for each component its identifier, its
+//! sampling factors and the quantization table it uses. All values are big
+//! endian.
+use crate::errors::GCameraError;
+use crate::jpeg::marker::JpegMarker;
+
+/// Number of bytes in the frame header before the component list.
+const HEADER_LENGTH: usize = 6;
+
+/// Number of bytes used by each component in the frame header.
+const COMPONENT_LENGTH: usize = 3;
+
+/// A single component of the image, as listed in the frame header.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FrameComponent {
+    /// The identifier of the component, used by the scan headers.
+    pub id: u8,
+
+    /// The horizontal sampling factor, from 1 to 4.
+    pub horizontal_sampling: u8,
+
+    /// The vertical sampling factor, from 1 to 4.
+    pub vertical_sampling: u8,
+
+    /// The quantization table used by the component.
+    pub quant_table: u8,
+}
+
+/// The frame header from a SOF segment.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FrameHeader {
+    /// The marker of the segment, which gives the coding process.
+    pub marker: JpegMarker,
+
+    /// The number of bits in each sample.
+    pub precision: u8,
+
+    /// The height of the image, or 0 if it is given by a DNL segment.
+    pub height: u16,
+
+    /// The width of the image.
+    pub width: u16,
+
+    /// The components of the image.
+    pub components: Vec<FrameComponent>,
+}
+
+impl FrameHeader {
+    /// Get the name of the coding process of the image.
+    ///
+    /// # Returns
+    /// The coding process, from the marker of the frame header.
+    pub const fn process_name(&self) -> &'static str {
+        #[allow(clippy::wildcard_enum_match_arm)] // Only the SOF markers hold a frame header
+        return match self.marker {
+            JpegMarker::SOF0 => "baseline",
+            JpegMarker::SOF1 => "extended sequential",
+            JpegMarker::SOF2 => "progressive",
+            JpegMarker::SOF3 => "lossless",
+            JpegMarker::SOF5 => "differential sequential",
+            JpegMarker::SOF6 => "differential progressive",
+            JpegMarker::SOF7 => "differential lossless",
+            JpegMarker::SOF9 => "extended sequential, arithmetic",
+            JpegMarker::SOF10 => "progressive, arithmetic",
+            JpegMarker::SOF11 => "lossless, arithmetic",
+            JpegMarker::SOF13 => "differential sequential, arithmetic",
+            JpegMarker::SOF14 => "differential progressive, arithmetic",
+            JpegMarker::SOF15 => "differential lossless, arithmetic",
+            _ => "unknown",
+        };
+    }
+
+    /// Get the chroma subsampling of the image.
+    ///
+    /// Images with a luma component and two chroma components that share
+    /// the same sampling factors get the usual `J:a:b` notation. Any other
+    /// image gets the sampling factors of each component.
+    ///
+    /// # Returns
+    /// The chroma subsampling, such as `4:2:0`.
+    pub fn subsampling(&self) -> String {
+        if let [luma, first_chroma, second_chroma] = self.components.as_slice() {
+            if first_chroma.horizontal_sampling == second_chroma.horizontal_sampling
+                && first_chroma.vertical_sampling == second_chroma.vertical_sampling
+            {
+                let ratios = (
+                    luma.horizontal_sampling
+                        .checked_div(first_chroma.horizontal_sampling)
+                        .filter(|ratio| {
+                            return ratio.checked_mul(first_chroma.horizontal_sampling)
+                                == Some(luma.horizontal_sampling);
+                        }),
+                    luma.vertical_sampling
+                        .checked_div(first_chroma.vertical_sampling)
+                        .filter(|ratio| {
+                            return ratio.checked_mul(first_chroma.vertical_sampling)
+                                == Some(luma.vertical_sampling);
+                        }),
+                );
+                let name = match ratios {
+                    (Some(1), Some(1)) => Some("4:4:4"),
+                    (Some(1), Some(2)) => Some("4:4:0"),
+                    (Some(2), Some(1)) => Some("4:2:2"),
+                    (Some(2), Some(2)) => Some("4:2:0"),
+                    (Some(4), Some(1)) => Some("4:1:1"),
+                    (Some(4), Some(2)) => Some("4:1:0"),
+                    (_, _) => None,
+                };
+                if let Some(subsampling) = name {
+                    return String::from(subsampling);
+                }
+            }
+        }
+        if self.components.len() == 1 {
+            return String::from("grayscale");
+        }
+        return self
+            .components
+            .iter()
+            .map(|component| {
+                return format!(
+                    "{}x{}",
+                    component.horizontal_sampling, component.vertical_sampling
+                );
+            })
+            .collect::<Vec<String>>()
+            .join(",");
+    }
+
+    /// Get a description of the frame header.
+    ///
+    /// # Returns
+    /// String with the size, subsampling and coding process of the image.
+    pub fn info_str(&self) -> String {
+        return format!(
+            "\
+Image dimensions:        {}x{}
+Chroma subsampling:      {}
+JPEG process:            {}, {}-bit",
+            self.width,
+            self.height,
+            self.subsampling(),
+            self.process_name(),
+            self.precision
+        );
+    }
+}
+
+impl TryFrom<(JpegMarker, &[u8])> for FrameHeader {
+    type Error = GCameraError;
+
+    /// Parse the frame header.
+    ///
+    /// # Arguments
+    /// * `marker`: The marker of the SOF segment.
+    /// * `data`: The data of the segment, after its length.
+    ///
+    /// # Returns
+    /// The parsed frame header.
+    ///
+    /// # Errors
+    /// Will error if the header is truncated, has trailing data, has no
+    /// components or a width of 0, or has a sampling factor outside of 1
+    /// to 4. The error gives an offset of 0, since the position of the
+    /// header is not known here.
+    fn try_from((marker, data): (JpegMarker, &[u8])) -> Result<Self, Self::Error> {
+        let Some((&[precision, height_high, height_low, width_high, width_low, count], list)) =
+            data.split_first_chunk::<HEADER_LENGTH>()
+        else {
+            return Err(GCameraError::InvalidFrameHeader { offset: 0 });
+        };
+        let width = u16::from_be_bytes([width_high, width_low]);
+        if count == 0 || width == 0 || list.len() != usize::from(count) * COMPONENT_LENGTH {
+            return Err(GCameraError::InvalidFrameHeader { offset: 0 });
+        }
+
+        let components = list
+            .chunks_exact(COMPONENT_LENGTH)
+            .map(|component| {
+                let &[id, sampling, quant_table] = component else {
+                    return Err(GCameraError::InvalidFrameHeader { offset: 0 });
+                };
+                let horizontal_sampling = sampling >> 4;
+                let vertical_sampling = sampling & 0x0F;
+                if !(1..=4).contains(&horizontal_sampling) || !(1..=4).contains(&vertical_sampling)
+                {
+                    return Err(GCameraError::InvalidFrameHeader { offset: 0 });
+                }
+                return Ok(FrameComponent {
+                    id,
+                    horizontal_sampling,
+                    vertical_sampling,
+                    quant_table,
+                });
+            })
+            .collect::<Result<Vec<FrameComponent>, GCameraError>>()?;
+
+        return Ok(Self {
+            marker,
+            precision,
+            height: u16::from_be_bytes([height_high, height_low]),
+            width,
+            components,
+        });
+    }
+}
+
+/// Check that a gain map can be applied to a primary image.
+///
+/// The gain map is scaled up to the size of the primary image, so its size
+/// must be the size of the primary image divided by the same factor in both
+/// directions, rounded up.
+///
+/// # Arguments
+/// * `primary`: The frame header of the primary image.
+/// * `gain_map`: The frame header of the gain map.
+///
+/// # Returns
+/// The factor the gain map is scaled up by.
+///
+/// # Errors
+/// Will error if the size of the gain map does not match the size of the
+/// primary image for any scale factor.
+pub fn gain_map_scale(primary: &FrameHeader, gain_map: &FrameHeader) -> Result<u16, GCameraError> {
+    let scale = primary.width.div_ceil(gain_map.width);
+    if primary.width.div_ceil(scale) != gain_map.width
+        || primary.height.div_ceil(scale) != gain_map.height
+    {
+        return Err(GCameraError::IncompatibleGainMapSize {
+            width: primary.width,
+            height: primary.height,
+            gain_map_width: gain_map.width,
+            gain_map_height: gain_map.height,
+        });
+    }
+    return Ok(scale);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create the data of a frame header for an 8-bit YCbCr image.
+    ///
+    /// # Arguments
+    /// * `width`: The width of the image.
+    /// * `height`: The height of the image.
+    /// * `luma_sampling`: The sampling factors of the luma component.
+    ///
+    /// # Returns
+    /// The data of the SOF segment.
+    fn create_frame(width: u16, height: u16, luma_sampling: u8) -> Vec<u8> {
+        let mut data = vec![0x08];
+        data.extend(height.to_be_bytes());
+        data.extend(width.to_be_bytes());
+        data.extend([
+            0x03,
+            0x01,
+            luma_sampling,
+            0x00,
+            0x02,
+            0x11,
+            0x01,
+            0x03,
+            0x11,
+            0x01,
+        ]);
+        return data;
+    }
+
+    /// Test parsing the frame header of a baseline 4:2:0 image.
+    #[test]
+    fn test_parse_frame() {
+        let data = create_frame(4032, 3024, 0x22);
+        let frame = FrameHeader::try_from((JpegMarker::SOF0, data.as_slice())).unwrap();
+
+        assert_eq!(frame.precision, 8);
+        assert_eq!(frame.width, 4032);
+        assert_eq!(frame.height, 3024);
+        assert_eq!(
+            frame.components[0],
+            FrameComponent {
+                id: 1,
+                horizontal_sampling: 2,
+                vertical_sampling: 2,
+                quant_table: 0,
+            }
+        );
+        assert_eq!(frame.components[2].quant_table, 1);
+        assert_eq!(
+            frame.info_str(),
+            "\
+Image dimensions:        4032x3024
+Chroma subsampling:      4:2:0
+JPEG process:            baseline, 8-bit"
+        );
+    }
+
+    /// Test naming the chroma subsampling of different images.
+    #[test]
+    fn test_subsampling() {
+        let subsampling = |luma_sampling: u8| {
+            let data = create_frame(16, 16, luma_sampling);
+            return FrameHeader::try_from((JpegMarker::SOF2, data.as_slice()))
+                .unwrap()
+                .subsampling();
+        };
+        assert_eq!(subsampling(0x11), "4:4:4");
+        assert_eq!(subsampling(0x21), "4:2:2");
+        assert_eq!(subsampling(0x41), "4:1:1");
+        assert_eq!(subsampling(0x12), "4:4:0");
+        assert_eq!(subsampling(0x32), "3x2,1x1,1x1");
+
+        let grayscale = [0x08, 0x00, 0x10, 0x00, 0x10, 0x01, 0x01, 0x11, 0x00];
+        let frame = FrameHeader::try_from((JpegMarker::SOF1, grayscale.as_slice())).unwrap();
+        assert_eq!(frame.subsampling(), "grayscale");
+        assert_eq!(frame.process_name(), "extended sequential");
+    }
+
+    /// Test parsing frame headers that are not valid.
+    #[test]
+    fn test_invalid_frame() {
+        let data = create_frame(16, 16, 0x22);
+        let invalid = [
+            data[..8].to_vec(),
+            create_frame(0, 16, 0x22),
+            create_frame(16, 16, 0x50),
+            create_frame(16, 16, 0x20),
+            [data.as_slice(), &[0x00]].concat(),
+        ];
+        for invalid_data in invalid {
+            assert_eq!(
+                FrameHeader::try_from((JpegMarker::SOF0, invalid_data.as_slice())),
+                Err(GCameraError::InvalidFrameHeader { offset: 0 })
+            );
+        }
+    }
+
+    /// Test checking that a gain map fits the primary image.
+    #[test]
+    fn test_gain_map_scale() {
+        let frame = |width: u16, height: u16| {
+            let data = create_frame(width, height, 0x22);
+            return FrameHeader::try_from((JpegMarker::SOF0, data.as_slice())).unwrap();
+        };
+        assert_eq!(gain_map_scale(&frame(4032, 3024), &frame(1008, 756)), Ok(4));
+        assert_eq!(gain_map_scale(&frame(4031, 3023), &frame(1008, 756)), Ok(4));
+        assert_eq!(
+            gain_map_scale(&frame(4032, 3024), &frame(4032, 3024)),
+            Ok(1)
+        );
+        assert_eq!(
+            gain_map_scale(&frame(4032, 3024), &frame(1008, 1008)),
+            Err(GCameraError::IncompatibleGainMapSize {
+                width: 4032,
+                height: 3024,
+                gain_map_width: 1008,
+                gain_map_height: 1008,
+            })
+        );
+        assert_eq!(
+            gain_map_scale(&frame(1008, 756), &frame(4032, 3024)),
+            Err(GCameraError::IncompatibleGainMapSize {
+                width: 1008,
+                height: 756,
+                gain_map_width: 4032,
+                gain_map_height: 3024,
+            })
+        );
+    }
+}
@@ -0,0 +1,477 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Logic for working with ICC color profiles.
+//!
+//! An ICC profile that does not fit in a single APP2 segment is split into
+//! chunks. Each segment starts with the `ICC_PROFILE` marker, followed by
+//! the one-based sequence number of the chunk and the total number of
+//! chunks. The profile itself starts with a 128 byte header, followed by a
+//! table of tags. All values in the profile are big endian.
+use std::fs;
+use std::path::Path;
+
+use crate::errors::GCameraError;
+use crate::jpeg::jpeg_components::{JpegSegment, MAX_DATA_LENGTH};
+use crate::jpeg::marker::JpegMarker;
+
+/// String that occurs at the start of each ICC profile segment.
+pub const ICC_MARKER: &[u8] = "ICC_PROFILE\0".as_bytes();
+
+/// Maximum number of bytes of the profile that can be stored in one chunk.
+const MAX_CHUNK_LENGTH: usize = MAX_DATA_LENGTH - ICC_MARKER.len() - 2;
+
+/// Length of the profile header.
+const HEADER_LENGTH: usize = 128;
+
+/// Signature that every profile holds at offset 36 of the header.
+const PROFILE_SIGNATURE: &[u8] = "acsp".as_bytes();
+
+/// Signature of the tag holding the description of the profile.
+const DESCRIPTION_TAG: &[u8] = "desc".as_bytes();
+
+/// A single chunk of an ICC profile, stored in a single APP2 segment.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct IccChunk {
+    /// The one-based sequence number of the chunk.
+    pub sequence: u8,
+
+    /// The total number of chunks in the profile.
+    pub count: u8,
+
+    /// The data in this chunk.
+    pub data: Vec<u8>,
+}
+
+impl IccChunk {
+    /// Create a `JpegSegment` holding this chunk.
+    ///
+    /// # Returns
+    /// Result holding the created APP2 segment.
+    ///
+    /// # Errors
+    /// Will error if the chunk is too large to fit in a single segment.
+    pub fn as_segment(&self) -> Result<JpegSegment, GCameraError> {
+        let data = [ICC_MARKER, &[self.sequence, self.count], &self.data].concat();
+        return JpegSegment::new(JpegMarker::APP2, &data);
+    }
+}
+
+/// Implementation to create a chunk from the bytes of an APP2 segment.
+impl TryFrom<&[u8]> for IccChunk {
+    type Error = GCameraError;
+
+    /// Create an instance from the bytes following the ICC profile marker.
+    ///
+    /// # Arguments
+    /// * `bytes`: The bytes of the segment after the marker.
+    ///
+    /// # Returns
+    /// Result holding the created chunk, or an error.
+    ///
+    /// # Errors
+    /// Will error if the bytes are too short to hold the chunk header. The
+    /// error gives an offset of 0, since the position of the segment is not
+    /// known here.
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        return match bytes {
+            [sequence, count, data @ ..] => Ok(Self {
+                sequence: *sequence,
+                count: *count,
+                data: data.to_vec(),
+            }),
+            _ => Err(GCameraError::InvalidIccChunk { offset: 0 }),
+        };
+    }
+}
+
+/// Merge the chunks of an ICC profile back into the complete profile.
+///
+/// # Arguments
+/// * `chunks`: The chunks found in the image, in any order, each with the
+///   offset of the segment holding it.
+///
+/// # Returns
+/// The complete profile.
+///
+/// # Errors
+/// Will error if there are no chunks, if the chunks disagree on the number
+/// of chunks, or if a sequence number is out of range, repeated or missing.
+/// The errors for a chunk that is not valid give the offset of its segment.
+pub fn merge_icc_chunks(chunks: &[(usize, IccChunk)]) -> Result<Vec<u8>, GCameraError> {
+    let count = chunks.first().ok_or(GCameraError::NoIccProfile)?.1.count;
+    if let Some((offset, _)) = chunks.iter().find(|(_, chunk)| {
+        return chunk.count != count || chunk.sequence == 0 || chunk.sequence > count;
+    }) {
+        return Err(GCameraError::InvalidIccChunk { offset: *offset });
+    }
+
+    let mut merged = Vec::new();
+    for sequence in 1..=count {
+        let mut matching = chunks
+            .iter()
+            .filter(|(_, chunk)| return chunk.sequence == sequence);
+        let (_, chunk) = matching
+            .next()
+            .ok_or(GCameraError::MissingIccChunk { sequence })?;
+        if let Some((offset, _)) = matching.next() {
+            return Err(GCameraError::InvalidIccChunk { offset: *offset });
+        }
+        merged.extend(&chunk.data);
+    }
+    return Ok(merged);
+}
+
+/// Create the segments holding an ICC profile.
+///
+/// # Arguments
+/// * `profile`: The complete profile.
+///
+/// # Returns
+/// Vector of APP2 segments holding the chunks of the profile.
+///
+/// # Errors
+/// Will error if the profile needs more than 255 chunks.
+pub fn create_icc_segments(profile: &[u8]) -> Result<Vec<JpegSegment>, GCameraError> {
+    let count = u8::try_from(profile.chunks(MAX_CHUNK_LENGTH).len()).map_err(|_| {
+        return GCameraError::IccProfileTooLarge {
+            length: profile.len(),
+        };
+    })?;
+    return profile
+        .chunks(MAX_CHUNK_LENGTH)
+        .zip(1..=count)
+        .map(|(data, sequence)| {
+            return IccChunk {
+                sequence,
+                count,
+                data: data.to_vec(),
+            }
+            .as_segment();
+        })
+        .collect();
+}
+
+/// Read a big endian unsigned 32-bit integer from the profile.
+///
+/// # Arguments
+/// * `data`: The profile.
+/// * `offset`: The offset of the integer.
+///
+/// # Returns
+/// The integer, or None if it is past the end of the profile.
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    return data
+        .get(offset..offset.checked_add(4)?)
+        .and_then(|bytes| return <[u8; 4]>::try_from(bytes).ok())
+        .map(u32::from_be_bytes);
+}
+
+/// Read a four character signature from the profile.
+///
+/// # Arguments
+/// * `data`: The profile.
+/// * `offset`: The offset of the signature.
+///
+/// # Returns
+/// The signature, without trailing spaces.
+///
+/// # Errors
+/// Will error if the signature is past the end of the profile.
+fn read_signature(data: &[u8], offset: usize) -> Result<String, GCameraError> {
+    return data
+        .get(offset..offset.saturating_add(4))
+        .map(|bytes| return String::from_utf8_lossy(bytes).trim_end().to_owned())
+        .ok_or(GCameraError::InvalidIccProfile);
+}
+
+/// Decode the text of a `desc` or `mluc` tag.
+///
+/// # Arguments
+/// * `tag`: The data of the tag.
+///
+/// # Returns
+/// The first string in the tag, or None if the tag cannot be decoded.
+fn decode_description(tag: &[u8]) -> Option<String> {
+    let text = match tag.get(..4)? {
+        b"desc" => {
+            let length = usize::try_from(read_u32(tag, 8)?).ok()?;
+            String::from_utf8_lossy(tag.get(12..length.checked_add(12)?)?).into_owned()
+        }
+        b"mluc" => {
+            let length = usize::try_from(read_u32(tag, 20)?).ok()?;
+            let offset = usize::try_from(read_u32(tag, 24)?).ok()?;
+            let units = tag
+                .get(offset..offset.checked_add(length)?)?
+                .chunks_exact(2)
+                .map(|unit| return <[u8; 2]>::try_from(unit).ok().map(u16::from_be_bytes))
+                .collect::<Option<Vec<u16>>>()?;
+            String::from_utf16(&units).ok()?
+        }
+        _ => return None,
+    };
+    return Some(text.trim_end_matches('\0').to_owned());
+}
+
+/// Find the description in the tag table of a profile.
+///
+/// # Arguments
+/// * `data`: The profile.
+///
+/// # Returns
+/// The description, or None if the profile does not have one.
+fn find_description(data: &[u8]) -> Option<String> {
+    let count = usize::try_from(read_u32(data, HEADER_LENGTH)?).ok()?;
+    let table = data.get(HEADER_LENGTH + 4..)?;
+    return table
+        .chunks_exact(12)
+        .take(count)
+        .find(|entry| return entry.starts_with(DESCRIPTION_TAG))
+        .and_then(|entry| {
+            let offset = usize::try_from(read_u32(entry, 4)?).ok()?;
+            let size = usize::try_from(read_u32(entry, 8)?).ok()?;
+            return decode_description(data.get(offset..offset.checked_add(size)?)?);
+        });
+}
+
+/// An ICC color profile, with the fields of its header.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct IccProfile {
+    /// The complete profile.
+    pub data: Vec<u8>,
+
+    /// The version of the profile format, such as `4.3.0`.
+    pub version: String,
+
+    /// The class of device the profile is for, such as `mntr`.
+    pub device_class: String,
+
+    /// The color space of the data, such as `RGB`.
+    pub color_space: String,
+
+    /// The profile connection space, such as `XYZ`.
+    pub connection_space: String,
+
+    /// The description of the profile, if it has one.
+    pub description: Option<String>,
+}
+
+impl IccProfile {
+    /// Get a description of the profile.
+    ///
+    /// # Returns
+    /// String with one line for each field of the profile.
+    pub fn info_str(&self) -> String {
+        let mut lines = Vec::new();
+        if let Some(description) = &self.description {
+            lines.push(format!("ICC profile:             {description}"));
+        }
+        lines.push(format!("ICC profile version:     {}", self.version));
+        lines.push(format!(
+            "ICC color space:         {} ({}, connection space {})",
+            self.color_space, self.device_class, self.connection_space
+        ));
+        return lines.join("\n");
+    }
+
+    /// Save the profile to a file.
+    ///
+    /// # Arguments
+    /// * `filepath`: Path to save the profile to.
+    ///
+    /// # Returns
+    /// Result of saving the file.
+    ///
+    /// # Errors
+    /// Will error if writing the file to the disk fails.
+    pub fn save(&self, filepath: &Path) -> Result<(), GCameraError> {
+        return fs::write(filepath, &self.data)
+            .map_err(|error| return GCameraError::IccProfileWriteError { kind: error.kind() });
+    }
+}
+
+impl TryFrom<Vec<u8>> for IccProfile {
+    type Error = GCameraError;
+
+    /// Parse the header of an ICC profile.
+    ///
+    /// # Arguments
+    /// * `data`: The complete profile.
+    ///
+    /// # Returns
+    /// The parsed profile.
+    ///
+    /// # Errors
+    /// Will error if the header is truncated, does not have the profile
+    /// signature, or gives a size that does not match the data.
+    fn try_from(data: Vec<u8>) -> Result<Self, Self::Error> {
+        let size = read_u32(&data, 0).ok_or(GCameraError::InvalidIccProfile)?;
+        if data.len() < HEADER_LENGTH
+            || data.get(36..40) != Some(PROFILE_SIGNATURE)
+            || usize::try_from(size).ok() != Some(data.len())
+        {
+            return Err(GCameraError::InvalidIccProfile);
+        }
+
+        let version = match data.get(8..10) {
+            Some(&[major, minor]) => format!("{major}.{}.{}", minor >> 4, minor & 0x0F),
+            _ => return Err(GCameraError::InvalidIccProfile),
+        };
+        return Ok(Self {
+            version,
+            device_class: read_signature(&data, 12)?,
+            color_space: read_signature(&data, 16)?,
+            connection_space: read_signature(&data, 20)?,
+            description: find_description(&data),
+            data,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create a version 2 RGB display profile with a description.
+    ///
+    /// # Arguments
+    /// * `description`: The description of the profile.
+    ///
+    /// # Returns
+    /// The profile.
+    ///
+    /// # Panics
+    /// Will panic if the description is too long for the profile.
+    fn create_profile(description: &str) -> Vec<u8> {
+        let text_length = u32::try_from(description.len() + 1).unwrap();
+        let mut tag = "desc\0\0\0\0".as_bytes().to_vec();
+        tag.extend(text_length.to_be_bytes());
+        tag.extend(description.as_bytes());
+        tag.push(0x00);
+
+        let tag_offset: u32 = 144;
+        let tag_size = u32::try_from(tag.len()).unwrap();
+        let mut data = (tag_offset + tag_size).to_be_bytes().to_vec();
+        data.extend("lcms".as_bytes());
+        data.extend([0x02, 0x10, 0x00, 0x00]);
+        data.extend("mntrRGB XYZ ".as_bytes());
+        data.resize(36, 0x00);
+        data.extend(PROFILE_SIGNATURE);
+        data.resize(HEADER_LENGTH, 0x00);
+        let tag_count: u32 = 1;
+        data.extend(tag_count.to_be_bytes());
+        data.extend(DESCRIPTION_TAG);
+        data.extend(tag_offset.to_be_bytes());
+        data.extend(tag_size.to_be_bytes());
+        data.extend(tag);
+        return data;
+    }
+
+    /// Test parsing the header and description of a profile.
+    #[test]
+    fn test_parse_profile() {
+        let profile = IccProfile::try_from(create_profile("sRGB IEC61966-2.1")).unwrap();
+
+        assert_eq!(profile.version, "2.1.0");
+        assert_eq!(profile.device_class, "mntr");
+        assert_eq!(profile.color_space, "RGB");
+        assert_eq!(profile.connection_space, "XYZ");
+        assert_eq!(profile.description.as_deref(), Some("sRGB IEC61966-2.1"));
+        assert_eq!(
+            profile.info_str(),
+            "\
+ICC profile:             sRGB IEC61966-2.1
+ICC profile version:     2.1.0
+ICC color space:         RGB (mntr, connection space XYZ)"
+        );
+    }
+
+    /// Test decoding the description of a version 4 profile.
+    #[test]
+    fn test_mluc_description() {
+        let mut tag = "mluc\0\0\0\0".as_bytes().to_vec();
+        let header: [u32; 2] = [1, 12];
+        for value in header {
+            tag.extend(value.to_be_bytes());
+        }
+        tag.extend("enUS".as_bytes());
+        let record: [u32; 2] = [6, 28];
+        for value in record {
+            tag.extend(value.to_be_bytes());
+        }
+        tag.extend([0x00, 0x50, 0x00, 0x33, 0x00, 0x00]);
+
+        assert_eq!(decode_description(&tag), Some(String::from("P3")));
+        assert_eq!(decode_description(&tag[..20]), None);
+    }
+
+    /// Test parsing data that is not a valid profile.
+    #[test]
+    fn test_invalid_profile() {
+        let mut data = create_profile("Test");
+        data.push(0x00);
+        assert_eq!(
+            IccProfile::try_from(data),
+            Err(GCameraError::InvalidIccProfile)
+        );
+        assert_eq!(
+            IccProfile::try_from(vec![0x00; 4]),
+            Err(GCameraError::InvalidIccProfile)
+        );
+    }
+
+    /// Test splitting a large profile into chunks, and merging them again.
+    #[test]
+    fn test_split_and_merge() {
+        let profile: Vec<u8> = [0x00, 0x01, 0x02].repeat(50_000);
+        let segments = create_icc_segments(&profile).unwrap();
+        assert_eq!(segments.len(), 3);
+
+        let mut chunks = segments
+            .iter()
+            .map(|segment| return (0, segment.as_icc_chunk().unwrap().unwrap()))
+            .collect::<Vec<(usize, IccChunk)>>();
+        assert_eq!(chunks[2].1.sequence, 3);
+        assert_eq!(chunks[2].1.count, 3);
+
+        chunks.reverse();
+        assert_eq!(merge_icc_chunks(&chunks), Ok(profile));
+    }
+
+    /// Test merging chunks with sequence numbers that are not valid.
+    #[test]
+    fn test_merge_invalid() {
+        // Each chunk is given an offset of ten times its sequence number.
+        let chunk = |sequence: u8, count: u8| {
+            let icc_chunk = IccChunk {
+                sequence,
+                count,
+                data: vec![sequence],
+            };
+            return (usize::from(sequence) * 10, icc_chunk);
+        };
+        assert_eq!(
+            merge_icc_chunks(&[chunk(1, 3), chunk(3, 3)]),
+            Err(GCameraError::MissingIccChunk { sequence: 2 })
+        );
+        assert_eq!(
+            merge_icc_chunks(&[chunk(1, 2), chunk(1, 2), chunk(2, 2)]),
+            Err(GCameraError::InvalidIccChunk { offset: 10 })
+        );
+        assert_eq!(
+            merge_icc_chunks(&[chunk(1, 2), chunk(2, 3)]),
+            Err(GCameraError::InvalidIccChunk { offset: 20 })
+        );
+        assert_eq!(
+            merge_icc_chunks(&[chunk(0, 1)]),
+            Err(GCameraError::InvalidIccChunk { offset: 0 })
+        );
+        assert_eq!(merge_icc_chunks(&[]), Err(GCameraError::NoIccProfile));
+        assert_eq!(
+            IccChunk::try_from([0x01].as_slice()),
+            Err(GCameraError::InvalidIccChunk { offset: 0 })
+        );
+    }
+}
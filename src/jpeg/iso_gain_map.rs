@@ -0,0 +1,350 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Logic for parsing ISO 21496-1 gain map metadata.
+//!
+//! Newer `UltraHDR` images store the gain map metadata as a binary structure
+//! in an APP2 segment, as well as in the `hdrgm` XMP properties. The
+//! primary image holds a segment with only the version numbers, while the
+//! gain map JPEG holds the full metadata. All values are big endian, and
+//! are stored as fractions.
+use crate::errors::GCameraError;
+use crate::jpeg::xmp::gain_map::GainMapMetadata;
+
+/// String that occurs at the start of the ISO 21496-1 APP2 segment.
+pub const ISO_GAIN_MAP_MARKER: &[u8] = "urn:iso:std:iso:ts:21496:-1".as_bytes();
+
+/// Flag set if there are values for all three color channels.
+const MULTI_CHANNEL_FLAG: u8 = 0x80;
+
+/// Flag set if the gain map is applied to the HDR base image to get the SDR image.
+const BACKWARD_DIRECTION_FLAG: u8 = 0x04;
+
+/// Flag set if all of the fractions share the same denominator.
+const COMMON_DENOMINATOR_FLAG: u8 = 0x08;
+
+/// Reader for the big endian values in the binary structure.
+struct Reader<'data> {
+    /// The data being read.
+    data: &'data [u8],
+
+    /// The position of the next byte to read.
+    position: usize,
+}
+
+impl Reader<'_> {
+    /// Read the next bytes.
+    ///
+    /// # Returns
+    /// The next `COUNT` bytes.
+    ///
+    /// # Errors
+    /// Will error if there are not enough bytes left.
+    fn read<const COUNT: usize>(&mut self) -> Result<[u8; COUNT], GCameraError> {
+        let end = self.position.saturating_add(COUNT);
+        let bytes = self
+            .data
+            .get(self.position..end)
+            .and_then(|slice| return <[u8; COUNT]>::try_from(slice).ok())
+            .ok_or(GCameraError::InvalidIsoGainMap)?;
+        self.position = end;
+        return Ok(bytes);
+    }
+
+    /// Read an unsigned 16 bit integer.
+    ///
+    /// # Returns
+    /// The integer that was read.
+    ///
+    /// # Errors
+    /// Will error if there are not enough bytes left.
+    fn read_u16(&mut self) -> Result<u16, GCameraError> {
+        return Ok(u16::from_be_bytes(self.read()?));
+    }
+
+    /// Read an unsigned 32 bit integer.
+    ///
+    /// # Returns
+    /// The integer that was read.
+    ///
+    /// # Errors
+    /// Will error if there are not enough bytes left.
+    fn read_u32(&mut self) -> Result<u32, GCameraError> {
+        return Ok(u32::from_be_bytes(self.read()?));
+    }
+
+    /// Read a signed 32 bit integer.
+    ///
+    /// # Returns
+    /// The integer that was read.
+    ///
+    /// # Errors
+    /// Will error if there are not enough bytes left.
+    fn read_i32(&mut self) -> Result<i32, GCameraError> {
+        return Ok(i32::from_be_bytes(self.read()?));
+    }
+
+    /// Read the denominator of a fraction.
+    ///
+    /// # Arguments
+    /// * `common`: The common denominator, if all fractions share one.
+    ///
+    /// # Returns
+    /// The denominator.
+    ///
+    /// # Errors
+    /// Will error if there are not enough bytes left.
+    fn read_denominator(&mut self, common: Option<u32>) -> Result<u32, GCameraError> {
+        return match common {
+            Some(denominator) => Ok(denominator),
+            None => self.read_u32(),
+        };
+    }
+}
+
+/// Convert a fraction to a floating point value.
+///
+/// # Arguments
+/// * `numerator`: The numerator of the fraction.
+/// * `denominator`: The denominator of the fraction.
+///
+/// # Returns
+/// The value of the fraction.
+///
+/// # Errors
+/// Will error if the denominator is zero.
+#[allow(clippy::float_arithmetic)] // The fractions have to be divided to get the parameter values
+fn fraction(numerator: f64, denominator: u32) -> Result<f64, GCameraError> {
+    if denominator == 0 {
+        return Err(GCameraError::InvalidIsoGainMap);
+    }
+    return Ok(numerator / f64::from(denominator));
+}
+
+/// Parse the payload of an ISO 21496-1 APP2 segment.
+///
+/// # Arguments
+/// * `data`: The payload, after the marker string and its null terminator.
+///
+/// # Returns
+/// The gain map metadata, or None if the payload only holds the version
+/// numbers. The version of the returned metadata is the minimum version
+/// and the writer version of the structure.
+///
+/// # Errors
+/// Will error if the payload is truncated, has a zero denominator, or has a
+/// minimum version that is not supported.
+pub fn parse_iso_gain_map(data: &[u8]) -> Result<Option<GainMapMetadata>, GCameraError> {
+    let mut reader = Reader { data, position: 0 };
+    let minimum_version = reader.read_u16()?;
+    let writer_version = reader.read_u16()?;
+    if minimum_version != 0 {
+        return Err(GCameraError::UnsupportedIsoGainMapVersion {
+            version: minimum_version,
+        });
+    }
+    if data.len() == reader.position {
+        return Ok(None);
+    }
+
+    let [flags] = reader.read()?;
+    let channel_count = if flags & MULTI_CHANNEL_FLAG == 0 {
+        1
+    } else {
+        3
+    };
+    let common_denominator = if flags & COMMON_DENOMINATOR_FLAG == 0 {
+        None
+    } else {
+        Some(reader.read_u32()?)
+    };
+
+    let base_numerator = reader.read_u32()?;
+    let base_headroom = fraction(
+        f64::from(base_numerator),
+        reader.read_denominator(common_denominator)?,
+    )?;
+    let alternate_numerator = reader.read_u32()?;
+    let alternate_headroom = fraction(
+        f64::from(alternate_numerator),
+        reader.read_denominator(common_denominator)?,
+    )?;
+
+    let mut channels: Vec<[f64; 5]> = Vec::with_capacity(channel_count);
+    for _ in 0..channel_count {
+        let mut values = [0.0; 5];
+        for (index, value) in values.iter_mut().enumerate() {
+            // The gamma is the only unsigned value of each channel.
+            let numerator = if index == 2 {
+                f64::from(reader.read_u32()?)
+            } else {
+                f64::from(reader.read_i32()?)
+            };
+            *value = fraction(numerator, reader.read_denominator(common_denominator)?)?;
+        }
+        channels.push(values);
+    }
+    let channel = |index: usize| {
+        let mut result = [0.0; 3];
+        for (color, value) in result.iter_mut().enumerate() {
+            *value = channels
+                .get(color)
+                .or(channels.first())
+                .and_then(|values| return values.get(index))
+                .copied()
+                .unwrap_or_default();
+        }
+        return result;
+    };
+
+    // The base and alternate values are for the SDR and HDR renditions, or
+    // the other way around when the base rendition is the HDR one.
+    let backward = flags & BACKWARD_DIRECTION_FLAG != 0;
+    let (base_offset, alternate_offset) = (channel(3), channel(4));
+    let (hdr_capacity_min, hdr_capacity_max) = if backward {
+        (alternate_headroom, base_headroom)
+    } else {
+        (base_headroom, alternate_headroom)
+    };
+    let offsets = if backward {
+        (alternate_offset, base_offset)
+    } else {
+        (base_offset, alternate_offset)
+    };
+
+    return Ok(Some(GainMapMetadata {
+        version: format!("{minimum_version}.{writer_version}"),
+        gain_map_min: channel(0),
+        gain_map_max: channel(1),
+        gamma: channel(2),
+        offset_sdr: offsets.0,
+        offset_hdr: offsets.1,
+        hdr_capacity_min,
+        hdr_capacity_max,
+        base_rendition_is_hdr: backward,
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Metadata with a common denominator, and a single channel.
+    const COMMON_DENOMINATOR: [u8; 37] = [
+        0x00, 0x00, 0x00, 0x00, // Versions
+        0x08, // Flags
+        0x00, 0x00, 0x00, 0x04, // Common denominator
+        0x00, 0x00, 0x00, 0x00, // Base headroom
+        0x00, 0x00, 0x00, 0x0A, // Alternate headroom
+        0xFF, 0xFF, 0xFF, 0xFE, // Gain map min
+        0x00, 0x00, 0x00, 0x0A, // Gain map max
+        0x00, 0x00, 0x00, 0x04, // Gamma
+        0x00, 0x00, 0x00, 0x01, // Base offset
+        0x00, 0x00, 0x00, 0x01, // Alternate offset
+    ];
+
+    /// Test parsing metadata that uses a common denominator.
+    #[test]
+    fn test_common_denominator() {
+        assert_eq!(
+            parse_iso_gain_map(&COMMON_DENOMINATOR),
+            Ok(Some(GainMapMetadata {
+                version: String::from("0.0"),
+                gain_map_min: [-0.5; 3],
+                gain_map_max: [2.5; 3],
+                gamma: [1.0; 3],
+                offset_sdr: [0.25; 3],
+                offset_hdr: [0.25; 3],
+                hdr_capacity_min: 0.0,
+                hdr_capacity_max: 2.5,
+                base_rendition_is_hdr: false,
+            }))
+        );
+    }
+
+    /// Test parsing metadata with separate denominators and three channels.
+    #[test]
+    fn test_multi_channel() {
+        let mut data = vec![0x00, 0x00, 0x00, 0x01, 0x84];
+        // Base and alternate headroom.
+        let headroom: [u32; 4] = [3, 1, 0, 1];
+        for value in headroom {
+            data.extend(value.to_be_bytes());
+        }
+        for channel in 1..=3 {
+            // Min, max, gamma, base offset and alternate offset.
+            let values: [i32; 10] = [0, 1, channel, 2, 1, 1, 1, 64, 1, 64];
+            for value in values {
+                data.extend(value.to_be_bytes());
+            }
+        }
+
+        assert_eq!(
+            parse_iso_gain_map(&data),
+            Ok(Some(GainMapMetadata {
+                version: String::from("0.1"),
+                gain_map_min: [0.0; 3],
+                gain_map_max: [0.5, 1.0, 1.5],
+                gamma: [1.0; 3],
+                offset_sdr: [0.015_625; 3],
+                offset_hdr: [0.015_625; 3],
+                hdr_capacity_min: 0.0,
+                hdr_capacity_max: 3.0,
+                base_rendition_is_hdr: true,
+            }))
+        );
+    }
+
+    /// Test that the offsets are swapped along with the headroom when the
+    /// base rendition is the HDR one.
+    #[test]
+    fn test_backward_direction() {
+        let mut data = COMMON_DENOMINATOR;
+        data[4] |= BACKWARD_DIRECTION_FLAG;
+        // Base offset of 0.25 and alternate offset of 0.5.
+        data[36] = 0x02;
+
+        assert_eq!(
+            parse_iso_gain_map(&data),
+            Ok(Some(GainMapMetadata {
+                version: String::from("0.0"),
+                gain_map_min: [-0.5; 3],
+                gain_map_max: [2.5; 3],
+                gamma: [1.0; 3],
+                offset_sdr: [0.5; 3],
+                offset_hdr: [0.25; 3],
+                hdr_capacity_min: 2.5,
+                hdr_capacity_max: 0.0,
+                base_rendition_is_hdr: true,
+            }))
+        );
+    }
+
+    /// Test parsing a payload that only holds the version numbers.
+    #[test]
+    fn test_version_only() {
+        assert_eq!(parse_iso_gain_map(&[0x00, 0x00, 0x00, 0x00]), Ok(None));
+    }
+
+    /// Test parsing payloads that are not valid.
+    #[test]
+    fn test_invalid() {
+        assert_eq!(
+            parse_iso_gain_map(&COMMON_DENOMINATOR[..20]),
+            Err(GCameraError::InvalidIsoGainMap)
+        );
+        assert_eq!(
+            parse_iso_gain_map(&[0x00, 0x01, 0x00, 0x00]),
+            Err(GCameraError::UnsupportedIsoGainMapVersion { version: 1 })
+        );
+
+        let mut zero_denominator = COMMON_DENOMINATOR;
+        zero_denominator[8] = 0x00;
+        assert_eq!(
+            parse_iso_gain_map(&zero_denominator),
+            Err(GCameraError::InvalidIsoGainMap)
+        );
+    }
+}
@@ -5,35 +5,134 @@
 */
 //! Logic for working with the actual JPEG image
 
+use std::borrow::Borrow;
+
 use crate::errors::GCameraError;
 use crate::jpeg::marker::JpegMarker;
 
+use crate::jpeg::exif::EXIF_MARKER;
+use crate::jpeg::extended_xmp::{ExtendedXMPChunk, EXTENDED_XMP_MARKER, GUID_LENGTH};
+use crate::jpeg::frame::FrameHeader;
+use crate::jpeg::icc::{IccChunk, ICC_MARKER};
+use crate::jpeg::iso_gain_map::ISO_GAIN_MAP_MARKER;
+use crate::jpeg::mpf::MPF_MARKER;
+use crate::jpeg::scan::ScanHeader;
+use crate::jpeg::tables::{
+    parse_huffman_tables, parse_quantization_tables, HuffmanTable, QuantizationTable,
+};
+use crate::jpeg::thumbnail::{JFXX_JPEG_CODE, JFXX_MARKER};
 use crate::jpeg::xmp::{XMPData, XMP_MARKER};
 
-/// Linear search for the next JPEG Segment.
+/// Find the end of the entropy-coded data of a scan.
+///
+/// A `0xFF` byte in the scan data is always followed by a stuffed `0x00`
+/// byte, by one of the restart markers, or by another `0xFF` fill byte.
+/// The scan ends at the first `0xFF` that is followed by any other known
+/// marker.
 ///
 /// # Arguments
-/// * `bytes`: The bytes to search for the next segment.
+/// * `bytes`: The entropy-coded data, and anything following it. This can
+///   be a slice, or the bytes of a reader.
 ///
 /// # Returns
-/// Offset that the next marker is at, or an error message
-fn find_next_segment(bytes: &[u8]) -> Result<usize, GCameraError> {
-    let search_result = bytes.windows(2).enumerate().find(|(_, window)| {
-        return window[0] == 0xFF && JpegMarker::try_from(window[1]).is_ok();
-    });
-
-    return match search_result {
-        Some((index, _)) => Ok(index),
-        None => Err(GCameraError::JpegMarkerNotFound),
-    };
+/// Offset of the marker that follows the scan, or an error message.
+///
+/// # Errors
+/// Will error if no marker follows the scan.
+pub fn find_scan_end<I>(bytes: I) -> Result<usize, GCameraError>
+where
+    I: IntoIterator,
+    I::Item: Borrow<u8>,
+{
+    let mut scan = bytes
+        .into_iter()
+        .map(|byte| return *byte.borrow())
+        .enumerate()
+        .peekable();
+    while let Some((position, byte)) = scan.next() {
+        if byte != 0xFF {
+            continue;
+        }
+        let Some(&(_, next_byte)) = scan.peek() else {
+            break;
+        };
+        match JpegMarker::try_from(next_byte) {
+            // A restart marker or a stuffed zero byte is part of the scan,
+            // while a fill byte may start the next marker.
+            Ok(marker) if marker.is_restart() => {
+                scan.next();
+            }
+            Ok(_) => return Ok(position),
+            Err(_) if next_byte == 0x00 => {
+                scan.next();
+            }
+            Err(_) => {}
+        }
+    }
+    return Err(GCameraError::JpegMarkerNotFound);
 }
 
+/// Check the restart markers in the entropy-coded data of a scan.
+///
+/// The restart markers should count up from RST0 and wrap around after
+/// RST7. The count starts again for every scan.
+///
+/// # Arguments
+/// * `scan`: The entropy-coded data of the scan.
+/// * `restart_interval`: The restart interval from the last DRI segment.
+///
+/// # Errors
+/// Will error if a restart marker is found when the restart interval is 0,
+/// or if a restart marker is out of order. The errors give the offset of
+/// the restart marker in the scan data.
+pub fn check_restart_markers(scan: &[u8], restart_interval: u16) -> Result<(), GCameraError> {
+    let mut expected_restart: u8 = 0;
+    let mut bytes = scan.iter().enumerate().peekable();
+    while let Some((offset, &byte)) = bytes.next() {
+        if byte != 0xFF {
+            continue;
+        }
+        let Some(&(_, &next_byte)) = bytes.peek() else {
+            break;
+        };
+        match JpegMarker::try_from(next_byte) {
+            Ok(marker) if marker.is_restart() => {
+                let found = next_byte & 0x07;
+                if restart_interval == 0 {
+                    return Err(GCameraError::UnexpectedRestartMarker { offset });
+                }
+                if found != expected_restart {
+                    return Err(GCameraError::RestartMarkerOutOfOrder {
+                        expected: expected_restart,
+                        found,
+                        offset,
+                    });
+                }
+                expected_restart = (found + 1) & 0x07;
+                bytes.next();
+            }
+            // Stuffed zero bytes and fill bytes need no checks.
+            Ok(_) | Err(_) => {}
+        }
+    }
+    return Ok(());
+}
+
+/// Maximum number of data bytes that a segment can hold.
+///
+/// The length of a segment is stored as a u16, and includes the two
+/// bytes used to store the length itself.
+pub const MAX_DATA_LENGTH: usize = 0xFFFD;
+
 /// A single JPEG segment.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Clone)]
 pub struct JpegSegment {
     /// The marker indicating the segment type.
     pub marker: JpegMarker,
 
+    /// The number of `0xFF` fill bytes before the marker.
+    fill_bytes: usize,
+
     /// The length of the segment
     /// For the SOS segment, this is only the length of the SOS header.
     /// Since SOI and EOI don't have data bytes, this is an Option
@@ -57,41 +156,37 @@ impl JpegSegment {
     /// * `data`: Segment data (as bytes)
     ///
     /// # Returns
-    /// Created segment
+    /// Result holding the created segment, or an error message.
+    ///
+    /// # Errors
+    /// Will error if the data is too long to fit in a single segment.
     ///
     /// # Panics
-    /// Will panic if attemping to create a SOS segment with a length shorter
-    /// Than 10.
-    pub fn new(marker: JpegMarker, data: &[u8]) -> Self {
+    /// Will panic if attemping to create a SOS segment.
+    #[allow(clippy::panic_in_result_fn)]
+    pub fn new(marker: JpegMarker, data: &[u8]) -> Result<Self, GCameraError> {
         assert_ne!(
             JpegMarker::SOS,
             marker,
             "Constructing a SOS Segment is not supported."
         );
-        #[allow(clippy::wildcard_enum_match_arm)]
-        match marker {
-            JpegMarker::SOI => {
-                return Self {
-                    marker,
-                    length: None,
-                    data: None,
-                }
-            }
-            JpegMarker::EOI => {
-                return Self {
-                    marker,
-                    length: None,
-                    data: None,
-                }
-            }
-            _ => {
-                return Self {
-                    marker,
-                    length: Some((data.len() + 2).try_into().unwrap()),
-                    data: Some(Vec::from(data)),
-                }
-            }
+        if !marker.has_length() {
+            return Ok(Self {
+                marker,
+                fill_bytes: 0,
+                length: None,
+                data: None,
+            });
+        }
+        if data.len() > MAX_DATA_LENGTH {
+            return Err(GCameraError::SegmentTooLarge { length: data.len() });
         }
+        return Ok(Self {
+            marker,
+            fill_bytes: 0,
+            length: Some((data.len() + 2).try_into().unwrap()),
+            data: Some(Vec::from(data)),
+        });
     }
 
     // TODO: Instead use TryFrom?
@@ -104,35 +199,105 @@ impl JpegSegment {
     /// Result containing either the created segment, or an error message.
     ///
     /// # Errors
-    /// Will error if creating a `JpegMarker` is not found.
+    /// Will error if creating a `JpegMarker` is not found, or if the bytes
+    /// end before the end of the segment.
     /// Additionally, if the segment is a SOS segment, will error
     /// if another segment cannot be found after the SOS Segment
-    ///
-    /// # Panics
-    /// Will panic if a segment has one (but not both of), a length
-    /// portion, and a data portion, as this is not possible under
-    /// the JPEG spec.
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, GCameraError> {
-        let marker = JpegMarker::try_from(bytes[1])?;
-
-        #[allow(clippy::wildcard_enum_match_arm)]
-        let (length, data_length) = match marker {
-            JpegMarker::SOI => (None, None),
-            JpegMarker::EOI => (None, None),
-            JpegMarker::SOS => (
-                Some((u16::from(bytes[2]) << 8) | u16::from(bytes[3])),
-                Some(find_next_segment(&bytes[2..])?),
-            ),
-            _ => {
-                let length = (u16::from(bytes[2]) << 8) | u16::from(bytes[3]);
-                (Some(length), Some(usize::from(length)))
-            }
+        return Self::from_bytes_at(bytes, 0);
+    }
+
+    /// Create a new segment from the bytes at an offset.
+    ///
+    /// # Arguments
+    /// * `bytes`: The bytes of the whole image.
+    /// * `offset`: The offset of the segment in the bytes, including any
+    ///   `0xFF` fill bytes before its marker.
+    ///
+    /// # Returns
+    /// Result containing either the created segment, or an error message.
+    ///
+    /// # Errors
+    /// Will error if the segment does not start with a known `JpegMarker`, if
+    /// the segment length is less than 2, or if the bytes end before the end
+    /// of the segment. These errors give the offset of the segment.
+    /// Additionally, if the segment is a SOS segment, will error if another
+    /// segment cannot be found after the scan.
+    pub fn from_bytes_at(bytes: &[u8], offset: usize) -> Result<Self, GCameraError> {
+        // Any number of 0xFF fill bytes may come before the marker.
+        let fill_bytes = bytes
+            .get(offset..)
+            .unwrap_or_default()
+            .windows(2)
+            .take_while(|pair| return pair == &[0xFF, 0xFF])
+            .count();
+        let start = offset + fill_bytes;
+        let segment = bytes.get(start..).unwrap_or_default();
+        let &[prefix, marker_byte, ..] = segment else {
+            return Err(GCameraError::TruncatedData {
+                structure: "segment marker",
+                offset: start,
+            });
+        };
+        if prefix != 0xFF {
+            return Err(GCameraError::MissingMarkerPrefix {
+                byte: prefix,
+                offset: start,
+            });
+        }
+        let marker =
+            JpegMarker::try_from(marker_byte).map_err(|error| return error.offset_by(start))?;
+        if !marker.has_length() {
+            return Ok(JpegSegment {
+                marker,
+                fill_bytes,
+                length: None,
+                data: None,
+            });
+        }
+
+        let &[_, _, high, low, ..] = segment else {
+            return Err(GCameraError::TruncatedData {
+                structure: "segment length",
+                offset: start,
+            });
+        };
+        let length = u16::from_be_bytes([high, low]);
+        if length < 2 {
+            return Err(GCameraError::InvalidLength {
+                structure: "segment",
+                offset: start,
+            });
+        }
+        let truncated = || {
+            return GCameraError::TruncatedData {
+                structure: "segment",
+                offset: start,
+            };
+        };
+
+        let header_end = 2 + usize::from(length);
+        let data_end = if marker == JpegMarker::SOS {
+            let scan = segment.get(header_end..).ok_or_else(truncated)?;
+            let scan_length = find_scan_end(scan).map_err(|error| {
+                if error == GCameraError::JpegMarkerNotFound {
+                    return GCameraError::TruncatedData {
+                        structure: "scan",
+                        offset: start + header_end,
+                    };
+                }
+                return error;
+            })?;
+            header_end + scan_length
+        } else {
+            header_end
         };
 
         return Ok(JpegSegment {
             marker,
-            length,
-            data: data_length.map(|len| return bytes[4..(2 + len)].to_vec()),
+            fill_bytes,
+            length: Some(length),
+            data: Some(segment.get(4..data_end).ok_or_else(truncated)?.to_vec()),
         });
     }
 
@@ -153,7 +318,16 @@ impl JpegSegment {
         };
 
         // The 2 at the start is for the marker and magic bytes
-        return 2 + len_size + data_size;
+        return self.fill_bytes + 2 + len_size + data_size;
+    }
+
+    /// Get the position of the data bytes of the segment.
+    ///
+    /// # Returns
+    /// The number of bytes before the data, from the start of the fill
+    /// bytes to the end of the length.
+    pub const fn data_position(&self) -> usize {
+        return self.fill_bytes + 4;
     }
 
     /// Get XMP data
@@ -164,25 +338,28 @@ impl JpegSegment {
     /// # Returns
     /// The XMP Data as a string, or None
     ///
-    /// # Panics
-    /// Will panic if an attempt to parse the data to a UTF8 string
-    /// fails.
-    fn as_xmp_str(&self) -> Option<String> {
+    /// # Errors
+    /// Will error if the XMP data is not a valid UTF-8 string. The error
+    /// gives the offset of the first byte that is not valid in the segment
+    /// data.
+    pub fn as_xmp_str(&self) -> Option<Result<String, GCameraError>> {
         // Extract the data from the struct only if the marker is the right type.
         let data = match (self.marker, &self.data) {
-            (JpegMarker::APP1, Some(data_bytes)) => data_bytes.clone(),
-            (_, _) => Vec::new(),
+            (JpegMarker::APP1, Some(data_bytes)) if data_bytes.starts_with(XMP_MARKER) => {
+                data_bytes
+            }
+            (_, _) => return None,
         };
 
-        // Check for the XMP Marker
-        if data.starts_with(XMP_MARKER) {
-            // Parse to string and return
-            let xml_offset = XMP_MARKER.len() + 1;
-            let xml_portion = Vec::from(&data[xml_offset..]);
-            return Some(String::from_utf8(xml_portion).unwrap());
-        } else {
-            return None;
-        }
+        // Parse to string and return
+        let xml_offset = XMP_MARKER.len() + 1;
+        let xml_portion = Vec::from(data.get(xml_offset..).unwrap_or_default());
+        return Some(String::from_utf8(xml_portion).map_err(|error| {
+            return GCameraError::InvalidUtf8 {
+                structure: "XMP segment",
+                offset: xml_offset + error.utf8_error().valid_up_to(),
+            };
+        }));
     }
     /// Get XMP Data
     ///
@@ -192,8 +369,255 @@ impl JpegSegment {
     /// # Returns
     /// The XMP Data, or None
     pub fn as_xmp_data(&self) -> Option<Result<XMPData, GCameraError>> {
-        if let Some(xmp_string) = self.as_xmp_str() {
-            return Some(XMPData::try_from(xmp_string));
+        return self
+            .as_xmp_str()
+            .map(|xmp_string| return xmp_string.and_then(XMPData::try_from));
+    }
+
+    /// Check if the segment holds either XMP or Extended XMP data.
+    ///
+    /// # Returns
+    /// Boolean indicating if the segment holds XMP data.
+    pub fn has_xmp(&self) -> bool {
+        return match (self.marker, &self.data) {
+            (JpegMarker::APP1, Some(data)) => {
+                data.starts_with(XMP_MARKER) || data.starts_with(EXTENDED_XMP_MARKER)
+            }
+            (_, _) => false,
+        };
+    }
+
+    /// Get Extended XMP chunk.
+    ///
+    /// If this segment holds a chunk of Extended XMP data, this will return
+    /// the `ExtendedXMPChunk` struct. Otherwise it will return None.
+    ///
+    /// # Returns
+    /// The Extended XMP chunk, or None.
+    pub fn as_extended_xmp_chunk(&self) -> Option<Result<ExtendedXMPChunk, GCameraError>> {
+        if let (JpegMarker::APP1, Some(data)) = (self.marker, &self.data) {
+            if data.starts_with(EXTENDED_XMP_MARKER) {
+                let chunk_offset = EXTENDED_XMP_MARKER.len() + 1;
+                return Some(ExtendedXMPChunk::try_from(
+                    data.get(chunk_offset..).unwrap_or(&[]),
+                ));
+            }
+        }
+        return None;
+    }
+
+    /// Get the GUID of an Extended XMP chunk.
+    ///
+    /// This only reads the GUID from the chunk header, without parsing the
+    /// rest of the chunk.
+    ///
+    /// # Returns
+    /// The bytes of the GUID, or None if this segment does not hold a chunk
+    /// of Extended XMP data that is long enough to have a GUID.
+    pub fn extended_xmp_guid(&self) -> Option<&[u8]> {
+        if let (JpegMarker::APP1, Some(data)) = (self.marker, &self.data) {
+            if data.starts_with(EXTENDED_XMP_MARKER) {
+                let chunk_offset = EXTENDED_XMP_MARKER.len() + 1;
+                return data.get(chunk_offset..chunk_offset + GUID_LENGTH);
+            }
+        }
+        return None;
+    }
+
+    /// Get EXIF data.
+    ///
+    /// If this segment is an APP1 segment holding EXIF data, this will
+    /// return the TIFF structure after the marker string.
+    ///
+    /// # Returns
+    /// The TIFF structure of the EXIF data, or None.
+    pub fn as_exif_data(&self) -> Option<&[u8]> {
+        if let (JpegMarker::APP1, Some(data)) = (self.marker, &self.data) {
+            if data.starts_with(EXIF_MARKER) {
+                return data.get(EXIF_MARKER.len()..);
+            }
+        }
+        return None;
+    }
+
+    /// Get JFXX thumbnail.
+    ///
+    /// If this segment is a JFXX APP0 segment holding a JPEG thumbnail, this
+    /// will return the JPEG data after the extension code.
+    ///
+    /// # Returns
+    /// The JPEG data of the thumbnail, or None.
+    pub fn as_jfxx_thumbnail(&self) -> Option<&[u8]> {
+        if let (JpegMarker::APP0, Some(data)) = (self.marker, &self.data) {
+            if let Some(extension) = data.strip_prefix(JFXX_MARKER) {
+                return extension.strip_prefix(&[JFXX_JPEG_CODE]);
+            }
+        }
+        return None;
+    }
+
+    /// Check if the segment is a JFXX segment.
+    ///
+    /// # Returns
+    /// Whether this is a JFXX APP0 segment, with any extension code.
+    pub fn is_jfxx(&self) -> bool {
+        return match (self.marker, &self.data) {
+            (JpegMarker::APP0, Some(data)) => data.starts_with(JFXX_MARKER),
+            (_, _) => false,
+        };
+    }
+
+    /// Get ISO 21496-1 gain map data.
+    ///
+    /// If this segment is an APP2 segment holding ISO 21496-1 gain map
+    /// metadata, this will return the data after the marker string.
+    ///
+    /// # Returns
+    /// The binary gain map metadata, or None.
+    pub fn as_iso_gain_map_data(&self) -> Option<&[u8]> {
+        if let (JpegMarker::APP2, Some(data)) = (self.marker, &self.data) {
+            if data.starts_with(ISO_GAIN_MAP_MARKER) {
+                return Some(data.get(ISO_GAIN_MAP_MARKER.len() + 1..).unwrap_or(&[]));
+            }
+        }
+        return None;
+    }
+
+    /// Get ICC profile chunk.
+    ///
+    /// If this segment is an APP2 segment holding a chunk of an ICC profile,
+    /// this will return the `IccChunk` struct. Otherwise it will return None.
+    ///
+    /// # Returns
+    /// The ICC profile chunk, or None.
+    pub fn as_icc_chunk(&self) -> Option<Result<IccChunk, GCameraError>> {
+        if let (JpegMarker::APP2, Some(data)) = (self.marker, &self.data) {
+            if let Some(chunk) = data.strip_prefix(ICC_MARKER) {
+                return Some(IccChunk::try_from(chunk));
+            }
+        }
+        return None;
+    }
+
+    /// Check if the segment holds a chunk of an ICC profile.
+    ///
+    /// # Returns
+    /// Whether this is an APP2 segment with the ICC profile marker.
+    pub fn is_icc(&self) -> bool {
+        return match (self.marker, &self.data) {
+            (JpegMarker::APP2, Some(data)) => data.starts_with(ICC_MARKER),
+            (_, _) => false,
+        };
+    }
+
+    /// Get MPF data.
+    ///
+    /// If this segment is an APP2 segment holding the MPF index, this will
+    /// return the TIFF structure after the marker string.
+    ///
+    /// # Returns
+    /// The TIFF structure of the MPF index, or None.
+    pub fn as_mpf_data(&self) -> Option<&[u8]> {
+        if let (JpegMarker::APP2, Some(data)) = (self.marker, &self.data) {
+            if data.starts_with(MPF_MARKER) {
+                return data.get(MPF_MARKER.len()..);
+            }
+        }
+        return None;
+    }
+
+    /// Get the frame header.
+    ///
+    /// If this segment is one of the SOF segments, this will parse the frame
+    /// header it holds. Otherwise it will return None.
+    ///
+    /// # Returns
+    /// The frame header, or None.
+    pub fn as_frame_header(&self) -> Option<Result<FrameHeader, GCameraError>> {
+        return match &self.data {
+            Some(data) if self.marker.is_start_of_frame() => {
+                Some(FrameHeader::try_from((self.marker, data.as_slice())))
+            }
+            Some(_) | None => None,
+        };
+    }
+
+    /// Get the quantization tables.
+    ///
+    /// If this segment is a DQT segment, this will parse the quantization
+    /// tables it holds. Otherwise it will return None.
+    ///
+    /// # Returns
+    /// The quantization tables, or None.
+    pub fn as_quantization_tables(&self) -> Option<Result<Vec<QuantizationTable>, GCameraError>> {
+        if let (JpegMarker::DQT, Some(data)) = (self.marker, &self.data) {
+            return Some(parse_quantization_tables(data));
+        }
+        return None;
+    }
+
+    /// Get the Huffman tables.
+    ///
+    /// If this segment is a DHT segment, this will parse the Huffman tables
+    /// it holds. Otherwise it will return None.
+    ///
+    /// # Returns
+    /// The Huffman tables, or None.
+    pub fn as_huffman_tables(&self) -> Option<Result<Vec<HuffmanTable>, GCameraError>> {
+        if let (JpegMarker::DHT, Some(data)) = (self.marker, &self.data) {
+            return Some(parse_huffman_tables(data));
+        }
+        return None;
+    }
+
+    /// Get the entropy-coded data of a scan.
+    ///
+    /// If this segment is a SOS segment, this will return the data after
+    /// the scan header. Otherwise it will return None.
+    ///
+    /// # Returns
+    /// The entropy-coded data, or None.
+    pub fn as_scan_data(&self) -> Option<&[u8]> {
+        if let (JpegMarker::SOS, Some(length), Some(data)) = (self.marker, self.length, &self.data)
+        {
+            let header_length = usize::from(length).checked_sub(2)?;
+            return data.get(header_length..);
+        }
+        return None;
+    }
+
+    /// Get the scan header.
+    ///
+    /// If this segment is a SOS segment, this will parse the scan header
+    /// before the entropy-coded data. Otherwise it will return None.
+    ///
+    /// # Returns
+    /// The scan header, or None.
+    pub fn as_scan_header(&self) -> Option<Result<ScanHeader, GCameraError>> {
+        if let (JpegMarker::SOS, Some(length), Some(data)) = (self.marker, self.length, &self.data)
+        {
+            let header = usize::from(length)
+                .checked_sub(2)
+                .and_then(|header_length| return data.get(..header_length))
+                .ok_or(GCameraError::InvalidScanHeader { offset: 0 });
+            return Some(header.and_then(ScanHeader::try_from));
+        }
+        return None;
+    }
+
+    /// Get the restart interval.
+    ///
+    /// If this segment is a DRI segment, this will return the number of
+    /// MCUs between each restart marker. Otherwise it will return None.
+    ///
+    /// # Returns
+    /// The restart interval, or None.
+    pub fn as_restart_interval(&self) -> Option<Result<u16, GCameraError>> {
+        if let (JpegMarker::DRI, Some(data)) = (self.marker, &self.data) {
+            return Some(match data.as_slice() {
+                &[high, low] => Ok(u16::from_be_bytes([high, low])),
+                _ => Err(GCameraError::InvalidRestartInterval),
+            });
         }
         return None;
     }
@@ -214,6 +638,7 @@ impl JpegSegment {
         };
 
         return [
+            vec![0xFF; self.fill_bytes].as_slice(),
             &[0xFF],
             &[u8::from(self.marker)],
             length_bytes.as_slice(),
@@ -227,14 +652,14 @@ impl JpegSegment {
 mod tests {
     use super::*;
 
-    mod find_next_segment_tests {
+    mod find_scan_end_tests {
         use super::*;
 
         /// Test valid discovery of next segment.
         #[test]
         fn test_valid_next_segment() {
             let test_bytes = [0x01, 0x02, 0x03, 0x04, 0x04, 0x06, 0xFF, 0xD9, 0xAB, 0xCD];
-            let found_index = find_next_segment(&test_bytes);
+            let found_index = find_scan_end(test_bytes);
             assert_eq!(found_index, Ok(6));
         }
 
@@ -243,16 +668,56 @@ mod tests {
         fn test_no_found_segment() {
             let test_bytes = [0x01, 0x02, 0x03, 0x04, 0x04, 0x06, 0xAB, 0xCD];
             assert_eq!(
-                find_next_segment(&test_bytes),
+                find_scan_end(test_bytes),
                 Err(GCameraError::JpegMarkerNotFound)
             );
         }
+
+        /// Test that restart markers in scan data are skipped.
+        #[test]
+        fn test_skip_restart_marker() {
+            let test_bytes = [0x01, 0xFF, 0xD0, 0x02, 0xFF, 0xD1, 0xFF, 0xC4, 0xAB];
+            assert_eq!(find_scan_end(test_bytes), Ok(6));
+        }
+
+        /// Test that stuffed bytes and fill bytes are part of the scan.
+        #[test]
+        fn test_skip_stuffing() {
+            // A stuffed 0xFF followed by data that looks like a marker.
+            let test_bytes = [0x01, 0xFF, 0x00, 0xD9, 0xFF, 0xFF, 0xFF, 0xD9];
+            assert_eq!(find_scan_end(test_bytes), Ok(6));
+        }
+
+        /// Test checking the restart markers against the restart interval.
+        #[test]
+        fn test_restart_interval() {
+            let test_bytes = [0x01, 0xFF, 0xD0, 0x02, 0xFF, 0xD2];
+            assert_eq!(
+                check_restart_markers(&test_bytes, 8),
+                Err(GCameraError::RestartMarkerOutOfOrder {
+                    expected: 1,
+                    found: 2,
+                    offset: 4
+                })
+            );
+            assert_eq!(
+                check_restart_markers(&test_bytes, 0),
+                Err(GCameraError::UnexpectedRestartMarker { offset: 1 })
+            );
+
+            let wrapped: Vec<u8> = (0xD0..=0xD7)
+                .chain([0xD0])
+                .flat_map(|marker| return [0x01, 0xFF, 0x00, 0xFF, 0xFF, marker])
+                .collect();
+            assert_eq!(check_restart_markers(&wrapped, 1), Ok(()));
+        }
+
         /// Test where magic is valid, but marker is not
         #[test]
         fn test_no_found_segment_valid_magic() {
-            let test_bytes = [0x01, 0x02, 0x03, 0x04, 0x04, 0x06, 0xFF, 0xFF, 0xAB, 0xCD];
+            let test_bytes = [0x01, 0x02, 0x03, 0x04, 0x04, 0x06, 0xFF, 0xFF, 0x00, 0xCD];
             assert_eq!(
-                find_next_segment(&test_bytes),
+                find_scan_end(test_bytes),
                 Err(GCameraError::JpegMarkerNotFound)
             );
         }
@@ -271,11 +736,12 @@ mod tests {
                 let segment = JpegSegment::new(JpegMarker::SOI, &[0x00, 0x01, 0x02]);
                 assert_eq!(
                     segment,
-                    JpegSegment {
+                    Ok(JpegSegment {
                         marker: JpegMarker::SOI,
+                        fill_bytes: 0,
                         length: None,
                         data: None
-                    }
+                    })
                 );
             }
 
@@ -285,11 +751,12 @@ mod tests {
                 let segment = JpegSegment::new(JpegMarker::EOI, &[0x00, 0x01, 0x02]);
                 assert_eq!(
                     segment,
-                    JpegSegment {
+                    Ok(JpegSegment {
                         marker: JpegMarker::EOI,
+                        fill_bytes: 0,
                         length: None,
                         data: None
-                    }
+                    })
                 );
             }
 
@@ -303,13 +770,14 @@ mod tests {
                 );
                 assert_eq!(
                     segment,
-                    JpegSegment {
+                    Ok(JpegSegment {
                         marker: JpegMarker::SOS,
+                        fill_bytes: 0,
                         length: Some(0x0C),
                         data: Some(vec![
                             0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09
                         ])
-                    }
+                    })
                 );
             }
 
@@ -319,11 +787,32 @@ mod tests {
                 let segment = JpegSegment::new(JpegMarker::APP0, &[0x00, 0x01, 0x02, 0x03]);
                 assert_eq!(
                     segment,
-                    JpegSegment {
+                    Ok(JpegSegment {
                         marker: JpegMarker::APP0,
+                        fill_bytes: 0,
                         length: Some(6),
                         data: Some(vec![0x00, 0x01, 0x02, 0x03])
-                    }
+                    })
+                );
+            }
+
+            /// Test creating a segment with the largest possible amount of data.
+            #[test]
+            fn test_new_max_length() {
+                let segment =
+                    JpegSegment::new(JpegMarker::APP1, &vec![0x00; MAX_DATA_LENGTH]).unwrap();
+                assert_eq!(segment.length, Some(0xFFFF));
+            }
+
+            /// Test creating a segment with too much data.
+            #[test]
+            fn test_new_too_large() {
+                let segment = JpegSegment::new(JpegMarker::APP1, &vec![0x00; MAX_DATA_LENGTH + 1]);
+                assert_eq!(
+                    segment,
+                    Err(GCameraError::SegmentTooLarge {
+                        length: MAX_DATA_LENGTH + 1
+                    })
                 );
             }
         }
@@ -341,6 +830,7 @@ mod tests {
                     result,
                     Ok(JpegSegment {
                         marker: JpegMarker::SOI,
+                        fill_bytes: 0,
                         length: None,
                         data: None
                     })
@@ -357,6 +847,7 @@ mod tests {
                     result,
                     Ok(JpegSegment {
                         marker: JpegMarker::EOI,
+                        fill_bytes: 0,
                         length: None,
                         data: None
                     })
@@ -373,6 +864,7 @@ mod tests {
                     result,
                     Ok(JpegSegment {
                         marker: JpegMarker::COM,
+                        fill_bytes: 0,
                         length: Some(4),
                         data: Some(vec![0x01, 0x02])
                     })
@@ -392,6 +884,7 @@ mod tests {
                     result,
                     Ok(JpegSegment {
                         marker: JpegMarker::SOS,
+                        fill_bytes: 0,
                         length: Some(4),
                         data: Some(vec![
                             0x01, 0x02, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
@@ -399,6 +892,61 @@ mod tests {
                     })
                 );
             }
+
+            /// Test that fill bytes before the marker are kept with the segment.
+            #[test]
+            fn test_fill_bytes() {
+                let bytes = [0xFF, 0xFF, 0xFF, 0xFE, 0x00, 0x03, 0x01];
+                let segment = JpegSegment::from_bytes(&bytes).unwrap();
+
+                assert_eq!(segment.marker, JpegMarker::COM);
+                assert_eq!(segment.data, Some(vec![0x01]));
+                assert_eq!(segment.byte_count(), bytes.len());
+                assert_eq!(segment.as_bytes(), bytes);
+            }
+
+            /// Test creating segments from bytes that end before the segment.
+            #[test]
+            fn test_truncated() {
+                let truncated = |structure: &'static str, offset: usize| {
+                    return Err(GCameraError::TruncatedData { structure, offset });
+                };
+                let bytes = [0x00, 0x00, 0xFF, 0xE1, 0x00, 0x06, 0x01, 0x02];
+
+                assert_eq!(
+                    JpegSegment::from_bytes_at(&bytes, 8),
+                    truncated("segment marker", 8)
+                );
+                assert_eq!(
+                    JpegSegment::from_bytes_at(&bytes[..5], 2),
+                    truncated("segment length", 2)
+                );
+                assert_eq!(
+                    JpegSegment::from_bytes_at(&bytes, 2),
+                    truncated("segment", 2)
+                );
+                assert_eq!(
+                    JpegSegment::from_bytes_at(&[0xFF, 0xDA, 0x00, 0x06, 0x01], 0),
+                    truncated("segment", 0)
+                );
+                assert_eq!(
+                    JpegSegment::from_bytes_at(&[0xFF, 0xDA, 0x00, 0x03, 0x01, 0x12, 0x34], 0),
+                    truncated("scan", 5)
+                );
+            }
+
+            /// Test creating a segment with a length that is too short to
+            /// hold the length itself.
+            #[test]
+            fn test_invalid_length() {
+                assert_eq!(
+                    JpegSegment::from_bytes_at(&[0x00, 0xFF, 0xE1, 0x00, 0x01], 1),
+                    Err(GCameraError::InvalidLength {
+                        structure: "segment",
+                        offset: 1
+                    })
+                );
+            }
         }
 
         /// Tests for the `byte_count` function.
@@ -410,6 +958,7 @@ mod tests {
             fn test_no_data() {
                 let segment = JpegSegment {
                     marker: JpegMarker::APP0,
+                    fill_bytes: 0,
                     length: None,
                     data: None,
                 };
@@ -422,6 +971,7 @@ mod tests {
             fn test_with_data() {
                 let segment = JpegSegment {
                     marker: JpegMarker::APP0,
+                    fill_bytes: 0,
                     length: Some(0x04),
                     data: Some(vec![0x01, 0x02]),
                 };
@@ -439,6 +989,7 @@ mod tests {
             fn test_eoi() {
                 let segment = JpegSegment {
                     marker: JpegMarker::EOI,
+                    fill_bytes: 0,
                     length: None,
                     data: None,
                 };
@@ -452,6 +1003,7 @@ mod tests {
             fn test_normal() {
                 let segment = JpegSegment {
                     marker: JpegMarker::APP0,
+                    fill_bytes: 0,
                     length: Some(0x04),
                     data: Some(vec![0x01, 0x02]),
                 };
@@ -468,11 +1020,12 @@ mod tests {
 
             let segment = JpegSegment {
                 marker: JpegMarker::APP1,
+                fill_bytes: 0,
                 length: Some(0x4EF),
                 data: Some(Vec::from(data)),
             };
 
-            assert_eq!(segment.as_xmp_str(), Some(expected_str));
+            assert_eq!(segment.as_xmp_str(), Some(Ok(expected_str)));
         }
 
         /// Test trying to get non XMP segment as xmp string when the marker is wrong.
@@ -480,22 +1033,122 @@ mod tests {
         fn test_as_xmp_str_wrong_marker() {
             let segment = JpegSegment {
                 marker: JpegMarker::APP0,
+                fill_bytes: 0,
                 length: Some(0x04),
                 data: Some(vec![0x01, 0x02, 0x03, 0x04]),
             };
 
             assert_eq!(segment.as_xmp_str(), None);
         }
-        /// Test trying to get non XMP segment as xmp string when the contents is not right
+
+        /// Test getting the segment as an Extended XMP chunk.
+        #[test]
+        fn test_as_extended_xmp_chunk() {
+            let data = [
+                "http://ns.adobe.com/xmp/extension/\x005D41402ABC4B2A76B9719D911017C592".as_bytes(),
+                &[0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00],
+                "hello".as_bytes(),
+            ]
+            .concat();
+            let segment = JpegSegment::new(JpegMarker::APP1, &data).unwrap();
+
+            assert_eq!(
+                segment.as_extended_xmp_chunk(),
+                Some(Ok(ExtendedXMPChunk {
+                    guid: String::from("5D41402ABC4B2A76B9719D911017C592"),
+                    full_length: 5,
+                    offset: 0,
+                    data: "hello".as_bytes().to_vec(),
+                }))
+            );
+        }
+
+        /// Test that the main XMP segment is not an Extended XMP chunk.
+        #[test]
+        fn test_as_extended_xmp_chunk_main_xmp() {
+            let data =
+                "http://ns.adobe.com/xap/1.0/\0<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"></x:xmpmeta>"
+                    .as_bytes();
+            let segment = JpegSegment::new(JpegMarker::APP1, data).unwrap();
+
+            assert_eq!(segment.as_extended_xmp_chunk(), None);
+        }
+
+        /// Test getting the ISO 21496-1 gain map data from an APP2 segment.
+        #[test]
+        fn test_as_iso_gain_map_data() {
+            let data = "urn:iso:std:iso:ts:21496:-1\0\0\0\0\0".as_bytes();
+            let segment = JpegSegment::new(JpegMarker::APP2, data).unwrap();
+            assert_eq!(
+                segment.as_iso_gain_map_data(),
+                Some([0x00, 0x00, 0x00, 0x00].as_slice())
+            );
+
+            let app1_segment = JpegSegment::new(JpegMarker::APP1, data).unwrap();
+            assert_eq!(app1_segment.as_iso_gain_map_data(), None);
+        }
+
+        /// Test getting the EXIF data from an APP1 segment.
+        #[test]
+        fn test_as_exif_data() {
+            let segment = JpegSegment::new(JpegMarker::APP1, "Exif\0\0II".as_bytes()).unwrap();
+            assert_eq!(segment.as_exif_data(), Some("II".as_bytes()));
+
+            let app2_segment = JpegSegment::new(JpegMarker::APP2, "Exif\0\0II".as_bytes()).unwrap();
+            assert_eq!(app2_segment.as_exif_data(), None);
+        }
+
+        /// Test getting the MPF data from an APP2 segment.
+        #[test]
+        fn test_as_mpf_data() {
+            let segment = JpegSegment::new(JpegMarker::APP2, "MPF\0MM".as_bytes()).unwrap();
+            assert_eq!(segment.as_mpf_data(), Some("MM".as_bytes()));
+
+            let iso_segment =
+                JpegSegment::new(JpegMarker::APP2, "urn:iso:std:iso:ts:21496:-1\0".as_bytes())
+                    .unwrap();
+            assert_eq!(iso_segment.as_mpf_data(), None);
+        }
+
+        /// Test getting the frame header from a SOF segment.
+        #[test]
+        fn test_as_frame_header() {
+            let data = [0x08, 0x00, 0x10, 0x00, 0x20, 0x01, 0x01, 0x11, 0x00];
+            let segment = JpegSegment::new(JpegMarker::SOF2, &data).unwrap();
+            let frame = segment.as_frame_header().unwrap().unwrap();
+            assert_eq!(frame.marker, JpegMarker::SOF2);
+            assert_eq!((frame.width, frame.height), (32, 16));
+
+            let dht_segment = JpegSegment::new(JpegMarker::DHT, &data).unwrap();
+            assert_eq!(dht_segment.as_frame_header(), None);
+        }
+
+        /// Test getting an XMP string from an APP1 segment without the XMP marker.
         #[test]
         fn test_as_xmp_str_wrong_data() {
             let segment = JpegSegment {
                 marker: JpegMarker::APP1,
+                fill_bytes: 0,
                 length: Some(0x04),
                 data: Some(vec![0x01, 0x02, 0x03, 0x04]),
             };
 
             assert_eq!(segment.as_xmp_str(), None);
         }
+
+        /// Test getting an XMP string that is not valid UTF-8.
+        #[test]
+        fn test_as_xmp_str_invalid_utf8() {
+            let data = [XMP_MARKER, &[0x00, 0x3C, 0xFF, 0x3E]].concat();
+            let segment = JpegSegment::new(JpegMarker::APP1, &data).unwrap();
+
+            assert_eq!(
+                segment.as_xmp_str(),
+                Some(Err(GCameraError::InvalidUtf8 {
+                    structure: "XMP segment",
+                    offset: XMP_MARKER.len() + 2,
+                }))
+            );
+        }
     }
 }
@@ -6,12 +6,30 @@
 //! Logic for the entire JPEG image.
 
 use crate::errors::GCameraError;
-use crate::jpeg::jpeg_components::JpegSegment;
+use crate::jpeg::exif::{
+    ExifData, ExifValue, IfdKind, EXIF_MARKER, TAG_COMPRESSION, TAG_IMAGE_LENGTH, TAG_IMAGE_WIDTH,
+    TAG_RESOLUTION_UNIT, TAG_X_RESOLUTION, TAG_Y_RESOLUTION,
+};
+use crate::jpeg::extended_xmp::{
+    compute_guid, create_segments, merge_chunks, split_xmp, ExtendedXMPChunk,
+};
+use crate::jpeg::frame::FrameHeader;
+use crate::jpeg::icc::{create_icc_segments, merge_icc_chunks, IccChunk, IccProfile};
+use crate::jpeg::iso_gain_map::parse_iso_gain_map;
+use crate::jpeg::jpeg_components::{check_restart_markers, JpegSegment};
 use crate::jpeg::marker::JpegMarker;
-use crate::jpeg::xmp::XMPData;
+use crate::jpeg::mpf::{MpIndex, MPF_MARKER};
+use crate::jpeg::scan::ScanHeader;
+use crate::jpeg::tables::{HuffmanTable, QuantizationTable};
+use crate::jpeg::thumbnail::{create_jfxx_segment, validate_thumbnail, Thumbnail, ThumbnailSource};
+use crate::jpeg::tiff::ByteOrder;
+use crate::jpeg::xmp::depth::{DepthMap, OriginalImage};
+use crate::jpeg::xmp::gain_map::GainMapMetadata;
+use crate::jpeg::xmp::serializer::{serialize, SerializeOptions};
+use crate::jpeg::xmp::{create_xmp_segment, strip_resources, XMPData};
 
 /// Struct for holding a single JPEG image.
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub struct JpegImage {
     /// Vector of the individual JPEG Segments in the image.
     pub segments: Vec<JpegSegment>,
@@ -30,24 +48,317 @@ impl JpegImage {
             .sum();
     }
 
+    /// Convert the image to bytes.
+    ///
+    /// # Returns
+    /// The JPEG image as a vector of bytes.
+    pub fn as_bytes(&self) -> Vec<u8> {
+        return self
+            .segments
+            .iter()
+            .flat_map(|segment| return segment.as_bytes())
+            .collect();
+    }
+
     /// Convert the image to bytes, removing motion data from the XMP
     ///
+    /// The resource properties are removed from both the main and the
+    /// Extended XMP data. If this changes the Extended XMP data, it is
+    /// written with a new GUID. The MPF index is removed, since the images
+    /// it lists are not part of the JPEG image.
+    ///
     /// # Returns
     /// The JPEG image as a vector of bytes, but with the motion data removed
     /// from the XMP data.
-    pub fn as_resourceless_bytes(&self) -> Vec<u8> {
-        return self
+    ///
+    /// # Errors
+    /// Will error if the XMP data in the image cannot be parsed.
+    pub fn as_resourceless_bytes(&self) -> Result<Vec<u8>, GCameraError> {
+        // The old GUID, the new GUID, and the segments of the new Extended XMP.
+        let mut new_extended: Option<(String, String, Vec<JpegSegment>)> = None;
+        if let Some(guid) = self
+            .get_xmp()
+            .ok()
+            .and_then(|xmp| return xmp.description.extended_xmp_id().map(String::from))
+        {
+            if let Ok(extended) = self.get_extended_xmp_str(&guid) {
+                let stripped = strip_resources(&extended)?;
+                if stripped != extended {
+                    let new_guid = compute_guid(stripped.as_bytes());
+                    new_extended = Some((guid, new_guid, create_segments(&stripped)?));
+                }
+            }
+        }
+
+        let mut bytes = Vec::new();
+        for segment in self
             .segments
             .iter()
-            .flat_map(|segment| {
+            .filter(|segment| return segment.as_mpf_data().is_none())
+        {
+            if let Some(xmp_str) = segment.as_xmp_str() {
                 // If it is the XMP segment, remove resources.
-                if let Some(xmp_data) = segment.as_xmp_data() {
-                    return xmp_data.unwrap().as_resourceless_segment().as_bytes();
+                let mut stripped = strip_resources(&xmp_str?)?;
+                if let Some((guid, new_guid, _)) = &new_extended {
+                    stripped = stripped.replace(guid.as_str(), new_guid);
+                }
+                bytes.extend(create_xmp_segment(&stripped)?.as_bytes());
+            } else if let Some((guid, _, chunks)) = &mut new_extended {
+                // The new chunks replace the first of the old chunks.
+                if segment
+                    .extended_xmp_guid()
+                    .is_some_and(|id| return id.eq_ignore_ascii_case(guid.as_bytes()))
+                {
+                    for chunk in chunks.drain(..) {
+                        bytes.extend(chunk.as_bytes());
+                    }
                 } else {
-                    return segment.as_bytes();
+                    bytes.extend(segment.as_bytes());
                 }
+            } else {
+                bytes.extend(segment.as_bytes());
+            }
+        }
+        return Ok(bytes);
+    }
+
+    /// Replace the XMP data in the image.
+    ///
+    /// All existing XMP and Extended XMP segments are removed. If the new XMP
+    /// data is too large to fit in a single segment, some of its properties
+    /// are moved into Extended XMP segments.
+    ///
+    /// # Arguments
+    /// * `xmp`: The new XMP data, as an XML string.
+    ///
+    /// # Errors
+    /// Will error if the XMP data cannot be parsed, or is too large to be
+    /// stored in the image.
+    pub fn set_xmp(&mut self, xmp: &str) -> Result<(), GCameraError> {
+        let (main, extended) = split_xmp(xmp)?;
+        let mut new_segments = vec![create_xmp_segment(&main)?];
+        if let Some(extended_xmp) = extended {
+            new_segments.extend(create_segments(&extended_xmp)?);
+        }
+
+        // Put the new segments where the old XMP was, or after the SOI and
+        // JFIF segments if there was no XMP data.
+        let insert_at = self
+            .segments
+            .iter()
+            .position(|segment| return segment.has_xmp())
+            .or_else(|| {
+                return self.segments.iter().position(|segment| {
+                    return !matches!(segment.marker, JpegMarker::SOI | JpegMarker::APP0);
+                });
             })
-            .collect();
+            .unwrap_or(self.segments.len());
+        self.segments.retain(|segment| return !segment.has_xmp());
+        self.segments.splice(insert_at..insert_at, new_segments);
+
+        return Ok(());
+    }
+
+    /// Replace the EXIF data in the image.
+    ///
+    /// The new segment is put where the old EXIF data was, or after the SOI
+    /// and JFIF segments if the image had no EXIF data.
+    ///
+    /// # Arguments
+    /// * `exif`: The new EXIF data.
+    ///
+    /// # Errors
+    /// Will error if the EXIF data cannot be written, or is too large to be
+    /// stored in a single segment.
+    pub fn set_exif(&mut self, exif: &ExifData) -> Result<(), GCameraError> {
+        let data = [EXIF_MARKER, &exif.as_bytes()?].concat();
+        let new_segment = JpegSegment::new(JpegMarker::APP1, &data)?;
+
+        if let Some(segment) = self
+            .segments
+            .iter_mut()
+            .find(|segment| return segment.as_exif_data().is_some())
+        {
+            *segment = new_segment;
+        } else {
+            let insert_at = self
+                .segments
+                .iter()
+                .position(|segment| {
+                    return !matches!(segment.marker, JpegMarker::SOI | JpegMarker::APP0);
+                })
+                .unwrap_or(self.segments.len());
+            self.segments.insert(insert_at, new_segment);
+        }
+
+        return Ok(());
+    }
+
+    /// Get the EXIF data, or empty EXIF data if the image has none.
+    ///
+    /// # Returns
+    /// The EXIF data of the image.
+    ///
+    /// # Errors
+    /// Will error if the EXIF data of the image is not valid.
+    fn get_exif_or_new(&self) -> Result<ExifData, GCameraError> {
+        return match self.get_exif() {
+            Err(GCameraError::NoExifData) => Ok(ExifData::new(ByteOrder::BigEndian)),
+            result => result,
+        };
+    }
+
+    /// Remove the location and identifying data from the image.
+    ///
+    /// The EXIF GPS data and identifying tags, and the XMP location and
+    /// identifying properties are removed. The motion photo properties are
+    /// kept, since this is used for the images embedded in a photo.
+    ///
+    /// # Returns
+    /// A description of each item that was removed.
+    ///
+    /// # Errors
+    /// Will error if the EXIF or XMP data cannot be parsed, or cannot be
+    /// stored in the image again.
+    pub fn scrub(&mut self) -> Result<Vec<String>, GCameraError> {
+        let mut removed = self.scrub_exif()?;
+        match self.get_xmp() {
+            Ok(xmp) => {
+                let extended = self.get_extended_xmp().ok();
+                let (tree, xmp_removed) =
+                    xmp.scrub(extended.as_ref().map(|data| return &data.tree), true);
+                if !xmp_removed.is_empty() {
+                    self.set_xmp(&serialize(&tree, SerializeOptions::default()))?;
+                    removed.extend(xmp_removed);
+                }
+            }
+            Err(GCameraError::NoXMPData) => {}
+            Err(error) => return Err(error),
+        }
+        return Ok(removed);
+    }
+
+    /// Remove the location and identifying data from the EXIF data.
+    ///
+    /// # Returns
+    /// A description of each item that was removed.
+    ///
+    /// # Errors
+    /// Will error if the EXIF data cannot be parsed, or cannot be stored in
+    /// the image again.
+    pub fn scrub_exif(&mut self) -> Result<Vec<String>, GCameraError> {
+        return match self.get_exif() {
+            Ok(mut exif) => {
+                let removed = exif.scrub();
+                if !removed.is_empty() {
+                    self.set_exif(&exif)?;
+                }
+                Ok(removed)
+            }
+            Err(GCameraError::NoExifData) => Ok(Vec::new()),
+            Err(error) => Err(error),
+        };
+    }
+
+    /// Get the thumbnails embedded in the image.
+    ///
+    /// # Returns
+    /// The EXIF thumbnail, followed by any JFXX thumbnails.
+    ///
+    /// # Errors
+    /// Will error if the EXIF data of the image is not valid.
+    pub fn get_thumbnails(&self) -> Result<Vec<Thumbnail>, GCameraError> {
+        let mut thumbnails = Vec::new();
+        if let Some(data) = self.get_exif_or_new()?.thumbnail_image {
+            thumbnails.push(Thumbnail {
+                source: ThumbnailSource::Exif,
+                data,
+            });
+        }
+        thumbnails.extend(self.segments.iter().filter_map(|segment| {
+            return segment.as_jfxx_thumbnail().map(|data| {
+                return Thumbnail {
+                    source: ThumbnailSource::Jfxx,
+                    data: data.to_vec(),
+                };
+            });
+        }));
+        return Ok(thumbnails);
+    }
+
+    /// Remove the thumbnails embedded in the image.
+    ///
+    /// IFD1 of the EXIF data is removed, along with all JFXX segments.
+    ///
+    /// # Returns
+    /// The number of thumbnails that were removed.
+    ///
+    /// # Errors
+    /// Will error if the EXIF data of the image is not valid.
+    pub fn remove_thumbnails(&mut self) -> Result<usize, GCameraError> {
+        let mut exif = self.get_exif_or_new()?;
+        let mut removed = 0;
+        if exif.thumbnail_image.is_some() || !exif.thumbnail.is_empty() {
+            removed += usize::from(exif.thumbnail_image.take().is_some());
+            exif.thumbnail.clear();
+            self.set_exif(&exif)?;
+        }
+
+        let count = self.segments.len();
+        self.segments.retain(|segment| return !segment.is_jfxx());
+        return Ok(removed + count - self.segments.len());
+    }
+
+    /// Replace the thumbnails embedded in the image.
+    ///
+    /// The EXIF thumbnail is replaced, or added if the image does not have
+    /// one. Any JFXX thumbnails are replaced with the same data. The
+    /// thumbnail is not generated from the image, so it is up to the caller
+    /// to provide one that matches it.
+    ///
+    /// The size tags in IFD1 are removed, since they are not recorded for a
+    /// JPEG thumbnail, and the resolution is reset to the default of 72 dpi.
+    ///
+    /// # Arguments
+    /// * `data`: The JPEG data of the new thumbnail.
+    ///
+    /// # Errors
+    /// Will error if the thumbnail is not a JPEG image, the EXIF data of the
+    /// image is not valid, or the thumbnail is too large to be stored.
+    pub fn set_thumbnail(&mut self, data: &[u8]) -> Result<(), GCameraError> {
+        validate_thumbnail(data)?;
+        let mut exif = self.get_exif_or_new()?;
+        exif.thumbnail_image = Some(data.to_vec());
+        exif.set(
+            IfdKind::Thumbnail,
+            TAG_COMPRESSION,
+            ExifValue::Short(vec![6]),
+        );
+        exif.remove(IfdKind::Thumbnail, TAG_IMAGE_WIDTH);
+        exif.remove(IfdKind::Thumbnail, TAG_IMAGE_LENGTH);
+        for tag in [TAG_X_RESOLUTION, TAG_Y_RESOLUTION] {
+            exif.set(IfdKind::Thumbnail, tag, ExifValue::Rational(vec![(72, 1)]));
+        }
+        exif.set(
+            IfdKind::Thumbnail,
+            TAG_RESOLUTION_UNIT,
+            ExifValue::Short(vec![2]),
+        );
+        self.set_exif(&exif)?;
+
+        if self
+            .segments
+            .iter()
+            .any(|segment| return segment.as_jfxx_thumbnail().is_some())
+        {
+            let jfxx = create_jfxx_segment(data)?;
+            for segment in &mut self.segments {
+                if segment.as_jfxx_thumbnail().is_some() {
+                    *segment = jfxx.clone();
+                }
+            }
+        }
+        return Ok(());
     }
 
     /// Get the XMP data from the image
@@ -64,6 +375,371 @@ impl JpegImage {
             .find_map(|e| return e.as_xmp_data())
             .unwrap_or(Err(GCameraError::NoXMPData));
     }
+
+    /// Get the Extended XMP data from the image.
+    ///
+    /// The chunks of Extended XMP data are gathered from all of the segments
+    /// and merged back together, using the GUID from the main XMP data.
+    ///
+    /// # Returns
+    /// The merged Extended XMP as `XMPData`.
+    ///
+    /// # Errors
+    /// Will return an error if there is no XMP or Extended XMP data in the
+    /// image, or if the Extended XMP chunks are not valid.
+    pub fn get_extended_xmp(&self) -> Result<XMPData, GCameraError> {
+        let xmp = self.get_xmp()?;
+        let guid = xmp
+            .description
+            .extended_xmp_id()
+            .ok_or(GCameraError::NoExtendedXMPData)?;
+        return XMPData::try_from(self.get_extended_xmp_str(guid)?);
+    }
+
+    /// Get the Extended XMP data from the image as a string.
+    ///
+    /// # Arguments
+    /// * `guid`: The GUID of the Extended XMP data.
+    ///
+    /// # Returns
+    /// The merged Extended XMP document.
+    ///
+    /// # Errors
+    /// Will return an error if there is no Extended XMP data with the GUID,
+    /// or if its chunks are not valid. A chunk that is not valid gives the
+    /// offset of its segment in the image.
+    fn get_extended_xmp_str(&self, guid: &str) -> Result<String, GCameraError> {
+        // Only the chunks with the matching GUID are parsed, so that a
+        // malformed chunk of unrelated Extended XMP data is ignored.
+        let chunks = self
+            .segment_offsets()
+            .filter(|(_, segment)| {
+                return segment
+                    .extended_xmp_guid()
+                    .is_some_and(|id| return id.eq_ignore_ascii_case(guid.as_bytes()));
+            })
+            .filter_map(|(offset, segment)| {
+                return segment
+                    .as_extended_xmp_chunk()
+                    .map(|chunk| return chunk.map_err(|error| return error.offset_by(offset)));
+            })
+            .collect::<Result<Vec<ExtendedXMPChunk>, GCameraError>>()?;
+
+        return merge_chunks(guid, &chunks);
+    }
+
+    /// Get the ISO 21496-1 gain map metadata from the image.
+    ///
+    /// Segments that only hold the version numbers, like the one in the
+    /// primary image of an `UltraHDR` photo, are skipped.
+    ///
+    /// # Returns
+    /// The gain map metadata from the first APP2 segment that holds all of it.
+    ///
+    /// # Errors
+    /// Will return an error if there is no ISO 21496-1 gain map metadata in
+    /// the image, or if it is not valid.
+    pub fn get_iso_gain_map_metadata(&self) -> Result<GainMapMetadata, GCameraError> {
+        for data in self
+            .segments
+            .iter()
+            .filter_map(|segment| return segment.as_iso_gain_map_data())
+        {
+            if let Some(metadata) = parse_iso_gain_map(data)? {
+                return Ok(metadata);
+            }
+        }
+        return Err(GCameraError::NoGainMapMetadata);
+    }
+
+    /// Get the EXIF data from the image.
+    ///
+    /// # Returns
+    /// The decoded EXIF data of the first EXIF segment.
+    ///
+    /// # Errors
+    /// Will return an error if there is no EXIF data in the image, or if it
+    /// is not valid.
+    pub fn get_exif(&self) -> Result<ExifData, GCameraError> {
+        let data = self
+            .segments
+            .iter()
+            .find_map(|segment| return segment.as_exif_data())
+            .ok_or(GCameraError::NoExifData)?;
+        return ExifData::try_from(data);
+    }
+
+    /// Get the MPF index from the image.
+    ///
+    /// # Returns
+    /// The offset of the TIFF header of the index from the start of the
+    /// image, which the offsets in the index are relative to, and the
+    /// parsed index.
+    ///
+    /// # Errors
+    /// Will return an error if there is no MPF index in the image, or if it
+    /// is not valid.
+    pub fn get_mpf_index(&self) -> Result<(usize, MpIndex), GCameraError> {
+        let mut position = 0;
+        for segment in &self.segments {
+            if let Some(data) = segment.as_mpf_data() {
+                // Skip the fill bytes, marker, length and MPF marker string.
+                let header_position = position + segment.data_position() + MPF_MARKER.len();
+                return Ok((header_position, MpIndex::try_from(data)?));
+            }
+            position += segment.byte_count();
+        }
+        return Err(GCameraError::NoMpfIndex);
+    }
+
+    /// Get the frame header of the image.
+    ///
+    /// # Returns
+    /// The frame header from the first SOF segment.
+    ///
+    /// # Errors
+    /// Will return an error if there is no SOF segment in the image, or if
+    /// its frame header is not valid. That error gives the offset of the
+    /// segment in the image.
+    pub fn get_frame_header(&self) -> Result<FrameHeader, GCameraError> {
+        let (offset, frame) = self
+            .segment_offsets()
+            .find_map(|(offset, segment)| {
+                return segment
+                    .as_frame_header()
+                    .map(|frame| return (offset, frame));
+            })
+            .ok_or(GCameraError::NoFrameHeader)?;
+        return frame.map_err(|error| return error.offset_by(offset));
+    }
+
+    /// Get the scan headers of the image.
+    ///
+    /// A baseline image has a single scan, while a progressive image has
+    /// one for each pass over the coefficients.
+    ///
+    /// # Returns
+    /// The header of each scan, in the order they appear.
+    ///
+    /// # Errors
+    /// Will return an error if a scan header is not valid, or a scan uses a
+    /// component that is not in the frame header. A scan header that is not
+    /// valid gives the offset of its segment in the image.
+    pub fn scans(&self) -> Result<Vec<ScanHeader>, GCameraError> {
+        let frame = match self.get_frame_header() {
+            Ok(frame) => Some(frame),
+            Err(GCameraError::NoFrameHeader) => None,
+            Err(error) => return Err(error),
+        };
+        let scans = self
+            .segment_offsets()
+            .filter_map(|(offset, segment)| {
+                return segment
+                    .as_scan_header()
+                    .map(|scan| return scan.map_err(|error| return error.offset_by(offset)));
+            })
+            .collect::<Result<Vec<ScanHeader>, GCameraError>>()?;
+
+        if let Some(frame_header) = frame {
+            for component in scans.iter().flat_map(|scan| return &scan.components) {
+                if !frame_header
+                    .components
+                    .iter()
+                    .any(|frame_component| return frame_component.id == component.selector)
+                {
+                    return Err(GCameraError::UnknownScanComponent {
+                        selector: component.selector,
+                    });
+                }
+            }
+        }
+        return Ok(scans);
+    }
+
+    /// Check the restart markers of every scan against the restart interval.
+    ///
+    /// Restart markers that are out of order do not stop the image from
+    /// being parsed, since the scans can still be found without them. This
+    /// reports them instead.
+    ///
+    /// # Errors
+    /// Will return an error if a DRI segment is not valid, a restart marker
+    /// is found when the restart interval is 0, or a restart marker is out
+    /// of order. The restart marker errors give the offset of the marker in
+    /// the image.
+    pub fn check_restart_markers(&self) -> Result<(), GCameraError> {
+        let mut restart_interval = 0;
+        for (offset, segment) in self.segment_offsets() {
+            if let Some(interval) = segment.as_restart_interval() {
+                restart_interval = interval?;
+            }
+            if let Some(scan) = segment.as_scan_data() {
+                let scan_offset = offset + segment.byte_count() - scan.len();
+                check_restart_markers(scan, restart_interval)
+                    .map_err(|error| return error.offset_by(scan_offset))?;
+            }
+        }
+        return Ok(());
+    }
+
+    /// Get the depth map of a portrait photo.
+    ///
+    /// The properties of the depth map are read from the main XMP data,
+    /// and then from the Extended XMP data if the image has any.
+    ///
+    /// # Returns
+    /// The depth map, with its data decoded.
+    ///
+    /// # Errors
+    /// Will error if the image has no depth map, or it is not valid.
+    pub fn get_depth_map(&self) -> Result<DepthMap, GCameraError> {
+        let main = self.get_xmp()?;
+        return match self.get_extended_xmp() {
+            Ok(extended) => DepthMap::from_trees(&[&main.tree, &extended.tree]),
+            Err(_) => DepthMap::from_trees(&[&main.tree]),
+        };
+    }
+
+    /// Get the original image of a lens blur photo.
+    ///
+    /// The original image is stored as base64 data in the XMP data, so it is
+    /// only decoded when it is requested.
+    ///
+    /// # Returns
+    /// The original image, with its data decoded.
+    ///
+    /// # Errors
+    /// Will error if the image has no original image, or it is not valid.
+    pub fn get_original_image(&self) -> Result<OriginalImage, GCameraError> {
+        let main = self.get_xmp()?;
+        return match self.get_extended_xmp() {
+            Ok(extended) => OriginalImage::from_trees(&[&main.tree, &extended.tree]),
+            Err(_) => OriginalImage::from_trees(&[&main.tree]),
+        };
+    }
+
+    /// Get the quantization tables of the image.
+    ///
+    /// # Returns
+    /// The tables from all of the DQT segments, in the order they appear.
+    ///
+    /// # Errors
+    /// Will return an error if any DQT segment is not valid.
+    pub fn get_quantization_tables(&self) -> Result<Vec<QuantizationTable>, GCameraError> {
+        return Ok(self
+            .segments
+            .iter()
+            .filter_map(|segment| return segment.as_quantization_tables())
+            .collect::<Result<Vec<Vec<QuantizationTable>>, GCameraError>>()?
+            .concat());
+    }
+
+    /// Get the Huffman tables of the image.
+    ///
+    /// # Returns
+    /// The tables from all of the DHT segments, in the order they appear.
+    ///
+    /// # Errors
+    /// Will return an error if any DHT segment is not valid.
+    pub fn get_huffman_tables(&self) -> Result<Vec<HuffmanTable>, GCameraError> {
+        return Ok(self
+            .segments
+            .iter()
+            .filter_map(|segment| return segment.as_huffman_tables())
+            .collect::<Result<Vec<Vec<HuffmanTable>>, GCameraError>>()?
+            .concat());
+    }
+
+    /// Estimate the IJG quality factor the image was encoded with.
+    ///
+    /// # Returns
+    /// The quality estimated from the luminance quantization table, or from
+    /// the first table if there is none with an identifier of 0.
+    ///
+    /// # Errors
+    /// Will return an error if the image has no quantization tables, or a
+    /// DQT segment is not valid.
+    pub fn estimate_quality(&self) -> Result<u16, GCameraError> {
+        let tables = self.get_quantization_tables()?;
+        return tables
+            .iter()
+            .find(|table| return table.id == 0)
+            .or(tables.first())
+            .map(QuantizationTable::estimate_quality)
+            .ok_or(GCameraError::NoQuantizationTable);
+    }
+
+    /// Get the ICC profile from the image.
+    ///
+    /// # Returns
+    /// The profile, reassembled from the chunks in the APP2 segments.
+    ///
+    /// # Errors
+    /// Will return an error if there is no ICC profile in the image, if any
+    /// of its chunks are missing or repeated, or if the profile is not valid.
+    /// A chunk that is not valid gives the offset of its segment in the
+    /// image.
+    pub fn get_icc_profile(&self) -> Result<IccProfile, GCameraError> {
+        let chunks = self
+            .segment_offsets()
+            .filter_map(|(offset, segment)| {
+                return segment.as_icc_chunk().map(|chunk| {
+                    return chunk
+                        .map(|icc_chunk| return (offset, icc_chunk))
+                        .map_err(|error| return error.offset_by(offset));
+                });
+            })
+            .collect::<Result<Vec<(usize, IccChunk)>, GCameraError>>()?;
+        return IccProfile::try_from(merge_icc_chunks(&chunks)?);
+    }
+
+    /// Get each segment of the image with its offset.
+    ///
+    /// # Returns
+    /// An iterator over the offset of each segment from the start of the
+    /// image, and the segment.
+    fn segment_offsets(&self) -> impl Iterator<Item = (usize, &JpegSegment)> {
+        return self.segments.iter().scan(0, |position, segment| {
+            let offset = *position;
+            *position += segment.byte_count();
+            return Some((offset, segment));
+        });
+    }
+
+    /// Replace the ICC profile in the image.
+    ///
+    /// The profile is split into as many APP2 segments as needed. The new
+    /// segments are put where the old profile was, or after the SOI, JFIF
+    /// and APP1 segments if the image had no profile.
+    ///
+    /// # Arguments
+    /// * `profile`: The new ICC profile.
+    ///
+    /// # Errors
+    /// Will error if the profile is not valid, or is too large to be stored
+    /// in the image.
+    pub fn set_icc_profile(&mut self, profile: &[u8]) -> Result<(), GCameraError> {
+        IccProfile::try_from(profile.to_vec())?;
+        let new_segments = create_icc_segments(profile)?;
+
+        let insert_at = self
+            .segments
+            .iter()
+            .position(|segment| return segment.is_icc())
+            .or_else(|| {
+                return self.segments.iter().position(|segment| {
+                    return !matches!(
+                        segment.marker,
+                        JpegMarker::SOI | JpegMarker::APP0 | JpegMarker::APP1
+                    );
+                });
+            })
+            .unwrap_or(self.segments.len());
+        self.segments.retain(|segment| return !segment.is_icc());
+        self.segments.splice(insert_at..insert_at, new_segments);
+
+        return Ok(());
+    }
 }
 
 impl TryFrom<&[u8]> for JpegImage {
@@ -77,7 +753,7 @@ impl TryFrom<&[u8]> for JpegImage {
     /// # Returns
     /// Resulting holding the created image, or an error message.
     fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
-        if bytes[0..2] != vec![0xFF, 0xD8] {
+        if !bytes.starts_with(&[0xFF, 0xD8]) {
             return Err(GCameraError::InvalidJpegMagic);
         }
 
@@ -87,10 +763,12 @@ impl TryFrom<&[u8]> for JpegImage {
         segments.push(JpegSegment::from_bytes(bytes)?);
         let mut offset = 0;
 
-        while !matches!(segments.last().unwrap().marker, JpegMarker::EOI) {
-            let prev = segments.last().unwrap();
+        while let Some(prev) = segments
+            .last()
+            .filter(|segment| return segment.marker != JpegMarker::EOI)
+        {
             offset += prev.byte_count();
-            segments.push(JpegSegment::from_bytes(&bytes[offset..])?);
+            segments.push(JpegSegment::from_bytes_at(bytes, offset)?);
         }
 
         return Ok(JpegImage { segments });
@@ -100,6 +778,21 @@ impl TryFrom<&[u8]> for JpegImage {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::jpeg::xmp::SemanticType;
+    /// Test parsing an image with fill bytes before its markers.
+    #[test]
+    fn test_fill_bytes() {
+        let bytes = [
+            0xFF, 0xD8, 0xFF, 0xFF, 0xFE, 0x00, 0x03, 0x01, 0xFF, 0xFF, 0xFF, 0xD9,
+        ];
+        let image = JpegImage::try_from(bytes.as_slice()).unwrap();
+
+        assert_eq!(image.segments.len(), 3);
+        assert_eq!(image.segments[2].marker, JpegMarker::EOI);
+        assert_eq!(image.image_size(), bytes.len());
+        assert_eq!(image.as_bytes(), bytes);
+    }
+
     /// Test converting the segment to vector of bytes
     #[test]
     fn test_to_bytes() {
@@ -110,7 +803,30 @@ mod test {
             ],
         };
 
-        assert_eq!(image.as_resourceless_bytes(), vec![0xFF, 0xD8, 0xFF, 0xD9]);
+        assert_eq!(
+            image.as_resourceless_bytes(),
+            Ok(vec![0xFF, 0xD8, 0xFF, 0xD9])
+        );
+    }
+
+    /// Test that removing the resources keeps the other XMP properties.
+    #[test]
+    fn test_resourceless_bytes_keeps_properties() {
+        let xmp_str = "<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:xmp='http://ns.adobe.com/xap/1.0/' xmlns:Container='http://ns.google.com/photos/1.0/container/' xmp:Rating='5'><Container:Directory/></rdf:Description></rdf:RDF></x:xmpmeta>";
+        let image = JpegImage {
+            segments: vec![
+                JpegSegment::from_bytes(&[0xFF, 0xD8]).unwrap(),
+                create_xmp_segment(xmp_str).unwrap(),
+                JpegSegment::from_bytes(&[0xFF, 0xD9]).unwrap(),
+            ],
+        };
+
+        let stripped =
+            JpegImage::try_from(image.as_resourceless_bytes().unwrap().as_slice()).unwrap();
+        assert_eq!(
+            stripped.segments[1].as_xmp_str(),
+            Some(Ok(String::from("<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:xmp='http://ns.adobe.com/xap/1.0/' xmlns:Container='http://ns.google.com/photos/1.0/container/' xmp:Rating='5'></rdf:Description></rdf:RDF></x:xmpmeta>")))
+        );
     }
 
     /// Test case for when there JPEG magic is invalid
@@ -122,6 +838,39 @@ mod test {
         assert_eq!(image, Err(GCameraError::InvalidJpegMagic));
     }
 
+    /// Test that the resources are also removed from the Extended XMP data.
+    #[test]
+    fn test_resourceless_bytes_extended_xmp() {
+        let extended_str = "<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:xmp='http://ns.adobe.com/xap/1.0/' xmlns:Container='http://ns.google.com/photos/1.0/container/' xmp:Rating='5'><Container:Directory/></rdf:Description></rdf:RDF></x:xmpmeta>";
+        let guid = compute_guid(extended_str.as_bytes());
+        let image = JpegImage {
+            segments: vec![
+                JpegSegment::from_bytes(&[0xFF, 0xD8]).unwrap(),
+                main_xmp_segment(&guid),
+                extended_xmp_segment(extended_str, 0, 100),
+                extended_xmp_segment(extended_str, 100, extended_str.len()),
+                JpegSegment::from_bytes(&[0xFF, 0xD9]).unwrap(),
+            ],
+        };
+        let stripped =
+            JpegImage::try_from(image.as_resourceless_bytes().unwrap().as_slice()).unwrap();
+        let extended = stripped.get_extended_xmp().unwrap();
+
+        assert_ne!(
+            stripped.get_xmp().unwrap().description.extended_xmp_id(),
+            Some(guid.as_str())
+        );
+        assert_eq!(
+            extended.get_str("http://ns.adobe.com/xap/1.0/", "Rating"),
+            Some("5")
+        );
+        assert_eq!(
+            extended.get("http://ns.google.com/photos/1.0/container/", "Directory"),
+            None
+        );
+        assert_eq!(stripped.segments.len(), 4);
+    }
+
     /// Test for when there is no XMP Data segment.
     #[test]
     fn test_no_xmp() {
@@ -146,4 +895,796 @@ mod test {
         let xmp_data = image.get_xmp();
         assert_eq!(xmp_data, XMPData::try_from(xmp_str));
     }
+
+    /// Create an XMP segment with the given Extended XMP GUID.
+    ///
+    /// # Arguments
+    /// * `guid`: The GUID to put in the `xmpNote:HasExtendedXMP` attribute.
+    ///
+    /// # Returns
+    /// Segment holding the main XMP data.
+    fn main_xmp_segment(guid: &str) -> JpegSegment {
+        let xmp_str = format!("<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:xmpNote='http://ns.adobe.com/xmp/note/' xmpNote:HasExtendedXMP='{guid}'/></rdf:RDF></x:xmpmeta>");
+        let data = [
+            "http://ns.adobe.com/xap/1.0/\0".as_bytes(),
+            xmp_str.as_bytes(),
+        ]
+        .concat();
+        return JpegSegment::new(JpegMarker::APP1, &data).unwrap();
+    }
+
+    /// Create an Extended XMP segment for a chunk of the given data.
+    ///
+    /// # Arguments
+    /// * `data`: The complete Extended XMP data.
+    /// * `start`: The start of the chunk within the data.
+    /// * `end`: The end of the chunk within the data.
+    ///
+    /// # Returns
+    /// Segment holding the chunk of Extended XMP data.
+    fn extended_xmp_segment(data: &str, start: usize, end: usize) -> JpegSegment {
+        let bytes = [
+            "http://ns.adobe.com/xmp/extension/\0".as_bytes(),
+            compute_guid(data.as_bytes()).as_bytes(),
+            &u32::try_from(data.len()).unwrap().to_be_bytes(),
+            &u32::try_from(start).unwrap().to_be_bytes(),
+            &data.as_bytes()[start..end],
+        ]
+        .concat();
+        return JpegSegment::new(JpegMarker::APP1, &bytes).unwrap();
+    }
+
+    /// Test getting the Extended XMP data when it is split across segments.
+    #[test]
+    fn test_get_extended_xmp() {
+        let extended_str = "<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:GDepth='http://ns.google.com/photos/1.0/depthmap/' GDepth:Data='abcd'/></rdf:RDF></x:xmpmeta>";
+        let guid = compute_guid(extended_str.as_bytes());
+        let image = JpegImage {
+            segments: vec![
+                JpegSegment::from_bytes(&[0xFF, 0xD8]).unwrap(),
+                main_xmp_segment(&guid),
+                extended_xmp_segment(extended_str, 100, extended_str.len()),
+                extended_xmp_segment(extended_str, 0, 100),
+                JpegSegment::from_bytes(&[0xFF, 0xD9]).unwrap(),
+            ],
+        };
+
+        assert_eq!(
+            image.get_extended_xmp(),
+            XMPData::try_from(String::from(extended_str))
+        );
+    }
+
+    /// Test getting the Extended XMP data when there is a malformed chunk with another GUID.
+    #[test]
+    fn test_get_extended_xmp_unrelated_chunk() {
+        let extended_str = "<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about=''/></rdf:RDF></x:xmpmeta>";
+        let guid = compute_guid(extended_str.as_bytes());
+        let unrelated = [
+            "http://ns.adobe.com/xmp/extension/\0".as_bytes(),
+            "00000000000000000000000000000000".as_bytes(),
+            &[0x00, 0x00],
+        ]
+        .concat();
+        let image = JpegImage {
+            segments: vec![
+                main_xmp_segment(&guid),
+                JpegSegment::new(JpegMarker::APP1, &unrelated).unwrap(),
+                extended_xmp_segment(extended_str, 0, extended_str.len()),
+            ],
+        };
+
+        assert_eq!(
+            image.get_extended_xmp(),
+            XMPData::try_from(String::from(extended_str))
+        );
+    }
+
+    /// Test getting the Extended XMP data when the main XMP does not reference any.
+    #[test]
+    fn test_get_extended_xmp_no_guid() {
+        let xmp_str = "<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about=''/></rdf:RDF></x:xmpmeta>";
+        let data = [
+            "http://ns.adobe.com/xap/1.0/\0".as_bytes(),
+            xmp_str.as_bytes(),
+        ]
+        .concat();
+        let image = JpegImage {
+            segments: vec![JpegSegment::new(JpegMarker::APP1, &data).unwrap()],
+        };
+
+        assert_eq!(
+            image.get_extended_xmp(),
+            Err(GCameraError::NoExtendedXMPData)
+        );
+    }
+
+    /// Test getting the Extended XMP data when a chunk is missing.
+    #[test]
+    fn test_get_extended_xmp_missing_chunk() {
+        let extended_str = "<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about=''/></rdf:RDF></x:xmpmeta>";
+        let guid = compute_guid(extended_str.as_bytes());
+        let image = JpegImage {
+            segments: vec![
+                main_xmp_segment(&guid),
+                extended_xmp_segment(extended_str, 50, extended_str.len()),
+            ],
+        };
+
+        assert_eq!(
+            image.get_extended_xmp(),
+            Err(GCameraError::ExtendedXMPOffsetMismatch {
+                expected: 0,
+                found: 50
+            })
+        );
+    }
+
+    /// Test getting the ISO 21496-1 gain map metadata, skipping the version only segment.
+    #[test]
+    #[allow(clippy::float_cmp)] // The values are parsed from exact fractions
+    fn test_get_iso_gain_map_metadata() {
+        let version_only = [
+            "urn:iso:std:iso:ts:21496:-1\0".as_bytes(),
+            &[0x00, 0x00, 0x00, 0x00],
+        ]
+        .concat();
+        let mut full = version_only.clone();
+        full.push(0x08);
+        let values: [u32; 8] = [1, 0, 2, 0, 3, 1, 0, 0];
+        for value in values {
+            full.extend(value.to_be_bytes());
+        }
+        let image = JpegImage {
+            segments: vec![
+                JpegSegment::from_bytes(&[0xFF, 0xD8]).unwrap(),
+                JpegSegment::new(JpegMarker::APP2, &version_only).unwrap(),
+                JpegSegment::new(JpegMarker::APP2, &full).unwrap(),
+                JpegSegment::from_bytes(&[0xFF, 0xD9]).unwrap(),
+            ],
+        };
+
+        let metadata = image.get_iso_gain_map_metadata().unwrap();
+        assert_eq!(metadata.gain_map_max, [3.0; 3]);
+        assert_eq!(metadata.hdr_capacity_max, 2.0);
+
+        let version_only_image = JpegImage {
+            segments: vec![JpegSegment::new(JpegMarker::APP2, &version_only).unwrap()],
+        };
+        assert_eq!(
+            version_only_image.get_iso_gain_map_metadata(),
+            Err(GCameraError::NoGainMapMetadata)
+        );
+    }
+
+    /// Create a big endian MPF index, as written by `UltraHDR` encoders.
+    ///
+    /// # Arguments
+    /// * `entries`: The attribute, size and offset of each image.
+    ///
+    /// # Returns
+    /// The TIFF structure holding the index.
+    ///
+    /// # Panics
+    /// Will panic if there are too many entries for the index.
+    fn create_index(entries: &[(u32, u32, u32)]) -> Vec<u8> {
+        let count = u32::try_from(entries.len()).unwrap();
+        let mut data = vec![0x4D, 0x4D, 0x00, 0x2A, 0x00, 0x00, 0x00, 0x08, 0x00, 0x03];
+        // Version, number of images and the list of images.
+        data.extend([0xB0, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x04]);
+        data.extend("0100".as_bytes());
+        data.extend([0xB0, 0x01, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01]);
+        data.extend(count.to_be_bytes());
+        data.extend([0xB0, 0x02, 0x00, 0x07]);
+        let list_offset: u32 = 0x32;
+        data.extend((count * 16).to_be_bytes());
+        data.extend(list_offset.to_be_bytes());
+        data.extend([0x00; 4]);
+        for (attribute, size, offset) in entries {
+            data.extend(attribute.to_be_bytes());
+            data.extend(size.to_be_bytes());
+            data.extend(offset.to_be_bytes());
+            data.extend([0x00; 4]);
+        }
+        return data;
+    }
+
+    /// Test finding the MPF index and the position of its TIFF header.
+    #[test]
+    fn test_get_mpf_index() {
+        let data = [MPF_MARKER, &create_index(&[(0x2003_0000, 100, 0)])].concat();
+        let image = JpegImage {
+            segments: vec![
+                JpegSegment::from_bytes(&[0xFF, 0xD8]).unwrap(),
+                JpegSegment::new(JpegMarker::APP0, &[0x00]).unwrap(),
+                JpegSegment::new(JpegMarker::APP2, &data).unwrap(),
+                JpegSegment::from_bytes(&[0xFF, 0xD9]).unwrap(),
+            ],
+        };
+
+        let (position, index) = image.get_mpf_index().unwrap();
+        assert_eq!(position, 15);
+        assert_eq!(index.entries[0].size, 100);
+
+        let bytes = [
+            &[0xFF, 0xD8, 0xFF, 0xFF, 0xFF, 0xE2],
+            u16::try_from(data.len() + 2)
+                .unwrap()
+                .to_be_bytes()
+                .as_slice(),
+            &data,
+            &[0xFF, 0xD9],
+        ]
+        .concat();
+        let filled = JpegImage::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(filled.get_mpf_index().unwrap().0, 12);
+        assert_eq!(
+            JpegImage {
+                segments: vec![JpegSegment::from_bytes(&[0xFF, 0xD8]).unwrap()],
+            }
+            .get_mpf_index(),
+            Err(GCameraError::NoMpfIndex)
+        );
+    }
+
+    /// Test replacing the XMP data with data small enough for a single segment.
+    #[test]
+    fn test_set_xmp_small() {
+        let xmp_str = "<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about=''/></rdf:RDF></x:xmpmeta>";
+        let mut image = JpegImage {
+            segments: vec![
+                JpegSegment::from_bytes(&[0xFF, 0xD8]).unwrap(),
+                main_xmp_segment("5D41402ABC4B2A76B9719D911017C592"),
+                extended_xmp_segment("hello", 0, 5),
+                JpegSegment::from_bytes(&[0xFF, 0xD9]).unwrap(),
+            ],
+        };
+        image.set_xmp(xmp_str).unwrap();
+
+        assert_eq!(
+            image.segments,
+            vec![
+                JpegSegment::from_bytes(&[0xFF, 0xD8]).unwrap(),
+                create_xmp_segment(xmp_str).unwrap(),
+                JpegSegment::from_bytes(&[0xFF, 0xD9]).unwrap(),
+            ]
+        );
+    }
+
+    /// Test that a large motion photo keeps its resources in the main XMP.
+    #[test]
+    fn test_set_xmp_large_motion_photo() {
+        // The directory is the largest property, so it would be moved first
+        // if the resource properties were not kept in the main XMP.
+        let label = "L".repeat(3000);
+        let properties: String = (0..40)
+            .map(|index| return format!(" ex:p{index}='{}'", "P".repeat(1700)))
+            .collect();
+        let xmp_str = format!("<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:ex='http://example.com/' xmlns:GCamera='http://ns.google.com/photos/1.0/camera/' xmlns:Container='http://ns.google.com/photos/1.0/container/' xmlns:Item='http://ns.google.com/photos/1.0/container/item/' GCamera:MotionPhoto='1' GCamera:MotionPhotoVersion='1'{properties}><Container:Directory><rdf:Seq><rdf:li rdf:parseType='Resource'><Container:Item Item:Mime='image/jpeg' Item:Semantic='Primary' Item:Label='{label}'/></rdf:li><rdf:li rdf:parseType='Resource'><Container:Item Item:Mime='video/mp4' Item:Semantic='MotionPhoto' Item:Length='100' Item:Padding='0'/></rdf:li></rdf:Seq></Container:Directory></rdf:Description></rdf:RDF></x:xmpmeta>");
+        let mut image = JpegImage {
+            segments: vec![
+                JpegSegment::from_bytes(&[0xFF, 0xD8]).unwrap(),
+                JpegSegment::from_bytes(&[0xFF, 0xD9]).unwrap(),
+            ],
+        };
+        image.set_xmp(&xmp_str).unwrap();
+        let parsed = JpegImage::try_from(image.as_bytes().as_slice()).unwrap();
+        let main = parsed.get_xmp().unwrap();
+
+        parsed.get_extended_xmp().unwrap();
+        assert_eq!(
+            main.resources
+                .iter()
+                .map(|item| return item.semantic)
+                .collect::<Vec<SemanticType>>(),
+            vec![SemanticType::Primary, SemanticType::MotionPhoto]
+        );
+        assert_eq!(
+            main.get_str("http://ns.google.com/photos/1.0/camera/", "MotionPhoto"),
+            Some("1")
+        );
+    }
+
+    /// Test adding EXIF data to an image, and then replacing it.
+    #[test]
+    fn test_set_exif() {
+        let mut image = JpegImage {
+            segments: vec![
+                JpegSegment::from_bytes(&[0xFF, 0xD8]).unwrap(),
+                JpegSegment::new(JpegMarker::APP0, &[0x00]).unwrap(),
+                JpegSegment::from_bytes(&[0xFF, 0xD9]).unwrap(),
+            ],
+        };
+        let mut exif = ExifData::new(ByteOrder::LittleEndian);
+        exif.set_by_name("Orientation=6").unwrap();
+        image.set_exif(&exif).unwrap();
+
+        assert_eq!(image.segments.len(), 4);
+        assert!(image.segments[2].as_exif_data().is_some());
+        assert_eq!(image.get_exif(), Ok(exif.clone()));
+
+        exif.set_by_name("Copyright=Jane Doe").unwrap();
+        image.set_exif(&exif).unwrap();
+        assert_eq!(image.segments.len(), 4);
+        assert_eq!(image.get_exif(), Ok(exif));
+    }
+
+    /// Test reading, replacing and removing the thumbnails.
+    #[test]
+    fn test_thumbnails() {
+        let old = [0xFF, 0xD8, 0x01, 0xFF, 0xD9];
+        let new = [0xFF, 0xD8, 0x02, 0xFF, 0xD9];
+        let mut exif = ExifData::new(ByteOrder::LittleEndian);
+        exif.thumbnail_image = Some(old.to_vec());
+        exif.set(
+            IfdKind::Thumbnail,
+            TAG_IMAGE_WIDTH,
+            ExifValue::Long(vec![512]),
+        );
+        exif.set(
+            IfdKind::Thumbnail,
+            TAG_X_RESOLUTION,
+            ExifValue::Rational(vec![(300, 1)]),
+        );
+        let mut image = JpegImage {
+            segments: vec![
+                JpegSegment::from_bytes(&[0xFF, 0xD8]).unwrap(),
+                create_jfxx_segment(&old).unwrap(),
+                JpegSegment::from_bytes(&[0xFF, 0xD9]).unwrap(),
+            ],
+        };
+        image.set_exif(&exif).unwrap();
+
+        let sources = |thumbnails: Vec<Thumbnail>| {
+            return thumbnails
+                .into_iter()
+                .map(|thumbnail| return (thumbnail.source, thumbnail.data))
+                .collect::<Vec<(ThumbnailSource, Vec<u8>)>>();
+        };
+        assert_eq!(
+            sources(image.get_thumbnails().unwrap()),
+            vec![
+                (ThumbnailSource::Exif, old.to_vec()),
+                (ThumbnailSource::Jfxx, old.to_vec())
+            ]
+        );
+
+        image.set_thumbnail(&new).unwrap();
+        assert_eq!(
+            sources(image.get_thumbnails().unwrap()),
+            vec![
+                (ThumbnailSource::Exif, new.to_vec()),
+                (ThumbnailSource::Jfxx, new.to_vec())
+            ]
+        );
+        let new_exif = image.get_exif().unwrap();
+        assert_eq!(new_exif.get(IfdKind::Thumbnail, TAG_IMAGE_WIDTH), None);
+        assert_eq!(
+            new_exif.get(IfdKind::Thumbnail, TAG_X_RESOLUTION),
+            Some(&ExifValue::Rational(vec![(72, 1)]))
+        );
+        assert_eq!(
+            image.set_thumbnail(&[0x00]),
+            Err(GCameraError::InvalidThumbnail)
+        );
+
+        assert_eq!(image.remove_thumbnails(), Ok(2));
+        assert_eq!(image.get_thumbnails(), Ok(Vec::new()));
+        assert_eq!(image.segments.len(), 3);
+        assert_eq!(image.get_exif().unwrap().thumbnail, Vec::new());
+    }
+
+    /// Create a version 2 RGB display profile with a description.
+    ///
+    /// # Arguments
+    /// * `description`: The description of the profile.
+    ///
+    /// # Returns
+    /// The profile.
+    ///
+    /// # Panics
+    /// Will panic if the description is too long for the profile.
+    fn create_icc_profile(description: &str) -> Vec<u8> {
+        let mut tag = "desc\0\0\0\0".as_bytes().to_vec();
+        tag.extend(u32::try_from(description.len() + 1).unwrap().to_be_bytes());
+        tag.extend(description.as_bytes());
+        tag.push(0x00);
+
+        let tag_offset: u32 = 144;
+        let tag_size = u32::try_from(tag.len()).unwrap();
+        let mut data = (tag_offset + tag_size).to_be_bytes().to_vec();
+        data.extend("lcms".as_bytes());
+        data.extend([0x02, 0x10, 0x00, 0x00]);
+        data.extend("mntrRGB XYZ ".as_bytes());
+        data.resize(36, 0x00);
+        data.extend("acsp".as_bytes());
+        data.resize(128, 0x00);
+        data.extend([0x00, 0x00, 0x00, 0x01]);
+        data.extend("desc".as_bytes());
+        data.extend(tag_offset.to_be_bytes());
+        data.extend(tag_size.to_be_bytes());
+        data.extend(tag);
+        return data;
+    }
+
+    /// Test adding an ICC profile to an image, and then replacing it with a
+    /// profile that needs more than one segment.
+    #[test]
+    fn test_icc_profile() {
+        let mut image = JpegImage {
+            segments: vec![
+                JpegSegment::from_bytes(&[0xFF, 0xD8]).unwrap(),
+                JpegSegment::new(JpegMarker::APP1, &[0x00]).unwrap(),
+                JpegSegment::from_bytes(&[0xFF, 0xD9]).unwrap(),
+            ],
+        };
+        assert_eq!(image.get_icc_profile(), Err(GCameraError::NoIccProfile));
+
+        let small = create_icc_profile("Small");
+        image.set_icc_profile(&small).unwrap();
+        assert_eq!(image.segments.len(), 4);
+        assert!(image.segments[2].is_icc());
+        assert_eq!(image.get_icc_profile().unwrap().data, small);
+
+        let description = "L".repeat(70_000);
+        let large = create_icc_profile(&description);
+        image.set_icc_profile(&large).unwrap();
+        assert_eq!(image.segments.len(), 5);
+        let profile = image.get_icc_profile().unwrap();
+        assert_eq!(profile.data, large);
+        assert_eq!(profile.description, Some(description));
+
+        image.segments.remove(3);
+        assert_eq!(
+            image.get_icc_profile(),
+            Err(GCameraError::MissingIccChunk { sequence: 2 })
+        );
+        assert_eq!(
+            image.set_icc_profile(&[0x00; 4]),
+            Err(GCameraError::InvalidIccProfile)
+        );
+    }
+
+    /// Test reading the tables and estimating the quality of an image.
+    #[test]
+    fn test_tables() {
+        let mut huffman = vec![0x00, 0x00, 0x01];
+        huffman.extend([0x00; 14]);
+        huffman.push(0x05);
+        let mut image = JpegImage {
+            segments: vec![
+                JpegSegment::from_bytes(&[0xFF, 0xD8]).unwrap(),
+                JpegSegment::from_bytes(&[0xFF, 0xD9]).unwrap(),
+            ],
+        };
+        assert_eq!(
+            image.estimate_quality(),
+            Err(GCameraError::NoQuantizationTable)
+        );
+
+        // Tables of ones, as used for quality 100.
+        let luminance = [[0x00].as_slice(), &[0x01; 64]].concat();
+        let chrominance = [[0x01].as_slice(), &[0x01; 64]].concat();
+        image.segments.splice(
+            1..1,
+            [
+                JpegSegment::new(JpegMarker::DQT, &luminance).unwrap(),
+                JpegSegment::new(JpegMarker::DQT, &chrominance).unwrap(),
+                JpegSegment::new(JpegMarker::DHT, &huffman).unwrap(),
+            ],
+        );
+        assert_eq!(image.get_quantization_tables().unwrap().len(), 2);
+        assert_eq!(image.get_huffman_tables().unwrap()[0].symbols, vec![0x05]);
+        assert_eq!(image.estimate_quality(), Ok(100));
+    }
+
+    /// Test parsing an arithmetic-coded image with restart markers.
+    #[test]
+    fn test_arithmetic_image() {
+        let bytes = [
+            0xFF, 0xD8, // SOI
+            0xFF, 0xCC, 0x00, 0x04, 0x00, 0x11, // DAC
+            0xFF, 0xC9, 0x00, 0x0B, 0x08, 0x00, 0x10, 0x00, 0x10, // SOF9
+            0x01, 0x01, 0x11, 0x00, // Frame component
+            0xFF, 0xDD, 0x00, 0x04, 0x00, 0x01, // DRI
+            0xFF, 0xDA, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x3F, 0x00, // SOS
+            0x12, 0xFF, 0xD0, 0x34, 0xFF, 0xD1, 0x56, // Scan data
+            0xFF, 0xD9, // EOI
+        ];
+        let image = JpegImage::try_from(bytes.as_slice()).unwrap();
+
+        assert_eq!(
+            image
+                .segments
+                .iter()
+                .map(|segment| return segment.marker)
+                .collect::<Vec<JpegMarker>>(),
+            vec![
+                JpegMarker::SOI,
+                JpegMarker::DAC,
+                JpegMarker::SOF9,
+                JpegMarker::DRI,
+                JpegMarker::SOS,
+                JpegMarker::EOI
+            ]
+        );
+        assert_eq!(
+            image.get_frame_header().unwrap().process_name(),
+            "extended sequential, arithmetic"
+        );
+        assert_eq!(image.as_bytes(), bytes);
+    }
+
+    /// Test that scans whose restart markers do not match the restart
+    /// interval are parsed, and reported by the check.
+    #[test]
+    fn test_invalid_restart_markers() {
+        let scan = |dri: &[u8], data: &[u8]| {
+            return [
+                &[0xFF, 0xD8],
+                dri,
+                &[0xFF, 0xDA, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x3F, 0x00],
+                data,
+                &[0xFF, 0xD9],
+            ]
+            .concat();
+        };
+        let check = |bytes: Vec<u8>| {
+            let image = JpegImage::try_from(bytes.as_slice()).unwrap();
+            assert_eq!(image.as_bytes(), bytes);
+            return image.check_restart_markers();
+        };
+        let dri = [0xFF, 0xDD, 0x00, 0x04, 0x00, 0x10];
+        assert_eq!(
+            check(scan(&[], &[0x12, 0xFF, 0xD0, 0x34])),
+            Err(GCameraError::UnexpectedRestartMarker { offset: 13 })
+        );
+        assert_eq!(
+            check(scan(&dri, &[0x12, 0xFF, 0xD1, 0x34])),
+            Err(GCameraError::RestartMarkerOutOfOrder {
+                expected: 0,
+                found: 1,
+                offset: 19
+            })
+        );
+        assert_eq!(
+            check(scan(&[0xFF, 0xDD, 0x00, 0x03, 0x00], &[0x12])),
+            Err(GCameraError::InvalidRestartInterval)
+        );
+        assert_eq!(check(scan(&dri, &[0x12, 0xFF, 0xD0, 0x34])), Ok(()));
+
+        let stuffed = scan(&dri, &[0x12, 0xFF, 0x00, 0xFF, 0xD0]);
+        let image = JpegImage::try_from(stuffed.as_slice()).unwrap();
+        assert_eq!(image.as_bytes(), stuffed);
+    }
+
+    /// Test that images which end early give the offset of the segment
+    /// being parsed.
+    #[test]
+    fn test_truncated_image() {
+        let bytes = [
+            0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x01, 0x02, 0xFF, 0xDA, 0x00, 0x03, 0x01, 0x12,
+            0x34, 0xFF, 0xD9,
+        ];
+        let image = JpegImage::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(image.as_bytes(), bytes);
+
+        let expected = [
+            (
+                2,
+                GCameraError::TruncatedData {
+                    structure: "segment marker",
+                    offset: 2,
+                },
+            ),
+            (
+                5,
+                GCameraError::TruncatedData {
+                    structure: "segment length",
+                    offset: 2,
+                },
+            ),
+            (
+                7,
+                GCameraError::TruncatedData {
+                    structure: "segment",
+                    offset: 2,
+                },
+            ),
+            (
+                15,
+                GCameraError::TruncatedData {
+                    structure: "scan",
+                    offset: 13,
+                },
+            ),
+        ];
+        assert_eq!(
+            JpegImage::try_from(&bytes[..1]),
+            Err(GCameraError::InvalidJpegMagic)
+        );
+        for (length, error) in expected {
+            assert_eq!(JpegImage::try_from(&bytes[..length]), Err(error));
+        }
+    }
+
+    /// Test that segments that are not valid give their offset in the image.
+    #[test]
+    fn test_invalid_segment_offsets() {
+        let app0 = [0xFF, 0xE0, 0x00, 0x04, 0x01, 0x02];
+        let image = |segment: &[u8]| {
+            let bytes = [&[0xFF, 0xD8], app0.as_slice(), segment, &[0xFF, 0xD9]].concat();
+            return JpegImage::try_from(bytes.as_slice());
+        };
+        assert_eq!(
+            image(&[0x00, 0xFE, 0x00, 0x02]),
+            Err(GCameraError::MissingMarkerPrefix {
+                byte: 0x00,
+                offset: 8
+            })
+        );
+        assert_eq!(
+            image(&[0xFF, 0x00]),
+            Err(GCameraError::UnknownJpegMarker {
+                marker_byte: 0x00,
+                offset: 8
+            })
+        );
+        assert_eq!(
+            image(&[0xFF, 0xFF, 0xC0, 0x00, 0x03, 0x08])
+                .unwrap()
+                .get_frame_header(),
+            Err(GCameraError::InvalidFrameHeader { offset: 8 })
+        );
+        assert_eq!(
+            image(&[0xFF, 0xDA, 0x00, 0x03, 0x00, 0x12])
+                .unwrap()
+                .scans(),
+            Err(GCameraError::InvalidScanHeader { offset: 8 })
+        );
+    }
+
+    /// Create a scan header.
+    ///
+    /// # Arguments
+    /// * `selectors`: The identifiers of the components in the scan.
+    /// * `spectral_selection`: The first and last coefficient of the scan.
+    /// * `approximation`: The high and low bit positions, in one byte.
+    ///
+    /// # Returns
+    /// The scan header, using table 0 for the first component and table 1
+    /// for the others.
+    fn create_scan_header(
+        selectors: &[u8],
+        spectral_selection: (u8, u8),
+        approximation: u8,
+    ) -> Vec<u8> {
+        let mut data = vec![u8::try_from(selectors.len()).unwrap_or(0)];
+        for (index, selector) in selectors.iter().enumerate() {
+            data.extend([*selector, if index == 0 { 0x00 } else { 0x11 }]);
+        }
+        data.extend([spectral_selection.0, spectral_selection.1, approximation]);
+        return data;
+    }
+
+    /// Test parsing, editing and writing a progressive image with several
+    /// scans separated by DHT segments.
+    #[test]
+    fn test_progressive_image() {
+        let scan = |header: Vec<u8>, data: &[u8]| {
+            let length = u16::try_from(header.len() + 2).unwrap();
+            return [
+                vec![0xFF, 0xDA],
+                length.to_be_bytes().to_vec(),
+                header,
+                data.to_vec(),
+            ]
+            .concat();
+        };
+        let huffman = [0xFF, 0xC4, 0x00, 0x14, 0x00, 0x01]
+            .into_iter()
+            .chain([0x00; 15])
+            .chain([0x00])
+            .collect::<Vec<u8>>();
+        let bytes = [
+            vec![
+                0xFF, 0xD8, 0xFF, 0xC2, 0x00, 0x11, 0x08, 0x00, 0x10, 0x00, 0x10, 0x03, 0x01, 0x22,
+                0x00, 0x02, 0x11, 0x01, 0x03, 0x11, 0x01,
+            ],
+            huffman.clone(),
+            scan(
+                create_scan_header(&[1, 2, 3], (0, 0), 0x01),
+                &[0x12, 0xFF, 0x00],
+            ),
+            huffman.clone(),
+            scan(create_scan_header(&[1], (1, 5), 0x02), &[0x34]),
+            huffman,
+            scan(create_scan_header(&[1], (1, 63), 0x21), &[0x56, 0xFF, 0xFF]),
+            scan(create_scan_header(&[2], (1, 63), 0x00), &[0x78]),
+            vec![0xFF, 0xD9],
+        ]
+        .concat();
+        let mut image = JpegImage::try_from(bytes.as_slice()).unwrap();
+
+        let scans = image.scans().unwrap();
+        assert_eq!(scans.len(), 4);
+        assert_eq!(scans[0].components.len(), 3);
+        assert_eq!((scans[1].spectral_start, scans[1].spectral_end), (1, 5));
+        assert!(scans[2].is_refinement());
+        assert_eq!(image.as_bytes(), bytes);
+
+        image
+            .set_xmp("<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about=''/></rdf:RDF></x:xmpmeta>")
+            .unwrap();
+        let rewritten = JpegImage::try_from(image.as_bytes().as_slice()).unwrap();
+        assert_eq!(rewritten.scans(), Ok(scans));
+        assert_eq!(rewritten.segments.len(), image.segments.len());
+        assert!(rewritten.segments[1].has_xmp());
+
+        let unknown_component = [
+            &bytes[..bytes.len() - 2],
+            &scan(create_scan_header(&[4], (1, 63), 0x00), &[0x00]),
+            &[0xFF, 0xD9],
+        ]
+        .concat();
+        assert_eq!(
+            JpegImage::try_from(unknown_component.as_slice())
+                .unwrap()
+                .scans(),
+            Err(GCameraError::UnknownScanComponent { selector: 4 })
+        );
+    }
+
+    /// Test adding XMP data to an image without any, that needs to be split
+    /// into Extended XMP.
+    #[test]
+    fn test_set_xmp_large() {
+        let depth_data = "A".repeat(100_000);
+        let xmp_str = format!("<x:xmpmeta xmlns:x='adobe:ns:meta/'><rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description rdf:about='' xmlns:GDepth='http://ns.google.com/photos/1.0/depthmap/' GDepth:Format='RangeInverse' GDepth:Data='{depth_data}'/></rdf:RDF></x:xmpmeta>");
+        let mut image = JpegImage {
+            segments: vec![
+                JpegSegment::from_bytes(&[0xFF, 0xD8]).unwrap(),
+                JpegSegment::new(JpegMarker::APP0, &[0x00]).unwrap(),
+                JpegSegment::from_bytes(&[0xFF, 0xD9]).unwrap(),
+            ],
+        };
+        image.set_xmp(&xmp_str).unwrap();
+
+        assert_eq!(image.segments.len(), 6);
+        assert_eq!(image.segments[1].marker, JpegMarker::APP0);
+        assert_eq!(
+            image
+                .get_xmp()
+                .unwrap()
+                .get_str("http://ns.google.com/photos/1.0/depthmap/", "Format"),
+            Some("RangeInverse")
+        );
+
+        let document = roxmltree::Document::parse(
+            &image
+                .segments
+                .iter()
+                .find_map(|segment| return segment.as_xmp_str())
+                .unwrap()
+                .unwrap(),
+        )
+        .unwrap()
+        .descendants()
+        .find(|n| return n.has_tag_name("Description"))
+        .map(|n| {
+            return (
+                n.attribute(("http://ns.google.com/photos/1.0/depthmap/", "Format"))
+                    .map(String::from),
+                n.attribute(("http://ns.google.com/photos/1.0/depthmap/", "Data"))
+                    .map(String::from),
+            );
+        });
+        assert_eq!(document, Some((Some(String::from("RangeInverse")), None)));
+
+        let extended = image.get_extended_xmp().unwrap();
+        assert_eq!(
+            extended.get_str("http://ns.google.com/photos/1.0/depthmap/", "Data"),
+            Some(depth_data.as_str())
+        );
+    }
 }
@@ -8,12 +8,16 @@
 use crate::errors::GCameraError;
 
 /// Enum of the different JPEG segment markers.
+///
+/// This covers every marker defined by ITU T.81. The reserved markers from
+/// 0x02 to 0xBF keep their byte value.
 #[allow(clippy::upper_case_acronyms)] // Allowing because names are upper for JPEG segments
 #[allow(clippy::missing_docs_in_private_items)] // Allowing since documenting this would be a pain
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 #[repr(u8)]
 pub enum JpegMarker {
     TEM = 0x01,
+    Reserved(u8) = 0x02,
     SOF0 = 0xC0,
     SOF1 = 0xC1,
     SOF2 = 0xC2,
@@ -22,6 +26,22 @@ pub enum JpegMarker {
     SOF5 = 0xC5,
     SOF6 = 0xC6,
     SOF7 = 0xC7,
+    JPG = 0xC8,
+    SOF9 = 0xC9,
+    SOF10 = 0xCA,
+    SOF11 = 0xCB,
+    DAC = 0xCC,
+    SOF13 = 0xCD,
+    SOF14 = 0xCE,
+    SOF15 = 0xCF,
+    RST0 = 0xD0,
+    RST1 = 0xD1,
+    RST2 = 0xD2,
+    RST3 = 0xD3,
+    RST4 = 0xD4,
+    RST5 = 0xD5,
+    RST6 = 0xD6,
+    RST7 = 0xD7,
     SOI = 0xD8,
     EOI = 0xD9,
     SOS = 0xDA,
@@ -29,6 +49,7 @@ pub enum JpegMarker {
     DNL = 0xDC,
     DRI = 0xDD,
     DHP = 0xDE,
+    EXP = 0xDF,
     APP0 = 0xE0,
     APP1 = 0xE1,
     APP2 = 0xE2,
@@ -45,9 +66,80 @@ pub enum JpegMarker {
     APP13 = 0xED,
     APP14 = 0xEE,
     APP15 = 0xEF,
+    JPG0 = 0xF0,
+    JPG1 = 0xF1,
+    JPG2 = 0xF2,
+    JPG3 = 0xF3,
+    JPG4 = 0xF4,
+    JPG5 = 0xF5,
+    JPG6 = 0xF6,
+    JPG7 = 0xF7,
+    JPG8 = 0xF8,
+    JPG9 = 0xF9,
+    JPG10 = 0xFA,
+    JPG11 = 0xFB,
+    JPG12 = 0xFC,
+    JPG13 = 0xFD,
     COM = 0xFE,
 }
 
+impl JpegMarker {
+    /// Check if the marker is followed by a length and data.
+    ///
+    /// SOI, EOI, TEM and the restart markers are stand-alone markers. Every
+    /// other marker starts a segment with a length.
+    ///
+    /// # Returns
+    /// Whether the marker is followed by a length.
+    pub const fn has_length(self) -> bool {
+        return !matches!(self, Self::TEM | Self::SOI | Self::EOI) && !self.is_restart();
+    }
+
+    /// Check if the marker is one of the restart markers.
+    ///
+    /// The restart markers only occur in the entropy-coded data of a scan.
+    ///
+    /// # Returns
+    /// Whether the marker is RST0 to RST7.
+    pub const fn is_restart(self) -> bool {
+        return matches!(
+            self,
+            Self::RST0
+                | Self::RST1
+                | Self::RST2
+                | Self::RST3
+                | Self::RST4
+                | Self::RST5
+                | Self::RST6
+                | Self::RST7
+        );
+    }
+
+    /// Check if the marker starts a frame.
+    ///
+    /// # Returns
+    /// Whether the marker is one of the SOF markers, which hold a frame
+    /// header.
+    pub const fn is_start_of_frame(self) -> bool {
+        return matches!(
+            self,
+            Self::SOF0
+                | Self::SOF1
+                | Self::SOF2
+                | Self::SOF3
+                | Self::SOF5
+                | Self::SOF6
+                | Self::SOF7
+                | Self::SOF9
+                | Self::SOF10
+                | Self::SOF11
+                | Self::SOF13
+                | Self::SOF14
+                | Self::SOF15
+        );
+    }
+}
+
 /// Conversion of a `JpegMarker` into a u8
 impl From<JpegMarker> for u8 {
     /// Convert `JpegMarker` to a u8
@@ -58,7 +150,73 @@ impl From<JpegMarker> for u8 {
     /// # Returns
     /// Integer form of the marker.
     fn from(value: JpegMarker) -> Self {
-        return value as u8;
+        return match value {
+            JpegMarker::TEM => 0x01,
+            JpegMarker::Reserved(byte) => byte,
+            JpegMarker::SOF0 => 0xC0,
+            JpegMarker::SOF1 => 0xC1,
+            JpegMarker::SOF2 => 0xC2,
+            JpegMarker::SOF3 => 0xC3,
+            JpegMarker::DHT => 0xC4,
+            JpegMarker::SOF5 => 0xC5,
+            JpegMarker::SOF6 => 0xC6,
+            JpegMarker::SOF7 => 0xC7,
+            JpegMarker::JPG => 0xC8,
+            JpegMarker::SOF9 => 0xC9,
+            JpegMarker::SOF10 => 0xCA,
+            JpegMarker::SOF11 => 0xCB,
+            JpegMarker::DAC => 0xCC,
+            JpegMarker::SOF13 => 0xCD,
+            JpegMarker::SOF14 => 0xCE,
+            JpegMarker::SOF15 => 0xCF,
+            JpegMarker::RST0 => 0xD0,
+            JpegMarker::RST1 => 0xD1,
+            JpegMarker::RST2 => 0xD2,
+            JpegMarker::RST3 => 0xD3,
+            JpegMarker::RST4 => 0xD4,
+            JpegMarker::RST5 => 0xD5,
+            JpegMarker::RST6 => 0xD6,
+            JpegMarker::RST7 => 0xD7,
+            JpegMarker::SOI => 0xD8,
+            JpegMarker::EOI => 0xD9,
+            JpegMarker::SOS => 0xDA,
+            JpegMarker::DQT => 0xDB,
+            JpegMarker::DNL => 0xDC,
+            JpegMarker::DRI => 0xDD,
+            JpegMarker::DHP => 0xDE,
+            JpegMarker::EXP => 0xDF,
+            JpegMarker::APP0 => 0xE0,
+            JpegMarker::APP1 => 0xE1,
+            JpegMarker::APP2 => 0xE2,
+            JpegMarker::APP3 => 0xE3,
+            JpegMarker::APP4 => 0xE4,
+            JpegMarker::APP5 => 0xE5,
+            JpegMarker::APP6 => 0xE6,
+            JpegMarker::APP7 => 0xE7,
+            JpegMarker::APP8 => 0xE8,
+            JpegMarker::APP9 => 0xE9,
+            JpegMarker::APP10 => 0xEA,
+            JpegMarker::APP11 => 0xEB,
+            JpegMarker::APP12 => 0xEC,
+            JpegMarker::APP13 => 0xED,
+            JpegMarker::APP14 => 0xEE,
+            JpegMarker::APP15 => 0xEF,
+            JpegMarker::JPG0 => 0xF0,
+            JpegMarker::JPG1 => 0xF1,
+            JpegMarker::JPG2 => 0xF2,
+            JpegMarker::JPG3 => 0xF3,
+            JpegMarker::JPG4 => 0xF4,
+            JpegMarker::JPG5 => 0xF5,
+            JpegMarker::JPG6 => 0xF6,
+            JpegMarker::JPG7 => 0xF7,
+            JpegMarker::JPG8 => 0xF8,
+            JpegMarker::JPG9 => 0xF9,
+            JpegMarker::JPG10 => 0xFA,
+            JpegMarker::JPG11 => 0xFB,
+            JpegMarker::JPG12 => 0xFC,
+            JpegMarker::JPG13 => 0xFD,
+            JpegMarker::COM => 0xFE,
+        };
     }
 }
 
@@ -73,9 +231,14 @@ impl TryFrom<u8> for JpegMarker {
     ///
     /// # Resturns
     /// Result of creating the instance, or an error message
+    ///
+    /// # Errors
+    /// Will error if the byte is not a known marker. The error gives an
+    /// offset of 0, since the position of the marker is not known here.
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         return match value {
             0x01 => Ok(Self::TEM),
+            0x02..=0xBF => Ok(Self::Reserved(value)),
             0xC0 => Ok(Self::SOF0),
             0xC1 => Ok(Self::SOF1),
             0xC2 => Ok(Self::SOF2),
@@ -84,6 +247,22 @@ impl TryFrom<u8> for JpegMarker {
             0xC5 => Ok(Self::SOF5),
             0xC6 => Ok(Self::SOF6),
             0xC7 => Ok(Self::SOF7),
+            0xC8 => Ok(Self::JPG),
+            0xC9 => Ok(Self::SOF9),
+            0xCA => Ok(Self::SOF10),
+            0xCB => Ok(Self::SOF11),
+            0xCC => Ok(Self::DAC),
+            0xCD => Ok(Self::SOF13),
+            0xCE => Ok(Self::SOF14),
+            0xCF => Ok(Self::SOF15),
+            0xD0 => Ok(Self::RST0),
+            0xD1 => Ok(Self::RST1),
+            0xD2 => Ok(Self::RST2),
+            0xD3 => Ok(Self::RST3),
+            0xD4 => Ok(Self::RST4),
+            0xD5 => Ok(Self::RST5),
+            0xD6 => Ok(Self::RST6),
+            0xD7 => Ok(Self::RST7),
             0xD8 => Ok(Self::SOI),
             0xD9 => Ok(Self::EOI),
             0xDA => Ok(Self::SOS),
@@ -91,6 +270,7 @@ impl TryFrom<u8> for JpegMarker {
             0xDC => Ok(Self::DNL),
             0xDD => Ok(Self::DRI),
             0xDE => Ok(Self::DHP),
+            0xDF => Ok(Self::EXP),
             0xE0 => Ok(Self::APP0),
             0xE1 => Ok(Self::APP1),
             0xE2 => Ok(Self::APP2),
@@ -107,8 +287,25 @@ impl TryFrom<u8> for JpegMarker {
             0xED => Ok(Self::APP13),
             0xEE => Ok(Self::APP14),
             0xEF => Ok(Self::APP15),
+            0xF0 => Ok(Self::JPG0),
+            0xF1 => Ok(Self::JPG1),
+            0xF2 => Ok(Self::JPG2),
+            0xF3 => Ok(Self::JPG3),
+            0xF4 => Ok(Self::JPG4),
+            0xF5 => Ok(Self::JPG5),
+            0xF6 => Ok(Self::JPG6),
+            0xF7 => Ok(Self::JPG7),
+            0xF8 => Ok(Self::JPG8),
+            0xF9 => Ok(Self::JPG9),
+            0xFA => Ok(Self::JPG10),
+            0xFB => Ok(Self::JPG11),
+            0xFC => Ok(Self::JPG12),
+            0xFD => Ok(Self::JPG13),
             0xFE => Ok(Self::COM),
-            _ => Err(GCameraError::UnknownJpegMarker { marker_byte: value }),
+            _ => Err(GCameraError::UnknownJpegMarker {
+                marker_byte: value,
+                offset: 0,
+            }),
         };
     }
 }
@@ -122,6 +319,9 @@ mod tests {
     fn test_to_from_u8_success() {
         let test_cases = vec![
             (0x01, JpegMarker::TEM),
+            (0x02, JpegMarker::Reserved(0x02)),
+            (0x4A, JpegMarker::Reserved(0x4A)),
+            (0xBF, JpegMarker::Reserved(0xBF)),
             (0xC0, JpegMarker::SOF0),
             (0xC1, JpegMarker::SOF1),
             (0xC2, JpegMarker::SOF2),
@@ -130,6 +330,22 @@ mod tests {
             (0xC5, JpegMarker::SOF5),
             (0xC6, JpegMarker::SOF6),
             (0xC7, JpegMarker::SOF7),
+            (0xC8, JpegMarker::JPG),
+            (0xC9, JpegMarker::SOF9),
+            (0xCA, JpegMarker::SOF10),
+            (0xCB, JpegMarker::SOF11),
+            (0xCC, JpegMarker::DAC),
+            (0xCD, JpegMarker::SOF13),
+            (0xCE, JpegMarker::SOF14),
+            (0xCF, JpegMarker::SOF15),
+            (0xD0, JpegMarker::RST0),
+            (0xD1, JpegMarker::RST1),
+            (0xD2, JpegMarker::RST2),
+            (0xD3, JpegMarker::RST3),
+            (0xD4, JpegMarker::RST4),
+            (0xD5, JpegMarker::RST5),
+            (0xD6, JpegMarker::RST6),
+            (0xD7, JpegMarker::RST7),
             (0xD8, JpegMarker::SOI),
             (0xD9, JpegMarker::EOI),
             (0xDA, JpegMarker::SOS),
@@ -137,6 +353,7 @@ mod tests {
             (0xDC, JpegMarker::DNL),
             (0xDD, JpegMarker::DRI),
             (0xDE, JpegMarker::DHP),
+            (0xDF, JpegMarker::EXP),
             (0xE0, JpegMarker::APP0),
             (0xE1, JpegMarker::APP1),
             (0xE2, JpegMarker::APP2),
@@ -153,6 +370,20 @@ mod tests {
             (0xED, JpegMarker::APP13),
             (0xEE, JpegMarker::APP14),
             (0xEF, JpegMarker::APP15),
+            (0xF0, JpegMarker::JPG0),
+            (0xF1, JpegMarker::JPG1),
+            (0xF2, JpegMarker::JPG2),
+            (0xF3, JpegMarker::JPG3),
+            (0xF4, JpegMarker::JPG4),
+            (0xF5, JpegMarker::JPG5),
+            (0xF6, JpegMarker::JPG6),
+            (0xF7, JpegMarker::JPG7),
+            (0xF8, JpegMarker::JPG8),
+            (0xF9, JpegMarker::JPG9),
+            (0xFA, JpegMarker::JPG10),
+            (0xFB, JpegMarker::JPG11),
+            (0xFC, JpegMarker::JPG12),
+            (0xFD, JpegMarker::JPG13),
             (0xFE, JpegMarker::COM),
         ];
         for (byte, marker) in test_cases {
@@ -164,9 +395,45 @@ mod tests {
     /// Test getting an error for invalid byte input
     #[test]
     fn test_invalid_from_u8() {
-        assert_eq!(
-            JpegMarker::try_from(0xFF),
-            Err(GCameraError::UnknownJpegMarker { marker_byte: 0xFF })
-        );
+        for byte in [0x00, 0xFF] {
+            assert_eq!(
+                JpegMarker::try_from(byte),
+                Err(GCameraError::UnknownJpegMarker {
+                    marker_byte: byte,
+                    offset: 0
+                })
+            );
+        }
+    }
+
+    /// Test which markers are followed by a length.
+    #[test]
+    fn test_has_length() {
+        let stand_alone = [
+            JpegMarker::TEM,
+            JpegMarker::SOI,
+            JpegMarker::EOI,
+            JpegMarker::RST0,
+            JpegMarker::RST7,
+        ];
+        for marker in stand_alone {
+            assert!(!marker.has_length());
+        }
+        let with_length = [
+            JpegMarker::SOF9,
+            JpegMarker::DAC,
+            JpegMarker::JPG,
+            JpegMarker::EXP,
+            JpegMarker::JPG13,
+            JpegMarker::SOS,
+            JpegMarker::COM,
+            JpegMarker::Reserved(0x02),
+        ];
+        for marker in with_length {
+            assert!(marker.has_length());
+        }
+        assert!(JpegMarker::RST3.is_restart());
+        assert!(JpegMarker::SOF15.is_start_of_frame());
+        assert!(!JpegMarker::DHT.is_start_of_frame());
     }
 }
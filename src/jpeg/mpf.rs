@@ -0,0 +1,305 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Logic for reading the Multi-Picture Format (MPF) index.
+//!
+//! The MPF index is stored in an APP2 segment of the primary image, as a
+//! TIFF structure. Its `MPEntry` tag lists every image in the file, with
+//! the offset of each secondary image measured from the start of the TIFF
+//! header. The first entry is the primary image, with an offset of 0.
+use crate::errors::GCameraError;
+use crate::jpeg::tiff::{parse_header, parse_ifd, ByteOrder};
+
+/// String that occurs at the start of the MPF APP2 segment.
+pub const MPF_MARKER: &[u8] = "MPF\0".as_bytes();
+
+/// Tag of the entry listing the images in the file.
+const MP_ENTRY_TAG: u16 = 0xB002;
+
+/// Size of a single image in the `MPEntry` list, in bytes.
+const MP_ENTRY_SIZE: usize = 16;
+
+/// Mask for the image type bits of the individual image attribute.
+const IMAGE_TYPE_MASK: u32 = 0x00FF_FFFF;
+
+/// Enumeration of the types of images in the MPF index.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MpImageType {
+    /// The primary image of a baseline MP file.
+    BaselinePrimary,
+
+    /// A large VGA sized thumbnail.
+    LargeThumbnailVga,
+
+    /// A large full HD sized thumbnail.
+    LargeThumbnailFullHd,
+
+    /// A panorama image.
+    Panorama,
+
+    /// An image of a stereoscopic pair.
+    Disparity,
+
+    /// An image of a multi-angle set.
+    MultiAngle,
+
+    /// An image with no type, such as an `UltraHDR` gain map.
+    Undefined,
+
+    /// Any other type code.
+    Other(u32),
+}
+
+impl From<u32> for MpImageType {
+    /// Get the image type from the individual image attribute.
+    ///
+    /// # Arguments
+    /// * `attribute`: The individual image attribute of the entry.
+    ///
+    /// # Returns
+    /// The type of the image.
+    fn from(attribute: u32) -> Self {
+        return match attribute & IMAGE_TYPE_MASK {
+            0x03_0000 => Self::BaselinePrimary,
+            0x01_0001 => Self::LargeThumbnailVga,
+            0x01_0002 => Self::LargeThumbnailFullHd,
+            0x02_0001 => Self::Panorama,
+            0x02_0002 => Self::Disparity,
+            0x02_0003 => Self::MultiAngle,
+            0x00_0000 => Self::Undefined,
+            other => Self::Other(other),
+        };
+    }
+}
+
+/// A single image listed in the MPF index.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MpEntry {
+    /// The individual image attribute, holding flags, format and type.
+    pub attribute: u32,
+
+    /// The size of the image, in bytes.
+    pub size: u32,
+
+    /// The offset of the image from the start of the TIFF header, or 0 for
+    /// the primary image.
+    pub offset: u32,
+
+    /// The entry numbers of the dependent images.
+    pub dependent_images: [u16; 2],
+}
+
+impl MpEntry {
+    /// Get the type of the image.
+    ///
+    /// # Returns
+    /// The type of the image, from its attribute.
+    pub fn image_type(&self) -> MpImageType {
+        return MpImageType::from(self.attribute);
+    }
+}
+
+/// The MPF index from the APP2 segment of the primary image.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MpIndex {
+    /// The byte order of the TIFF structure.
+    pub byte_order: ByteOrder,
+
+    /// The images listed in the index.
+    pub entries: Vec<MpEntry>,
+
+    /// The offset of the `MPEntry` list from the start of the TIFF header.
+    entries_offset: usize,
+}
+
+impl MpIndex {
+    /// Overwrite the size and offset of an entry in the stored index.
+    ///
+    /// # Arguments
+    /// * `data`: The TIFF structure the index was read from.
+    /// * `index`: The number of the entry to update.
+    /// * `size`: The new size of the image.
+    /// * `offset`: The new offset of the image.
+    ///
+    /// # Errors
+    /// Will error if the entry is not within the data.
+    pub fn write_entry(
+        &self,
+        data: &mut [u8],
+        index: usize,
+        size: u32,
+        offset: u32,
+    ) -> Result<(), GCameraError> {
+        let entry_offset = index
+            .checked_mul(MP_ENTRY_SIZE)
+            .and_then(|relative| return relative.checked_add(self.entries_offset))
+            .ok_or(GCameraError::InvalidMpfIndex)?;
+        self.byte_order
+            .write_u32(data, entry_offset.saturating_add(4), size)?;
+        return self
+            .byte_order
+            .write_u32(data, entry_offset.saturating_add(8), offset);
+    }
+
+    /// Get a description of the images in the index.
+    ///
+    /// # Returns
+    /// String with one line for each image.
+    pub fn info_str(&self) -> String {
+        return self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                return format!(
+                    "MPF image {index}:             type {:?}, size {}, offset {}",
+                    entry.image_type(),
+                    entry.size,
+                    entry.offset
+                );
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+    }
+}
+
+impl TryFrom<&[u8]> for MpIndex {
+    type Error = GCameraError;
+
+    /// Parse the MPF index.
+    ///
+    /// # Arguments
+    /// * `data`: The TIFF structure, after the `MPF` marker of the segment.
+    ///
+    /// # Returns
+    /// The parsed index.
+    ///
+    /// # Errors
+    /// Will error if the TIFF structure is not valid, or it does not hold a
+    /// valid list of images.
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let (byte_order, ifd_offset) = parse_header(data)?;
+        let ifd = parse_ifd(data, byte_order, ifd_offset)?;
+        let list = ifd.get(MP_ENTRY_TAG).ok_or(GCameraError::InvalidMpfIndex)?;
+        if list.value.is_empty() || list.value.len() % MP_ENTRY_SIZE != 0 {
+            return Err(GCameraError::InvalidMpfIndex);
+        }
+
+        let entries = list
+            .value
+            .chunks_exact(MP_ENTRY_SIZE)
+            .map(|entry| {
+                return Ok(MpEntry {
+                    attribute: byte_order.read_u32(entry, 0)?,
+                    size: byte_order.read_u32(entry, 4)?,
+                    offset: byte_order.read_u32(entry, 8)?,
+                    dependent_images: [
+                        byte_order.read_u16(entry, 12)?,
+                        byte_order.read_u16(entry, 14)?,
+                    ],
+                });
+            })
+            .collect::<Result<Vec<MpEntry>, GCameraError>>()?;
+
+        return Ok(Self {
+            byte_order,
+            entries,
+            entries_offset: list.value_offset,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create a big endian MPF index, as written by `UltraHDR` encoders.
+    ///
+    /// # Arguments
+    /// * `entries`: The attribute, size and offset of each image.
+    ///
+    /// # Returns
+    /// The TIFF structure holding the index.
+    ///
+    /// # Panics
+    /// Will panic if there are too many entries for the index.
+    fn create_index(entries: &[(u32, u32, u32)]) -> Vec<u8> {
+        let count = u32::try_from(entries.len()).unwrap();
+        let mut data = vec![0x4D, 0x4D, 0x00, 0x2A, 0x00, 0x00, 0x00, 0x08, 0x00, 0x03];
+        // Version, number of images and the list of images.
+        data.extend([0xB0, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x04]);
+        data.extend("0100".as_bytes());
+        data.extend([0xB0, 0x01, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01]);
+        data.extend(count.to_be_bytes());
+        data.extend([0xB0, 0x02, 0x00, 0x07]);
+        let list_offset: u32 = 0x32;
+        data.extend((count * 16).to_be_bytes());
+        data.extend(list_offset.to_be_bytes());
+        data.extend([0x00; 4]);
+        for (attribute, size, offset) in entries {
+            data.extend(attribute.to_be_bytes());
+            data.extend(size.to_be_bytes());
+            data.extend(offset.to_be_bytes());
+            data.extend([0x00; 4]);
+        }
+        return data;
+    }
+
+    /// Test parsing an index with a primary image and a gain map.
+    #[test]
+    fn test_parse_index() {
+        let data = create_index(&[(0x2003_0000, 1000, 0), (0x0000_0000, 200, 950)]);
+        let index = MpIndex::try_from(data.as_slice()).unwrap();
+
+        assert_eq!(index.byte_order, ByteOrder::BigEndian);
+        assert_eq!(
+            index.entries,
+            vec![
+                MpEntry {
+                    attribute: 0x2003_0000,
+                    size: 1000,
+                    offset: 0,
+                    dependent_images: [0, 0],
+                },
+                MpEntry {
+                    attribute: 0,
+                    size: 200,
+                    offset: 950,
+                    dependent_images: [0, 0],
+                },
+            ]
+        );
+        assert_eq!(index.entries[0].image_type(), MpImageType::BaselinePrimary);
+        assert_eq!(index.entries[1].image_type(), MpImageType::Undefined);
+    }
+
+    /// Test updating the size and offset of an image.
+    #[test]
+    fn test_write_entry() {
+        let mut data = create_index(&[(0x2003_0000, 1000, 0), (0x0000_0000, 200, 950)]);
+        let index = MpIndex::try_from(data.as_slice()).unwrap();
+        index.write_entry(&mut data, 1, 300, 1200).unwrap();
+
+        let updated = MpIndex::try_from(data.as_slice()).unwrap();
+        assert_eq!(updated.entries[1].size, 300);
+        assert_eq!(updated.entries[1].offset, 1200);
+        assert_eq!(
+            index.write_entry(&mut data, 2, 300, 1200),
+            Err(GCameraError::InvalidTiffOffset { offset: 0x56 })
+        );
+    }
+
+    /// Test parsing an index without a list of images.
+    #[test]
+    fn test_missing_entries() {
+        let data = [
+            0x49, 0x49, 0x2A, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(
+            MpIndex::try_from(data.as_slice()),
+            Err(GCameraError::InvalidMpfIndex)
+        );
+    }
+}
@@ -0,0 +1,205 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Logic for parsing the scan headers of a JPEG image.
+//!
+//! Each SOS segment starts with a scan header, followed by the
+//! entropy-coded data of the scan. A baseline image has a single scan
+//! holding every component, while a progressive image has many scans, each
+//! holding part of the coefficients of one or more components. The scans
+//! of a progressive image are often separated by DHT segments.
+use crate::errors::GCameraError;
+
+/// A single component of a scan, as listed in the scan header.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ScanComponent {
+    /// The identifier of the component in the frame header.
+    pub selector: u8,
+
+    /// The Huffman table used for the DC coefficients.
+    pub dc_table: u8,
+
+    /// The Huffman table used for the AC coefficients.
+    pub ac_table: u8,
+}
+
+/// The header of a single scan.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ScanHeader {
+    /// The components in the scan.
+    pub components: Vec<ScanComponent>,
+
+    /// The index of the first coefficient in the scan, in zigzag order.
+    pub spectral_start: u8,
+
+    /// The index of the last coefficient in the scan, in zigzag order.
+    pub spectral_end: u8,
+
+    /// The bit position of the previous scan of these coefficients, or 0
+    /// for the first scan of them.
+    pub approximation_high: u8,
+
+    /// The bit position the coefficients are shifted by in this scan.
+    pub approximation_low: u8,
+}
+
+impl ScanHeader {
+    /// Check if the scan refines coefficients sent by an earlier scan.
+    ///
+    /// # Returns
+    /// Whether this is a successive approximation refinement scan.
+    pub const fn is_refinement(&self) -> bool {
+        return self.approximation_high != 0;
+    }
+}
+
+impl TryFrom<&[u8]> for ScanHeader {
+    type Error = GCameraError;
+
+    /// Parse the scan header.
+    ///
+    /// # Arguments
+    /// * `data`: The scan header, without the length or the scan data.
+    ///
+    /// # Returns
+    /// The parsed scan header.
+    ///
+    /// # Errors
+    /// Will error if the header is truncated or has trailing data, if it has
+    /// no components or more than four, or if the spectral selection is out
+    /// of order. The error gives an offset of 0, since the position of the
+    /// header is not known here.
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let Some((&count, rest)) = data.split_first() else {
+            return Err(GCameraError::InvalidScanHeader { offset: 0 });
+        };
+        if !(1..=4).contains(&count) {
+            return Err(GCameraError::InvalidScanHeader { offset: 0 });
+        }
+        let (list, parameters) = rest
+            .split_at_checked(usize::from(count) * 2)
+            .ok_or(GCameraError::InvalidScanHeader { offset: 0 })?;
+        let &[spectral_start, spectral_end, approximation] = parameters else {
+            return Err(GCameraError::InvalidScanHeader { offset: 0 });
+        };
+        if spectral_start > spectral_end || spectral_end > 63 {
+            return Err(GCameraError::InvalidScanHeader { offset: 0 });
+        }
+
+        let components = list
+            .chunks_exact(2)
+            .map(|component| {
+                let &[selector, tables] = component else {
+                    return Err(GCameraError::InvalidScanHeader { offset: 0 });
+                };
+                return Ok(ScanComponent {
+                    selector,
+                    dc_table: tables >> 4,
+                    ac_table: tables & 0x0F,
+                });
+            })
+            .collect::<Result<Vec<ScanComponent>, GCameraError>>()?;
+
+        return Ok(Self {
+            components,
+            spectral_start,
+            spectral_end,
+            approximation_high: approximation >> 4,
+            approximation_low: approximation & 0x0F,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create a scan header.
+    ///
+    /// # Arguments
+    /// * `selectors`: The identifiers of the components in the scan.
+    /// * `spectral_selection`: The first and last coefficient of the scan.
+    /// * `approximation`: The high and low bit positions, in one byte.
+    ///
+    /// # Returns
+    /// The scan header, using table 0 for the first component and table 1
+    /// for the others.
+    fn create_scan_header(
+        selectors: &[u8],
+        spectral_selection: (u8, u8),
+        approximation: u8,
+    ) -> Vec<u8> {
+        let mut data = vec![u8::try_from(selectors.len()).unwrap_or(0)];
+        for (index, selector) in selectors.iter().enumerate() {
+            data.extend([*selector, if index == 0 { 0x00 } else { 0x11 }]);
+        }
+        data.extend([spectral_selection.0, spectral_selection.1, approximation]);
+        return data;
+    }
+
+    /// Test parsing the header of a baseline scan.
+    #[test]
+    fn test_parse_baseline_scan() {
+        let data = create_scan_header(&[1, 2, 3], (0, 63), 0x00);
+        let scan = ScanHeader::try_from(data.as_slice()).unwrap();
+
+        assert_eq!(
+            scan.components,
+            vec![
+                ScanComponent {
+                    selector: 1,
+                    dc_table: 0,
+                    ac_table: 0,
+                },
+                ScanComponent {
+                    selector: 2,
+                    dc_table: 1,
+                    ac_table: 1,
+                },
+                ScanComponent {
+                    selector: 3,
+                    dc_table: 1,
+                    ac_table: 1,
+                },
+            ]
+        );
+        assert_eq!((scan.spectral_start, scan.spectral_end), (0, 63));
+        assert!(!scan.is_refinement());
+    }
+
+    /// Test parsing the header of a progressive refinement scan.
+    #[test]
+    fn test_parse_refinement_scan() {
+        let data = create_scan_header(&[1], (1, 5), 0x21);
+        let scan = ScanHeader::try_from(data.as_slice()).unwrap();
+
+        assert_eq!(scan.components.len(), 1);
+        assert_eq!((scan.spectral_start, scan.spectral_end), (1, 5));
+        assert_eq!(scan.approximation_high, 2);
+        assert_eq!(scan.approximation_low, 1);
+        assert!(scan.is_refinement());
+    }
+
+    /// Test parsing scan headers that are not valid.
+    #[test]
+    fn test_invalid_scan_header() {
+        let valid = create_scan_header(&[1, 2], (0, 63), 0x00);
+        let invalid = [
+            Vec::new(),
+            create_scan_header(&[], (0, 63), 0x00),
+            create_scan_header(&[1, 2, 3, 4, 5], (0, 63), 0x00),
+            create_scan_header(&[1], (6, 5), 0x00),
+            create_scan_header(&[1], (0, 64), 0x00),
+            valid[..valid.len() - 1].to_vec(),
+            [valid.as_slice(), &[0x00]].concat(),
+        ];
+        for data in invalid {
+            assert_eq!(
+                ScanHeader::try_from(data.as_slice()),
+                Err(GCameraError::InvalidScanHeader { offset: 0 })
+            );
+        }
+    }
+}
@@ -0,0 +1,345 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Logic for parsing the quantization and Huffman tables of a JPEG image.
+//!
+//! A DQT segment holds one or more quantization tables, each with 64
+//! values stored in zigzag order. A DHT segment holds one or more Huffman
+//! tables, each with the number of codes of every length from 1 to 16,
+//! followed by the symbols for those codes.
+//!
+//! Most encoders scale the example tables from Annex K of the JPEG
+//! specification by a quality factor, in the same way as the IJG library.
+//! This is used to estimate the quality an image was encoded with.
+use crate::errors::GCameraError;
+
+/// Number of values in a quantization table.
+const TABLE_SIZE: usize = 64;
+
+/// Number of code lengths in a Huffman table.
+const CODE_LENGTHS: usize = 16;
+
+/// Position in the block of each value of a table, in zigzag order.
+const ZIGZAG: [usize; TABLE_SIZE] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+/// The example luminance quantization table, in block order.
+const LUMINANCE_TABLE: [u16; TABLE_SIZE] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56,
+    14, 17, 22, 29, 51, 87, 80, 62, 18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113,
+    92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+/// The example chrominance quantization table, in block order.
+const CHROMINANCE_TABLE: [u16; TABLE_SIZE] = [
+    17, 18, 24, 47, 99, 99, 99, 99, 18, 21, 26, 66, 99, 99, 99, 99, 24, 26, 56, 99, 99, 99, 99, 99,
+    47, 66, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+/// Scale one of the example tables by a quality factor, as the IJG library
+/// does for baseline images.
+///
+/// # Arguments
+/// * `table`: The example table to scale.
+/// * `quality`: The quality factor, from 1 to 100.
+///
+/// # Returns
+/// The scaled table, in block order.
+fn scale_table(table: &[u16; TABLE_SIZE], quality: u16) -> [u16; TABLE_SIZE] {
+    let low_quality_scale: u32 = 5000;
+    let scale = if quality < 50 {
+        low_quality_scale
+            .checked_div(u32::from(quality))
+            .unwrap_or(low_quality_scale)
+    } else {
+        200 - u32::from(quality.min(100)) * 2
+    };
+    return table.map(|value| {
+        let scaled = (u32::from(value) * scale + 50)
+            .checked_div(100)
+            .unwrap_or(0);
+        return u16::try_from(scaled.clamp(1, 255)).unwrap_or(255);
+    });
+}
+
+/// A single quantization table.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct QuantizationTable {
+    /// The destination identifier of the table, from 0 to 3.
+    pub id: u8,
+
+    /// Whether the values of the table are stored as 16-bit integers.
+    pub is_16_bit: bool,
+
+    /// The values of the table, in block order rather than zigzag order.
+    pub values: [u16; TABLE_SIZE],
+}
+
+impl QuantizationTable {
+    /// Estimate the IJG quality factor the table was created with.
+    ///
+    /// The table is compared with the example luminance table if its
+    /// identifier is 0, and with the example chrominance table otherwise.
+    /// The quality factor whose scaled table is closest to this table is
+    /// used, so tables from encoders that do not scale the example tables
+    /// still get an approximate quality.
+    ///
+    /// # Returns
+    /// The estimated quality factor, from 1 to 100.
+    pub fn estimate_quality(&self) -> u16 {
+        let example = if self.id == 0 {
+            &LUMINANCE_TABLE
+        } else {
+            &CHROMINANCE_TABLE
+        };
+        let distance = |quality: u16| {
+            return scale_table(example, quality)
+                .iter()
+                .zip(self.values)
+                .map(|(expected, value)| return u32::from(expected.abs_diff(value)))
+                .sum::<u32>();
+        };
+        // Search from the highest quality, so that ties get the higher one.
+        return (1..=100)
+            .rev()
+            .min_by_key(|&quality| return distance(quality))
+            .unwrap_or(100);
+    }
+}
+
+/// Parse the quantization tables in a DQT segment.
+///
+/// # Arguments
+/// * `data`: The data of the segment, after its length.
+///
+/// # Returns
+/// The tables in the segment.
+///
+/// # Errors
+/// Will error if a table is truncated, or has an unknown precision.
+pub fn parse_quantization_tables(data: &[u8]) -> Result<Vec<QuantizationTable>, GCameraError> {
+    let mut tables = Vec::new();
+    let mut remaining = data;
+    while let Some((&info, rest)) = remaining.split_first() {
+        let is_16_bit = match info >> 4 {
+            0 => false,
+            1 => true,
+            _ => return Err(GCameraError::InvalidQuantizationTable),
+        };
+        let value_size = if is_16_bit { 2 } else { 1 };
+        let (table, next) = rest
+            .split_at_checked(TABLE_SIZE * value_size)
+            .ok_or(GCameraError::InvalidQuantizationTable)?;
+
+        let mut values = [0; TABLE_SIZE];
+        for (&position, value) in ZIGZAG.iter().zip(table.chunks_exact(value_size)) {
+            let entry = values
+                .get_mut(position)
+                .ok_or(GCameraError::InvalidQuantizationTable)?;
+            *entry = match *value {
+                [byte] => u16::from(byte),
+                [high, low] => u16::from_be_bytes([high, low]),
+                _ => return Err(GCameraError::InvalidQuantizationTable),
+            };
+        }
+        tables.push(QuantizationTable {
+            id: info & 0x0F,
+            is_16_bit,
+            values,
+        });
+        remaining = next;
+    }
+    return Ok(tables);
+}
+
+/// Enumeration of the classes of Huffman tables.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HuffmanClass {
+    /// A table for the DC coefficients, or for lossless images.
+    Dc,
+
+    /// A table for the AC coefficients.
+    Ac,
+}
+
+/// A single Huffman table.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HuffmanTable {
+    /// The class of the table.
+    pub class: HuffmanClass,
+
+    /// The destination identifier of the table, from 0 to 3.
+    pub id: u8,
+
+    /// The number of codes of each length, from 1 to 16 bits.
+    pub code_counts: [u8; CODE_LENGTHS],
+
+    /// The symbols for each code, ordered by code length.
+    pub symbols: Vec<u8>,
+}
+
+/// Parse the Huffman tables in a DHT segment.
+///
+/// # Arguments
+/// * `data`: The data of the segment, after its length.
+///
+/// # Returns
+/// The tables in the segment.
+///
+/// # Errors
+/// Will error if a table is truncated, or has an unknown class.
+pub fn parse_huffman_tables(data: &[u8]) -> Result<Vec<HuffmanTable>, GCameraError> {
+    let mut tables = Vec::new();
+    let mut remaining = data;
+    while let Some((&info, rest)) = remaining.split_first() {
+        let class = match info >> 4 {
+            0 => HuffmanClass::Dc,
+            1 => HuffmanClass::Ac,
+            _ => return Err(GCameraError::InvalidHuffmanTable),
+        };
+        let (&code_counts, symbols_start) = rest
+            .split_first_chunk::<CODE_LENGTHS>()
+            .ok_or(GCameraError::InvalidHuffmanTable)?;
+        let symbol_count = code_counts
+            .iter()
+            .map(|&count| return usize::from(count))
+            .sum();
+        let (symbols, next) = symbols_start
+            .split_at_checked(symbol_count)
+            .ok_or(GCameraError::InvalidHuffmanTable)?;
+
+        tables.push(HuffmanTable {
+            class,
+            id: info & 0x0F,
+            code_counts,
+            symbols: symbols.to_vec(),
+        });
+        remaining = next;
+    }
+    return Ok(tables);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create the data of a DQT segment holding the tables for a quality.
+    ///
+    /// # Arguments
+    /// * `quality`: The IJG quality factor of the tables.
+    ///
+    /// # Returns
+    /// The data of the segment, with an 8-bit luminance and chrominance table.
+    ///
+    /// # Panics
+    /// Will panic if a scaled value does not fit in a byte.
+    fn create_quantization_tables(quality: u16) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (id, example) in [(0x00, LUMINANCE_TABLE), (0x01, CHROMINANCE_TABLE)] {
+            let table = scale_table(&example, quality);
+            data.push(id);
+            data.extend(
+                ZIGZAG
+                    .iter()
+                    .map(|&position| return u8::try_from(table[position]).unwrap()),
+            );
+        }
+        return data;
+    }
+
+    /// Test parsing the tables of a DQT segment.
+    #[test]
+    fn test_parse_quantization_tables() {
+        let tables = parse_quantization_tables(&create_quantization_tables(50)).unwrap();
+
+        assert_eq!(tables.len(), 2);
+        assert_eq!(tables[0].id, 0);
+        assert!(!tables[0].is_16_bit);
+        assert_eq!(tables[0].values, LUMINANCE_TABLE);
+        assert_eq!(tables[1].id, 1);
+        assert_eq!(tables[1].values, CHROMINANCE_TABLE);
+    }
+
+    /// Test parsing a table with 16-bit values.
+    #[test]
+    fn test_parse_16_bit_table() {
+        let mut data = vec![0x12];
+        for index in 0..64 {
+            data.extend([0x01, index]);
+        }
+        let tables = parse_quantization_tables(&data).unwrap();
+
+        assert_eq!(tables[0].id, 2);
+        assert!(tables[0].is_16_bit);
+        assert_eq!(tables[0].values[0], 0x0100);
+        assert_eq!(tables[0].values[8], 0x0102);
+        assert_eq!(tables[0].values[63], 0x013F);
+    }
+
+    /// Test estimating the quality of the tables.
+    #[test]
+    fn test_estimate_quality() {
+        for quality in [10, 50, 75, 90, 95, 100] {
+            let tables = parse_quantization_tables(&create_quantization_tables(quality)).unwrap();
+            assert_eq!(tables[0].estimate_quality(), quality);
+            assert_eq!(tables[1].estimate_quality(), quality);
+        }
+
+        // A table that does not come from the example tables.
+        let mut data = vec![0x00];
+        data.extend([0x04; 64]);
+        let flat = parse_quantization_tables(&data).unwrap();
+        assert_eq!(flat[0].estimate_quality(), 98);
+    }
+
+    /// Test parsing DQT segments that are not valid.
+    #[test]
+    fn test_invalid_quantization_tables() {
+        let data = create_quantization_tables(75);
+        assert_eq!(
+            parse_quantization_tables(&data[..100]),
+            Err(GCameraError::InvalidQuantizationTable)
+        );
+        assert_eq!(
+            parse_quantization_tables(&[0x20]),
+            Err(GCameraError::InvalidQuantizationTable)
+        );
+    }
+
+    /// Test parsing the tables of a DHT segment.
+    #[test]
+    fn test_parse_huffman_tables() {
+        let mut data = vec![0x00, 0x00, 0x01, 0x05];
+        data.extend([0x00; 13]);
+        data.extend([0x00, 0x01, 0x02, 0x03, 0x04, 0x05]);
+        data.push(0x11);
+        data.extend([0x01; 2]);
+        data.extend([0x00; 14]);
+        data.extend([0x01, 0x02]);
+        let tables = parse_huffman_tables(&data).unwrap();
+
+        assert_eq!(tables.len(), 2);
+        assert_eq!(tables[0].class, HuffmanClass::Dc);
+        assert_eq!(tables[0].id, 0);
+        assert_eq!(tables[0].code_counts[1..3], [0x01, 0x05]);
+        assert_eq!(tables[0].symbols, vec![0x00, 0x01, 0x02, 0x03, 0x04, 0x05]);
+        assert_eq!(tables[1].class, HuffmanClass::Ac);
+        assert_eq!(tables[1].id, 1);
+        assert_eq!(tables[1].symbols, vec![0x01, 0x02]);
+
+        assert_eq!(
+            parse_huffman_tables(&data[..data.len() - 1]),
+            Err(GCameraError::InvalidHuffmanTable)
+        );
+        assert_eq!(
+            parse_huffman_tables(&[0x20]),
+            Err(GCameraError::InvalidHuffmanTable)
+        );
+    }
+}
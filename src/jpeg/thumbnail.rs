@@ -0,0 +1,130 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Logic for the thumbnails embedded in an image.
+//!
+//! Thumbnails are stored in IFD1 of the EXIF data, or in a JFXX APP0
+//! segment. Only JFXX thumbnails with the JPEG extension code are read,
+//! since the palette and RGB thumbnails are not JPEG data.
+use std::fs;
+use std::path::Path;
+
+use crate::errors::GCameraError;
+use crate::jpeg::jpeg_components::JpegSegment;
+use crate::jpeg::marker::JpegMarker;
+
+/// String that occurs at the start of the JFXX APP0 segment.
+pub const JFXX_MARKER: &[u8] = "JFXX\0".as_bytes();
+
+/// Extension code of a JFXX thumbnail stored as JPEG data.
+pub const JFXX_JPEG_CODE: u8 = 0x10;
+
+/// Enumeration of the places a thumbnail can be stored.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ThumbnailSource {
+    /// IFD1 of the EXIF data.
+    Exif,
+
+    /// A JFXX APP0 segment.
+    Jfxx,
+}
+
+impl ThumbnailSource {
+    /// Get the name of the source.
+    ///
+    /// # Returns
+    /// The name of the source, in lower case.
+    pub fn as_str(self) -> &'static str {
+        return match self {
+            Self::Exif => "exif",
+            Self::Jfxx => "jfxx",
+        };
+    }
+}
+
+/// A thumbnail embedded in an image.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Thumbnail {
+    /// Where the thumbnail is stored.
+    pub source: ThumbnailSource,
+
+    /// The JPEG data of the thumbnail.
+    pub data: Vec<u8>,
+}
+
+impl Thumbnail {
+    /// Save the thumbnail to a file.
+    ///
+    /// # Arguments
+    /// * `filepath`: Path to save the thumbnail to.
+    ///
+    /// # Returns
+    /// Result of saving the file.
+    ///
+    /// # Errors
+    /// Will error if writing the file to the disk fails.
+    pub fn save(&self, filepath: &Path) -> Result<(), GCameraError> {
+        return fs::write(filepath, &self.data)
+            .map_err(|error| return GCameraError::ThumbnailWriteError { kind: error.kind() });
+    }
+}
+
+/// Check that thumbnail data is a JPEG image.
+///
+/// # Arguments
+/// * `data`: The thumbnail data.
+///
+/// # Errors
+/// Will error if the data does not start and end with the JPEG markers.
+pub fn validate_thumbnail(data: &[u8]) -> Result<(), GCameraError> {
+    if data.starts_with(&[0xFF, 0xD8]) && data.ends_with(&[0xFF, 0xD9]) {
+        return Ok(());
+    }
+    return Err(GCameraError::InvalidThumbnail);
+}
+
+/// Create a JFXX segment holding a JPEG thumbnail.
+///
+/// # Arguments
+/// * `data`: The JPEG data of the thumbnail.
+///
+/// # Returns
+/// Result holding the created APP0 segment.
+///
+/// # Errors
+/// Will error if the thumbnail is too large to fit in a single segment.
+pub fn create_jfxx_segment(data: &[u8]) -> Result<JpegSegment, GCameraError> {
+    let segment_data = [JFXX_MARKER, &[JFXX_JPEG_CODE], data].concat();
+    return JpegSegment::new(JpegMarker::APP0, &segment_data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test checking that thumbnails are JPEG images.
+    #[test]
+    fn test_validate_thumbnail() {
+        assert_eq!(validate_thumbnail(&[0xFF, 0xD8, 0x00, 0xFF, 0xD9]), Ok(()));
+        assert_eq!(
+            validate_thumbnail(&[0x89, 0x50, 0x4E, 0x47]),
+            Err(GCameraError::InvalidThumbnail)
+        );
+    }
+
+    /// Test creating a JFXX segment, and reading the thumbnail back.
+    #[test]
+    fn test_jfxx_segment() {
+        let segment = create_jfxx_segment(&[0xFF, 0xD8, 0xFF, 0xD9]).unwrap();
+        assert_eq!(
+            segment.as_bytes(),
+            [0xFF, 0xE0, 0x00, 0x0C, 0x4A, 0x46, 0x58, 0x58, 0x00, 0x10, 0xFF, 0xD8, 0xFF, 0xD9]
+        );
+        assert_eq!(
+            segment.as_jfxx_thumbnail(),
+            Some([0xFF, 0xD8, 0xFF, 0xD9].as_slice())
+        );
+    }
+}
@@ -0,0 +1,608 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Logic for reading and writing TIFF structures.
+//!
+//! EXIF data in APP1 and the MPF index in APP2 are stored as a TIFF header,
+//! followed by Image File Directories (IFDs). Each IFD is a list of tagged
+//! entries, and all offsets are relative to the start of the TIFF header.
+use crate::errors::GCameraError;
+
+/// Size of a single entry in an IFD, in bytes.
+pub const IFD_ENTRY_SIZE: usize = 12;
+
+/// Field type of 8-bit unsigned integers.
+pub const TYPE_BYTE: u16 = 1;
+
+/// Field type of null terminated ASCII strings.
+pub const TYPE_ASCII: u16 = 2;
+
+/// Field type of 16-bit unsigned integers.
+pub const TYPE_SHORT: u16 = 3;
+
+/// Field type of 32-bit unsigned integers.
+pub const TYPE_LONG: u16 = 4;
+
+/// Field type of fractions of two 32-bit unsigned integers.
+pub const TYPE_RATIONAL: u16 = 5;
+
+/// Field type of 8-bit signed integers.
+pub const TYPE_SBYTE: u16 = 6;
+
+/// Field type of bytes that are interpreted based on the tag.
+pub const TYPE_UNDEFINED: u16 = 7;
+
+/// Field type of 16-bit signed integers.
+pub const TYPE_SSHORT: u16 = 8;
+
+/// Field type of 32-bit signed integers.
+pub const TYPE_SLONG: u16 = 9;
+
+/// Field type of fractions of two 32-bit signed integers.
+pub const TYPE_SRATIONAL: u16 = 10;
+
+/// Field type of single precision floating point numbers.
+pub const TYPE_FLOAT: u16 = 11;
+
+/// Field type of double precision floating point numbers.
+pub const TYPE_DOUBLE: u16 = 12;
+
+/// Byte order of the values in a TIFF structure.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ByteOrder {
+    /// Least significant byte first, marked with `II`.
+    LittleEndian,
+
+    /// Most significant byte first, marked with `MM`.
+    BigEndian,
+}
+
+impl ByteOrder {
+    /// Read an unsigned 16-bit integer.
+    ///
+    /// # Arguments
+    /// * `data`: The TIFF structure.
+    /// * `offset`: The offset of the integer from the start of the structure.
+    ///
+    /// # Returns
+    /// The integer at the offset.
+    ///
+    /// # Errors
+    /// Will error if the integer is past the end of the data.
+    #[allow(clippy::little_endian_bytes)] // TIFF structures can be stored in either byte order
+    pub fn read_u16(self, data: &[u8], offset: usize) -> Result<u16, GCameraError> {
+        let bytes: [u8; 2] = read_array(data, offset)?;
+        return Ok(match self {
+            Self::LittleEndian => u16::from_le_bytes(bytes),
+            Self::BigEndian => u16::from_be_bytes(bytes),
+        });
+    }
+
+    /// Read an unsigned 32-bit integer.
+    ///
+    /// # Arguments
+    /// * `data`: The TIFF structure.
+    /// * `offset`: The offset of the integer from the start of the structure.
+    ///
+    /// # Returns
+    /// The integer at the offset.
+    ///
+    /// # Errors
+    /// Will error if the integer is past the end of the data.
+    #[allow(clippy::little_endian_bytes)] // TIFF structures can be stored in either byte order
+    pub fn read_u32(self, data: &[u8], offset: usize) -> Result<u32, GCameraError> {
+        let bytes: [u8; 4] = read_array(data, offset)?;
+        return Ok(match self {
+            Self::LittleEndian => u32::from_le_bytes(bytes),
+            Self::BigEndian => u32::from_be_bytes(bytes),
+        });
+    }
+
+    /// Read an unsigned 64-bit integer.
+    ///
+    /// # Arguments
+    /// * `data`: The TIFF structure.
+    /// * `offset`: The offset of the integer from the start of the structure.
+    ///
+    /// # Returns
+    /// The integer at the offset.
+    ///
+    /// # Errors
+    /// Will error if the integer is past the end of the data.
+    #[allow(clippy::little_endian_bytes)] // TIFF structures can be stored in either byte order
+    pub fn read_u64(self, data: &[u8], offset: usize) -> Result<u64, GCameraError> {
+        let bytes: [u8; 8] = read_array(data, offset)?;
+        return Ok(match self {
+            Self::LittleEndian => u64::from_le_bytes(bytes),
+            Self::BigEndian => u64::from_be_bytes(bytes),
+        });
+    }
+
+    /// Convert an unsigned 16-bit integer to bytes.
+    ///
+    /// # Arguments
+    /// * `value`: The integer to convert.
+    ///
+    /// # Returns
+    /// The bytes of the integer.
+    #[allow(clippy::little_endian_bytes)] // TIFF structures can be stored in either byte order
+    pub fn u16_bytes(self, value: u16) -> [u8; 2] {
+        return match self {
+            Self::LittleEndian => value.to_le_bytes(),
+            Self::BigEndian => value.to_be_bytes(),
+        };
+    }
+
+    /// Convert an unsigned 32-bit integer to bytes.
+    ///
+    /// # Arguments
+    /// * `value`: The integer to convert.
+    ///
+    /// # Returns
+    /// The bytes of the integer.
+    #[allow(clippy::little_endian_bytes)] // TIFF structures can be stored in either byte order
+    pub fn u32_bytes(self, value: u32) -> [u8; 4] {
+        return match self {
+            Self::LittleEndian => value.to_le_bytes(),
+            Self::BigEndian => value.to_be_bytes(),
+        };
+    }
+
+    /// Convert an unsigned 64-bit integer to bytes.
+    ///
+    /// # Arguments
+    /// * `value`: The integer to convert.
+    ///
+    /// # Returns
+    /// The bytes of the integer.
+    #[allow(clippy::little_endian_bytes)] // TIFF structures can be stored in either byte order
+    pub fn u64_bytes(self, value: u64) -> [u8; 8] {
+        return match self {
+            Self::LittleEndian => value.to_le_bytes(),
+            Self::BigEndian => value.to_be_bytes(),
+        };
+    }
+
+    /// Write an unsigned 32-bit integer over existing data.
+    ///
+    /// # Arguments
+    /// * `data`: The TIFF structure.
+    /// * `offset`: The offset of the integer from the start of the structure.
+    /// * `value`: The integer to write.
+    ///
+    /// # Errors
+    /// Will error if the integer would be written past the end of the data.
+    pub fn write_u32(self, data: &mut [u8], offset: usize, value: u32) -> Result<(), GCameraError> {
+        let bytes = self.u32_bytes(value);
+        data.get_mut(offset..offset.saturating_add(bytes.len()))
+            .ok_or(GCameraError::InvalidTiffOffset { offset })?
+            .copy_from_slice(&bytes);
+        return Ok(());
+    }
+}
+
+/// A single tagged entry of an IFD.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct IfdEntry {
+    /// The tag identifying the entry.
+    pub tag: u16,
+
+    /// The type of the values in the entry.
+    pub field_type: u16,
+
+    /// The number of values in the entry.
+    pub count: u32,
+
+    /// The offset of the values from the start of the TIFF structure.
+    /// Values of four bytes or less are stored in the entry itself.
+    pub value_offset: usize,
+
+    /// The raw bytes of the values.
+    pub value: Vec<u8>,
+}
+
+impl IfdEntry {
+    /// Get the values of a `SHORT` or `LONG` entry.
+    ///
+    /// # Arguments
+    /// * `order`: The byte order of the TIFF structure.
+    ///
+    /// # Returns
+    /// The values of the entry, or None if it does not hold integers.
+    pub fn as_u32_values(&self, order: ByteOrder) -> Option<Vec<u32>> {
+        return match self.field_type {
+            TYPE_SHORT => self
+                .value
+                .chunks_exact(2)
+                .map(|chunk| return order.read_u16(chunk, 0).ok().map(u32::from))
+                .collect(),
+            TYPE_LONG => self
+                .value
+                .chunks_exact(4)
+                .map(|chunk| return order.read_u32(chunk, 0).ok())
+                .collect(),
+            _ => None,
+        };
+    }
+
+    /// Get the first value of a `SHORT` or `LONG` entry.
+    ///
+    /// # Arguments
+    /// * `order`: The byte order of the TIFF structure.
+    ///
+    /// # Returns
+    /// The first value of the entry, or None if it does not hold integers.
+    pub fn as_u32(&self, order: ByteOrder) -> Option<u32> {
+        return self.as_u32_values(order)?.first().copied();
+    }
+}
+
+/// An Image File Directory.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Ifd {
+    /// The entries of the directory, in the order they are stored.
+    pub entries: Vec<IfdEntry>,
+
+    /// The offset of the next directory, or 0 if this is the last one.
+    pub next_offset: u32,
+}
+
+impl Ifd {
+    /// Find the entry with the given tag.
+    ///
+    /// # Arguments
+    /// * `tag`: The tag to search for.
+    ///
+    /// # Returns
+    /// The entry, or None if the directory does not have one with the tag.
+    pub fn get(&self, tag: u16) -> Option<&IfdEntry> {
+        return self.entries.iter().find(|entry| return entry.tag == tag);
+    }
+}
+
+/// Read a fixed number of bytes.
+///
+/// # Arguments
+/// * `data`: The TIFF structure.
+/// * `offset`: The offset of the bytes from the start of the structure.
+///
+/// # Returns
+/// The bytes at the offset.
+///
+/// # Errors
+/// Will error if the bytes are past the end of the data.
+fn read_array<const COUNT: usize>(data: &[u8], offset: usize) -> Result<[u8; COUNT], GCameraError> {
+    return data
+        .get(offset..offset.saturating_add(COUNT))
+        .and_then(|slice| return <[u8; COUNT]>::try_from(slice).ok())
+        .ok_or(GCameraError::InvalidTiffOffset { offset });
+}
+
+/// Convert an offset in a TIFF structure to a `usize`.
+///
+/// # Arguments
+/// * `offset`: The offset to convert.
+///
+/// # Returns
+/// The offset, or `usize::MAX` if it does not fit, so that reading at it
+/// fails as being past the end of the data.
+pub fn to_usize(offset: u32) -> usize {
+    return usize::try_from(offset).unwrap_or(usize::MAX);
+}
+
+/// Get the size of a single value of the given field type.
+///
+/// # Arguments
+/// * `field_type`: The field type of the value.
+///
+/// # Returns
+/// The size in bytes, or None if the field type is unknown.
+pub fn type_size(field_type: u16) -> Option<usize> {
+    return match field_type {
+        TYPE_BYTE | TYPE_ASCII | TYPE_SBYTE | TYPE_UNDEFINED => Some(1),
+        TYPE_SHORT | TYPE_SSHORT => Some(2),
+        TYPE_LONG | TYPE_SLONG | TYPE_FLOAT => Some(4),
+        TYPE_RATIONAL | TYPE_SRATIONAL | TYPE_DOUBLE => Some(8),
+        _ => None,
+    };
+}
+
+/// Parse the header at the start of a TIFF structure.
+///
+/// # Arguments
+/// * `data`: The TIFF structure.
+///
+/// # Returns
+/// The byte order of the structure, and the offset of the first IFD.
+///
+/// # Errors
+/// Will error if the byte order marker or the magic number is not valid.
+pub fn parse_header(data: &[u8]) -> Result<(ByteOrder, u32), GCameraError> {
+    let order = match data.get(0..2) {
+        Some(b"II") => ByteOrder::LittleEndian,
+        Some(b"MM") => ByteOrder::BigEndian,
+        _ => return Err(GCameraError::InvalidTiffHeader),
+    };
+    if order.read_u16(data, 2)? != 0x2A {
+        return Err(GCameraError::InvalidTiffHeader);
+    }
+    return Ok((order, order.read_u32(data, 4)?));
+}
+
+/// Parse the IFD at the given offset.
+///
+/// Entries with an unknown field type are skipped, since the size of their
+/// values is not known.
+///
+/// # Arguments
+/// * `data`: The TIFF structure.
+/// * `order`: The byte order of the structure.
+/// * `offset`: The offset of the IFD from the start of the structure.
+///
+/// # Returns
+/// The parsed IFD.
+///
+/// # Errors
+/// Will error if the IFD, or the values of any of its entries, are past the
+/// end of the data.
+pub fn parse_ifd(data: &[u8], order: ByteOrder, offset: u32) -> Result<Ifd, GCameraError> {
+    let start = to_usize(offset);
+    let count = usize::from(order.read_u16(data, start)?);
+    let mut entries = Vec::with_capacity(count);
+    let mut entry_offset = start.saturating_add(2);
+    for _ in 0..count {
+        let field_type = order.read_u16(data, entry_offset.saturating_add(2))?;
+        let value_count = order.read_u32(data, entry_offset.saturating_add(4))?;
+        if let Some(size) = type_size(field_type) {
+            let value_size = usize::try_from(value_count)
+                .ok()
+                .and_then(|value_count_usize| return value_count_usize.checked_mul(size))
+                .ok_or(GCameraError::InvalidTiffOffset {
+                    offset: entry_offset,
+                })?;
+            let value_offset = if value_size <= 4 {
+                entry_offset.saturating_add(8)
+            } else {
+                to_usize(order.read_u32(data, entry_offset.saturating_add(8))?)
+            };
+            let value = data
+                .get(value_offset..value_offset.saturating_add(value_size))
+                .ok_or(GCameraError::InvalidTiffOffset {
+                    offset: value_offset,
+                })?;
+            entries.push(IfdEntry {
+                tag: order.read_u16(data, entry_offset)?,
+                field_type,
+                count: value_count,
+                value_offset,
+                value: value.to_vec(),
+            });
+        }
+        entry_offset = entry_offset.saturating_add(IFD_ENTRY_SIZE);
+    }
+
+    return Ok(Ifd {
+        entries,
+        next_offset: order.read_u32(data, entry_offset)?,
+    });
+}
+
+/// Create the header of a TIFF structure.
+///
+/// # Arguments
+/// * `order`: The byte order of the structure.
+///
+/// # Returns
+/// The header, with the first IFD directly after it.
+pub fn create_header(order: ByteOrder) -> Vec<u8> {
+    let marker = match order {
+        ByteOrder::LittleEndian => "II".as_bytes(),
+        ByteOrder::BigEndian => "MM".as_bytes(),
+    };
+    return [marker, &order.u16_bytes(0x2A), &order.u32_bytes(8)].concat();
+}
+
+/// Convert the length of a TIFF structure to an offset within it.
+///
+/// # Arguments
+/// * `length`: The length to convert.
+///
+/// # Returns
+/// The length as a 32-bit offset.
+///
+/// # Errors
+/// Will error if the length does not fit in 32 bits.
+pub fn to_offset(length: usize) -> Result<u32, GCameraError> {
+    return u32::try_from(length)
+        .map_err(|_| return GCameraError::InvalidTiffOffset { offset: length });
+}
+
+/// Write an IFD to the end of a TIFF structure.
+///
+/// The entries are sorted by tag, and any values larger than four bytes
+/// are written after the IFD, starting on an even offset.
+///
+/// # Arguments
+/// * `data`: The TIFF structure to add the IFD to.
+/// * `order`: The byte order of the structure.
+/// * `entries`: The entries to write. Their value offsets are ignored.
+///
+/// # Returns
+/// The offset of the IFD, and the written IFD with the offset of each value
+/// in the structure. The offset of the next IFD is written as 0.
+///
+/// # Errors
+/// Will error if an entry has too many values, or the structure becomes too
+/// large for 32-bit offsets.
+pub fn write_ifd(
+    data: &mut Vec<u8>,
+    order: ByteOrder,
+    entries: &[IfdEntry],
+) -> Result<(usize, Ifd), GCameraError> {
+    let mut sorted = entries.to_vec();
+    sorted.sort_by_key(|entry| return entry.tag);
+
+    if data.len() % 2 != 0 {
+        data.push(0x00);
+    }
+    let count = u16::try_from(sorted.len())
+        .map_err(|_| return GCameraError::InvalidTiffOffset { offset: data.len() })?;
+    let start = data.len();
+    let values_start = start + 2 + IFD_ENTRY_SIZE * sorted.len() + 4;
+    data.extend(order.u16_bytes(count));
+
+    let mut values = Vec::new();
+    for (index, entry) in sorted.iter_mut().enumerate() {
+        data.extend(order.u16_bytes(entry.tag));
+        data.extend(order.u16_bytes(entry.field_type));
+        data.extend(order.u32_bytes(entry.count));
+        if entry.value.len() <= 4 {
+            entry.value_offset = start + 2 + IFD_ENTRY_SIZE * index + 8;
+            data.extend(&entry.value);
+            data.resize(data.len() + 4 - entry.value.len(), 0x00);
+        } else {
+            entry.value_offset = values_start + values.len();
+            data.extend(order.u32_bytes(to_offset(entry.value_offset)?));
+            values.extend(&entry.value);
+            if entry.value.len() % 2 != 0 {
+                values.push(0x00);
+            }
+        }
+    }
+    data.extend(order.u32_bytes(0));
+    data.extend(values);
+    to_offset(data.len())?;
+
+    return Ok((
+        start,
+        Ifd {
+            entries: sorted,
+            next_offset: 0,
+        },
+    ));
+}
+
+/// Get the offset of the field holding the next IFD offset.
+///
+/// # Arguments
+/// * `start`: The offset of the IFD.
+/// * `ifd`: The IFD.
+///
+/// # Returns
+/// The offset of the field after the entries of the IFD.
+pub fn next_offset_position(start: usize, ifd: &Ifd) -> usize {
+    return start + 2 + IFD_ENTRY_SIZE * ifd.entries.len();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Little endian structure with a single IFD holding a short value and
+    /// a string stored after the IFD.
+    const LITTLE_ENDIAN: [u8; 44] = [
+        0x49, 0x49, 0x2A, 0x00, 0x08, 0x00, 0x00, 0x00, // Header
+        0x02, 0x00, // Entry count
+        0x12, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00, // Short
+        0x0F, 0x01, 0x02, 0x00, 0x06, 0x00, 0x00, 0x00, 0x26, 0x00, 0x00, 0x00, // ASCII
+        0x00, 0x00, 0x00, 0x00, // Next IFD
+        0x47, 0x6F, 0x6F, 0x67, 0x6C, 0x00, // "Googl"
+    ];
+
+    /// Test parsing the header and IFD of a little endian structure.
+    #[test]
+    fn test_parse_little_endian() {
+        let (order, offset) = parse_header(&LITTLE_ENDIAN).unwrap();
+        assert_eq!(order, ByteOrder::LittleEndian);
+        assert_eq!(offset, 8);
+
+        let ifd = parse_ifd(&LITTLE_ENDIAN, order, 8).unwrap();
+        assert_eq!(ifd.next_offset, 0);
+        assert_eq!(ifd.get(0x0112).unwrap().as_u32(order), Some(6));
+        assert_eq!(
+            ifd.get(0x010F),
+            Some(&IfdEntry {
+                tag: 0x010F,
+                field_type: TYPE_ASCII,
+                count: 6,
+                value_offset: 0x26,
+                value: "Googl\0".as_bytes().to_vec(),
+            })
+        );
+        assert_eq!(ifd.get(0x010F).unwrap().as_u32(order), None);
+    }
+
+    /// Test parsing a big endian structure, and writing a value into it.
+    #[test]
+    fn test_big_endian() {
+        let mut data = vec![
+            0x4D, 0x4D, 0x00, 0x2A, 0x00, 0x00, 0x00, 0x08, // Header
+            0x00, 0x01, // Entry count
+            0xB0, 0x01, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, // Long
+            0x00, 0x00, 0x00, 0x00, // Next IFD
+        ];
+        let (order, offset) = parse_header(&data).unwrap();
+        assert_eq!(order, ByteOrder::BigEndian);
+
+        let ifd = parse_ifd(&data, order, offset).unwrap();
+        let entry = ifd.get(0xB001).unwrap();
+        assert_eq!(entry.as_u32(order), Some(2));
+
+        order.write_u32(&mut data, entry.value_offset, 3).unwrap();
+        assert_eq!(order.read_u32(&data, entry.value_offset), Ok(3));
+        assert_eq!(
+            order.write_u32(&mut data, 24, 3),
+            Err(GCameraError::InvalidTiffOffset { offset: 24 })
+        );
+    }
+
+    /// Test writing an IFD and parsing it back.
+    #[test]
+    fn test_write_ifd() {
+        let order = ByteOrder::BigEndian;
+        let mut data = create_header(order);
+        let long = IfdEntry {
+            tag: 0x0112,
+            field_type: TYPE_SHORT,
+            count: 1,
+            value_offset: 0,
+            value: vec![0x00, 0x03],
+        };
+        let ascii = IfdEntry {
+            tag: 0x010F,
+            field_type: TYPE_ASCII,
+            count: 5,
+            value_offset: 0,
+            value: "Pixe\0".as_bytes().to_vec(),
+        };
+        let (start, written) = write_ifd(&mut data, order, &[long, ascii]).unwrap();
+        assert_eq!(start, 8);
+        assert_eq!(next_offset_position(start, &written), 34);
+        assert_eq!(data.len(), 44);
+
+        let parsed = parse_ifd(&data, order, 8).unwrap();
+        assert_eq!(parsed, written);
+        assert_eq!(parsed.entries[0].tag, 0x010F);
+        assert_eq!(parsed.entries[0].value_offset, 38);
+        assert_eq!(parsed.entries[1].as_u32(order), Some(3));
+    }
+
+    /// Test parsing structures that are not valid.
+    #[test]
+    fn test_invalid() {
+        assert_eq!(
+            parse_header(&[0x49, 0x4D, 0x2A, 0x00, 0x08, 0x00, 0x00, 0x00]),
+            Err(GCameraError::InvalidTiffHeader)
+        );
+        assert_eq!(
+            parse_header(&[0x49, 0x49, 0x2B, 0x00, 0x08, 0x00, 0x00, 0x00]),
+            Err(GCameraError::InvalidTiffHeader)
+        );
+        assert_eq!(
+            parse_ifd(&LITTLE_ENDIAN[..40], ByteOrder::LittleEndian, 8),
+            Err(GCameraError::InvalidTiffOffset { offset: 0x26 })
+        );
+    }
+}
@@ -4,33 +4,287 @@
 * file, You can obtain one at https: //mozilla.org/MPL/2.0/.
 */
 //! Logic for parsing the XMP data in an image.
+pub mod depth;
+pub mod gain_map;
+pub mod model;
+pub mod serializer;
+
 use roxmltree::{Document, ExpandedName, Node};
+use std::cmp::Reverse;
+use std::ops::Range;
 use std::str;
 
 use crate::errors::GCameraError;
 use crate::jpeg::marker::JpegMarker;
 
 use crate::jpeg::jpeg_components::JpegSegment;
+use crate::jpeg::xmp::model::{ArrayKind, XMPProperty, XMPTree, XMPValue};
+use crate::jpeg::xmp::serializer::{serialize, SerializeOptions};
 
 // Namespace consants.
 
 /// RDF Namespace
-const RDF_NS: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+pub const RDF_NS: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
 /// XMP Note Namespace
-const XMP_NOTE_NS: &str = "http://ns.adobe.com/xmp/note/";
+pub const XMP_NOTE_NS: &str = "http://ns.adobe.com/xmp/note/";
 
 /// Google generic camera info.
-const GCAMERA_NS: &str = "http://ns.google.com/photos/1.0/camera/";
+pub const GCAMERA_NS: &str = "http://ns.google.com/photos/1.0/camera/";
 
 /// Google resource container info
-const CONTAINER_NS: &str = "http://ns.google.com/photos/1.0/container/";
+pub const CONTAINER_NS: &str = "http://ns.google.com/photos/1.0/container/";
 
 /// Google Resource item info
 const ITEM_NS: &str = "http://ns.google.com/photos/1.0/container/item/";
 
+/// EXIF properties, including the GPS properties.
+const EXIF_NS: &str = "http://ns.adobe.com/exif/1.0/";
+
+/// Additional EXIF properties from the EXIF 2.3 standard.
+const EXIF_EX_NS: &str = "http://cipa.jp/exif/1.0/";
+
+/// Additional camera properties from Adobe.
+const AUX_NS: &str = "http://ns.adobe.com/exif/1.0/aux/";
+
+/// Photoshop properties, including the city and country.
+const PHOTOSHOP_NS: &str = "http://ns.adobe.com/photoshop/1.0/";
+
+/// IPTC core properties, including the location.
+const IPTC_CORE_NS: &str = "http://iptc.org/std/Iptc4xmpCore/1.0/xmlns/";
+
+/// IPTC extension properties, including the locations shown.
+const IPTC_EXT_NS: &str = "http://iptc.org/std/Iptc4xmpExt/2008-02-29/";
+
 /// String that occurs at the start of the XMP section
 pub const XMP_MARKER: &[u8] = "http://ns.adobe.com/xap/1.0/".as_bytes();
 
+/// Create a `JpegSegment` holding the given XMP data.
+///
+/// # Arguments
+/// * `xml`: The XMP data, as an XML string.
+///
+/// # Returns
+/// Result holding the created APP1 segment.
+///
+/// # Errors
+/// Will error if the XMP data is too large to fit in a single segment.
+pub fn create_xmp_segment(xml: &str) -> Result<JpegSegment, GCameraError> {
+    let data = [XMP_MARKER, &[0x00], xml.as_bytes()].concat();
+    return JpegSegment::new(JpegMarker::APP1, &data);
+}
+
+/// Find the range of an attribute in the original XML text.
+///
+/// # Arguments
+/// * `text`: The original XML text.
+/// * `position`: The position of the start of the attribute.
+///
+/// # Returns
+/// The range of the attribute, from the start of its name to the end of
+/// its closing quote.
+pub fn attribute_range(text: &str, position: usize) -> Range<usize> {
+    let after_name = &text.as_bytes()[position..];
+    return after_name
+        .iter()
+        .position(|byte| return *byte == b'"' || *byte == b'\'')
+        .and_then(|quote_index| {
+            let quote = after_name[quote_index];
+            return after_name[quote_index + 1..]
+                .iter()
+                .position(|byte| return *byte == quote)
+                .map(|value_len| return position..position + quote_index + value_len + 2);
+        })
+        .unwrap_or(position..text.len());
+}
+
+/// Check if a name is one of the motion photo names that are removed when stripping resources.
+///
+/// # Arguments
+/// * `namespace`: The namespace of the name.
+/// * `name`: The local name.
+///
+/// # Returns
+/// Boolean indicating if the name should be removed.
+fn is_resource_name(namespace: Option<&str>, name: &str) -> bool {
+    return match namespace {
+        Some(GCAMERA_NS) => name.starts_with("MotionPhoto"),
+        Some(CONTAINER_NS) => name == "Directory",
+        _ => false,
+    };
+}
+
+/// Check if a property holds location or identifying data.
+///
+/// # Arguments
+/// * `namespace`: The namespace of the property.
+/// * `name`: The name of the property.
+///
+/// # Returns
+/// Boolean indicating if the property is removed when scrubbing.
+fn is_private_property(namespace: &str, name: &str) -> bool {
+    return match namespace {
+        EXIF_NS => name.starts_with("GPS") || name == "ImageUniqueID",
+        EXIF_EX_NS => matches!(
+            name,
+            "CameraOwnerName" | "BodySerialNumber" | "LensSerialNumber" | "ImageUniqueID"
+        ),
+        AUX_NS => matches!(name, "SerialNumber" | "LensSerialNumber" | "OwnerName"),
+        PHOTOSHOP_NS => matches!(name, "City" | "State" | "Country"),
+        IPTC_CORE_NS => matches!(name, "Location" | "CountryCode"),
+        IPTC_EXT_NS => matches!(name, "LocationCreated" | "LocationShown"),
+        _ => false,
+    };
+}
+
+/// Check if a property describes a motion photo.
+///
+/// # Arguments
+/// * `namespace`: The namespace of the property.
+/// * `name`: The name of the property.
+///
+/// # Returns
+/// Boolean indicating if the property is removed with the motion video.
+fn is_motion_property(namespace: &str, name: &str) -> bool {
+    return namespace == GCAMERA_NS
+        && (name.starts_with("MotionPhoto") || name.starts_with("MicroVideo"));
+}
+
+/// Get the fields of an item in a `Container:Directory`.
+///
+/// # Arguments
+/// * `item`: The value of the `rdf:li` element that holds the item.
+///
+/// # Returns
+/// The fields of the `Container:Item` structure, or None if the value does
+/// not hold one.
+fn item_fields(item: &mut XMPValue) -> Option<&mut Vec<XMPProperty>> {
+    let XMPValue::Struct(properties) = item else {
+        return None;
+    };
+    let container_item = properties.iter_mut().find(|property| {
+        return property.namespace == CONTAINER_NS && property.name == "Item";
+    })?;
+    let XMPValue::Struct(fields) = &mut container_item.value else {
+        return None;
+    };
+    return Some(fields);
+}
+
+/// Update the lengths of the items in a `Container:Directory`.
+///
+/// The items other than the primary image are matched with the resources
+/// in order. Nothing is changed unless every item has the same semantic
+/// type as its resource.
+///
+/// # Arguments
+/// * `tree`: The XMP properties holding the directory.
+/// * `lengths`: The semantic type and new length of each resource.
+///
+/// # Returns
+/// Boolean indicating if any of the lengths were changed.
+pub fn set_item_lengths(tree: &mut XMPTree, lengths: &[(SemanticType, usize)]) -> bool {
+    let Some(XMPProperty {
+        value: XMPValue::Array(_, items),
+        ..
+    }) = tree.properties.iter_mut().find(|property| {
+        return property.namespace == CONTAINER_NS && property.name == "Directory";
+    })
+    else {
+        return false;
+    };
+    let semantic = |fields: &[XMPProperty]| {
+        return fields
+            .iter()
+            .find(|field| return field.namespace == ITEM_NS && field.name == "Semantic")
+            .and_then(|field| return field.value.as_str())
+            .map(String::from);
+    };
+    let mut resources: Vec<&mut Vec<XMPProperty>> = items
+        .iter_mut()
+        .filter_map(item_fields)
+        .filter(|fields| return semantic(fields).as_deref() != Some(SemanticType::Primary.as_str()))
+        .collect();
+    if resources.len() != lengths.len()
+        || resources
+            .iter()
+            .zip(lengths)
+            .any(|(fields, (semantic_type, _))| {
+                return semantic(fields).as_deref() != Some(semantic_type.as_str());
+            })
+    {
+        return false;
+    }
+
+    let mut changed = false;
+    for (fields, (_, length)) in resources.iter_mut().zip(lengths) {
+        let field = fields
+            .iter_mut()
+            .find(|field| return field.namespace == ITEM_NS && field.name == "Length");
+        if let Some(length_field) = field {
+            let old_length = length_field
+                .value
+                .as_str()
+                .and_then(|text| return text.trim().parse::<usize>().ok());
+            if old_length != Some(*length) {
+                length_field.value = XMPValue::Simple(length.to_string());
+                changed = true;
+            }
+        }
+    }
+    return changed;
+}
+
+/// Remove the resources from XMP data, keeping all other properties.
+///
+/// The `Container:Directory` property and the `GCamera:MotionPhoto*`
+/// properties are removed from each description. All other text in the
+/// XMP data is written back unchanged.
+///
+/// # Arguments
+/// * `xmp`: The XMP data, as an XML string.
+///
+/// # Returns
+/// The XMP data with the resource properties removed.
+///
+/// # Errors
+/// Will error if the XMP data cannot be parsed.
+pub fn strip_resources(xmp: &str) -> Result<String, GCameraError> {
+    let document = Document::parse(xmp)
+        .map_err(|xml_error| return GCameraError::XMLParsingError { xml_error })?;
+
+    let mut removals: Vec<Range<usize>> = Vec::new();
+    for description in document
+        .descendants()
+        .filter(|n| return n.tag_name() == ExpandedName::from((RDF_NS, "Description")))
+    {
+        removals.extend(
+            description
+                .attributes()
+                .filter(|attribute| {
+                    return is_resource_name(attribute.namespace(), attribute.name());
+                })
+                .map(|attribute| return attribute_range(xmp, attribute.position())),
+        );
+        removals.extend(
+            description
+                .children()
+                .filter(|child| {
+                    return child.is_element()
+                        && is_resource_name(child.tag_name().namespace(), child.tag_name().name());
+                })
+                .map(|child| return child.range()),
+        );
+    }
+
+    // Remove from the end first so that the earlier ranges remain valid.
+    removals.sort_by_key(|range| return Reverse(range.start));
+    let mut stripped = String::from(xmp);
+    for range in removals {
+        stripped.replace_range(range, "");
+    }
+    return Ok(stripped);
+}
+
 /// Convert an XML attribute in a node to a string.
 ///
 /// # Arguments
@@ -103,6 +357,35 @@ pub enum MimeType {
 
     ///MP4 Video
     Mp4,
+
+    /// PNG Image.
+    Png,
+}
+
+impl MimeType {
+    /// Get the MIME type as a string.
+    ///
+    /// # Returns
+    /// The MIME type, as it is written in the XMP data.
+    pub fn as_str(self) -> &'static str {
+        return match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Mp4 => "video/mp4",
+            Self::Png => "image/png",
+        };
+    }
+
+    /// Get the file extension usually used for the MIME type.
+    ///
+    /// # Returns
+    /// The file extension, without a leading dot.
+    pub fn extension(self) -> &'static str {
+        return match self {
+            Self::Jpeg => "jpg",
+            Self::Mp4 => "mp4",
+            Self::Png => "png",
+        };
+    }
 }
 
 impl TryFrom<String> for MimeType {
@@ -112,6 +395,7 @@ impl TryFrom<String> for MimeType {
         return match value.as_str() {
             "image/jpeg" => Ok(Self::Jpeg),
             "video/mp4" => Ok(Self::Mp4),
+            "image/png" => Ok(Self::Png),
             _ => Err(GCameraError::UnknownMimeType { mime: value }),
         };
     }
@@ -128,6 +412,25 @@ pub enum SemanticType {
 
     /// A gain map, used for UltraHDR image formats
     GainMap,
+
+    /// An image listed in the MPF index that is not described by the XMP
+    /// data.
+    Secondary,
+}
+
+impl SemanticType {
+    /// Get the semantic type as a string.
+    ///
+    /// # Returns
+    /// The semantic type, as it is written in the XMP data.
+    pub fn as_str(self) -> &'static str {
+        return match self {
+            Self::Primary => "Primary",
+            Self::MotionPhoto => "MotionPhoto",
+            Self::GainMap => "GainMap",
+            Self::Secondary => "Secondary",
+        };
+    }
 }
 
 /// Implementation to try to create a semantic enum from a string slice.
@@ -165,6 +468,43 @@ pub struct Description {
 
     /// TODO: Figure this out
     motion_photo_timestamp_us: Option<u32>,
+
+    /// Indicator for the presence of a legacy (version 1) motion photo.
+    micro_video: Option<u32>,
+
+    /// The version of the legacy motion photo.
+    micro_video_version: Option<u32>,
+
+    /// Offset of the legacy motion photo video from the end of the file.
+    micro_video_offset: Option<usize>,
+
+    /// Timestamp of the frame in the video that matches the still image,
+    /// or -1 if not specified.
+    micro_video_timestamp_us: Option<i64>,
+}
+
+impl Description {
+    /// Get the identifier of the extended XMP data.
+    ///
+    /// # Returns
+    /// The GUID of the extended XMP data, if the image has any.
+    pub fn extended_xmp_id(&self) -> Option<&str> {
+        return self.extended_xmp_id.as_deref();
+    }
+
+    /// Get the offset of the legacy micro video from the end of the file.
+    ///
+    /// Older motion photos store the video with `GCamera:MicroVideo*`
+    /// attributes instead of a `Container:Directory`.
+    ///
+    /// # Returns
+    /// The number of bytes from the start of the video to the end of the
+    /// file, if the image is a legacy motion photo.
+    pub fn micro_video_offset(&self) -> Option<usize> {
+        return self
+            .micro_video_offset
+            .filter(|_| return self.micro_video == Some(1));
+    }
 }
 
 /// Implementation to create description from XML Node
@@ -188,6 +528,14 @@ impl TryFrom<Node<'_, '_>> for Description {
                 GCAMERA_NS,
                 "MotionPhotoPresentationTimestampUs",
             )?,
+            micro_video: parse_attribute(xml_element, GCAMERA_NS, "MicroVideo")?,
+            micro_video_version: parse_attribute(xml_element, GCAMERA_NS, "MicroVideoVersion")?,
+            micro_video_offset: parse_attribute(xml_element, GCAMERA_NS, "MicroVideoOffset")?,
+            micro_video_timestamp_us: parse_attribute(
+                xml_element,
+                GCAMERA_NS,
+                "MicroVideoPresentationTimestampUs",
+            )?,
         });
     }
 }
@@ -216,6 +564,37 @@ pub struct Item {
     pub uri: Option<String>,
 }
 
+impl Item {
+    /// Convert the item to an entry of a `Container:Directory`.
+    ///
+    /// # Returns
+    /// The value of the `rdf:li` element that holds the item.
+    pub fn as_xmp_value(&self) -> XMPValue {
+        let simple = |name: &str, value: &str| {
+            return XMPProperty::new(ITEM_NS, name, XMPValue::Simple(String::from(value)));
+        };
+        let mut fields = vec![
+            simple("Mime", self.mimetype.as_str()),
+            simple("Semantic", self.semantic.as_str()),
+        ];
+        if let Some(length) = self.length {
+            fields.push(simple("Length", &length.to_string()));
+        }
+        fields.push(simple("Padding", &self.padding.to_string()));
+        if let Some(label) = &self.label {
+            fields.push(simple("Label", label));
+        }
+        if let Some(uri) = &self.uri {
+            fields.push(simple("URI", uri));
+        }
+        return XMPValue::Struct(vec![XMPProperty::new(
+            CONTAINER_NS,
+            "Item",
+            XMPValue::Struct(fields),
+        )]);
+    }
+}
+
 /// Implementation to create item from a XML Node.
 impl TryFrom<Node<'_, '_>> for Item {
     type Error = GCameraError;
@@ -240,33 +619,227 @@ pub struct XMPData {
 
     /// Vector of the resources defined in the file, according to the XMP data.
     pub resources: Vec<Item>,
+
+    /// All of the properties in the XMP data.
+    pub tree: XMPTree,
 }
 
 impl XMPData {
-    /// Create `JpegSegment` for XMP data that has no resources.
+    /// Look up a property by its namespace and path.
+    ///
+    /// See the `model` module for the syntax of paths.
+    ///
+    /// # Arguments
+    /// * `namespace`: The namespace of the first step of the path.
+    /// * `path`: The path of the property.
     ///
     /// # Returns
-    /// Segment for XMP Resource that does not have any resources.
-    pub fn as_resourceless_segment(&self) -> JpegSegment {
-        // FIXME: Test for case with depth map
+    /// The value at the path, or None if it could not be found.
+    pub fn get(&self, namespace: &str, path: &str) -> Option<&XMPValue> {
+        return self.tree.get(namespace, path);
+    }
 
-        let extended_xmp_note = match &self.description.extended_xmp_id {
-            Some(note) => format!("\n      xmpNote:HasExtendedXMP=\"{note}\""),
-            None => String::new(),
-        };
+    /// Look up the text of a property by its namespace and path.
+    ///
+    /// # Arguments
+    /// * `namespace`: The namespace of the first step of the path.
+    /// * `path`: The path of the property.
+    ///
+    /// # Returns
+    /// The text of the value at the path, or None if it could not be
+    /// found or is not a simple value.
+    pub fn get_str(&self, namespace: &str, path: &str) -> Option<&str> {
+        return self.tree.get_str(namespace, path);
+    }
+
+    /// Convert a legacy `MicroVideo` motion photo to the container format.
+    ///
+    /// The `GCamera:MicroVideo*` properties are replaced with the
+    /// `GCamera:MotionPhoto*` properties, and a `Container:Directory` that
+    /// lists the primary image and the video. The properties of the Extended
+    /// XMP data are merged into the result, since the Extended XMP has to be
+    /// split from the main XMP again when it is written.
+    ///
+    /// # Arguments
+    /// * `extended`: The Extended XMP data of the image, if it has any.
+    ///
+    /// # Returns
+    /// The converted properties.
+    ///
+    /// # Errors
+    /// Will error if the XMP data is not for a legacy motion photo.
+    pub fn convert_micro_video(&self, extended: Option<&XMPTree>) -> Result<XMPTree, GCameraError> {
+        let video_length = self
+            .description
+            .micro_video_offset()
+            .ok_or(GCameraError::NotMicroVideo)?;
+
+        let mut tree = self.merged_tree(extended);
+
+        for name in [
+            "MicroVideo",
+            "MicroVideoVersion",
+            "MicroVideoOffset",
+            "MicroVideoPresentationTimestampUs",
+        ] {
+            tree.remove(GCAMERA_NS, name);
+        }
+
+        tree.set(
+            GCAMERA_NS,
+            "MotionPhoto",
+            XMPValue::Simple(String::from("1")),
+        );
+        tree.set(
+            GCAMERA_NS,
+            "MotionPhotoVersion",
+            XMPValue::Simple(String::from("1")),
+        );
+        // A timestamp of -1 means that it is not known.
+        if let Some(timestamp) = self
+            .description
+            .micro_video_timestamp_us
+            .filter(|timestamp| return *timestamp >= 0)
+        {
+            tree.set(
+                GCAMERA_NS,
+                "MotionPhotoPresentationTimestampUs",
+                XMPValue::Simple(timestamp.to_string()),
+            );
+        }
 
-        let xml_string = format!(
-            "\
-<x:xmpmeta xmlns:x=\"adobe:ns:meta/\" x:xmptk=\"Adobe XMP Core 5.1.0-jc003\">
-  <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">
-    <rdf:Description rdf:about=\"\"
-      xmlns:xmpNote=\"http://ns.adobe.com/xmp/note/\"{extended_xmp_note}/>
-  </rdf:RDF>
-</x:xmpmeta>"
+        let items = [
+            Item {
+                mimetype: MimeType::Jpeg,
+                length: Some(0),
+                padding: 0,
+                semantic: SemanticType::Primary,
+                label: None,
+                uri: None,
+            },
+            Item {
+                mimetype: MimeType::Mp4,
+                length: Some(video_length),
+                padding: 0,
+                semantic: SemanticType::MotionPhoto,
+                label: None,
+                uri: None,
+            },
+        ];
+        tree.set(
+            CONTAINER_NS,
+            "Directory",
+            XMPValue::Array(
+                ArrayKind::Seq,
+                items.iter().map(Item::as_xmp_value).collect(),
+            ),
         );
-        let data = [XMP_MARKER, &[0x00], xml_string.as_bytes()].concat();
 
-        return JpegSegment::new(JpegMarker::APP1, &data);
+        for (uri, prefix) in [
+            (GCAMERA_NS, "GCamera"),
+            (CONTAINER_NS, "Container"),
+            (ITEM_NS, "Item"),
+        ] {
+            tree.namespaces
+                .entry(String::from(uri))
+                .or_insert_with(|| return String::from(prefix));
+        }
+
+        // Keep the simple properties first, so they are all written as
+        // attributes of a single description.
+        tree.properties
+            .sort_by_key(|property| return !matches!(property.value, XMPValue::Simple(_)));
+
+        return Ok(tree);
+    }
+
+    /// Remove the location and identifying properties.
+    ///
+    /// The properties of the Extended XMP data are merged into the result,
+    /// in the same way as when converting a legacy motion photo.
+    ///
+    /// # Arguments
+    /// * `extended`: The Extended XMP data of the image, if it has any.
+    /// * `keep_motion`: Whether to keep the properties describing the motion
+    ///   photo, and its item in the `Container:Directory`.
+    ///
+    /// # Returns
+    /// The remaining properties, and a description of each item that was
+    /// removed.
+    pub fn scrub(&self, extended: Option<&XMPTree>, keep_motion: bool) -> (XMPTree, Vec<String>) {
+        let mut tree = self.merged_tree(extended);
+        let mut removed = Vec::new();
+        let namespaces = tree.namespaces.clone();
+        tree.properties.retain(|property| {
+            let private = is_private_property(&property.namespace, &property.name)
+                || (!keep_motion && is_motion_property(&property.namespace, &property.name));
+            if private {
+                let prefix = namespaces
+                    .get(&property.namespace)
+                    .unwrap_or(&property.namespace);
+                removed.push(format!("XMP property {prefix}:{}", property.name));
+            }
+            return !private;
+        });
+
+        if !keep_motion {
+            let directory = tree.properties.iter_mut().find(|property| {
+                return property.namespace == CONTAINER_NS && property.name == "Directory";
+            });
+            if let Some(XMPProperty {
+                value: XMPValue::Array(_, items),
+                ..
+            }) = directory
+            {
+                let count = items.len();
+                items.retain(|item| {
+                    return item
+                        .field(CONTAINER_NS, "Item")
+                        .and_then(|fields| return fields.field(ITEM_NS, "Semantic"))
+                        .and_then(XMPValue::as_str)
+                        != Some(SemanticType::MotionPhoto.as_str());
+                });
+                for _ in items.len()..count {
+                    removed.push(String::from("XMP directory item MotionPhoto"));
+                }
+            }
+        }
+        return (tree, removed);
+    }
+
+    /// Merge the Extended XMP data into a copy of the main XMP data.
+    ///
+    /// # Arguments
+    /// * `extended`: The Extended XMP data of the image, if it has any.
+    ///
+    /// # Returns
+    /// All of the properties, without the `xmpNote:HasExtendedXMP` property.
+    fn merged_tree(&self, extended: Option<&XMPTree>) -> XMPTree {
+        let mut tree = self.tree.clone();
+        if let Some(extended_tree) = extended {
+            for (uri, prefix) in &extended_tree.namespaces {
+                tree.namespaces
+                    .entry(uri.clone())
+                    .or_insert_with(|| return prefix.clone());
+            }
+            tree.properties
+                .extend(extended_tree.properties.iter().cloned());
+        }
+        tree.remove(XMP_NOTE_NS, "HasExtendedXMP");
+        return tree;
+    }
+
+    /// Serialize the XMP data to RDF/XML.
+    ///
+    /// Parsing the output again gives data equal to this instance.
+    ///
+    /// # Arguments
+    /// * `options`: Options controlling the output.
+    ///
+    /// # Returns
+    /// The XMP data, as an XML string.
+    pub fn serialize(&self, options: SerializeOptions) -> String {
+        return serialize(&self.tree, options);
     }
 }
 
@@ -290,11 +863,12 @@ impl TryFrom<Document<'_>> for XMPData {
             let resource_nodes = document
                 .descendants()
                 .filter(|n| return n.tag_name() == ExpandedName::from((CONTAINER_NS, "Item")))
-                .map(|n| return Item::try_from(n).unwrap()); // FIXME: Get rid of unwrap
+                .map(Item::try_from);
 
             return Ok(Self {
                 description: Description::try_from(node)?,
-                resources: resource_nodes.collect(),
+                resources: resource_nodes.collect::<Result<Vec<Item>, GCameraError>>()?,
+                tree: XMPTree::from(&document),
             });
         } else {
             return Err(GCameraError::DescriptionNodeNotFound);
@@ -497,6 +1071,7 @@ mod tests {
             let cases = vec![
                 (String::from("image/jpeg"), MimeType::Jpeg),
                 (String::from("video/mp4"), MimeType::Mp4),
+                (String::from("image/png"), MimeType::Png),
             ];
 
             for (input, expected) in cases {
@@ -580,9 +1155,68 @@ mod tests {
                     motion_photo: Some(1),
                     motion_photo_version: Some(1),
                     motion_photo_timestamp_us: Some(968644),
+                    micro_video: None,
+                    micro_video_version: None,
+                    micro_video_offset: None,
+                    micro_video_timestamp_us: None,
                 }),
             );
         }
+
+        /// Test initializing from a legacy `MicroVideo` description.
+        #[test]
+        fn test_init_micro_video() {
+            let test_xml =
+                "<rdf:Description xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\"
+                    rdf:about=\"\"
+                    xmlns:GCamera=\"http://ns.google.com/photos/1.0/camera/\"
+                    GCamera:MicroVideo=\"1\"
+                    GCamera:MicroVideoVersion=\"1\"
+                    GCamera:MicroVideoOffset=\"2451179\"
+                    GCamera:MicroVideoPresentationTimestampUs=\"-1\"/>";
+
+            let document = Document::parse(test_xml).unwrap();
+            let xml_element = document
+                .descendants()
+                .find(|n| return n.tag_name().name() == "Description")
+                .unwrap();
+            let description = Description::try_from(xml_element).unwrap();
+
+            assert_eq!(
+                description,
+                Description {
+                    extended_xmp_id: None,
+                    motion_photo: None,
+                    motion_photo_version: None,
+                    motion_photo_timestamp_us: None,
+                    micro_video: Some(1),
+                    micro_video_version: Some(1),
+                    micro_video_offset: Some(2451179),
+                    micro_video_timestamp_us: Some(-1),
+                },
+            );
+            assert_eq!(description.micro_video_offset(), Some(2451179));
+        }
+
+        /// Test that the `MicroVideoOffset` is ignored if `MicroVideo` is not set.
+        #[test]
+        fn test_micro_video_offset_disabled() {
+            let test_xml =
+                "<rdf:Description xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\"
+                    rdf:about=\"\"
+                    xmlns:GCamera=\"http://ns.google.com/photos/1.0/camera/\"
+                    GCamera:MicroVideo=\"0\"
+                    GCamera:MicroVideoOffset=\"2451179\"/>";
+
+            let document = Document::parse(test_xml).unwrap();
+            let xml_element = document
+                .descendants()
+                .find(|n| return n.tag_name().name() == "Description")
+                .unwrap();
+            let description = Description::try_from(xml_element).unwrap();
+
+            assert_eq!(description.micro_video_offset(), None);
+        }
     }
 
     mod test_item {
@@ -692,37 +1326,43 @@ mod tests {
             )
             .unwrap();
 
-            let data = XMPData::try_from(document);
+            let data = XMPData::try_from(document).unwrap();
 
             assert_eq!(
-                data,
-                Ok(XMPData {
-                    description: Description {
-                        extended_xmp_id: Some(String::from("DD558CA2166AEC119A42CDFB02D4F1EF")),
-                        motion_photo: Some(1),
-                        motion_photo_version: Some(1),
-                        motion_photo_timestamp_us: Some(968644),
+                data.description,
+                Description {
+                    extended_xmp_id: Some(String::from("DD558CA2166AEC119A42CDFB02D4F1EF")),
+                    motion_photo: Some(1),
+                    motion_photo_version: Some(1),
+                    motion_photo_timestamp_us: Some(968644),
+                    micro_video: None,
+                    micro_video_version: None,
+                    micro_video_offset: None,
+                    micro_video_timestamp_us: None,
+                }
+            );
+            assert_eq!(
+                data.resources,
+                vec![
+                    Item {
+                        mimetype: MimeType::Jpeg,
+                        semantic: SemanticType::Primary,
+                        length: Some(0),
+                        padding: 0,
+                        uri: None,
+                        label: None,
                     },
-                    resources: vec![
-                        Item {
-                            mimetype: MimeType::Jpeg,
-                            semantic: SemanticType::Primary,
-                            length: Some(0),
-                            padding: 0,
-                            uri: None,
-                            label: None,
-                        },
-                        Item {
-                            mimetype: MimeType::Mp4,
-                            semantic: SemanticType::MotionPhoto,
-                            length: Some(4906025),
-                            padding: 0,
-                            uri: None,
-                            label: None,
-                        },
-                    ],
-                },)
+                    Item {
+                        mimetype: MimeType::Mp4,
+                        semantic: SemanticType::MotionPhoto,
+                        length: Some(4906025),
+                        padding: 0,
+                        uri: None,
+                        label: None,
+                    },
+                ]
             );
+            assert_eq!(data.get_str(GCAMERA_NS, "MotionPhotoVersion"), Some("1"));
         }
 
         /// Test for when there is no description node
@@ -779,37 +1419,43 @@ mod tests {
                 </x:xmpmeta>",
             );
 
-            let data = XMPData::try_from(xml_string);
+            let data = XMPData::try_from(xml_string).unwrap();
 
             assert_eq!(
-                data,
-                Ok(XMPData {
-                    description: Description {
-                        extended_xmp_id: Some(String::from("DD558CA2166AEC119A42CDFB02D4F1EF")),
-                        motion_photo: Some(1),
-                        motion_photo_version: Some(1),
-                        motion_photo_timestamp_us: Some(968644),
+                data.description,
+                Description {
+                    extended_xmp_id: Some(String::from("DD558CA2166AEC119A42CDFB02D4F1EF")),
+                    motion_photo: Some(1),
+                    motion_photo_version: Some(1),
+                    motion_photo_timestamp_us: Some(968644),
+                    micro_video: None,
+                    micro_video_version: None,
+                    micro_video_offset: None,
+                    micro_video_timestamp_us: None,
+                }
+            );
+            assert_eq!(
+                data.resources,
+                vec![
+                    Item {
+                        mimetype: MimeType::Jpeg,
+                        semantic: SemanticType::Primary,
+                        length: Some(0),
+                        padding: 0,
+                        uri: None,
+                        label: None,
                     },
-                    resources: vec![
-                        Item {
-                            mimetype: MimeType::Jpeg,
-                            semantic: SemanticType::Primary,
-                            length: Some(0),
-                            padding: 0,
-                            uri: None,
-                            label: None,
-                        },
-                        Item {
-                            mimetype: MimeType::Mp4,
-                            semantic: SemanticType::MotionPhoto,
-                            length: Some(4906025),
-                            padding: 0,
-                            uri: None,
-                            label: None,
-                        },
-                    ],
-                }),
+                    Item {
+                        mimetype: MimeType::Mp4,
+                        semantic: SemanticType::MotionPhoto,
+                        length: Some(4906025),
+                        padding: 0,
+                        uri: None,
+                        label: None,
+                    },
+                ]
             );
+            assert_eq!(data.get_str(GCAMERA_NS, "MotionPhotoVersion"), Some("1"));
         }
 
         /// Test case for invalid string
@@ -825,57 +1471,263 @@ mod tests {
             ));
         }
 
-        /// Test the `as_resourceless_segment` method when there is extended XMP data
+        /// Test converting a legacy `MicroVideo` motion photo.
         #[test]
-        fn test_resourceless_segment_with_extended() {
-            let expected_data_bytes = "\
-http://ns.adobe.com/xap/1.0/\x00<x:xmpmeta xmlns:x=\"adobe:ns:meta/\" x:xmptk=\"Adobe XMP Core 5.1.0-jc003\">
-  <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">
-    <rdf:Description rdf:about=\"\"
-      xmlns:xmpNote=\"http://ns.adobe.com/xmp/note/\"
-      xmpNote:HasExtendedXMP=\"ABCDEFG\"/>
-  </rdf:RDF>
-</x:xmpmeta>".as_bytes();
-            let data = XMPData {
-                description: Description {
-                    extended_xmp_id: Some(String::from("ABCDEFG")),
+        fn test_convert_micro_video() {
+            let data = XMPData::try_from(String::from(
+                "<x:xmpmeta xmlns:x='adobe:ns:meta/'>
+                <rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'>
+                    <rdf:Description rdf:about=''
+                    xmlns:xmp='http://ns.adobe.com/xap/1.0/'
+                    xmlns:xmpNote='http://ns.adobe.com/xmp/note/'
+                    xmlns:GCamera='http://ns.google.com/photos/1.0/camera/'
+                    xmpNote:HasExtendedXMP='DD558CA2166AEC119A42CDFB02D4F1EF'
+                    GCamera:MicroVideo='1'
+                    GCamera:MicroVideoVersion='1'
+                    GCamera:MicroVideoOffset='2451179'
+                    GCamera:MicroVideoPresentationTimestampUs='968644'>
+                    <xmp:Rating>4</xmp:Rating>
+                    </rdf:Description>
+                </rdf:RDF>
+                </x:xmpmeta>",
+            ))
+            .unwrap();
+            let mut extended = XMPTree::default();
+            extended
+                .namespaces
+                .insert(String::from("http://ns.example.com/"), String::from("ex"));
+            extended.set(
+                "http://ns.example.com/",
+                "Data",
+                XMPValue::Simple(String::from("abc")),
+            );
+
+            let tree = data.convert_micro_video(Some(&extended)).unwrap();
+            let converted =
+                XMPData::try_from(serialize(&tree, SerializeOptions::default())).unwrap();
+
+            assert_eq!(
+                converted.description,
+                Description {
+                    extended_xmp_id: None,
                     motion_photo: Some(1),
                     motion_photo_version: Some(1),
-                    motion_photo_timestamp_us: Some(5),
-                },
-                resources: Vec::new(),
-            };
-            let created_segment = data.as_resourceless_segment();
+                    motion_photo_timestamp_us: Some(968644),
+                    micro_video: None,
+                    micro_video_version: None,
+                    micro_video_offset: None,
+                    micro_video_timestamp_us: None,
+                }
+            );
             assert_eq!(
-                created_segment,
-                JpegSegment::new(JpegMarker::APP1, expected_data_bytes)
+                converted.resources,
+                vec![
+                    Item {
+                        mimetype: MimeType::Jpeg,
+                        length: Some(0),
+                        padding: 0,
+                        semantic: SemanticType::Primary,
+                        label: None,
+                        uri: None,
+                    },
+                    Item {
+                        mimetype: MimeType::Mp4,
+                        length: Some(2451179),
+                        padding: 0,
+                        semantic: SemanticType::MotionPhoto,
+                        label: None,
+                        uri: None,
+                    },
+                ]
+            );
+            assert_eq!(
+                converted.get_str("http://ns.adobe.com/xap/1.0/", "Rating"),
+                Some("4")
+            );
+            assert_eq!(
+                converted.get_str("http://ns.example.com/", "Data"),
+                Some("abc")
             );
         }
 
-        /// Test the `as_resourceless_segment` method when there is no extended XMP data
+        /// Test converting data that is not a legacy motion photo.
         #[test]
-        fn test_resourceless_segment_without_extended() {
-            let expected_data_bytes = "\
-http://ns.adobe.com/xap/1.0/\x00<x:xmpmeta xmlns:x=\"adobe:ns:meta/\" x:xmptk=\"Adobe XMP Core 5.1.0-jc003\">
-  <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">
-    <rdf:Description rdf:about=\"\"
-      xmlns:xmpNote=\"http://ns.adobe.com/xmp/note/\"/>
-  </rdf:RDF>
-</x:xmpmeta>".as_bytes();
-            let data = XMPData {
-                description: Description {
-                    extended_xmp_id: None,
-                    motion_photo: Some(1),
-                    motion_photo_version: Some(1),
-                    motion_photo_timestamp_us: Some(5),
-                },
-                resources: Vec::new(),
-            };
-            let created_segment = data.as_resourceless_segment();
+        fn test_convert_micro_video_not_legacy() {
+            let data = XMPData::try_from(String::from(
+                "<x:xmpmeta xmlns:x='adobe:ns:meta/'>
+                <rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'>
+                    <rdf:Description rdf:about=''
+                    xmlns:GCamera='http://ns.google.com/photos/1.0/camera/'
+                    GCamera:MotionPhoto='1'/>
+                </rdf:RDF>
+                </x:xmpmeta>",
+            ))
+            .unwrap();
+
+            assert_eq!(
+                data.convert_micro_video(None),
+                Err(GCameraError::NotMicroVideo)
+            );
+        }
+
+        /// XMP data of a motion photo with location and identifying properties.
+        const PRIVATE_XMP: &str = "<x:xmpmeta xmlns:x='adobe:ns:meta/'>
+            <rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'>
+                <rdf:Description rdf:about=''
+                xmlns:xmp='http://ns.adobe.com/xap/1.0/'
+                xmlns:exif='http://ns.adobe.com/exif/1.0/'
+                xmlns:aux='http://ns.adobe.com/exif/1.0/aux/'
+                xmlns:photoshop='http://ns.adobe.com/photoshop/1.0/'
+                xmlns:GCamera='http://ns.google.com/photos/1.0/camera/'
+                xmlns:Container='http://ns.google.com/photos/1.0/container/'
+                xmlns:Item='http://ns.google.com/photos/1.0/container/item/'
+                xmp:Rating='4'
+                exif:GPSLatitude='52,22.0N'
+                aux:SerialNumber='12345'
+                photoshop:City='Amsterdam'
+                GCamera:MotionPhoto='1'>
+                <Container:Directory>
+                    <rdf:Seq>
+                    <rdf:li rdf:parseType='Resource'>
+                        <Container:Item Item:Mime='image/jpeg' Item:Semantic='Primary' Item:Length='0'/>
+                    </rdf:li>
+                    <rdf:li rdf:parseType='Resource'>
+                        <Container:Item Item:Mime='video/mp4' Item:Semantic='MotionPhoto' Item:Length='100'/>
+                    </rdf:li>
+                    </rdf:Seq>
+                </Container:Directory>
+                </rdf:Description>
+            </rdf:RDF>
+            </x:xmpmeta>";
+
+        /// Test removing the location, identifying and motion photo properties.
+        #[test]
+        fn test_scrub() {
+            let data = XMPData::try_from(String::from(PRIVATE_XMP)).unwrap();
+            let (tree, removed) = data.scrub(None, false);
+            let scrubbed =
+                XMPData::try_from(serialize(&tree, SerializeOptions::default())).unwrap();
+
+            assert_eq!(
+                removed,
+                vec![
+                    String::from("XMP property exif:GPSLatitude"),
+                    String::from("XMP property aux:SerialNumber"),
+                    String::from("XMP property photoshop:City"),
+                    String::from("XMP property GCamera:MotionPhoto"),
+                    String::from("XMP directory item MotionPhoto"),
+                ]
+            );
+            assert_eq!(scrubbed.description.motion_photo, None);
+            assert_eq!(scrubbed.resources.len(), 1);
             assert_eq!(
-                created_segment,
-                JpegSegment::new(JpegMarker::APP1, expected_data_bytes)
+                scrubbed.get_str("http://ns.adobe.com/xap/1.0/", "Rating"),
+                Some("4")
             );
         }
+
+        /// Test removing the location properties, while keeping the motion photo.
+        #[test]
+        fn test_scrub_keep_motion() {
+            let data = XMPData::try_from(String::from(PRIVATE_XMP)).unwrap();
+            let (tree, removed) = data.scrub(None, true);
+            let scrubbed =
+                XMPData::try_from(serialize(&tree, SerializeOptions::default())).unwrap();
+
+            assert_eq!(removed.len(), 3);
+            assert_eq!(scrubbed.description.motion_photo, Some(1));
+            assert_eq!(scrubbed.resources, data.resources);
+        }
+    }
+    /// Test finding the range of attributes with both types of quote.
+    #[test]
+    fn test_attribute_range() {
+        let text = "<a b='1' c=\"2'3\"/>";
+        assert_eq!(text.get(attribute_range(text, 3)), Some("b='1'"));
+        assert_eq!(text.get(attribute_range(text, 9)), Some("c=\"2'3\""));
+    }
+
+    /// Tests for the `strip_resources` function.
+    mod test_strip_resources {
+        use super::*;
+
+        /// Test that only the resource properties are removed.
+        #[test]
+        fn test_strip_resources() {
+            let xmp = "<x:xmpmeta xmlns:x='adobe:ns:meta/' x:xmptk='Adobe XMP Core 5.1.0-jc003'>
+                <rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'>
+                    <rdf:Description rdf:about=''
+                    xmlns:xmpNote='http://ns.adobe.com/xmp/note/'
+                    xmlns:xmp='http://ns.adobe.com/xap/1.0/'
+                    xmlns:hdrgm='http://ns.adobe.com/hdr-gain-map/1.0/'
+                    xmlns:GCamera='http://ns.google.com/photos/1.0/camera/'
+                    xmlns:Container='http://ns.google.com/photos/1.0/container/'
+                    xmlns:Item='http://ns.google.com/photos/1.0/container/item/'
+                    xmpNote:HasExtendedXMP='DD558CA2166AEC119A42CDFB02D4F1EF'
+                    xmp:Rating='4'
+                    hdrgm:Version='1.0'
+                    GCamera:SpecialTypeID='com.google.android.apps.camera.gallery.specialtype.SpecialType-MOTION_PHOTO'
+                    GCamera:MotionPhoto='1'
+                    GCamera:MotionPhotoVersion='1'
+                    GCamera:MotionPhotoPresentationTimestampUs='968644'>
+                    <Container:Directory>
+                        <rdf:Seq>
+                        <rdf:li rdf:parseType='Resource'>
+                            <Container:Item
+                            Item:Mime='image/jpeg'
+                            Item:Semantic='Primary'
+                            Item:Length='0'
+                            Item:Padding='0' />
+                        </rdf:li>
+                        </rdf:Seq>
+                    </Container:Directory>
+                    </rdf:Description>
+                    <rdf:Description rdf:about='' xmlns:dc='http://purl.org/dc/elements/1.1/'>
+                    <dc:subject><rdf:Bag><rdf:li>cat</rdf:li></rdf:Bag></dc:subject>
+                    </rdf:Description>
+                </rdf:RDF>
+                </x:xmpmeta>";
+
+            let expected = "<x:xmpmeta xmlns:x='adobe:ns:meta/' x:xmptk='Adobe XMP Core 5.1.0-jc003'>
+                <rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'>
+                    <rdf:Description rdf:about=''
+                    xmlns:xmpNote='http://ns.adobe.com/xmp/note/'
+                    xmlns:xmp='http://ns.adobe.com/xap/1.0/'
+                    xmlns:hdrgm='http://ns.adobe.com/hdr-gain-map/1.0/'
+                    xmlns:GCamera='http://ns.google.com/photos/1.0/camera/'
+                    xmlns:Container='http://ns.google.com/photos/1.0/container/'
+                    xmlns:Item='http://ns.google.com/photos/1.0/container/item/'
+                    xmpNote:HasExtendedXMP='DD558CA2166AEC119A42CDFB02D4F1EF'
+                    xmp:Rating='4'
+                    hdrgm:Version='1.0'
+                    GCamera:SpecialTypeID='com.google.android.apps.camera.gallery.specialtype.SpecialType-MOTION_PHOTO'
+                    \n                    \n                    >
+                    \n                    </rdf:Description>
+                    <rdf:Description rdf:about='' xmlns:dc='http://purl.org/dc/elements/1.1/'>
+                    <dc:subject><rdf:Bag><rdf:li>cat</rdf:li></rdf:Bag></dc:subject>
+                    </rdf:Description>
+                </rdf:RDF>
+                </x:xmpmeta>";
+
+            assert_eq!(strip_resources(xmp), Ok(String::from(expected)));
+        }
+
+        /// Test removing motion photo properties that are stored as elements.
+        #[test]
+        fn test_strip_element_properties() {
+            let xmp = "<rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description xmlns:GCamera='http://ns.google.com/photos/1.0/camera/'><GCamera:MotionPhoto>1</GCamera:MotionPhoto><GCamera:BurstID>abc</GCamera:BurstID></rdf:Description></rdf:RDF>";
+            let expected = "<rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'><rdf:Description xmlns:GCamera='http://ns.google.com/photos/1.0/camera/'><GCamera:BurstID>abc</GCamera:BurstID></rdf:Description></rdf:RDF>";
+
+            assert_eq!(strip_resources(xmp), Ok(String::from(expected)));
+        }
+
+        /// Test stripping XMP data that is not valid XML.
+        #[test]
+        fn test_strip_invalid() {
+            assert!(matches!(
+                strip_resources("Hello World"),
+                Err(GCameraError::XMLParsingError { .. })
+            ));
+        }
     }
 }
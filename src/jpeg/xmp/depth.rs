@@ -0,0 +1,480 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Logic for reading the depth map and original image of portrait photos.
+//!
+//! Portrait photos store a depth map using the `GDepth` namespace, the
+//! settings of the lens blur effect using the `GFocus` namespace, and the
+//! original image without any blur using the `GImage` namespace. The depth
+//! map and image data are usually too large for the main XMP data, so they
+//! are found in the Extended XMP data.
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use std::fs;
+use std::path::Path;
+
+use crate::errors::GCameraError;
+use crate::jpeg::xmp::model::{find_str, parse_property, required, XMPTree};
+use crate::jpeg::xmp::MimeType;
+
+/// Google depth map info.
+pub const GDEPTH_NS: &str = "http://ns.google.com/photos/1.0/depthmap/";
+
+/// Google lens blur focus info.
+pub const GFOCUS_NS: &str = "http://ns.google.com/photos/1.0/focus/";
+
+/// Google original image info.
+pub const GIMAGE_NS: &str = "http://ns.google.com/photos/1.0/image/";
+
+/// Enumeration of the ways depth values can be stored in the depth map.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DepthFormat {
+    /// The values are the inverse of the depth, scaled between near and far.
+    RangeInverse,
+
+    /// The values are the depth, scaled linearly between near and far.
+    RangeLinear,
+}
+
+impl DepthFormat {
+    /// Get the depth format as a string.
+    ///
+    /// # Returns
+    /// The depth format, as it is written in the XMP data.
+    pub fn as_str(self) -> &'static str {
+        return match self {
+            Self::RangeInverse => "RangeInverse",
+            Self::RangeLinear => "RangeLinear",
+        };
+    }
+}
+
+impl TryFrom<&str> for DepthFormat {
+    type Error = GCameraError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        return match value {
+            "RangeInverse" => Ok(Self::RangeInverse),
+            "RangeLinear" => Ok(Self::RangeLinear),
+            _ => Err(GCameraError::UnknownDepthFormat {
+                format: String::from(value),
+            }),
+        };
+    }
+}
+
+/// Settings of the lens blur effect.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct FocusInfo {
+    /// The amount of blur to apply to objects at infinity.
+    pub blur_at_infinity: Option<f64>,
+
+    /// The distance to the plane in focus, in the units of the depth map.
+    pub focal_distance: Option<f64>,
+
+    /// The horizontal position of the focal point, between 0 and 1.
+    pub focal_point_x: Option<f64>,
+
+    /// The vertical position of the focal point, between 0 and 1.
+    pub focal_point_y: Option<f64>,
+}
+
+/// A depth map read from the XMP data.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DepthMap {
+    /// The way the depth values are stored.
+    pub format: DepthFormat,
+
+    /// The depth of the nearest point.
+    pub near: f64,
+
+    /// The depth of the furthest point.
+    pub far: f64,
+
+    /// The units of `near` and `far`, such as `m` or `mm`.
+    pub units: Option<String>,
+
+    /// How the depth was measured, such as `OpticalAxis` or `OpticRay`.
+    pub measure_type: Option<String>,
+
+    /// The MIME type of the depth image.
+    pub mimetype: MimeType,
+
+    /// The decoded depth image.
+    pub data: Vec<u8>,
+
+    /// Settings of the lens blur effect.
+    pub focus: FocusInfo,
+}
+
+/// The original image of a lens blur photo, read from the XMP data.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OriginalImage {
+    /// The MIME type of the image.
+    pub mimetype: MimeType,
+
+    /// The decoded image.
+    pub data: Vec<u8>,
+}
+
+/// Decode a property that holds base64 data.
+///
+/// Any whitespace in the data is ignored.
+///
+/// # Arguments
+/// * `trees`: The trees to search, in order.
+/// * `namespace`: The namespace of the property.
+/// * `name`: The name of the property.
+///
+/// # Returns
+/// The decoded data.
+///
+/// # Errors
+/// Will error if the property is missing, or is not valid base64.
+fn decode_property(
+    trees: &[&XMPTree],
+    namespace: &str,
+    name: &str,
+) -> Result<Vec<u8>, GCameraError> {
+    let encoded: String = required(find_str(trees, namespace, name), name)?
+        .chars()
+        .filter(|character| return !character.is_ascii_whitespace())
+        .collect();
+    return STANDARD.decode(encoded).map_err(|_| {
+        return GCameraError::InvalidBase64 {
+            property: String::from(name),
+        };
+    });
+}
+
+impl FocusInfo {
+    /// Read the lens blur settings from XMP data.
+    ///
+    /// # Arguments
+    /// * `trees`: The trees to read from, in order of precedence.
+    ///
+    /// # Returns
+    /// The lens blur settings.
+    ///
+    /// # Errors
+    /// Will error if one of the settings is not a number.
+    pub fn from_trees(trees: &[&XMPTree]) -> Result<Self, GCameraError> {
+        return Ok(Self {
+            blur_at_infinity: parse_property(trees, GFOCUS_NS, "BlurAtInfinity")?,
+            focal_distance: parse_property(trees, GFOCUS_NS, "FocalDistance")?,
+            focal_point_x: parse_property(trees, GFOCUS_NS, "FocalPointX")?,
+            focal_point_y: parse_property(trees, GFOCUS_NS, "FocalPointY")?,
+        });
+    }
+}
+
+impl DepthMap {
+    /// Read the depth map from XMP data.
+    ///
+    /// # Arguments
+    /// * `trees`: The trees to read from, in order of precedence. This is
+    ///   usually the main XMP data, followed by the Extended XMP data.
+    ///
+    /// # Returns
+    /// The depth map.
+    ///
+    /// # Errors
+    /// Will error if there is no depth map, or if any of its properties are
+    /// missing or invalid.
+    pub fn from_trees(trees: &[&XMPTree]) -> Result<Self, GCameraError> {
+        let format = find_str(trees, GDEPTH_NS, "Format").ok_or(GCameraError::NoDepthMap)?;
+        return Ok(Self {
+            format: DepthFormat::try_from(format)?,
+            near: required(parse_property(trees, GDEPTH_NS, "Near")?, "Near")?,
+            far: required(parse_property(trees, GDEPTH_NS, "Far")?, "Far")?,
+            units: find_str(trees, GDEPTH_NS, "Units").map(String::from),
+            measure_type: find_str(trees, GDEPTH_NS, "MeasureType").map(String::from),
+            mimetype: MimeType::try_from(String::from(required(
+                find_str(trees, GDEPTH_NS, "Mime"),
+                "Mime",
+            )?))?,
+            data: decode_property(trees, GDEPTH_NS, "Data")?,
+            focus: FocusInfo::from_trees(trees)?,
+        });
+    }
+
+    /// Get a description of the parameters of the depth map.
+    ///
+    /// # Returns
+    /// String with one `Name: value` line for each parameter.
+    pub fn params_str(&self) -> String {
+        let optional = [
+            ("Units", self.units.clone()),
+            ("MeasureType", self.measure_type.clone()),
+            (
+                "BlurAtInfinity",
+                self.focus
+                    .blur_at_infinity
+                    .map(|value| return value.to_string()),
+            ),
+            (
+                "FocalDistance",
+                self.focus
+                    .focal_distance
+                    .map(|value| return value.to_string()),
+            ),
+            (
+                "FocalPointX",
+                self.focus
+                    .focal_point_x
+                    .map(|value| return value.to_string()),
+            ),
+            (
+                "FocalPointY",
+                self.focus
+                    .focal_point_y
+                    .map(|value| return value.to_string()),
+            ),
+        ];
+
+        let mut lines = vec![
+            format!("Format: {}", self.format.as_str()),
+            format!("Near: {}", self.near),
+            format!("Far: {}", self.far),
+            format!("Mime: {}", self.mimetype.as_str()),
+        ];
+        lines.extend(
+            optional
+                .into_iter()
+                .filter_map(|(name, value)| return Some(format!("{name}: {}", value?))),
+        );
+        let mut params = lines.join("\n");
+        params.push('\n');
+        return params;
+    }
+
+    /// Save the depth image, and a text file holding its parameters.
+    ///
+    /// The parameters are saved next to the image, with a `txt` extension.
+    ///
+    /// # Arguments
+    /// * `filepath`: Path to save the depth image to.
+    ///
+    /// # Returns
+    /// Result of saving the files.
+    ///
+    /// # Errors
+    /// Will error if writing either file to the disk fails.
+    pub fn save(&self, filepath: &Path) -> Result<(), GCameraError> {
+        fs::write(filepath, &self.data)
+            .map_err(|error| return GCameraError::DepthWriteError { kind: error.kind() })?;
+        return fs::write(filepath.with_extension("txt"), self.params_str())
+            .map_err(|error| return GCameraError::DepthWriteError { kind: error.kind() });
+    }
+}
+
+impl OriginalImage {
+    /// Read the original image from XMP data.
+    ///
+    /// # Arguments
+    /// * `trees`: The trees to read from, in order of precedence. This is
+    ///   usually the main XMP data, followed by the Extended XMP data.
+    ///
+    /// # Returns
+    /// The original image.
+    ///
+    /// # Errors
+    /// Will error if there is no original image, or if it is not valid.
+    pub fn from_trees(trees: &[&XMPTree]) -> Result<Self, GCameraError> {
+        let mime = find_str(trees, GIMAGE_NS, "Mime").ok_or(GCameraError::NoOriginalImage)?;
+        return Ok(Self {
+            mimetype: MimeType::try_from(String::from(mime))?,
+            data: decode_property(trees, GIMAGE_NS, "Data")?,
+        });
+    }
+
+    /// Save the original image to a file.
+    ///
+    /// # Arguments
+    /// * `filepath`: Path to save the image to.
+    ///
+    /// # Errors
+    /// Will error if writing the image to the disk fails.
+    pub fn save(&self, filepath: &Path) -> Result<(), GCameraError> {
+        return fs::write(filepath, &self.data)
+            .map_err(|error| return GCameraError::OriginalImageWriteError { kind: error.kind() });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jpeg::xmp::XMPData;
+
+    /// Main XMP data of a portrait photo.
+    const MAIN_XMP: &str = "<x:xmpmeta xmlns:x='adobe:ns:meta/'>
+        <rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'>
+            <rdf:Description rdf:about=''
+                xmlns:GFocus='http://ns.google.com/photos/1.0/focus/'
+                xmlns:GDepth='http://ns.google.com/photos/1.0/depthmap/'
+                xmlns:GImage='http://ns.google.com/photos/1.0/image/'
+                GImage:Mime='image/jpeg'
+                GFocus:BlurAtInfinity='0.0125'
+                GFocus:FocalDistance='1.5'
+                GFocus:FocalPointX='0.5'
+                GFocus:FocalPointY='0.25'
+                GDepth:Format='RangeInverse'
+                GDepth:Near='0.5'
+                GDepth:Far='12.25'
+                GDepth:Units='m'
+                GDepth:MeasureType='OpticalAxis'
+                GDepth:Mime='image/png'/>
+        </rdf:RDF>
+    </x:xmpmeta>";
+
+    /// Extended XMP data of a portrait photo.
+    const EXTENDED_XMP: &str = "<x:xmpmeta xmlns:x='adobe:ns:meta/'>
+        <rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'>
+            <rdf:Description rdf:about=''
+                xmlns:GDepth='http://ns.google.com/photos/1.0/depthmap/'
+                xmlns:GImage='http://ns.google.com/photos/1.0/image/'
+                GImage:Data='/9j/4A=='
+                GDepth:Data='iVBO
+                    Rw=='/>
+        </rdf:RDF>
+    </x:xmpmeta>";
+
+    /// Parse XMP data into a tree.
+    ///
+    /// # Arguments
+    /// * `xmp`: The XMP data to parse.
+    ///
+    /// # Returns
+    /// The tree of the XMP data.
+    fn tree(xmp: &str) -> XMPTree {
+        return XMPData::try_from(String::from(xmp)).unwrap().tree;
+    }
+
+    /// Test reading a depth map split across main and Extended XMP.
+    #[test]
+    fn test_from_trees() {
+        let main = tree(MAIN_XMP);
+        let extended = tree(EXTENDED_XMP);
+        let depth_map = DepthMap::from_trees(&[&main, &extended]);
+
+        assert_eq!(
+            depth_map,
+            Ok(DepthMap {
+                format: DepthFormat::RangeInverse,
+                near: 0.5,
+                far: 12.25,
+                units: Some(String::from("m")),
+                measure_type: Some(String::from("OpticalAxis")),
+                mimetype: MimeType::Png,
+                data: vec![0x89, 0x50, 0x4E, 0x47],
+                focus: FocusInfo {
+                    blur_at_infinity: Some(0.0125),
+                    focal_distance: Some(1.5),
+                    focal_point_x: Some(0.5),
+                    focal_point_y: Some(0.25),
+                },
+            })
+        );
+    }
+
+    /// Test reading from data without a depth map.
+    #[test]
+    fn test_no_depth_map() {
+        let extended = tree(EXTENDED_XMP);
+        assert_eq!(
+            DepthMap::from_trees(&[&extended]),
+            Err(GCameraError::NoDepthMap)
+        );
+    }
+
+    /// Test reading a depth map whose data is missing.
+    #[test]
+    fn test_missing_data() {
+        let main = tree(MAIN_XMP);
+        assert_eq!(
+            DepthMap::from_trees(&[&main]),
+            Err(GCameraError::XMLMissingAttribute {
+                attribute: String::from("Data")
+            })
+        );
+    }
+
+    /// Test reading a depth map whose data is not valid base64.
+    #[test]
+    fn test_invalid_data() {
+        let main = tree(MAIN_XMP);
+        let extended = tree(&EXTENDED_XMP.replace("Rw==", "R!=="));
+        assert_eq!(
+            DepthMap::from_trees(&[&main, &extended]),
+            Err(GCameraError::InvalidBase64 {
+                property: String::from("Data")
+            })
+        );
+    }
+
+    /// Test reading the original image split across main and Extended XMP.
+    #[test]
+    fn test_original_image() {
+        let main = tree(MAIN_XMP);
+        let extended = tree(EXTENDED_XMP);
+
+        assert_eq!(
+            OriginalImage::from_trees(&[&main, &extended]),
+            Ok(OriginalImage {
+                mimetype: MimeType::Jpeg,
+                data: vec![0xFF, 0xD8, 0xFF, 0xE0],
+            })
+        );
+        assert_eq!(
+            OriginalImage::from_trees(&[&extended]),
+            Err(GCameraError::NoOriginalImage)
+        );
+        assert_eq!(
+            OriginalImage::from_trees(&[&main]),
+            Err(GCameraError::XMLMissingAttribute {
+                attribute: String::from("Data")
+            })
+        );
+    }
+
+    /// Test the `try_from` method of `DepthFormat`.
+    #[test]
+    fn test_depth_format() {
+        assert_eq!(
+            DepthFormat::try_from("RangeLinear"),
+            Ok(DepthFormat::RangeLinear)
+        );
+        assert_eq!(
+            DepthFormat::try_from("Hello"),
+            Err(GCameraError::UnknownDepthFormat {
+                format: String::from("Hello")
+            })
+        );
+    }
+
+    /// Test the `params_str` method.
+    #[test]
+    fn test_params_str() {
+        let main = tree(MAIN_XMP);
+        let extended = tree(EXTENDED_XMP);
+        let mut depth_map = DepthMap::from_trees(&[&main, &extended]).unwrap();
+        depth_map.focus.focal_point_y = None;
+
+        assert_eq!(
+            depth_map.params_str(),
+            "\
+Format: RangeInverse
+Near: 0.5
+Far: 12.25
+Mime: image/png
+Units: m
+MeasureType: OpticalAxis
+BlurAtInfinity: 0.0125
+FocalDistance: 1.5
+FocalPointX: 0.5
+"
+        );
+    }
+}
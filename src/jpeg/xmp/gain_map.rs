@@ -0,0 +1,394 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Logic for reading the metadata of `UltraHDR` gain maps.
+//!
+//! The primary image of an `UltraHDR` photo holds `hdrgm:Version` in its XMP
+//! data, and the embedded gain map JPEG holds the rest of the `hdrgm`
+//! properties. Properties that can differ per color channel are either a
+//! single value, or an `rdf:Seq` with one value for each channel.
+use crate::errors::GCameraError;
+use crate::jpeg::xmp::model::{find_str, parse_property, parse_text, required, XMPTree, XMPValue};
+
+/// Adobe gain map info.
+pub const HDRGM_NS: &str = "http://ns.adobe.com/hdr-gain-map/1.0/";
+
+/// Default value of `hdrgm:OffsetSDR` and `hdrgm:OffsetHDR`.
+const DEFAULT_OFFSET: f64 = 0.015_625;
+
+/// Largest difference between two values that are considered the same.
+const MAX_DIFFERENCE: f64 = 0.000_01;
+
+/// Metadata describing how to apply a gain map to the primary image.
+#[derive(Debug, PartialEq, Clone)]
+pub struct GainMapMetadata {
+    /// The version of the gain map format.
+    pub version: String,
+
+    /// The log2 of the minimum gain, for each color channel.
+    pub gain_map_min: [f64; 3],
+
+    /// The log2 of the maximum gain, for each color channel.
+    pub gain_map_max: [f64; 3],
+
+    /// The gamma applied to the stored gain map values, for each color channel.
+    pub gamma: [f64; 3],
+
+    /// Offset added to the SDR pixel values, for each color channel.
+    pub offset_sdr: [f64; 3],
+
+    /// Offset added to the HDR pixel values, for each color channel.
+    pub offset_hdr: [f64; 3],
+
+    /// The log2 of the display headroom at which the gain map starts to be applied.
+    pub hdr_capacity_min: f64,
+
+    /// The log2 of the display headroom at which the gain map is fully applied.
+    pub hdr_capacity_max: f64,
+
+    /// Whether the primary image is the HDR rendition.
+    pub base_rendition_is_hdr: bool,
+}
+
+/// Parse a property that may hold one value for each color channel.
+///
+/// # Arguments
+/// * `trees`: The trees to search, in order.
+/// * `name`: The name of the property.
+///
+/// # Returns
+/// The value for each channel, or None if the property was not found.
+///
+/// # Errors
+/// Will error if the values cannot be parsed, or there is not either one
+/// value or three values.
+fn parse_channels(trees: &[&XMPTree], name: &str) -> Result<Option<[f64; 3]>, GCameraError> {
+    let Some(value) = trees
+        .iter()
+        .find_map(|tree| return tree.get(HDRGM_NS, name))
+    else {
+        return Ok(None);
+    };
+
+    let texts: Vec<&str> = match value {
+        XMPValue::Array(_, items) => items.iter().filter_map(XMPValue::as_str).collect(),
+        XMPValue::Simple(_) | XMPValue::Localized { .. } | XMPValue::Struct(_) => {
+            value.as_str().into_iter().collect()
+        }
+    };
+    let values = texts
+        .into_iter()
+        .map(parse_text)
+        .collect::<Result<Vec<f64>, GCameraError>>()?;
+
+    return match values.as_slice() {
+        [single] => Ok(Some([*single; 3])),
+        [red, green, blue] => Ok(Some([*red, *green, *blue])),
+        _ => Err(GCameraError::InvalidGainMapChannels {
+            property: String::from(name),
+            count: values.len(),
+        }),
+    };
+}
+
+impl GainMapMetadata {
+    /// Read the gain map metadata from XMP data.
+    ///
+    /// # Arguments
+    /// * `trees`: The trees to read from, in order of precedence. This is
+    ///   usually the XMP data of the gain map JPEG, followed by the XMP
+    ///   data of the primary image.
+    ///
+    /// # Returns
+    /// The gain map metadata, with defaults filled in for any optional
+    /// properties that are missing.
+    ///
+    /// # Errors
+    /// Will error if there is no gain map metadata, or if any of its
+    /// properties are missing or invalid.
+    pub fn from_trees(trees: &[&XMPTree]) -> Result<Self, GCameraError> {
+        let version =
+            find_str(trees, HDRGM_NS, "Version").ok_or(GCameraError::NoGainMapMetadata)?;
+        let base_rendition_is_hdr = match find_str(trees, HDRGM_NS, "BaseRenditionIsHDR") {
+            Some(text) => match text.trim() {
+                "True" | "true" => true,
+                "False" | "false" => false,
+                _ => {
+                    return Err(GCameraError::XMLAttributeParseError {
+                        attribute: Some(String::from(text)),
+                    })
+                }
+            },
+            None => false,
+        };
+
+        return Ok(Self {
+            version: String::from(version),
+            gain_map_min: parse_channels(trees, "GainMapMin")?.unwrap_or([0.0; 3]),
+            gain_map_max: required(parse_channels(trees, "GainMapMax")?, "GainMapMax")?,
+            gamma: parse_channels(trees, "Gamma")?.unwrap_or([1.0; 3]),
+            offset_sdr: parse_channels(trees, "OffsetSDR")?.unwrap_or([DEFAULT_OFFSET; 3]),
+            offset_hdr: parse_channels(trees, "OffsetHDR")?.unwrap_or([DEFAULT_OFFSET; 3]),
+            hdr_capacity_min: parse_property(trees, HDRGM_NS, "HDRCapacityMin")?.unwrap_or(0.0),
+            hdr_capacity_max: required(
+                parse_property(trees, HDRGM_NS, "HDRCapacityMax")?,
+                "HDRCapacityMax",
+            )?,
+            base_rendition_is_hdr,
+        });
+    }
+
+    /// Compare the gain map parameters with another copy of the metadata.
+    ///
+    /// The version is not compared, since the XMP and ISO 21496-1 copies
+    /// use different version numbers.
+    ///
+    /// # Arguments
+    /// * `other`: The metadata to compare with.
+    ///
+    /// # Returns
+    /// The names of the parameters that differ.
+    #[allow(clippy::float_arithmetic)] // One copy holds fractions, so the values need a tolerance
+    pub fn differences(&self, other: &Self) -> Vec<&'static str> {
+        let close = |first: f64, second: f64| return (first - second).abs() <= MAX_DIFFERENCE;
+        let close_channels = |first: [f64; 3], second: [f64; 3]| {
+            return first
+                .iter()
+                .zip(second.iter())
+                .all(|(first_value, second_value)| return close(*first_value, *second_value));
+        };
+
+        return [
+            (
+                "GainMapMin",
+                close_channels(self.gain_map_min, other.gain_map_min),
+            ),
+            (
+                "GainMapMax",
+                close_channels(self.gain_map_max, other.gain_map_max),
+            ),
+            ("Gamma", close_channels(self.gamma, other.gamma)),
+            (
+                "OffsetSDR",
+                close_channels(self.offset_sdr, other.offset_sdr),
+            ),
+            (
+                "OffsetHDR",
+                close_channels(self.offset_hdr, other.offset_hdr),
+            ),
+            (
+                "HDRCapacityMin",
+                close(self.hdr_capacity_min, other.hdr_capacity_min),
+            ),
+            (
+                "HDRCapacityMax",
+                close(self.hdr_capacity_max, other.hdr_capacity_max),
+            ),
+            (
+                "BaseRenditionIsHDR",
+                self.base_rendition_is_hdr == other.base_rendition_is_hdr,
+            ),
+        ]
+        .into_iter()
+        .filter(|(_, same)| return !same)
+        .map(|(name, _)| return name)
+        .collect();
+    }
+
+    /// Get a description of the gain map metadata.
+    ///
+    /// # Returns
+    /// String with one line for each value.
+    pub fn info_str(&self) -> String {
+        return format!(
+            "\
+Gain map version:        {}
+Gain map min:            {:?}
+Gain map max:            {:?}
+Gain map gamma:          {:?}
+Gain map offset SDR:     {:?}
+Gain map offset HDR:     {:?}
+HDR capacity min:        {}
+HDR capacity max:        {}
+Base rendition is HDR:   {}",
+            self.version,
+            self.gain_map_min,
+            self.gain_map_max,
+            self.gamma,
+            self.offset_sdr,
+            self.offset_hdr,
+            self.hdr_capacity_min,
+            self.hdr_capacity_max,
+            self.base_rendition_is_hdr,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jpeg::xmp::XMPData;
+
+    /// XMP data of the primary image of an `UltraHDR` photo.
+    const PRIMARY_XMP: &str = "<x:xmpmeta xmlns:x='adobe:ns:meta/'>
+        <rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'>
+            <rdf:Description rdf:about=''
+                xmlns:hdrgm='http://ns.adobe.com/hdr-gain-map/1.0/'
+                hdrgm:Version='1.0'/>
+        </rdf:RDF>
+    </x:xmpmeta>";
+
+    /// XMP data of the gain map image of an `UltraHDR` photo.
+    const GAIN_MAP_XMP: &str = "<x:xmpmeta xmlns:x='adobe:ns:meta/'>
+        <rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'>
+            <rdf:Description rdf:about=''
+                xmlns:hdrgm='http://ns.adobe.com/hdr-gain-map/1.0/'
+                hdrgm:Version='1.0'
+                hdrgm:GainMapMin='0'
+                hdrgm:Gamma='1'
+                hdrgm:OffsetSDR='0'
+                hdrgm:OffsetHDR='0'
+                hdrgm:HDRCapacityMin='0'
+                hdrgm:HDRCapacityMax='2.3'
+                hdrgm:BaseRenditionIsHDR='False'>
+                <hdrgm:GainMapMax>
+                    <rdf:Seq>
+                        <rdf:li>2.5</rdf:li>
+                        <rdf:li>2.25</rdf:li>
+                        <rdf:li>2</rdf:li>
+                    </rdf:Seq>
+                </hdrgm:GainMapMax>
+            </rdf:Description>
+        </rdf:RDF>
+    </x:xmpmeta>";
+
+    /// Parse XMP data into a tree.
+    ///
+    /// # Arguments
+    /// * `xmp`: The XMP data to parse.
+    ///
+    /// # Returns
+    /// The tree of the XMP data.
+    fn tree(xmp: &str) -> XMPTree {
+        return XMPData::try_from(String::from(xmp)).unwrap().tree;
+    }
+
+    /// Test reading the metadata from the gain map and primary image.
+    #[test]
+    fn test_from_trees() {
+        let gain_map = tree(GAIN_MAP_XMP);
+        let primary = tree(PRIMARY_XMP);
+
+        assert_eq!(
+            GainMapMetadata::from_trees(&[&gain_map, &primary]),
+            Ok(GainMapMetadata {
+                version: String::from("1.0"),
+                gain_map_min: [0.0; 3],
+                gain_map_max: [2.5, 2.25, 2.0],
+                gamma: [1.0; 3],
+                offset_sdr: [0.0; 3],
+                offset_hdr: [0.0; 3],
+                hdr_capacity_min: 0.0,
+                hdr_capacity_max: 2.3,
+                base_rendition_is_hdr: false,
+            })
+        );
+    }
+
+    /// Test that the defaults are used for missing optional properties.
+    #[test]
+    fn test_defaults() {
+        let gain_map = tree(
+            "<x:xmpmeta xmlns:x='adobe:ns:meta/'>
+            <rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'>
+                <rdf:Description rdf:about=''
+                    xmlns:hdrgm='http://ns.adobe.com/hdr-gain-map/1.0/'
+                    hdrgm:GainMapMax='3'
+                    hdrgm:HDRCapacityMax='3'/>
+            </rdf:RDF>
+        </x:xmpmeta>",
+        );
+        let primary = tree(PRIMARY_XMP);
+
+        assert_eq!(
+            GainMapMetadata::from_trees(&[&gain_map, &primary]),
+            Ok(GainMapMetadata {
+                version: String::from("1.0"),
+                gain_map_min: [0.0; 3],
+                gain_map_max: [3.0; 3],
+                gamma: [1.0; 3],
+                offset_sdr: [DEFAULT_OFFSET; 3],
+                offset_hdr: [DEFAULT_OFFSET; 3],
+                hdr_capacity_min: 0.0,
+                hdr_capacity_max: 3.0,
+                base_rendition_is_hdr: false,
+            })
+        );
+    }
+
+    /// Test reading data that does not have any gain map metadata.
+    #[test]
+    fn test_no_metadata() {
+        assert_eq!(
+            GainMapMetadata::from_trees(&[&XMPTree::default()]),
+            Err(GCameraError::NoGainMapMetadata)
+        );
+    }
+
+    /// Test reading the primary image alone, which is missing required values.
+    #[test]
+    fn test_missing_required() {
+        let primary = tree(PRIMARY_XMP);
+        assert_eq!(
+            GainMapMetadata::from_trees(&[&primary]),
+            Err(GCameraError::XMLMissingAttribute {
+                attribute: String::from("GainMapMax")
+            })
+        );
+    }
+
+    /// Test a per channel property with the wrong number of values.
+    #[test]
+    fn test_bad_channel_count() {
+        let gain_map = tree(&GAIN_MAP_XMP.replace("<rdf:li>2</rdf:li>", ""));
+        assert_eq!(
+            GainMapMetadata::from_trees(&[&gain_map]),
+            Err(GCameraError::InvalidGainMapChannels {
+                property: String::from("GainMapMax"),
+                count: 2
+            })
+        );
+    }
+
+    /// Test comparing two copies of the metadata.
+    #[test]
+    fn test_differences() {
+        let gain_map = tree(GAIN_MAP_XMP);
+        let metadata = GainMapMetadata::from_trees(&[&gain_map]).unwrap();
+        let mut other = metadata.clone();
+        other.version = String::from("0.0");
+        other.hdr_capacity_max = 2.300_000_1;
+        assert!(metadata.differences(&other).is_empty());
+
+        other.gamma = [1.0, 1.5, 1.0];
+        other.base_rendition_is_hdr = true;
+        assert_eq!(
+            metadata.differences(&other),
+            vec!["Gamma", "BaseRenditionIsHDR"]
+        );
+    }
+
+    /// Test an invalid boolean value.
+    #[test]
+    fn test_bad_boolean() {
+        let gain_map = tree(&GAIN_MAP_XMP.replace("'False'", "'Maybe'"));
+        assert_eq!(
+            GainMapMetadata::from_trees(&[&gain_map]),
+            Err(GCameraError::XMLAttributeParseError {
+                attribute: Some(String::from("Maybe"))
+            })
+        );
+    }
+}
@@ -0,0 +1,770 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Generic model of the properties stored in XMP data.
+//!
+//! Every property in the XMP data is kept, no matter its namespace. Each
+//! property can be a simple value, a structure of other properties, or an
+//! array (`rdf:Seq`, `rdf:Bag` or `rdf:Alt`) of values. Properties can be
+//! looked up using a namespace and a path.
+//!
+//! # Paths
+//! A path is a list of steps separated by `/`. Each step is the name of a
+//! property, optionally followed by a one-based array index in square
+//! brackets. The first step is looked up in the namespace given with the
+//! path. Later steps may be prefixed with one of the namespace prefixes
+//! declared in the XMP data, otherwise the namespace of the previous step
+//! is used. For example, the MIME type of the second item in a Google
+//! container directory can be found with the namespace
+//! `http://ns.google.com/photos/1.0/container/` and the path
+//! `Directory[2]/Item/Item:Mime`.
+use roxmltree::{Document, ExpandedName, Node};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use crate::errors::GCameraError;
+use crate::jpeg::xmp::RDF_NS;
+
+/// Namespace of the `x:xmpmeta` wrapper element.
+pub const META_NS: &str = "adobe:ns:meta/";
+
+/// Namespace of the built in XML attributes, such as `xml:lang`.
+pub const XML_NS: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// Enumeration of the different kinds of XMP arrays.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ArrayKind {
+    /// Ordered array (`rdf:Seq`).
+    Seq,
+
+    /// Unordered array (`rdf:Bag`).
+    Bag,
+
+    /// Array of alternatives (`rdf:Alt`).
+    Alt,
+}
+
+impl ArrayKind {
+    /// Get the name of the RDF element used for the array kind.
+    ///
+    /// # Returns
+    /// The local name of the RDF element.
+    pub fn rdf_name(self) -> &'static str {
+        return match self {
+            Self::Seq => "Seq",
+            Self::Bag => "Bag",
+            Self::Alt => "Alt",
+        };
+    }
+
+    /// Get the array kind from the local name of an RDF element.
+    ///
+    /// # Arguments
+    /// * `name`: The local name of the RDF element.
+    ///
+    /// # Returns
+    /// The array kind, or None if the name is not an array element.
+    fn from_rdf_name(name: &str) -> Option<Self> {
+        return match name {
+            "Seq" => Some(Self::Seq),
+            "Bag" => Some(Self::Bag),
+            "Alt" => Some(Self::Alt),
+            _ => None,
+        };
+    }
+}
+
+/// The value of a single XMP property.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum XMPValue {
+    /// A simple text value.
+    Simple(String),
+
+    /// A text value with an `xml:lang` qualifier.
+    Localized {
+        /// The language of the value.
+        language: String,
+
+        /// The text value.
+        value: String,
+    },
+
+    /// A structure of named fields.
+    Struct(Vec<XMPProperty>),
+
+    /// An array of values.
+    Array(ArrayKind, Vec<XMPValue>),
+}
+
+impl XMPValue {
+    /// Get the value as a string.
+    ///
+    /// # Returns
+    /// The text of the value, or None if it is not a simple or localized value.
+    pub fn as_str(&self) -> Option<&str> {
+        return match self {
+            Self::Simple(value) | Self::Localized { value, .. } => Some(value),
+            Self::Struct(_) | Self::Array(_, _) => None,
+        };
+    }
+
+    /// Get the fields of the value, if it is a structure.
+    ///
+    /// # Returns
+    /// The fields of the structure, or None if it is not a structure.
+    pub fn as_struct(&self) -> Option<&[XMPProperty]> {
+        return match self {
+            Self::Struct(fields) => Some(fields),
+            Self::Simple(_) | Self::Localized { .. } | Self::Array(_, _) => None,
+        };
+    }
+
+    /// Get the items of the value, if it is an array.
+    ///
+    /// # Returns
+    /// The items of the array, or None if it is not an array.
+    pub fn as_array(&self) -> Option<&[XMPValue]> {
+        return match self {
+            Self::Array(_, items) => Some(items),
+            Self::Simple(_) | Self::Localized { .. } | Self::Struct(_) => None,
+        };
+    }
+
+    /// Get a field of the value, if it is a structure.
+    ///
+    /// # Arguments
+    /// * `namespace`: The namespace of the field.
+    /// * `name`: The name of the field.
+    ///
+    /// # Returns
+    /// The value of the field, or None if it could not be found.
+    pub fn field(&self, namespace: &str, name: &str) -> Option<&XMPValue> {
+        return find_property(self.as_struct()?, namespace, name);
+    }
+}
+
+/// A single named XMP property.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct XMPProperty {
+    /// The namespace URI of the property.
+    pub namespace: String,
+
+    /// The name of the property within its namespace.
+    pub name: String,
+
+    /// The value of the property.
+    pub value: XMPValue,
+}
+
+impl XMPProperty {
+    /// Create a new property.
+    ///
+    /// # Arguments
+    /// * `namespace`: The namespace URI of the property.
+    /// * `name`: The name of the property.
+    /// * `value`: The value of the property.
+    ///
+    /// # Returns
+    /// The created property.
+    pub fn new(namespace: &str, name: &str, value: XMPValue) -> Self {
+        return Self {
+            namespace: String::from(namespace),
+            name: String::from(name),
+            value,
+        };
+    }
+}
+
+/// Find a property in a list of properties.
+///
+/// # Arguments
+/// * `properties`: The properties to search.
+/// * `namespace`: The namespace of the property.
+/// * `name`: The name of the property.
+///
+/// # Returns
+/// The value of the first matching property, or None.
+fn find_property<'props>(
+    properties: &'props [XMPProperty],
+    namespace: &str,
+    name: &str,
+) -> Option<&'props XMPValue> {
+    return properties
+        .iter()
+        .find(|property| return property.namespace == namespace && property.name == name)
+        .map(|property| return &property.value);
+}
+
+/// Split a path step into its name and array index.
+///
+/// # Arguments
+/// * `step`: The step to split, such as `Directory[2]`.
+///
+/// # Returns
+/// Tuple of the name and the optional one-based index, or None if the
+/// index is not a valid number.
+fn split_index(step: &str) -> Option<(&str, Option<usize>)> {
+    return match step
+        .strip_suffix(']')
+        .and_then(|s| return s.split_once('['))
+    {
+        Some((name, index)) => Some((name, Some(index.parse().ok()?))),
+        None => Some((step, None)),
+    };
+}
+
+/// All of the properties in XMP data.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct XMPTree {
+    /// The namespaces declared in the XMP data, mapping URIs to prefixes.
+    pub namespaces: BTreeMap<String, String>,
+
+    /// The top level properties.
+    pub properties: Vec<XMPProperty>,
+}
+
+impl XMPTree {
+    /// Get the URI of a namespace prefix declared in the data.
+    ///
+    /// # Arguments
+    /// * `prefix`: The prefix to find the URI of.
+    ///
+    /// # Returns
+    /// The URI of the prefix, or None if the prefix is not declared.
+    pub fn namespace_uri(&self, prefix: &str) -> Option<&str> {
+        return self
+            .namespaces
+            .iter()
+            .find(|(_, declared)| return *declared == prefix)
+            .map(|(uri, _)| return uri.as_str());
+    }
+
+    /// Look up a property by its namespace and path.
+    ///
+    /// See the module documentation for the syntax of paths.
+    ///
+    /// # Arguments
+    /// * `namespace`: The namespace of the first step of the path.
+    /// * `path`: The path of the property.
+    ///
+    /// # Returns
+    /// The value at the path, or None if it could not be found.
+    pub fn get(&self, namespace: &str, path: &str) -> Option<&XMPValue> {
+        let mut current_namespace = String::from(namespace);
+        let mut current_value: Option<&XMPValue> = None;
+
+        for step in path.split('/') {
+            let (qualified_name, index) = split_index(step)?;
+            let name = match qualified_name.split_once(':') {
+                Some((prefix, name)) => {
+                    current_namespace = String::from(self.namespace_uri(prefix)?);
+                    name
+                }
+                None => qualified_name,
+            };
+
+            let fields = match current_value {
+                Some(value) => value.as_struct()?,
+                None => &self.properties,
+            };
+            let found = find_property(fields, &current_namespace, name)?;
+
+            current_value = match index {
+                Some(position) => Some(found.as_array()?.get(position.checked_sub(1)?)?),
+                None => Some(found),
+            };
+        }
+
+        return current_value;
+    }
+
+    /// Look up the text of a property by its namespace and path.
+    ///
+    /// # Arguments
+    /// * `namespace`: The namespace of the first step of the path.
+    /// * `path`: The path of the property.
+    ///
+    /// # Returns
+    /// The text of the value at the path, or None if it could not be found
+    /// or is not a simple value.
+    pub fn get_str(&self, namespace: &str, path: &str) -> Option<&str> {
+        return self.get(namespace, path)?.as_str();
+    }
+
+    /// Set the value of a top level property.
+    ///
+    /// If the property already exists, its value is replaced in place.
+    /// Otherwise, it is added to the end of the properties.
+    ///
+    /// # Arguments
+    /// * `namespace`: The namespace of the property.
+    /// * `name`: The name of the property.
+    /// * `value`: The new value of the property.
+    pub fn set(&mut self, namespace: &str, name: &str, value: XMPValue) {
+        match self
+            .properties
+            .iter_mut()
+            .find(|property| return property.namespace == namespace && property.name == name)
+        {
+            Some(property) => property.value = value,
+            None => self
+                .properties
+                .push(XMPProperty::new(namespace, name, value)),
+        }
+    }
+
+    /// Remove a top level property.
+    ///
+    /// # Arguments
+    /// * `namespace`: The namespace of the property.
+    /// * `name`: The name of the property.
+    ///
+    /// # Returns
+    /// The value of the removed property, or None if it was not found.
+    pub fn remove(&mut self, namespace: &str, name: &str) -> Option<XMPValue> {
+        let index = self
+            .properties
+            .iter()
+            .position(|property| return property.namespace == namespace && property.name == name)?;
+        return Some(self.properties.remove(index).value);
+    }
+}
+
+/// Parse the text of a property using the `str.parse` method.
+///
+/// Whitespace around the text is ignored.
+///
+/// # Arguments
+/// * `text`: The text to parse.
+///
+/// # Returns
+/// The parsed value.
+///
+/// # Errors
+/// Will error if the text cannot be parsed.
+pub fn parse_text<T: FromStr>(text: &str) -> Result<T, GCameraError> {
+    return text.trim().parse().map_err(|_| {
+        return GCameraError::XMLAttributeParseError {
+            attribute: Some(String::from(text)),
+        };
+    });
+}
+
+/// Find the text of a property in the first tree that has it.
+///
+/// # Arguments
+/// * `trees`: The trees to search, in order.
+/// * `namespace`: The namespace of the property.
+/// * `name`: The name of the property.
+///
+/// # Returns
+/// The text of the property, or None if no tree has it.
+pub fn find_str<'tree>(
+    trees: &[&'tree XMPTree],
+    namespace: &str,
+    name: &str,
+) -> Option<&'tree str> {
+    return trees
+        .iter()
+        .find_map(|tree| return tree.get_str(namespace, name));
+}
+
+/// Parse a property using the `str.parse` method.
+///
+/// # Arguments
+/// * `trees`: The trees to search, in order.
+/// * `namespace`: The namespace of the property.
+/// * `name`: The name of the property.
+///
+/// # Returns
+/// Option holding the parsed property, or None if no tree has it.
+///
+/// # Errors
+/// Will error if the property cannot be parsed.
+pub fn parse_property<T: FromStr>(
+    trees: &[&XMPTree],
+    namespace: &str,
+    name: &str,
+) -> Result<Option<T>, GCameraError> {
+    return find_str(trees, namespace, name).map(parse_text).transpose();
+}
+
+/// Convert a missing property into an error.
+///
+/// # Arguments
+/// * `value`: The value of the property, if it was found.
+/// * `name`: The name of the property.
+///
+/// # Returns
+/// The value of the property.
+///
+/// # Errors
+/// Will error if the property was not found.
+pub fn required<T>(value: Option<T>, name: &str) -> Result<T, GCameraError> {
+    return value.ok_or(GCameraError::XMLMissingAttribute {
+        attribute: String::from(name),
+    });
+}
+
+/// Check if a namespace URI is one that is used for the XMP/RDF syntax itself.
+///
+/// # Arguments
+/// * `uri`: The URI to check.
+///
+/// # Returns
+/// Boolean indicating if the namespace is a syntax namespace.
+pub fn is_syntax_namespace(uri: &str) -> bool {
+    return uri == RDF_NS || uri == META_NS || uri == XML_NS;
+}
+
+/// Check if a node is an RDF element with the given name.
+///
+/// # Arguments
+/// * `node`: The node to check.
+/// * `name`: The local name of the RDF element.
+///
+/// # Returns
+/// Boolean indicating if the node is the RDF element.
+fn is_rdf(node: Node, name: &str) -> bool {
+    return node.tag_name() == ExpandedName::from((RDF_NS, name));
+}
+
+/// Parse the properties stored as attributes on a node.
+///
+/// # Arguments
+/// * `node`: The node to read the attributes of.
+///
+/// # Returns
+/// Vector of the properties.
+fn parse_attribute_properties(node: Node) -> Vec<XMPProperty> {
+    return node
+        .attributes()
+        .filter_map(|attribute| {
+            let namespace = attribute.namespace()?;
+            if is_syntax_namespace(namespace) {
+                return None;
+            }
+            return Some(XMPProperty::new(
+                namespace,
+                attribute.name(),
+                XMPValue::Simple(String::from(attribute.value())),
+            ));
+        })
+        .collect();
+}
+
+/// Parse the properties stored as child elements of a node.
+///
+/// # Arguments
+/// * `node`: The node to read the children of.
+///
+/// # Returns
+/// Vector of the properties.
+fn parse_element_properties(node: Node) -> Vec<XMPProperty> {
+    return node
+        .children()
+        .filter(|child| return child.is_element())
+        .filter_map(|child| {
+            let namespace = child.tag_name().namespace()?;
+            return Some(XMPProperty::new(
+                namespace,
+                child.tag_name().name(),
+                parse_value(child),
+            ));
+        })
+        .collect();
+}
+
+/// Parse the value of a property element, or of an array item.
+///
+/// # Arguments
+/// * `node`: The property element or `rdf:li` element.
+///
+/// # Returns
+/// The parsed value.
+fn parse_value(node: Node) -> XMPValue {
+    if node.attribute((RDF_NS, "parseType")) == Some("Resource") {
+        return XMPValue::Struct(parse_element_properties(node));
+    }
+    if let Some(resource) = node.attribute((RDF_NS, "resource")) {
+        return XMPValue::Simple(String::from(resource));
+    }
+
+    if let Some(child) = node.children().find(|child| return child.is_element()) {
+        if let Some(kind) = child
+            .tag_name()
+            .namespace()
+            .filter(|namespace| return *namespace == RDF_NS)
+            .and_then(|_| return ArrayKind::from_rdf_name(child.tag_name().name()))
+        {
+            let items = child
+                .children()
+                .filter(|item| return is_rdf(*item, "li"))
+                .map(parse_value)
+                .collect();
+            return XMPValue::Array(kind, items);
+        }
+        if is_rdf(child, "Description") {
+            let mut fields = parse_attribute_properties(child);
+            fields.extend(parse_element_properties(child));
+            return XMPValue::Struct(fields);
+        }
+    }
+
+    // Structures can also be written with their fields as attributes.
+    let mut fields = parse_attribute_properties(node);
+    fields.extend(parse_element_properties(node));
+    if !fields.is_empty() {
+        return XMPValue::Struct(fields);
+    }
+
+    let text = String::from(node.text().unwrap_or_default());
+    return match node.attribute((XML_NS, "lang")) {
+        Some(language) => XMPValue::Localized {
+            language: String::from(language),
+            value: text,
+        },
+        None => XMPValue::Simple(text),
+    };
+}
+
+/// Implementation to create the tree from an XML Document.
+impl From<&Document<'_>> for XMPTree {
+    /// Create an instance from an XML Document.
+    ///
+    /// The properties of all of the `rdf:Description` elements are merged
+    /// into a single tree.
+    ///
+    /// # Arguments
+    /// * `document`: The XML Document to create the instance from.
+    ///
+    /// # Returns
+    /// Instance created from the given XML Document.
+    fn from(document: &Document) -> Self {
+        let mut namespaces = BTreeMap::new();
+        for node in document.descendants() {
+            for namespace in node.namespaces() {
+                if let Some(prefix) = namespace.name() {
+                    if !is_syntax_namespace(namespace.uri()) {
+                        namespaces
+                            .entry(String::from(namespace.uri()))
+                            .or_insert_with(|| return String::from(prefix));
+                    }
+                }
+            }
+        }
+
+        let properties = document
+            .descendants()
+            .filter(|node| {
+                return is_rdf(*node, "Description")
+                    && node
+                        .parent_element()
+                        .is_some_and(|parent| return is_rdf(parent, "RDF"));
+            })
+            .flat_map(|description| {
+                let mut description_properties = parse_attribute_properties(description);
+                description_properties.extend(parse_element_properties(description));
+                return description_properties;
+            })
+            .collect();
+
+        return Self {
+            namespaces,
+            properties,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// XMP data used in the tests.
+    const TEST_XMP: &str =
+        "<x:xmpmeta xmlns:x='adobe:ns:meta/' x:xmptk='Adobe XMP Core 5.1.0-jc003'>
+        <rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'>
+            <rdf:Description rdf:about=''
+                xmlns:xmp='http://ns.adobe.com/xap/1.0/'
+                xmlns:Container='http://ns.google.com/photos/1.0/container/'
+                xmlns:Item='http://ns.google.com/photos/1.0/container/item/'
+                xmp:Rating='4'>
+                <Container:Directory>
+                    <rdf:Seq>
+                        <rdf:li rdf:parseType='Resource'>
+                            <Container:Item Item:Mime='image/jpeg' Item:Semantic='Primary'/>
+                        </rdf:li>
+                        <rdf:li rdf:parseType='Resource'>
+                            <Container:Item Item:Mime='video/mp4' Item:Semantic='MotionPhoto'/>
+                        </rdf:li>
+                    </rdf:Seq>
+                </Container:Directory>
+            </rdf:Description>
+            <rdf:Description rdf:about=''
+                xmlns:dc='http://purl.org/dc/elements/1.1/'
+                xmlns:exif='http://ns.adobe.com/exif/1.0/'>
+                <dc:subject><rdf:Bag><rdf:li>cat</rdf:li><rdf:li>dog</rdf:li></rdf:Bag></dc:subject>
+                <dc:title><rdf:Alt><rdf:li xml:lang='x-default'>Pets</rdf:li></rdf:Alt></dc:title>
+                <dc:source rdf:resource='http://example.com/'/>
+                <exif:Flash rdf:parseType='Resource'>
+                    <exif:Fired>False</exif:Fired>
+                </exif:Flash>
+                <exif:OECF><rdf:Description exif:Columns='2'/></exif:OECF>
+            </rdf:Description>
+        </rdf:RDF>
+    </x:xmpmeta>";
+
+    /// Namespace of the Dublin Core properties.
+    const DC_NS: &str = "http://purl.org/dc/elements/1.1/";
+
+    /// Namespace of the EXIF properties.
+    const EXIF_NS: &str = "http://ns.adobe.com/exif/1.0/";
+
+    /// Namespace of the container properties.
+    const CONTAINER_NS: &str = "http://ns.google.com/photos/1.0/container/";
+
+    /// Parse the test XMP data into a tree.
+    ///
+    /// # Returns
+    /// The parsed tree.
+    fn test_tree() -> XMPTree {
+        return XMPTree::from(&Document::parse(TEST_XMP).unwrap());
+    }
+
+    /// Test the namespaces that are read from the document.
+    #[test]
+    fn test_namespaces() {
+        let tree = test_tree();
+        assert_eq!(
+            tree.namespaces.values().collect::<Vec<&String>>(),
+            vec!["exif", "xmp", "Container", "Item", "dc"]
+        );
+        assert_eq!(tree.namespace_uri("dc"), Some(DC_NS));
+        assert_eq!(tree.namespace_uri("rdf"), None);
+    }
+
+    /// Test getting a simple property that is stored as an attribute.
+    #[test]
+    fn test_get_attribute() {
+        assert_eq!(
+            test_tree().get_str("http://ns.adobe.com/xap/1.0/", "Rating"),
+            Some("4")
+        );
+    }
+
+    /// Test getting items from a bag.
+    #[test]
+    fn test_get_bag() {
+        let tree = test_tree();
+        assert_eq!(
+            tree.get(DC_NS, "subject"),
+            Some(&XMPValue::Array(
+                ArrayKind::Bag,
+                vec![
+                    XMPValue::Simple(String::from("cat")),
+                    XMPValue::Simple(String::from("dog"))
+                ]
+            ))
+        );
+        assert_eq!(tree.get_str(DC_NS, "subject[2]"), Some("dog"));
+        assert_eq!(tree.get(DC_NS, "subject[0]"), None);
+        assert_eq!(tree.get(DC_NS, "subject[3]"), None);
+    }
+
+    /// Test getting a localized item of an alternative array.
+    #[test]
+    fn test_get_alt() {
+        assert_eq!(
+            test_tree().get(DC_NS, "title[1]"),
+            Some(&XMPValue::Localized {
+                language: String::from("x-default"),
+                value: String::from("Pets")
+            })
+        );
+    }
+
+    /// Test getting a property that uses `rdf:resource`.
+    #[test]
+    fn test_get_resource() {
+        assert_eq!(
+            test_tree().get_str(DC_NS, "source"),
+            Some("http://example.com/")
+        );
+    }
+
+    /// Test getting structure fields, in all of the ways they can be written.
+    #[test]
+    fn test_get_struct_fields() {
+        let tree = test_tree();
+        assert_eq!(tree.get_str(EXIF_NS, "Flash/Fired"), Some("False"));
+        assert_eq!(tree.get_str(EXIF_NS, "OECF/Columns"), Some("2"));
+        assert_eq!(
+            tree.get_str(CONTAINER_NS, "Directory[2]/Item/Item:Mime"),
+            Some("video/mp4")
+        );
+        assert_eq!(
+            tree.get(CONTAINER_NS, "Directory[1]/Item")
+                .and_then(|item| return item.field(
+                    "http://ns.google.com/photos/1.0/container/item/",
+                    "Semantic"
+                ))
+                .and_then(XMPValue::as_str),
+            Some("Primary")
+        );
+    }
+
+    /// Test paths that cannot be found.
+    #[test]
+    fn test_get_missing() {
+        let tree = test_tree();
+        assert_eq!(tree.get(DC_NS, "creator"), None);
+        assert_eq!(tree.get(DC_NS, "subject/Rating"), None);
+        assert_eq!(tree.get(DC_NS, "subject[a]"), None);
+        assert_eq!(
+            tree.get(CONTAINER_NS, "Directory[1]/Item/unknown:Mime"),
+            None
+        );
+        assert_eq!(tree.get_str(CONTAINER_NS, "Directory"), None);
+    }
+
+    /// Test setting and removing top level properties.
+    #[test]
+    fn test_set_remove() {
+        let mut tree = test_tree();
+        let xmp_ns = "http://ns.adobe.com/xap/1.0/";
+
+        tree.set(xmp_ns, "Rating", XMPValue::Simple(String::from("5")));
+        assert_eq!(tree.get_str(xmp_ns, "Rating"), Some("5"));
+        assert_eq!(
+            tree.properties
+                .first()
+                .map(|property| return property.name.as_str()),
+            Some("Rating")
+        );
+
+        tree.set(xmp_ns, "Label", XMPValue::Simple(String::from("Red")));
+        assert_eq!(
+            tree.properties
+                .last()
+                .map(|property| return property.name.as_str()),
+            Some("Label")
+        );
+
+        assert_eq!(
+            tree.remove(xmp_ns, "Rating"),
+            Some(XMPValue::Simple(String::from("5")))
+        );
+        assert_eq!(tree.remove(xmp_ns, "Rating"), None);
+        assert_eq!(tree.get(xmp_ns, "Rating"), None);
+    }
+
+    /// Test the `split_index` function.
+    #[test]
+    fn test_split_index() {
+        assert_eq!(split_index("Directory"), Some(("Directory", None)));
+        assert_eq!(split_index("Directory[12]"), Some(("Directory", Some(12))));
+        assert_eq!(split_index("Directory[x]"), None);
+    }
+}
@@ -0,0 +1,507 @@
+/*
+* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at https: //mozilla.org/MPL/2.0/.
+*/
+//! Serializing the XMP model back to RDF/XML.
+//!
+//! Simple properties are written as attributes of an `rdf:Description`
+//! and all other properties as child elements. A new `rdf:Description` is
+//! started whenever a simple property follows an element, so that parsing
+//! the output again gives the properties in the same order.
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::jpeg::xmp::model::{
+    is_syntax_namespace, XMPProperty, XMPTree, XMPValue, META_NS, XML_NS,
+};
+use crate::jpeg::xmp::RDF_NS;
+
+/// Identifier used in the `<?xpacket?>` header, as given in the XMP specification.
+const PACKET_ID: &str = "W5M0MpCehiHzreSzNTczkc9d";
+
+/// Name of the tool written into the `x:xmptk` attribute.
+const TOOLKIT: &str = concat!("gcamera_tools ", env!("CARGO_PKG_VERSION"));
+
+/// Length of each line of padding, including the newline.
+const PADDING_LINE_LENGTH: usize = 100;
+
+/// Options for serializing XMP data.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct SerializeOptions {
+    /// Wrap the data in `<?xpacket?>` processing instructions.
+    pub packet_wrapper: bool,
+
+    /// Number of bytes of whitespace to add after the XMP data, so that it
+    /// can be edited in place later.
+    pub padding: usize,
+}
+
+/// Escape text for use in XML.
+///
+/// # Arguments
+/// * `text`: The text to escape.
+/// * `attribute`: Whether the text is an attribute value, in which case
+///   quotes and whitespace characters are also escaped.
+///
+/// # Returns
+/// The escaped text.
+fn escape(text: &str, attribute: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match (character, attribute) {
+            ('&', _) => escaped.push_str("&amp;"),
+            ('<', _) => escaped.push_str("&lt;"),
+            ('>', _) => escaped.push_str("&gt;"),
+            ('\r', _) => escaped.push_str("&#xD;"),
+            ('"', true) => escaped.push_str("&quot;"),
+            ('\n', true) => escaped.push_str("&#xA;"),
+            ('\t', true) => escaped.push_str("&#x9;"),
+            _ => escaped.push(character),
+        }
+    }
+    return escaped;
+}
+
+/// Collect the namespaces used by a list of properties.
+///
+/// # Arguments
+/// * `properties`: The properties to collect the namespaces of.
+/// * `namespaces`: Set to add the namespaces to.
+fn collect_namespaces<'tree>(
+    properties: &'tree [XMPProperty],
+    namespaces: &mut BTreeSet<&'tree str>,
+) {
+    for property in properties {
+        namespaces.insert(&property.namespace);
+        collect_value_namespaces(&property.value, namespaces);
+    }
+}
+
+/// Collect the namespaces used by the fields or items of a value.
+///
+/// # Arguments
+/// * `value`: The value to collect the namespaces of.
+/// * `namespaces`: Set to add the namespaces to.
+fn collect_value_namespaces<'tree>(value: &'tree XMPValue, namespaces: &mut BTreeSet<&'tree str>) {
+    match value {
+        XMPValue::Struct(fields) => collect_namespaces(fields, namespaces),
+        XMPValue::Array(_, items) => {
+            for item in items {
+                collect_value_namespaces(item, namespaces);
+            }
+        }
+        XMPValue::Simple(_) | XMPValue::Localized { .. } => {}
+    }
+}
+
+/// Pick the prefix to write for each namespace.
+///
+/// The prefixes declared in the tree are used where possible. Namespaces
+/// without a usable prefix are given generated ones, such as `ns1`.
+///
+/// # Arguments
+/// * `tree`: The tree that will be serialized.
+///
+/// # Returns
+/// Map of namespace URIs to prefixes. The syntax namespaces are included,
+/// but do not need to be declared.
+fn assign_prefixes(tree: &XMPTree) -> BTreeMap<String, String> {
+    let mut prefixes = BTreeMap::from([
+        (String::from(META_NS), String::from("x")),
+        (String::from(RDF_NS), String::from("rdf")),
+        (String::from(XML_NS), String::from("xml")),
+    ]);
+    let mut used: BTreeSet<String> = prefixes.values().cloned().collect();
+    used.insert(String::from("xmlns"));
+
+    for (uri, prefix) in &tree.namespaces {
+        if !is_syntax_namespace(uri) && !used.contains(prefix) {
+            used.insert(prefix.clone());
+            prefixes.insert(uri.clone(), prefix.clone());
+        }
+    }
+
+    let mut used_namespaces = BTreeSet::new();
+    collect_namespaces(&tree.properties, &mut used_namespaces);
+    let mut counter: usize = 0;
+    for uri in used_namespaces {
+        if !prefixes.contains_key(uri) {
+            let prefix = loop {
+                counter = counter.saturating_add(1);
+                let candidate = format!("ns{counter}");
+                if !used.contains(&candidate) {
+                    break candidate;
+                }
+            };
+            used.insert(prefix.clone());
+            prefixes.insert(String::from(uri), prefix);
+        }
+    }
+
+    return prefixes;
+}
+
+/// Get the qualified name of a property.
+///
+/// # Arguments
+/// * `prefixes`: Map of namespace URIs to prefixes.
+/// * `property`: The property to get the name of.
+///
+/// # Returns
+/// The name of the property, with the prefix of its namespace.
+fn qualified_name(prefixes: &BTreeMap<String, String>, property: &XMPProperty) -> String {
+    let prefix = prefixes
+        .get(&property.namespace)
+        .map_or("", |prefix| return prefix.as_str());
+    return format!("{prefix}:{}", property.name);
+}
+
+/// Write a value as an XML element.
+///
+/// # Arguments
+/// * `lines`: The lines of XML to add to.
+/// * `prefixes`: Map of namespace URIs to prefixes.
+/// * `indent`: The indentation level of the element.
+/// * `name`: The qualified name of the element.
+/// * `value`: The value to write.
+fn write_element(
+    lines: &mut Vec<String>,
+    prefixes: &BTreeMap<String, String>,
+    indent: usize,
+    name: &str,
+    value: &XMPValue,
+) {
+    let spaces = " ".repeat(indent);
+    match value {
+        XMPValue::Simple(text) => {
+            lines.push(format!("{spaces}<{name}>{}</{name}>", escape(text, false)));
+        }
+        XMPValue::Localized {
+            language,
+            value: text,
+        } => {
+            lines.push(format!(
+                "{spaces}<{name} xml:lang=\"{}\">{}</{name}>",
+                escape(language, true),
+                escape(text, false)
+            ));
+        }
+        XMPValue::Struct(fields) if fields.is_empty() => {
+            lines.push(format!("{spaces}<{name} rdf:parseType=\"Resource\"/>"));
+        }
+        XMPValue::Struct(fields)
+            if fields
+                .iter()
+                .all(|field| return matches!(field.value, XMPValue::Simple(_))) =>
+        {
+            let attributes: Vec<String> = fields
+                .iter()
+                .map(|field| {
+                    return format!(
+                        " {}=\"{}\"",
+                        qualified_name(prefixes, field),
+                        escape(field.value.as_str().unwrap_or_default(), true)
+                    );
+                })
+                .collect();
+            lines.push(format!("{spaces}<{name}{}/>", attributes.concat()));
+        }
+        XMPValue::Struct(fields) => {
+            lines.push(format!("{spaces}<{name} rdf:parseType=\"Resource\">"));
+            for field in fields {
+                write_element(
+                    lines,
+                    prefixes,
+                    indent.saturating_add(1),
+                    &qualified_name(prefixes, field),
+                    &field.value,
+                );
+            }
+            lines.push(format!("{spaces}</{name}>"));
+        }
+        XMPValue::Array(kind, items) => {
+            let array_name = kind.rdf_name();
+            lines.push(format!("{spaces}<{name}>"));
+            lines.push(format!("{spaces} <rdf:{array_name}>"));
+            for item in items {
+                write_element(lines, prefixes, indent.saturating_add(2), "rdf:li", item);
+            }
+            lines.push(format!("{spaces} </rdf:{array_name}>"));
+            lines.push(format!("{spaces}</{name}>"));
+        }
+    }
+}
+
+/// Write a single `rdf:Description` element.
+///
+/// # Arguments
+/// * `lines`: The lines of XML to add to.
+/// * `prefixes`: Map of namespace URIs to prefixes.
+/// * `attributes`: The simple properties to write as attributes.
+/// * `elements`: The properties to write as child elements.
+fn write_description(
+    lines: &mut Vec<String>,
+    prefixes: &BTreeMap<String, String>,
+    attributes: &[&XMPProperty],
+    elements: &[&XMPProperty],
+) {
+    lines.push(String::from("  <rdf:Description rdf:about=\"\""));
+    for (uri, prefix) in prefixes {
+        if !is_syntax_namespace(uri) {
+            lines.push(format!("    xmlns:{prefix}=\"{}\"", escape(uri, true)));
+        }
+    }
+    for property in attributes {
+        lines.push(format!(
+            "    {}=\"{}\"",
+            qualified_name(prefixes, property),
+            escape(property.value.as_str().unwrap_or_default(), true)
+        ));
+    }
+
+    if elements.is_empty() {
+        lines.push(String::from("  />"));
+    } else {
+        lines.push(String::from("  >"));
+        for property in elements {
+            write_element(
+                lines,
+                prefixes,
+                3,
+                &qualified_name(prefixes, property),
+                &property.value,
+            );
+        }
+        lines.push(String::from("  </rdf:Description>"));
+    }
+}
+
+/// Serialize an XMP tree to RDF/XML.
+///
+/// # Arguments
+/// * `tree`: The tree to serialize.
+/// * `options`: Options controlling the output.
+///
+/// # Returns
+/// The XMP data, as an XML string.
+pub fn serialize(tree: &XMPTree, options: SerializeOptions) -> String {
+    let prefixes = assign_prefixes(tree);
+    let mut lines = Vec::new();
+
+    if options.packet_wrapper {
+        lines.push(format!("<?xpacket begin=\"\u{feff}\" id=\"{PACKET_ID}\"?>"));
+    }
+    lines.push(format!(
+        "<x:xmpmeta xmlns:x=\"{META_NS}\" x:xmptk=\"{TOOLKIT}\">"
+    ));
+    lines.push(format!(" <rdf:RDF xmlns:rdf=\"{RDF_NS}\">"));
+
+    let mut attributes: Vec<&XMPProperty> = Vec::new();
+    let mut elements: Vec<&XMPProperty> = Vec::new();
+    for property in &tree.properties {
+        if matches!(property.value, XMPValue::Simple(_)) {
+            // Attributes cannot come after elements, so start a new description.
+            if !elements.is_empty() {
+                write_description(&mut lines, &prefixes, &attributes, &elements);
+                attributes.clear();
+                elements.clear();
+            }
+            attributes.push(property);
+        } else {
+            elements.push(property);
+        }
+    }
+    write_description(&mut lines, &prefixes, &attributes, &elements);
+
+    lines.push(String::from(" </rdf:RDF>"));
+    lines.push(String::from("</x:xmpmeta>"));
+
+    let mut xml = lines.join("\n");
+    xml.push('\n');
+    xml.extend((1..=options.padding).map(|index| {
+        return if index % PADDING_LINE_LENGTH == 0 {
+            '\n'
+        } else {
+            ' '
+        };
+    }));
+    if options.packet_wrapper {
+        xml.push_str("<?xpacket end=\"w\"?>");
+    }
+    return xml;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jpeg::xmp::model::ArrayKind;
+    use crate::jpeg::xmp::XMPData;
+    use roxmltree::Document;
+
+    /// XMP data used in the tests.
+    const TEST_XMP: &str =
+        "<x:xmpmeta xmlns:x='adobe:ns:meta/' x:xmptk='Adobe XMP Core 5.1.0-jc003'>
+        <rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'>
+            <rdf:Description rdf:about=''
+                xmlns:xmp='http://ns.adobe.com/xap/1.0/'
+                xmlns:xmpNote='http://ns.adobe.com/xmp/note/'
+                xmlns:GCamera='http://ns.google.com/photos/1.0/camera/'
+                xmlns:Container='http://ns.google.com/photos/1.0/container/'
+                xmlns:Item='http://ns.google.com/photos/1.0/container/item/'
+                xmpNote:HasExtendedXMP='DD558CA2166AEC119A42CDFB02D4F1EF'
+                GCamera:MotionPhoto='1'
+                GCamera:MotionPhotoVersion='1'
+                GCamera:MotionPhotoPresentationTimestampUs='968644'>
+                <Container:Directory>
+                    <rdf:Seq>
+                        <rdf:li rdf:parseType='Resource'>
+                            <Container:Item Item:Mime='image/jpeg' Item:Semantic='Primary'
+                                Item:Length='0' Item:Padding='0'/>
+                        </rdf:li>
+                        <rdf:li rdf:parseType='Resource'>
+                            <Container:Item Item:Mime='video/mp4' Item:Semantic='MotionPhoto'
+                                Item:Length='4906025' Item:Padding='0'/>
+                        </rdf:li>
+                    </rdf:Seq>
+                </Container:Directory>
+                <xmp:Rating>4</xmp:Rating>
+            </rdf:Description>
+            <rdf:Description rdf:about=''
+                xmlns:dc='http://purl.org/dc/elements/1.1/'
+                xmlns:exif='http://ns.adobe.com/exif/1.0/'
+                exif:Make='&lt;Google &amp; &quot;Pixel&quot;&gt;'>
+                <dc:subject><rdf:Bag><rdf:li>cat</rdf:li><rdf:li>a &lt; b</rdf:li></rdf:Bag></dc:subject>
+                <dc:title><rdf:Alt><rdf:li xml:lang='x-default'>Pets</rdf:li></rdf:Alt></dc:title>
+                <dc:description xml:lang='en'>Line one
+Line two</dc:description>
+                <dc:source rdf:resource='http://example.com/?a=1&amp;b=2'/>
+                <exif:Flash rdf:parseType='Resource'>
+                    <exif:Fired>False</exif:Fired>
+                    <exif:Modes><rdf:Seq><rdf:li><rdf:Bag><rdf:li>1</rdf:li></rdf:Bag></rdf:li></rdf:Seq></exif:Modes>
+                </exif:Flash>
+                <exif:OECF><rdf:Description exif:Columns='2'/></exif:OECF>
+                <exif:Empty rdf:parseType='Resource'/>
+                <exif:Nothing><rdf:Seq/></exif:Nothing>
+            </rdf:Description>
+        </rdf:RDF>
+    </x:xmpmeta>";
+
+    /// Parse the test XMP data.
+    ///
+    /// # Returns
+    /// The parsed XMP data.
+    fn test_data() -> XMPData {
+        return XMPData::try_from(String::from(TEST_XMP)).unwrap();
+    }
+
+    /// Test that serializing and parsing again gives an equal model.
+    #[test]
+    fn test_round_trip() {
+        let data = test_data();
+        let serialized = data.serialize(SerializeOptions::default());
+
+        assert_eq!(XMPData::try_from(serialized.clone()), Ok(data));
+        assert!(!serialized.contains("xpacket"));
+        assert!(serialized.ends_with("</x:xmpmeta>\n"));
+    }
+
+    /// Test that serializing the output again gives the same output.
+    #[test]
+    fn test_stable() {
+        let serialized = test_data().serialize(SerializeOptions::default());
+        let reserialized = XMPData::try_from(serialized.clone())
+            .unwrap()
+            .serialize(SerializeOptions::default());
+        assert_eq!(serialized, reserialized);
+    }
+
+    /// Test the packet wrapper and padding.
+    #[test]
+    fn test_packet_wrapper() {
+        let data = test_data();
+        let unpadded = data.serialize(SerializeOptions {
+            packet_wrapper: true,
+            padding: 0,
+        });
+        let padded = data.serialize(SerializeOptions {
+            packet_wrapper: true,
+            padding: 250,
+        });
+
+        assert!(
+            padded.starts_with("<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>")
+        );
+        assert!(padded.ends_with("<?xpacket end=\"w\"?>"));
+        assert_eq!(padded.len(), unpadded.len() + 250);
+        assert_eq!(
+            padded.matches('\n').count(),
+            unpadded.matches('\n').count() + 2
+        );
+        assert_eq!(XMPData::try_from(padded), Ok(data));
+    }
+
+    /// Test the `escape` function.
+    #[test]
+    fn test_escape() {
+        let text = "<a & \"b\">\n\t'c'\r";
+        assert_eq!(escape(text, false), "&lt;a &amp; \"b\"&gt;\n\t'c'&#xD;");
+        assert_eq!(
+            escape(text, true),
+            "&lt;a &amp; &quot;b&quot;&gt;&#xA;&#x9;'c'&#xD;"
+        );
+    }
+
+    /// Test that namespaces without a usable prefix are given generated ones.
+    #[test]
+    fn test_generated_prefixes() {
+        let tree = XMPTree {
+            namespaces: BTreeMap::from([(
+                String::from("http://ns.example.com/b/"),
+                String::from("rdf"),
+            )]),
+            properties: vec![
+                XMPProperty::new(
+                    "http://ns.example.com/a/",
+                    "Name",
+                    XMPValue::Simple(String::from("A")),
+                ),
+                XMPProperty::new(
+                    "http://ns.example.com/b/",
+                    "List",
+                    XMPValue::Array(
+                        ArrayKind::Alt,
+                        vec![XMPValue::Struct(vec![XMPProperty::new(
+                            "http://ns.example.com/a/",
+                            "Field",
+                            XMPValue::Simple(String::from("B")),
+                        )])],
+                    ),
+                ),
+            ],
+        };
+
+        let serialized = serialize(&tree, SerializeOptions::default());
+        let document = Document::parse(&serialized).unwrap();
+        let parsed = XMPTree::from(&document);
+
+        assert_eq!(parsed.properties, tree.properties);
+        assert_eq!(
+            parsed.namespace_uri("ns1"),
+            Some("http://ns.example.com/a/")
+        );
+        assert_eq!(
+            parsed.namespace_uri("ns2"),
+            Some("http://ns.example.com/b/")
+        );
+    }
+
+    /// Test that an empty tree is written as a single empty description.
+    #[test]
+    fn test_empty() {
+        let serialized = serialize(&XMPTree::default(), SerializeOptions::default());
+        let document = Document::parse(&serialized).unwrap();
+
+        assert_eq!(XMPTree::from(&document), XMPTree::default());
+        assert_eq!(serialized.matches("<rdf:Description").count(), 1);
+    }
+}
@@ -65,6 +65,7 @@
 #![allow(clippy::min_ident_chars)]
 pub mod camera_image;
 pub mod cli;
-mod debug_components;
+pub mod debug_components;
 pub mod errors;
-mod jpeg;
+pub mod image_index;
+pub mod jpeg;